@@ -0,0 +1,46 @@
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use rub::Lexer;
+use std::hint::black_box;
+
+/// a program of every kind of token, comments and indentation, repeated to about `size` bytes
+fn program(size: usize) -> String {
+    let chunk = r#"
+// squares every element and adds them up
+struct Point {
+    x: Int,
+    y: Float,
+}
+
+/* a block comment /* nested */ over
+   several lines */
+fn sum_of_squares(values: Vec<Int>) -> Int {
+    let total = 0;
+    for (let i = 0; i < values.len(); i = i + 1) {
+        total = total + values.get(i) * values.get(i);
+    }
+    return total;
+}
+
+let point = Point { x: 1_000, y: 2.5 };
+let message = "the sum of squares is ";
+if (point.x >= 10 and !false or point.y != 0.0) {
+    print(message + sum_of_squares([1, 2, 3]) as String);
+}
+"#;
+    chunk.repeat(size / chunk.len() + 1)
+}
+
+fn lex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    for size in [64 * 1024, 4 * 1024 * 1024] {
+        let source = program(size);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_function(format!("{} KiB", size / 1024), |b| {
+            b.iter(|| Lexer::new(black_box(&source)).lex().tokens.len())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lex);
+criterion_main!(benches);
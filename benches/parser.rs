@@ -0,0 +1,59 @@
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use rub::{Lexer, Parser};
+use std::hint::black_box;
+
+/// `1 + (2 * (3 - (...)))` nested `depth` times
+fn nested_expression(depth: usize) -> String {
+    let mut source = "let x = ".to_string();
+    for i in 0..depth {
+        source.push_str(&format!("{i} {} (", ["+", "*", "-", "/"][i % 4]));
+    }
+    source.push('0');
+    source.push_str(&")".repeat(depth));
+    source.push(';');
+    source
+}
+
+/// declarations, loops, calls and struct literals, one after another
+fn statement_list(count: usize) -> String {
+    let chunk = r#"
+struct Point { x: Int, y: Int }
+fn area(p: Point) -> Int { return p.x * p.y; }
+let p = Point { x: 3, y: 4 };
+for (let i = 0; i < 10; i = i + 1) { if (area(p) > i and i != 5) { print(i); } else { p.x = p.x + 1; } }
+let squares = [1, 4, 9].map(|n| n * n);
+"#;
+    chunk.repeat(count)
+}
+
+/// statements with errors the parser has to recover from, each reported and skipped
+fn error_recovery(count: usize) -> String {
+    let chunk = "let = ;\nfn (a b { return }\nlet y = (1 + ;\nstruct { x: }\nif x { print(1) \nlet z = 3;\n";
+    chunk.repeat(count)
+}
+
+fn parse(c: &mut Criterion) {
+    let corpora = [
+        ("nested expression", nested_expression(256)),
+        ("statement list", statement_list(2_000)),
+        ("error recovery", error_recovery(2_000)),
+    ];
+
+    let mut group = c.benchmark_group("parse");
+    for (name, source) in &corpora {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.lex().tokens;
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_function(*name, |b| {
+            b.iter_batched(
+                || tokens.clone(),
+                |tokens| black_box(Parser::new(tokens, source.clone()).parse().ast),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
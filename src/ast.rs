@@ -1,23 +1,21 @@
 use crate::TokenKind;
+use crate::type_inferrer::Type;
+use crate::visitor::{Visitor, walk_expr, walk_stmt};
 use miette::SourceSpan;
 
+/// unique among the nodes of a parse, analysis results like types and bindings are keyed by it, the parser hands them
+/// out and `Parser::with_first_node_id` continues the ids of an earlier parse
+pub type NodeId = usize;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AstNode<T> {
     pub node: T,
     pub span: SourceSpan,
-    pub node_id: usize,
+    pub node_id: NodeId,
 }
 
 impl<T> AstNode<T> {
-    pub fn new(node: T, span: SourceSpan) -> Self {
-        static mut NODE_ID: usize = 1;
-
-        let node_id = unsafe {
-            let id = NODE_ID;
-            NODE_ID += 1;
-            id
-        };
-
+    pub fn new(node: T, span: SourceSpan, node_id: NodeId) -> Self {
         Self { node, span, node_id }
     }
 }
@@ -35,6 +33,8 @@ pub enum UnresolvedType {
         base: Box<UnresolvedType>,
         args: Vec<UnresolvedType>,
     },
+    /// `{x: Int, y: Int}`, fields sorted by name
+    Record(Vec<(String, UnresolvedType)>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -56,6 +56,137 @@ pub struct Delimiter {
 pub struct Program {
     pub statements: Vec<AstNode<Stmt>>,
     pub span: SourceSpan,
+    /// the ids of the program's nodes are below it, nodes added later and later parses continue from here
+    pub next_node_id: NodeId,
+}
+
+impl Program {
+    /// the top-level function declarations in source order
+    pub fn functions(&self) -> impl Iterator<Item = &FunDeclStmt> {
+        self.statements.iter().filter_map(|stmt| match &stmt.node {
+            Stmt::FunDecl(fun_decl) => Some(fun_decl),
+            _ => None,
+        })
+    }
+
+    /// calls `f` with every statement and expression, parents before their children and otherwise in source order
+    pub fn walk(&self, f: impl FnMut(Node<'_>)) {
+        Walker { f }.visit_program(self);
+    }
+
+    /// the innermost statement or expression whose span contains `offset`, e.g. the one under an editor's cursor
+    pub fn find_node_at(&self, offset: usize) -> Option<Node<'_>> {
+        let contains = |node: &Node| {
+            let span = node.span();
+            span.offset() <= offset && offset < span.offset() + span.len()
+        };
+        let mut found = self.statements.iter().map(Node::Stmt).find(contains)?;
+        while let Some(child) = found.children().into_iter().find(contains) {
+            found = child;
+        }
+        Some(found)
+    }
+}
+
+/// a statement or an expression of a program, for queries that don't care which
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Node<'a> {
+    Stmt(&'a AstNode<Stmt>),
+    Expr(&'a AstNode<Expr>),
+}
+
+impl<'a> Node<'a> {
+    pub fn span(&self) -> SourceSpan {
+        match self {
+            Node::Stmt(stmt) => stmt.span,
+            Node::Expr(expr) => expr.span,
+        }
+    }
+
+    pub fn node_id(&self) -> NodeId {
+        match self {
+            Node::Stmt(stmt) => stmt.node_id,
+            Node::Expr(expr) => expr.node_id,
+        }
+    }
+
+    /// the statements and expressions directly inside this one in source order, the ones `walk_stmt` and
+    /// `walk_expr` visit, statements and the tail of a block belong to the node owning the block
+    pub fn children(&self) -> Vec<Node<'a>> {
+        let mut children = vec![];
+        match self {
+            Node::Stmt(stmt) => match &stmt.node {
+                Stmt::ExprStmtNode(expr_stmt) => children.push(Node::Expr(&expr_stmt.expr)),
+                Stmt::VarDecl(var_decl) => children.extend(var_decl.initializer.as_ref().map(Node::Expr)),
+                Stmt::FunDecl(fun_decl) => push_block(&mut children, &fun_decl.body.node),
+                Stmt::StructDecl(_) | Stmt::TypeAlias(_) => {}
+                Stmt::While(while_stmt) => {
+                    children.push(Node::Expr(&while_stmt.condition));
+                    push_block(&mut children, &while_stmt.body.node);
+                }
+                Stmt::For(for_stmt) => {
+                    children.extend(for_stmt.initializer.as_deref().map(Node::Stmt));
+                    children.push(Node::Expr(&for_stmt.condition));
+                    children.extend(for_stmt.increment.as_ref().map(Node::Expr));
+                    push_block(&mut children, &for_stmt.body.node);
+                }
+                Stmt::Return(return_stmt) => children.extend(return_stmt.expr.as_ref().map(Node::Expr)),
+            },
+            Node::Expr(expr) => match &expr.node {
+                Expr::Literal(LiteralExpr::VecLiteral(elements)) => children.extend(elements.iter().map(Node::Expr)),
+                Expr::Literal(_) | Expr::Variable(_) => {}
+                Expr::Unary(unary) => children.push(Node::Expr(&unary.expr)),
+                Expr::Binary(binary) => children.extend([Node::Expr(&binary.left), Node::Expr(&binary.right)]),
+                Expr::Logical(logical) => children.extend([Node::Expr(&logical.left), Node::Expr(&logical.right)]),
+                Expr::Grouping(inner) => children.push(Node::Expr(inner)),
+                Expr::Assign(assign) => children.push(Node::Expr(&assign.value)),
+                Expr::Call(call) => {
+                    children.push(Node::Expr(&call.callee));
+                    children.extend(call.arguments.iter().map(Node::Expr));
+                }
+                Expr::Lambda(lambda) => push_block(&mut children, &lambda.body.node),
+                Expr::Block(block) => push_block(&mut children, block),
+                Expr::If(if_expr) => {
+                    children.push(Node::Expr(&if_expr.condition));
+                    push_block(&mut children, &if_expr.then_branch.node);
+                    if let Some(else_branch) = &if_expr.else_branch {
+                        push_block(&mut children, &else_branch.node);
+                    }
+                }
+                Expr::MethodCall(method_call) => {
+                    children.push(Node::Expr(&method_call.receiver));
+                    children.extend(method_call.arguments.iter().map(Node::Expr));
+                }
+                Expr::StructInit(struct_init) => children.extend(struct_init.fields.iter().map(|(_, value)| Node::Expr(value))),
+                Expr::Record(record) => children.extend(record.fields.iter().map(|(_, value)| Node::Expr(value))),
+                Expr::Cast(cast) => children.push(Node::Expr(&cast.expr)),
+                Expr::FieldAccess(field_access) => children.push(Node::Expr(&field_access.receiver)),
+                Expr::FieldAssign(field_assign) => children.extend([Node::Expr(&field_assign.receiver), Node::Expr(&field_assign.value)]),
+            },
+        }
+        children
+    }
+}
+
+fn push_block<'a>(children: &mut Vec<Node<'a>>, block: &'a BlockExpr) {
+    children.extend(block.statements.iter().map(Node::Stmt));
+    children.extend(block.expr.as_deref().map(Node::Expr));
+}
+
+struct Walker<F> {
+    f: F,
+}
+
+impl<F: FnMut(Node<'_>)> Visitor for Walker<F> {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        (self.f)(Node::Stmt(stmt));
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        (self.f)(Node::Expr(expr));
+        walk_expr(self, expr);
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -64,13 +195,69 @@ pub enum Stmt {
     VarDecl(VarDeclStmt),
     FunDecl(FunDeclStmt),
     StructDecl(StructDeclStmt),
+    TypeAlias(TypeAliasStmt),
     While(WhileStmt),
     For(ForStmt),
     Return(ReturnStmt),
 }
 
+impl Stmt {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Stmt::ExprStmtNode(_) => "expression statement",
+            Stmt::VarDecl(_) => "variable declaration",
+            Stmt::FunDecl(_) => "function declaration",
+            Stmt::StructDecl(_) => "struct declaration",
+            Stmt::TypeAlias(_) => "type alias",
+            Stmt::While(_) => "while",
+            Stmt::For(_) => "for",
+            Stmt::Return(_) => "return",
+        }
+    }
+
+    /// the attributes written before a declaration, other statements have none
+    pub fn attributes(&self) -> &[Attribute] {
+        match self {
+            Stmt::VarDecl(var_decl) => &var_decl.attributes,
+            Stmt::FunDecl(fun_decl) => &fun_decl.attributes,
+            Stmt::StructDecl(struct_decl) => &struct_decl.attributes,
+            Stmt::TypeAlias(type_alias) => &type_alias.attributes,
+            _ => &[],
+        }
+    }
+}
+
 pub type Ident = AstNode<String>;
 
+/// `@name` or `@name(args)` before a declaration, parsed the same for every name, what it means is up to the
+/// passes reading it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: Ident,
+    pub args: Vec<AttributeArg>,
+    pub span: SourceSpan,
+}
+
+impl Attribute {
+    /// the argument named `name`, or else the unnamed one at `position`
+    pub fn arg(&self, name: &str, position: usize) -> Option<&LiteralExpr> {
+        let named = self
+            .args
+            .iter()
+            .find(|arg| arg.name.as_ref().is_some_and(|arg_name| arg_name.node == name));
+        named
+            .or_else(|| self.args.get(position).filter(|arg| arg.name.is_none()))
+            .map(|arg| &arg.value.node)
+    }
+}
+
+/// a literal argument of an attribute, optionally named like `expected_error = "..."`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeArg {
+    pub name: Option<Ident>,
+    pub value: AstNode<LiteralExpr>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct ExprStmt {
     pub expr: AstNode<Expr>,
@@ -78,6 +265,7 @@ pub struct ExprStmt {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct VarDeclStmt {
+    pub attributes: Vec<Attribute>,
     pub ident: Ident,
     pub initializer: Option<AstNode<Expr>>,
     pub type_annotation: Option<AstNode<UnresolvedType>>,
@@ -91,6 +279,7 @@ pub struct TypedIdent {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunDeclStmt {
+    pub attributes: Vec<Attribute>,
     pub name: Ident,
     pub params: Vec<TypedIdent>,
     pub body: AstNode<BlockExpr>,
@@ -100,10 +289,19 @@ pub struct FunDeclStmt {
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct StructDeclStmt {
+    pub attributes: Vec<Attribute>,
     pub ident: Ident,
     pub fields: Vec<TypedIdent>,
 }
 
+/// `type Name = Type;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAliasStmt {
+    pub attributes: Vec<Attribute>,
+    pub ident: Ident,
+    pub ty: AstNode<UnresolvedType>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct WhileStmt {
     pub condition: AstNode<Expr>,
@@ -140,6 +338,32 @@ pub enum Expr {
     StructInit(StructInitExpr),
     FieldAccess(FieldAccessExpr),
     FieldAssign(FieldAssignExpr),
+    Record(RecordExpr),
+    Cast(CastExpr),
+}
+
+impl Expr {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Expr::Literal(_) => "literal",
+            Expr::Unary(_) => "unary",
+            Expr::Binary(_) => "binary",
+            Expr::Grouping(_) => "grouping",
+            Expr::Variable(_) => "variable",
+            Expr::Assign(_) => "assignment",
+            Expr::Logical(_) => "logical",
+            Expr::Call(_) => "call",
+            Expr::Lambda(_) => "lambda",
+            Expr::Block(_) => "block",
+            Expr::If(_) => "if",
+            Expr::MethodCall(_) => "method call",
+            Expr::StructInit(_) => "struct initialization",
+            Expr::FieldAccess(_) => "field access",
+            Expr::FieldAssign(_) => "field assignment",
+            Expr::Record(_) => "record",
+            Expr::Cast(_) => "cast",
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -165,6 +389,8 @@ pub struct LogicalExpr {
 #[derive(Debug, Clone, PartialEq)]
 pub struct AssignExpr {
     pub target: Ident,
+    /// the `=`
+    pub equal_span: SourceSpan,
     pub value: Box<AstNode<Expr>>,
 }
 
@@ -207,6 +433,21 @@ pub struct StructInitExpr {
     pub fields: Vec<(Ident, AstNode<Expr>)>,
 }
 
+/// `{ x: 1, y: 2 }`, an anonymous struct typed by its fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordExpr {
+    pub fields: Vec<(Ident, AstNode<Expr>)>,
+}
+
+/// `expr as Type`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CastExpr {
+    pub expr: Box<AstNode<Expr>>,
+    pub ty: AstNode<UnresolvedType>,
+    /// `ty` resolved when parsing, aliases are still names
+    pub target: Type,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct FieldAccessExpr {
     pub receiver: Box<AstNode<Expr>>,
@@ -217,19 +458,38 @@ pub struct FieldAccessExpr {
 pub struct FieldAssignExpr {
     pub receiver: Box<AstNode<Expr>>,
     pub field: Ident,
+    /// the `=`
+    pub equal_span: SourceSpan,
     pub value: Box<AstNode<Expr>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LiteralExpr {
-    Int(i64),
-    Float(f64),
+    Int(Number<i64>),
+    Float(Number<f64>),
     String(String),
     Bool(bool),
     VecLiteral(Vec<AstNode<Expr>>),
     Nil,
 }
 
+impl LiteralExpr {
+    pub fn int(value: i64) -> Self {
+        LiteralExpr::Int(Number { value, text: None })
+    }
+
+    pub fn float(value: f64) -> Self {
+        LiteralExpr::Float(Number { value, text: None })
+    }
+}
+
+/// a number literal, `text` is how it is written in source, e.g. `1_000.50`, and `None` for computed numbers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Number<T> {
+    pub value: T,
+    pub text: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum UnaryOp {
     Bang,
@@ -0,0 +1,173 @@
+use crate::ast::{AstNode, Attribute, Program, Stmt};
+use crate::error::AttributeError;
+use crate::error::AttributeError::{InvalidAttributeTarget, UnexpectedAttributeArgument, UnknownAttribute};
+use crate::visitor::{Visitor, walk_stmt};
+use miette::Report;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// the declarations attributes are written before
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeTarget {
+    Variable,
+    Function,
+    Struct,
+    TypeAlias,
+}
+
+impl AttributeTarget {
+    pub const ALL: [AttributeTarget; 4] = [
+        AttributeTarget::Variable,
+        AttributeTarget::Function,
+        AttributeTarget::Struct,
+        AttributeTarget::TypeAlias,
+    ];
+
+    fn of(stmt: &Stmt) -> Option<Self> {
+        match stmt {
+            Stmt::VarDecl(_) => Some(AttributeTarget::Variable),
+            Stmt::FunDecl(_) => Some(AttributeTarget::Function),
+            Stmt::StructDecl(_) => Some(AttributeTarget::Struct),
+            Stmt::TypeAlias(_) => Some(AttributeTarget::TypeAlias),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AttributeTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeTarget::Variable => write!(f, "variable"),
+            AttributeTarget::Function => write!(f, "function"),
+            AttributeTarget::Struct => write!(f, "struct"),
+            AttributeTarget::TypeAlias => write!(f, "type alias"),
+        }
+    }
+}
+
+/// where an attribute can be written and the arguments it takes
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeSpec {
+    pub targets: Vec<AttributeTarget>,
+    /// argument names in order, positional arguments fill them from the start
+    pub params: Vec<String>,
+}
+
+impl AttributeSpec {
+    pub fn new(targets: &[AttributeTarget], params: &[&str]) -> Self {
+        Self {
+            targets: targets.to_vec(),
+            params: params.iter().map(|param| param.to_string()).collect(),
+        }
+    }
+}
+
+/// the attributes programs may use, `deprecated`, `test` and `inline` and whatever an embedder adds
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeRegistry {
+    attributes: BTreeMap<String, AttributeSpec>,
+}
+
+impl Default for AttributeRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AttributeRegistry {
+    pub fn new() -> Self {
+        Self {
+            attributes: BTreeMap::from([
+                ("deprecated".to_string(), AttributeSpec::new(&AttributeTarget::ALL, &["note"])),
+                (
+                    "test".to_string(),
+                    AttributeSpec::new(&[AttributeTarget::Function], &["expected_error"]),
+                ),
+                ("inline".to_string(), AttributeSpec::new(&[AttributeTarget::Function], &[])),
+            ]),
+        }
+    }
+
+    /// adds an attribute or replaces the one with the same name
+    pub fn with_attribute(mut self, name: &str, spec: AttributeSpec) -> Self {
+        self.attributes.insert(name.to_string(), spec);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AttributeSpec> {
+        self.attributes.get(name)
+    }
+
+    /// unknown attributes, attributes before the wrong kind of declaration and arguments they don't take
+    pub fn check(&self, program: &Program, source: &str) -> Vec<Report> {
+        let mut checker = AttributeChecker {
+            registry: self,
+            source,
+            errors: vec![],
+        };
+        checker.visit_program(program);
+        checker.errors
+    }
+}
+
+struct AttributeChecker<'a> {
+    registry: &'a AttributeRegistry,
+    source: &'a str,
+    errors: Vec<Report>,
+}
+
+impl AttributeChecker<'_> {
+    fn report(&mut self, error: AttributeError) {
+        self.errors.push(error.into());
+    }
+
+    fn check(&mut self, attribute: &Attribute, target: AttributeTarget) {
+        let name = &attribute.name.node;
+        let registry = self.registry;
+        let Some(spec) = registry.get(name) else {
+            self.report(UnknownAttribute {
+                src: self.source.to_string(),
+                span: attribute.name.span,
+                name: name.clone(),
+            });
+            return;
+        };
+        if !spec.targets.contains(&target) {
+            let targets: Vec<String> = spec.targets.iter().map(ToString::to_string).collect();
+            self.report(InvalidAttributeTarget {
+                src: self.source.to_string(),
+                span: attribute.span,
+                name: name.clone(),
+                target: target.to_string(),
+                targets: targets.join(", "),
+            });
+        }
+
+        let params = &spec.params;
+        for (i, arg) in attribute.args.iter().enumerate() {
+            let known = match &arg.name {
+                Some(arg_name) => params.contains(&arg_name.node),
+                None => i < params.len(),
+            };
+            if !known {
+                self.report(UnexpectedAttributeArgument {
+                    src: self.source.to_string(),
+                    span: arg.name.as_ref().map_or(arg.value.span, |arg_name| arg_name.span),
+                    name: name.clone(),
+                    params: if params.is_empty() { "none".to_string() } else { params.join(", ") },
+                });
+            }
+        }
+    }
+}
+
+impl Visitor for AttributeChecker<'_> {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Some(target) = AttributeTarget::of(&stmt.node) {
+            for attribute in stmt.node.attributes() {
+                self.check(attribute, target);
+            }
+        }
+        walk_stmt(self, stmt);
+    }
+}
@@ -1,7 +1,12 @@
 use crate::error::InterpreterError;
-use crate::error::RuntimeError::IndexOutOfBounds;
-use crate::interpreters::Value;
-use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "regex")]
+use crate::error::RuntimeError::InvalidPattern;
+use crate::error::RuntimeError::{IndexOutOfBounds, InvalidTime};
+use crate::interpreters::{Interpreter, Value};
+#[cfg(feature = "regex")]
+use crate::shared::Mutable;
+use crate::shared::Shared;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub fn clock_native(_args: Vec<Value>) -> Result<Value, InterpreterError> {
     let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
@@ -18,6 +23,222 @@ pub fn print_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
     Ok(Value::Nil)
 }
 
+pub fn to_string_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::String(Shared::from(args[0].to_printable_value())))
+}
+
+pub fn type_of_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::String(Shared::from(args[0].type_name())))
+}
+
+pub fn is_number_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Bool(matches!(args[0], Value::Int(_) | Value::Float(_))))
+}
+
+pub fn is_string_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Bool(matches!(args[0], Value::String(_))))
+}
+
+pub fn is_bool_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Bool(matches!(args[0], Value::Bool(_))))
+}
+
+pub fn is_vec_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Bool(matches!(args[0], Value::Vec(_))))
+}
+
+pub fn is_nil_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Bool(matches!(args[0], Value::Nil)))
+}
+
+pub fn schedule_native(interpreter: &mut Interpreter, args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let [Value::Int(delay), callback] = &args[..] else { unreachable!() };
+    interpreter.schedule(Duration::from_millis((*delay).max(0) as u64), callback.clone());
+    Ok(Value::Nil)
+}
+
+pub fn run_event_loop_native(interpreter: &mut Interpreter, _args: Vec<Value>) -> Result<Value, InterpreterError> {
+    interpreter.run_event_loop()?;
+    Ok(Value::Nil)
+}
+
+const MILLIS_PER_SECOND: i64 = 1000;
+const MILLIS_PER_MINUTE: i64 = 60 * MILLIS_PER_SECOND;
+const MILLIS_PER_HOUR: i64 = 60 * MILLIS_PER_MINUTE;
+const MILLIS_PER_DAY: i64 = 24 * MILLIS_PER_HOUR;
+
+/// milliseconds since the unix epoch, the unit of every time and duration native
+pub fn now_native(_args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    Ok(Value::Int(now.as_millis() as i64))
+}
+
+pub fn seconds_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Int(args[0].to_int() * MILLIS_PER_SECOND))
+}
+
+pub fn minutes_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Int(args[0].to_int() * MILLIS_PER_MINUTE))
+}
+
+pub fn hours_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Int(args[0].to_int() * MILLIS_PER_HOUR))
+}
+
+pub fn days_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Int(args[0].to_int() * MILLIS_PER_DAY))
+}
+
+fn invalid_time(message: String) -> InterpreterError {
+    InterpreterError::RuntimeError(InvalidTime {
+        src: String::new(),
+        span: 0.into(),
+        message,
+    })
+}
+
+/// formats a UTC timestamp, supports `%Y`, `%m`, `%d`, `%H`, `%M`, `%S`, `%f` (milliseconds) and `%%`
+pub fn format_time_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let timestamp = args[0].to_int();
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(MILLIS_PER_DAY));
+    let millis = timestamp.rem_euclid(MILLIS_PER_DAY);
+
+    let mut text = String::new();
+    let mut format = args[1].to_string().chars();
+    while let Some(char) = format.next() {
+        if char != '%' {
+            text.push(char);
+            continue;
+        }
+        match format.next() {
+            Some('Y') => text.push_str(&format!("{year:04}")),
+            Some('m') => text.push_str(&format!("{month:02}")),
+            Some('d') => text.push_str(&format!("{day:02}")),
+            Some('H') => text.push_str(&format!("{:02}", millis / MILLIS_PER_HOUR)),
+            Some('M') => text.push_str(&format!("{:02}", millis % MILLIS_PER_HOUR / MILLIS_PER_MINUTE)),
+            Some('S') => text.push_str(&format!("{:02}", millis % MILLIS_PER_MINUTE / MILLIS_PER_SECOND)),
+            Some('f') => text.push_str(&format!("{:03}", millis % MILLIS_PER_SECOND)),
+            Some('%') => text.push('%'),
+            Some(other) => return Err(invalid_time(format!("Unknown format specifier `%{other}`"))),
+            None => return Err(invalid_time("The format ends with a lone `%`".to_string())),
+        }
+    }
+    Ok(Value::String(Shared::from(text)))
+}
+
+/// parses a UTC time written in the format of `formatTime`, fields missing from the format default to the epoch
+pub fn parse_time_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let text = args[0].to_string();
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second, mut millis) = (0, 0, 0, 0);
+
+    let mut rest = text;
+    let mut format = args[1].to_string().chars();
+    while let Some(char) = format.next() {
+        if char != '%' {
+            rest = rest
+                .strip_prefix(char)
+                .ok_or_else(|| invalid_time(format!("Expected `{char}` in `{text}`")))?;
+            continue;
+        }
+        let (field, digits, range) = match format.next() {
+            Some('Y') => (&mut year, 4, 0..=9999),
+            Some('m') => (&mut month, 2, 1..=12),
+            Some('d') => (&mut day, 2, 1..=31),
+            Some('H') => (&mut hour, 2, 0..=23),
+            Some('M') => (&mut minute, 2, 0..=59),
+            Some('S') => (&mut second, 2, 0..=59),
+            Some('f') => (&mut millis, 3, 0..=999),
+            Some('%') => {
+                rest = rest
+                    .strip_prefix('%')
+                    .ok_or_else(|| invalid_time(format!("Expected `%` in `{text}`")))?;
+                continue;
+            }
+            Some(other) => return Err(invalid_time(format!("Unknown format specifier `%{other}`"))),
+            None => return Err(invalid_time("The format ends with a lone `%`".to_string())),
+        };
+        let value = rest
+            .get(..digits)
+            .filter(|number| number.bytes().all(|byte| byte.is_ascii_digit()))
+            .and_then(|number| number.parse().ok())
+            .filter(|value| range.contains(value))
+            .ok_or_else(|| {
+                invalid_time(format!(
+                    "Expected {digits} digits between {} and {} in `{text}`",
+                    range.start(),
+                    range.end()
+                ))
+            })?;
+        *field = value;
+        rest = &rest[digits..];
+    }
+    if !rest.is_empty() {
+        return Err(invalid_time(format!("Unexpected `{rest}` at the end of `{text}`")));
+    }
+
+    let days = days_from_civil(year, month, day);
+    let timestamp = days * MILLIS_PER_DAY + hour * MILLIS_PER_HOUR + minute * MILLIS_PER_MINUTE + second * MILLIS_PER_SECOND + millis;
+    Ok(Value::Int(timestamp))
+}
+
+/// days since the unix epoch of a proleptic gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(feature = "regex")]
+fn compile_pattern(pattern: &Value) -> Result<regex::Regex, InterpreterError> {
+    regex::Regex::new(pattern.to_string()).map_err(|err| {
+        InterpreterError::RuntimeError(InvalidPattern {
+            src: String::new(),
+            span: 0.into(),
+            message: err.to_string(),
+        })
+    })
+}
+
+#[cfg(feature = "regex")]
+pub fn matches_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let regex = compile_pattern(&args[1])?;
+    Ok(Value::Bool(regex.is_match(args[0].to_string())))
+}
+
+#[cfg(feature = "regex")]
+pub fn find_all_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let regex = compile_pattern(&args[1])?;
+    let found = regex
+        .find_iter(args[0].to_string())
+        .map(|found| Value::String(Shared::from(found.as_str())))
+        .collect();
+    Ok(Value::Vec(Shared::new(Mutable::new(found))))
+}
+
+#[cfg(feature = "regex")]
+pub fn replace_all_native(args: Vec<Value>) -> Result<Value, InterpreterError> {
+    let regex = compile_pattern(&args[1])?;
+    let replaced = regex.replace_all(args[0].to_string(), args[2].to_string());
+    Ok(Value::String(Shared::from(replaced.as_ref())))
+}
+
 pub fn vec_len_method(args: Vec<Value>) -> Result<Value, InterpreterError> {
     let Value::Vec(arr) = &args[0] else { unreachable!() };
     Ok(Value::Int(arr.borrow().len() as i64))
@@ -0,0 +1,173 @@
+use crate::ast::{AstNode, Expr, Program, Stmt};
+use crate::sarif::escape;
+use crate::visitor::{Visitor, walk_expr, walk_stmt};
+use miette::SourceSpan;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// caller name used for code outside of any function
+pub const TOP_LEVEL: &str = "<top-level>";
+
+/// static call graph, referencing a function (e.g. passing it as an argument) counts as calling it
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CallGraph {
+    pub functions: BTreeMap<String, SourceSpan>,
+    pub calls: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl CallGraph {
+    pub fn build(program: &Program) -> Self {
+        let mut declarations = DeclarationCollector::default();
+        declarations.visit_program(program);
+
+        let mut builder = CallGraphBuilder {
+            graph: CallGraph {
+                functions: declarations.functions,
+                calls: BTreeMap::new(),
+            },
+            callers: vec![TOP_LEVEL.to_string()],
+        };
+        builder.visit_program(program);
+        builder.graph
+    }
+
+    /// functions that can't be reached from top-level code or `main`
+    pub fn unreachable_functions(&self) -> Vec<(&str, SourceSpan)> {
+        let mut reachable = BTreeSet::new();
+        let mut pending = vec![TOP_LEVEL];
+        if self.functions.contains_key("main") {
+            pending.push("main");
+        }
+
+        while let Some(caller) = pending.pop() {
+            if !reachable.insert(caller) {
+                continue;
+            }
+            if let Some(callees) = self.calls.get(caller) {
+                pending.extend(callees.iter().map(String::as_str));
+            }
+        }
+
+        self.functions
+            .iter()
+            .filter(|(name, _)| !reachable.contains(name.as_str()))
+            .map(|(name, span)| (name.as_str(), *span))
+            .collect()
+    }
+
+    /// whether `name` can end up calling itself
+    pub fn is_recursive(&self, name: &str) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut pending: Vec<&str> = self.calls.get(name).into_iter().flatten().map(String::as_str).collect();
+
+        while let Some(caller) = pending.pop() {
+            if caller == name {
+                return true;
+            }
+            if visited.insert(caller)
+                && let Some(callees) = self.calls.get(caller)
+            {
+                pending.extend(callees.iter().map(String::as_str));
+            }
+        }
+        false
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+        dot.push_str(&format!("    \"{TOP_LEVEL}\" [shape=box];\n"));
+        for name in self.functions.keys() {
+            dot.push_str(&format!("    \"{name}\";\n"));
+        }
+        for (caller, callees) in &self.calls {
+            for callee in callees {
+                dot.push_str(&format!("    \"{caller}\" -> \"{callee}\";\n"));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let functions: Vec<String> = self
+            .functions
+            .iter()
+            .map(|(name, span)| {
+                format!(
+                    "{{\"name\":\"{}\",\"offset\":{},\"length\":{}}}",
+                    escape(name),
+                    span.offset(),
+                    span.len()
+                )
+            })
+            .collect();
+        let calls: Vec<String> = self
+            .calls
+            .iter()
+            .flat_map(|(caller, callees)| {
+                callees
+                    .iter()
+                    .map(move |callee| format!("{{\"caller\":\"{}\",\"callee\":\"{}\"}}", escape(caller), escape(callee)))
+            })
+            .collect();
+
+        format!("{{\"functions\":[{}],\"calls\":[{}]}}", functions.join(","), calls.join(","))
+    }
+}
+
+#[derive(Default)]
+struct DeclarationCollector {
+    functions: BTreeMap<String, SourceSpan>,
+}
+
+impl Visitor for DeclarationCollector {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Stmt::FunDecl(fun_decl) = &stmt.node {
+            self.functions.insert(fun_decl.name.node.clone(), fun_decl.name.span);
+        }
+        walk_stmt(self, stmt);
+    }
+}
+
+struct CallGraphBuilder {
+    graph: CallGraph,
+    callers: Vec<String>,
+}
+
+impl Visitor for CallGraphBuilder {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Stmt::FunDecl(fun_decl) = &stmt.node {
+            self.callers.push(fun_decl.name.node.clone());
+            walk_stmt(self, stmt);
+            self.callers.pop();
+        } else {
+            walk_stmt(self, stmt);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        if let Expr::Variable(ident) = &expr.node
+            && self.graph.functions.contains_key(&ident.node)
+        {
+            let caller = self.callers.last().unwrap().clone();
+            self.graph.calls.entry(caller).or_default().insert(ident.node.clone());
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_names_are_escaped() {
+        let graph = CallGraph {
+            functions: BTreeMap::from([(r#"say "hi""#.to_string(), SourceSpan::from(0..3))]),
+            calls: BTreeMap::from([(TOP_LEVEL.to_string(), BTreeSet::from([r"C:\dir".to_string()]))]),
+        };
+        assert_eq!(
+            graph.to_json(),
+            r#"{"functions":[{"name":"say \"hi\"","offset":0,"length":3}],"calls":[{"caller":"<top-level>","callee":"C:\\dir"}]}"#
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::ast::{AstNode, BlockExpr, Expr, FunDeclStmt, IfExpr, Program, Stmt};
+use crate::call_graph::TOP_LEVEL;
+use std::collections::VecDeque;
+use std::ops::Range;
+
+pub type BlockId = usize;
+
+/// `if` expressions nested inside other expressions are kept as a single node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CfgNode<'a> {
+    Stmt(&'a AstNode<Stmt>),
+    /// loop and `if` conditions, `for` increments and trailing block expressions
+    Expr(&'a AstNode<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BasicBlock<'a> {
+    pub nodes: Vec<CfgNode<'a>>,
+    pub successors: Vec<BlockId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlFlowGraph<'a> {
+    pub name: String,
+    pub blocks: Vec<BasicBlock<'a>>,
+    pub entry: BlockId,
+    pub exit: BlockId,
+    pub loops: Vec<Loop<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop<'a> {
+    pub condition: &'a AstNode<Expr>,
+    /// block evaluating the condition, back edges of the loop point here
+    pub header: BlockId,
+    pub body: Range<BlockId>,
+}
+
+impl<'a> ControlFlowGraph<'a> {
+    /// one graph for the top-level code followed by one per function declaration
+    pub fn build_all(program: &'a Program) -> Vec<Self> {
+        let mut builder = CfgBuilder::default();
+        let mut graphs = vec![builder.build(TOP_LEVEL, &program.statements, None)];
+
+        let mut next_function = 0;
+        while next_function < builder.functions.len() {
+            let fun_decl = builder.functions[next_function];
+            let body = &fun_decl.body.node;
+            graphs.push(builder.build(&fun_decl.name.node, &body.statements, body.expr.as_deref()));
+            next_function += 1;
+        }
+        graphs
+    }
+
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![vec![]; self.blocks.len()];
+        for (id, block) in self.blocks.iter().enumerate() {
+            for &successor in &block.successors {
+                predecessors[successor].push(id);
+            }
+        }
+        predecessors
+    }
+
+    pub fn reachable_blocks(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut pending = vec![self.entry];
+        while let Some(id) = pending.pop() {
+            if !reachable[id] {
+                reachable[id] = true;
+                pending.extend(&self.blocks[id].successors);
+            }
+        }
+        reachable
+    }
+}
+
+#[derive(Default)]
+struct CfgBuilder<'a> {
+    blocks: Vec<BasicBlock<'a>>,
+    current: BlockId,
+    exit: BlockId,
+    loops: Vec<Loop<'a>>,
+    /// function declarations found while building, they get their own graph
+    functions: Vec<&'a FunDeclStmt>,
+}
+
+impl<'a> CfgBuilder<'a> {
+    fn build(&mut self, name: &str, stmts: &'a [AstNode<Stmt>], tail: Option<&'a AstNode<Expr>>) -> ControlFlowGraph<'a> {
+        self.blocks = vec![];
+        let entry = self.new_block();
+        self.exit = self.new_block();
+        self.current = entry;
+
+        for stmt in stmts {
+            self.build_stmt(stmt);
+        }
+        if let Some(tail) = tail {
+            self.build_tail_expr(tail);
+        }
+        self.add_edge(self.current, self.exit);
+
+        ControlFlowGraph {
+            name: name.to_string(),
+            blocks: std::mem::take(&mut self.blocks),
+            entry,
+            exit: self.exit,
+            loops: std::mem::take(&mut self.loops),
+        }
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.blocks.push(BasicBlock::default());
+        self.blocks.len() - 1
+    }
+
+    fn add_edge(&mut self, from: BlockId, to: BlockId) {
+        self.blocks[from].successors.push(to);
+    }
+
+    fn push_node(&mut self, node: CfgNode<'a>) {
+        self.blocks[self.current].nodes.push(node);
+    }
+
+    /// starts a new block that `current` falls through to
+    fn start_block(&mut self) -> BlockId {
+        let block = self.new_block();
+        self.add_edge(self.current, block);
+        self.current = block;
+        block
+    }
+
+    fn build_stmt(&mut self, stmt: &'a AstNode<Stmt>) {
+        match &stmt.node {
+            Stmt::ExprStmtNode(expr_stmt) => match &expr_stmt.expr.node {
+                Expr::If(if_expr) => self.build_if(if_expr),
+                Expr::Block(block) => self.build_block(block),
+                _ => self.push_node(CfgNode::Stmt(stmt)),
+            },
+            Stmt::FunDecl(fun_decl) => {
+                self.functions.push(fun_decl);
+                self.push_node(CfgNode::Stmt(stmt));
+            }
+            Stmt::While(while_stmt) => self.build_loop(&while_stmt.condition, &while_stmt.body.node, None),
+            Stmt::For(for_stmt) => {
+                if let Some(initializer) = &for_stmt.initializer {
+                    self.build_stmt(initializer);
+                }
+                self.build_loop(&for_stmt.condition, &for_stmt.body.node, for_stmt.increment.as_ref());
+            }
+            Stmt::Return(_) => {
+                self.push_node(CfgNode::Stmt(stmt));
+                self.add_edge(self.current, self.exit);
+                // code after a return lives in a block without predecessors
+                self.current = self.new_block();
+            }
+            Stmt::VarDecl(_) | Stmt::StructDecl(_) | Stmt::TypeAlias(_) => self.push_node(CfgNode::Stmt(stmt)),
+        }
+    }
+
+    fn build_loop(&mut self, condition: &'a AstNode<Expr>, body: &'a BlockExpr, increment: Option<&'a AstNode<Expr>>) {
+        let header = self.start_block();
+        self.push_node(CfgNode::Expr(condition));
+
+        self.start_block();
+        self.build_block(body);
+        if let Some(increment) = increment {
+            self.push_node(CfgNode::Expr(increment));
+        }
+        self.add_edge(self.current, header);
+
+        self.loops.push(Loop {
+            condition,
+            header,
+            body: header + 1..self.blocks.len(),
+        });
+        self.current = header;
+        self.start_block();
+    }
+
+    fn build_block(&mut self, block: &'a BlockExpr) {
+        for stmt in &block.statements {
+            self.build_stmt(stmt);
+        }
+        if let Some(tail) = &block.expr {
+            self.build_tail_expr(tail);
+        }
+    }
+
+    fn build_tail_expr(&mut self, expr: &'a AstNode<Expr>) {
+        match &expr.node {
+            Expr::If(if_expr) => self.build_if(if_expr),
+            Expr::Block(block) => self.build_block(block),
+            _ => self.push_node(CfgNode::Expr(expr)),
+        }
+    }
+
+    fn build_if(&mut self, if_expr: &'a IfExpr) {
+        self.push_node(CfgNode::Expr(&if_expr.condition));
+        let condition = self.current;
+        let join = self.new_block();
+
+        self.start_block();
+        self.build_block(&if_expr.then_branch.node);
+        self.add_edge(self.current, join);
+
+        self.current = condition;
+        if let Some(else_branch) = &if_expr.else_branch {
+            self.start_block();
+            self.build_block(&else_branch.node);
+        }
+        self.add_edge(self.current, join);
+
+        self.current = join;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+/// a dataflow analysis over a [`ControlFlowGraph`], solved with [`solve`]
+pub trait Analysis<'a> {
+    type Fact: Clone + PartialEq;
+
+    const DIRECTION: Direction;
+
+    /// initial fact of every block
+    fn bottom(&self) -> Self::Fact;
+
+    /// fact at the entry block (forward) or the exit block (backward)
+    fn boundary(&self) -> Self::Fact;
+
+    fn join(&self, left: &Self::Fact, right: &Self::Fact) -> Self::Fact;
+
+    fn transfer(&self, node: &CfgNode<'a>, fact: &Self::Fact) -> Self::Fact;
+}
+
+/// facts at the start and end of each block in program order, regardless of the direction
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataflowResult<F> {
+    pub before: Vec<F>,
+    pub after: Vec<F>,
+}
+
+pub fn solve<'a, A: Analysis<'a>>(cfg: &ControlFlowGraph<'a>, analysis: &A) -> DataflowResult<A::Fact> {
+    let predecessors = cfg.predecessors();
+    let mut before = vec![analysis.bottom(); cfg.blocks.len()];
+    let mut after = vec![analysis.bottom(); cfg.blocks.len()];
+
+    let mut worklist: VecDeque<BlockId> = (0..cfg.blocks.len()).collect();
+    while let Some(id) = worklist.pop_front() {
+        let block = &cfg.blocks[id];
+
+        match A::DIRECTION {
+            Direction::Forward => {
+                let input = if id == cfg.entry {
+                    analysis.boundary()
+                } else {
+                    predecessors[id]
+                        .iter()
+                        .fold(analysis.bottom(), |fact, &pred| analysis.join(&fact, &after[pred]))
+                };
+                let output = block.nodes.iter().fold(input.clone(), |fact, node| analysis.transfer(node, &fact));
+                before[id] = input;
+                if output != after[id] {
+                    after[id] = output;
+                    worklist.extend(&block.successors);
+                }
+            }
+            Direction::Backward => {
+                let input = if id == cfg.exit {
+                    analysis.boundary()
+                } else {
+                    block
+                        .successors
+                        .iter()
+                        .fold(analysis.bottom(), |fact, &succ| analysis.join(&fact, &before[succ]))
+                };
+                let output = block
+                    .nodes
+                    .iter()
+                    .rev()
+                    .fold(input.clone(), |fact, node| analysis.transfer(node, &fact));
+                after[id] = input;
+                if output != before[id] {
+                    before[id] = output;
+                    worklist.extend(&predecessors[id]);
+                }
+            }
+        }
+    }
+
+    DataflowResult { before, after }
+}
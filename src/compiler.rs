@@ -0,0 +1,148 @@
+use crate::ast::Program;
+use crate::attributes::AttributeRegistry;
+use crate::resolver::Bindings;
+use crate::type_inferrer::{SymbolTable, Type, TypeVarId};
+use crate::{Lexer, Parser, Resolver, TypeInferrer};
+use miette::{Report, Severity};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// a source after the front end, the diagnostics of all phases that ran and the program if they found no errors
+#[derive(Debug)]
+pub struct Compilation {
+    /// the prelude followed by the checked source, the text the diagnostics' spans refer to
+    pub source: String,
+    /// where the checked source starts in `source`
+    pub prelude_len: usize,
+    pub diagnostics: Vec<Report>,
+    pub program: Option<CheckedProgram>,
+}
+
+/// everything the interpreter needs to run a program, immutable so threads can share it
+#[derive(Debug)]
+pub struct CheckedProgram {
+    pub program: Program,
+    pub type_env: HashMap<TypeVarId, Type>,
+    pub bindings: Bindings,
+    pub symbols: SymbolTable,
+}
+
+/// checks sources once and hands out the cached compilation for the same source again, clones share the cache so
+/// it can be used from several threads, e.g. by a web service checking the same snippets over and over
+#[derive(Debug, Clone, Default)]
+pub struct Compiler {
+    cache: Arc<Mutex<HashMap<u64, Arc<Compilation>>>>,
+    prelude: String,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// declarations every checked source can use, it is put in front of the source like a loaded session, it is
+    /// checked again with every source that isn't cached yet, the cache only saves checking the same source twice
+    pub fn with_prelude(mut self, prelude: &str) -> Self {
+        self.prelude = format!("{prelude}\n");
+        self
+    }
+
+    /// sources are checked without holding the lock, when two threads check the same source the first result is kept
+    pub fn check(&self, source: &str) -> Arc<Compilation> {
+        let source = format!("{}{source}", self.prelude);
+        let key = hash(&source);
+        if let Some(compilation) = self.cache.lock().unwrap().get(&key) {
+            // a hash collision is checked again and replaces the cached compilation
+            if compilation.source == source {
+                return compilation.clone();
+            }
+        }
+
+        let compilation = Arc::new(compile(&source, self.prelude.len()));
+        let mut cache = self.cache.lock().unwrap();
+        let cached = cache.entry(key).or_insert_with(|| compilation.clone());
+        if cached.source != source {
+            *cached = compilation;
+        }
+        cached.clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
+
+fn hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn has_errors(diagnostics: &[Report]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity().unwrap_or(Severity::Error) == Severity::Error)
+}
+
+/// the phases `main` runs before linting, stopping after the first with errors, lexing and parsing count as one
+fn compile(source: &str, prelude_len: usize) -> Compilation {
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.lex().tokens;
+    let mut parser = Parser::new(tokens, source.to_string());
+    let program = parser.parse().ast;
+    // the tokens borrow the lexer until the parser is gone
+    let parse_errors = parser.into_errors();
+    let mut diagnostics = lexer.into_errors();
+    diagnostics.extend(parse_errors);
+    if !has_errors(&diagnostics) {
+        diagnostics.extend(AttributeRegistry::new().check(&program, source));
+    }
+    if has_errors(&diagnostics) {
+        return Compilation {
+            source: source.to_string(),
+            prelude_len,
+            diagnostics,
+            program: None,
+        };
+    }
+
+    let mut resolver = Resolver::new(&program, source.to_string());
+    resolver.resolve();
+    let bindings = resolver.bindings().clone();
+    diagnostics.extend(resolver.into_errors());
+    if has_errors(&diagnostics) {
+        return Compilation {
+            source: source.to_string(),
+            prelude_len,
+            diagnostics,
+            program: None,
+        };
+    }
+
+    let mut type_inferrer = TypeInferrer::new(&program, source.to_string());
+    let type_env = type_inferrer.infer().type_env.clone();
+    let symbols = type_inferrer.symbols();
+    diagnostics.extend(type_inferrer.into_errors());
+    let checked = CheckedProgram {
+        type_env,
+        bindings,
+        symbols,
+        program,
+    };
+
+    Compilation {
+        source: source.to_string(),
+        prelude_len,
+        program: (!has_errors(&diagnostics)).then_some(checked),
+        diagnostics,
+    }
+}
@@ -1,7 +1,7 @@
 use crate::TokenKind;
 use crate::interpreters::ControlFlow;
 use crate::type_inferrer::Type;
-use miette::{Diagnostic, SourceSpan, diagnostic};
+use miette::{Diagnostic, LabeledSpan, SourceSpan};
 use thiserror::Error;
 
 #[derive(Debug)]
@@ -33,6 +33,18 @@ pub enum RuntimeError {
         span: SourceSpan,
     },
 
+    #[error("Cannot concatenate String with {type_name}")]
+    #[diagnostic(help("Convert the value with `toString` first"), code(runtime::invalid_concatenation))]
+    InvalidConcatenation {
+        #[source_code]
+        src: String,
+
+        #[label("mixed concatenation here")]
+        span: SourceSpan,
+
+        type_name: String,
+    },
+
     #[error("Index out of bounds: {index} (length: {length})")]
     #[diagnostic(help("Array index is outside the valid range"), code(runtime::index_out_of_bounds))]
     IndexOutOfBounds {
@@ -45,10 +57,163 @@ pub enum RuntimeError {
         index: usize,
         length: usize,
     },
+
+    #[error("Invalid pattern")]
+    #[diagnostic(help("{message}"), code(runtime::invalid_pattern))]
+    InvalidPattern {
+        #[source_code]
+        src: String,
+
+        #[label("pattern used here")]
+        span: SourceSpan,
+
+        message: String,
+    },
+
+    #[error("Invalid time")]
+    #[diagnostic(help("{message}"), code(runtime::invalid_time))]
+    InvalidTime {
+        #[source_code]
+        src: String,
+
+        #[label("time used here")]
+        span: SourceSpan,
+
+        message: String,
+    },
+
+    #[error("Cannot cast '{value}' to {target}")]
+    #[diagnostic(help("The value has no {target} representation"), code(runtime::invalid_cast))]
+    InvalidCast {
+        #[source_code]
+        src: String,
+
+        #[label("cast here")]
+        span: SourceSpan,
+
+        value: String,
+        target: String,
+    },
+
+    #[error("Cannot call '{found}', it is not a function")]
+    #[diagnostic(help("Only function values can be called"), code(runtime::not_callable))]
+    NotCallable {
+        #[source_code]
+        src: String,
+
+        #[label("called here")]
+        span: SourceSpan,
+
+        found: String,
+    },
+
+    #[error("Wrong number of arguments: expected {expected}, found {found}")]
+    #[diagnostic(help("The function takes {expected} arguments"), code(runtime::wrong_argument_count))]
+    WrongArgumentCount {
+        #[source_code]
+        src: String,
+
+        #[label("called here")]
+        span: SourceSpan,
+
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("Argument {position} must be {expected}, found '{found}'")]
+    #[diagnostic(help("Pass a value of the parameter's type"), code(runtime::argument_type_mismatch))]
+    ArgumentTypeMismatch {
+        #[source_code]
+        src: String,
+
+        #[label("called here")]
+        span: SourceSpan,
+
+        position: usize,
+        expected: String,
+        found: String,
+    },
+
+    #[error("Cannot suspend inside an expression")]
+    #[diagnostic(
+        help("Host calls that return a pending result must be a top-level statement or variable initializer"),
+        code(runtime::suspend_in_expression)
+    )]
+    SuspendInExpression {
+        #[source_code]
+        src: String,
+
+        #[label("pending host call here")]
+        span: SourceSpan,
+    },
+
+    #[error("Expected a single expression")]
+    #[diagnostic(
+        help("Only programs parsed with `Parser::parse_expression` can be evaluated"),
+        code(runtime::not_an_expression)
+    )]
+    NotAnExpression {
+        #[source_code]
+        src: String,
+
+        #[label("this is not an expression")]
+        span: SourceSpan,
+    },
+
+    #[error("Return statement used outside of a function")]
+    #[diagnostic(
+        help("Return statements can only be used inside functions"),
+        code(runtime::return_outside_function)
+    )]
+    ReturnOutsideFunction {
+        #[source_code]
+        src: String,
+
+        #[label("returns from here")]
+        span: SourceSpan,
+    },
+}
+
+impl RuntimeError {
+    /// points an error raised by a native function, which knows no source, at the call, an error of script code the
+    /// native called back into already points at its source and is kept
+    pub fn at(mut self, source: String, location: SourceSpan) -> Self {
+        match &mut self {
+            RuntimeError::UnprintableValue { src, span, .. }
+            | RuntimeError::DivisionByZero { src, span }
+            | RuntimeError::InvalidConcatenation { src, span, .. }
+            | RuntimeError::IndexOutOfBounds { src, span, .. }
+            | RuntimeError::InvalidPattern { src, span, .. }
+            | RuntimeError::InvalidTime { src, span, .. }
+            | RuntimeError::InvalidCast { src, span, .. }
+            | RuntimeError::NotCallable { src, span, .. }
+            | RuntimeError::WrongArgumentCount { src, span, .. }
+            | RuntimeError::ArgumentTypeMismatch { src, span, .. }
+            | RuntimeError::SuspendInExpression { src, span }
+            | RuntimeError::NotAnExpression { src, span }
+            | RuntimeError::ReturnOutsideFunction { src, span } => {
+                if src.is_empty() {
+                    *src = source;
+                    *span = location;
+                }
+            }
+        }
+        self
+    }
 }
 
 #[derive(Debug, Error, Diagnostic)]
 pub enum TypeInferrerError {
+    #[error("how the types in the previous error were inferred")]
+    #[diagnostic(code(type_inferrer::explanation), severity(Advice))]
+    TypeExplanation {
+        #[source_code]
+        src: String,
+
+        #[label(collection)]
+        steps: Vec<LabeledSpan>,
+    },
+
     #[error("Cannot declare struct '{name}' with duplicate field names")]
     #[diagnostic(help("Struct fields must have unique names"), code(type_inferrer::duplicate_field_on_declaration))]
     DuplicateFieldDeclaration {
@@ -108,7 +273,7 @@ pub enum TypeInferrerError {
         field: String,
         struct_name: String,
     },
-    #[error("Type mismatch: expected {expected:?}, found {found:?}")]
+    #[error("Type mismatch: expected {expected}, found {found}")]
     #[diagnostic(help("The types don't match"), code(type_inferrer::type_mismatch))]
     TypeMismatch {
         #[source_code]
@@ -121,6 +286,126 @@ pub enum TypeInferrerError {
         found: Type,
     },
 
+    #[error("'{operator}' is not valid for every member of {union}")]
+    #[diagnostic(
+        help("Check the type with `typeOf` first to narrow it"),
+        code(type_inferrer::invalid_union_operand)
+    )]
+    InvalidUnionOperand {
+        #[source_code]
+        src: String,
+
+        #[label("operand of a union type")]
+        span: SourceSpan,
+
+        operator: String,
+        union: Type,
+    },
+
+    #[error("Type mismatch: {member} of {union} is not {expected}")]
+    #[diagnostic(
+        help("Check the type with `typeOf` first to narrow it"),
+        code(type_inferrer::union_member_mismatch)
+    )]
+    UnionMemberMismatch {
+        #[source_code]
+        src: String,
+
+        #[label("union used here")]
+        span: SourceSpan,
+
+        union: Type,
+        member: Type,
+        expected: Type,
+    },
+
+    #[error("Cannot cast {from} to {to}")]
+    #[diagnostic(
+        help("Only Int, Float, String and Bool convert into each other, and a union into one of its members"),
+        code(type_inferrer::invalid_cast)
+    )]
+    InvalidCast {
+        #[source_code]
+        src: String,
+
+        #[label("invalid cast")]
+        span: SourceSpan,
+
+        from: Type,
+        to: Type,
+    },
+
+    #[error("casting a value of type {ty} to its own type")]
+    #[diagnostic(help("remove the cast"), code(type_inferrer::pointless_cast), severity(Warning))]
+    PointlessCast {
+        #[source_code]
+        src: String,
+
+        #[label("this cast does nothing")]
+        span: SourceSpan,
+
+        ty: Type,
+    },
+
+    #[error("this division will always fail at runtime")]
+    #[diagnostic(help("the divisor is a literal zero"), code(type_inferrer::division_by_zero), severity(Warning))]
+    DivisionByZero {
+        #[source_code]
+        src: String,
+
+        #[label("dividing by zero here")]
+        span: SourceSpan,
+    },
+
+    #[error("this comparison is always {value}")]
+    #[diagnostic(
+        help("both operands are literals, the result doesn't depend on the program"),
+        code(type_inferrer::constant_comparison),
+        severity(Warning)
+    )]
+    ConstantComparison {
+        #[source_code]
+        src: String,
+
+        #[label("always {value}")]
+        span: SourceSpan,
+
+        value: bool,
+    },
+
+    #[error("index {index} is out of range for a vec of length {length}")]
+    #[diagnostic(
+        help("the call will always fail at runtime"),
+        code(type_inferrer::index_out_of_range),
+        severity(Warning)
+    )]
+    LiteralIndexOutOfRange {
+        #[source_code]
+        src: String,
+
+        #[label("out of range")]
+        span: SourceSpan,
+
+        index: i64,
+        length: usize,
+    },
+
+    #[error("Cannot add {found} to a String")]
+    #[diagnostic(
+        help("Strings are not converted implicitly, use `toString({operand})`"),
+        code(type_inferrer::string_concatenation)
+    )]
+    StringConcatenation {
+        #[source_code]
+        src: String,
+
+        #[label("this is {found}, not String")]
+        span: SourceSpan,
+
+        found: Type,
+        operand: String,
+    },
+
     #[error("Type annotations needed for '{name}'")]
     #[diagnostic(help("Variable needs an initial value or type annotation"), code(type_inferrer::cannot_infer_type))]
     CannotInferType {
@@ -138,13 +423,16 @@ pub enum TypeInferrerError {
         #[source_code]
         src: String,
 
-        #[label("incorrect number of arguments")]
+        #[label(primary, "incorrect number of arguments")]
         span: SourceSpan,
 
+        #[label("function declared here")]
+        declaration: Option<SourceSpan>,
+
         expected: usize,
         found: usize,
     },
-    #[error("Cannot call non-function type '{found:?}'")]
+    #[error("Cannot call non-function type '{found}'")]
     #[diagnostic(
         help("This value is not callable - only functions can be called"),
         code(type_inferrer::not_callable)
@@ -159,7 +447,7 @@ pub enum TypeInferrerError {
         found: Type,
     },
 
-    #[error("Condition must be boolean")]
+    #[error("Condition must be boolean, found {found}")]
     #[diagnostic(
         help("If conditions, while loops, and other conditionals require boolean expressions"),
         code(type_inferrer::non_boolean_condition)
@@ -168,13 +456,13 @@ pub enum TypeInferrerError {
         #[source_code]
         src: String,
 
-        #[label("non-boolean condition here")]
+        #[label("this condition has type {found}")]
         span: SourceSpan,
 
         found: Type,
     },
 
-    #[error("Method '{method}' does not exist on type {base_type:?}")]
+    #[error("Method '{method}' does not exist on type {base_type}")]
     #[diagnostic(help("This type doesn't have the requested method"), code(type_inferrer::unknown_method))]
     UnknownMethod {
         #[source_code]
@@ -190,6 +478,10 @@ pub enum TypeInferrerError {
 
 #[derive(Debug, Error, Diagnostic)]
 pub enum ResolverError {
+    #[error("Resolving was cancelled")]
+    #[diagnostic(code(resolver::cancelled))]
+    Cancelled,
+
     #[error("'{name}' is not a struct")]
     #[diagnostic(code(resolver::not_a_struct))]
     NotAStruct {
@@ -269,8 +561,11 @@ pub enum ResolverError {
         #[source_code]
         src: String,
 
-        #[label("duplicate parameter name")]
+        #[label(primary, "duplicate parameter name")]
         span: SourceSpan,
+
+        #[label("previous parameter here")]
+        previous: SourceSpan,
     },
 
     #[error("Cannot declare function '{function_name}' with duplicate parameter names")]
@@ -279,9 +574,12 @@ pub enum ResolverError {
         #[source_code]
         src: String,
 
-        #[label("duplicate parameter name")]
+        #[label(primary, "duplicate parameter name")]
         span: SourceSpan,
 
+        #[label("previous parameter here")]
+        previous: SourceSpan,
+
         function_name: String,
     },
     #[error("Function '{name}' is already defined")]
@@ -290,9 +588,12 @@ pub enum ResolverError {
         #[source_code]
         src: String,
 
-        #[label("function already defined")]
+        #[label(primary, "function already defined")]
         span: SourceSpan,
 
+        #[label("previous declaration here")]
+        previous: Option<SourceSpan>,
+
         name: String,
     },
 
@@ -302,131 +603,610 @@ pub enum ResolverError {
         #[source_code]
         src: String,
 
-        #[label("struct already defined")]
+        #[label(primary, "struct already defined")]
         span: SourceSpan,
 
+        #[label("previous declaration here")]
+        previous: Option<SourceSpan>,
+
         name: String,
     },
-}
 
-#[derive(Debug, Error, Diagnostic)]
-pub enum ParseError {
-    #[error("Expected identifier")]
-    #[diagnostic(code(parser::expected_identifier), help("Expected {context} name here"))]
-    ExpectedIdentifier {
+    #[error("Type '{name}' is already defined")]
+    #[diagnostic(help("A type alias or struct with this name already exists"), code(resolver::duplicate_type_alias))]
+    DuplicateTypeAlias {
         #[source_code]
         src: String,
 
-        #[label("expected identifier here")]
+        #[label(primary, "type already defined")]
         span: SourceSpan,
 
-        context: String,
+        #[label("previous declaration here")]
+        previous: Option<SourceSpan>,
+
+        name: String,
     },
 
-    #[error("Expected block")]
-    #[diagnostic(code(parser::missing_block), help("Expected a block enclosed in braces"))]
-    MissingBlock {
+    #[error("Type alias '{name}' refers to itself")]
+    #[diagnostic(
+        help("An alias is expanded where it is used, so it can't contain itself"),
+        code(resolver::recursive_type_alias)
+    )]
+    RecursiveTypeAlias {
         #[source_code]
         src: String,
 
-        #[label("expected block here")]
+        #[label("recursive type alias")]
         span: SourceSpan,
+
+        name: String,
     },
 
-    #[error("Expected {expected}, found {found:?}")]
-    #[diagnostic(help("The parser expected a different token here."), code(parser::unexpected_token))]
-    UnexpectedToken {
+    #[error("Undefined type '{name}'")]
+    #[diagnostic(help("Declare it with `type {name} = ...;`"), code(resolver::undefined_type))]
+    UndefinedType {
         #[source_code]
         src: String,
 
-        #[label("unexpected token found here")]
+        #[label("undefined type used here")]
         span: SourceSpan,
 
-        expected: String,
-        found: TokenKind,
+        name: String,
     },
-    #[error("Missing semicolon")]
-    #[diagnostic(help("statements must end with a semicolon (`;`)."), code(parser::missing_semicolon))]
-    MissingSemicolon {
+
+    #[error("assignment used as a condition")]
+    #[diagnostic(
+        help("use `==` to compare instead of assigning"),
+        code(resolver::assignment_in_condition),
+        severity(Warning)
+    )]
+    AssignmentInCondition {
         #[source_code]
         src: String,
 
-        #[label("expected ';' here")]
+        #[label("help: replace with `==`")]
         span: SourceSpan,
+
+        suggestion: Suggestion,
     },
+}
 
-    #[error("unnecessary trailing semicolon")]
-    #[diagnostic(help("help: remove this semicolon"), code(parser::redundant_semicolon), severity(Warning))]
-    RedundantSemicolon {
-        #[source_code]
-        src: String,
+/// an edit that fixes a diagnostic and can be applied as is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub span: SourceSpan,
+    pub replacement: String,
+}
 
-        #[label("help: remove this semicolon")]
-        span: SourceSpan,
-    },
+impl ParseError {
+    pub fn suggestion(&self) -> Option<&Suggestion> {
+        match self {
+            ParseError::MissingComma { suggestion, .. } => Some(suggestion),
+            _ => None,
+        }
+    }
+}
 
-    #[error("unnecessary parenthesis")]
-    #[diagnostic(help("these parentheses are not needed"), code(parser::redundant_parenthesis), severity(Warning))]
-    RedundantParenthesis {
+impl ResolverError {
+    pub fn suggestion(&self) -> Option<&Suggestion> {
+        match self {
+            ResolverError::AssignmentInCondition { suggestion, .. } => Some(suggestion),
+            _ => None,
+        }
+    }
+}
+
+impl LintWarning {
+    pub fn suggestion(&self) -> Option<&Suggestion> {
+        match self {
+            LintWarning::BoolComparison { suggestion, .. }
+            | LintWarning::NegatedComparison { suggestion, .. }
+            | LintWarning::IfReturnsCondition { suggestion, .. }
+            | LintWarning::EmptyBranch { suggestion, .. }
+            | LintWarning::MagicNumber { suggestion, .. } => Some(suggestion),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum LintWarning {
+    #[error("function '{name}' is never called")]
+    #[diagnostic(
+        help("it is not reachable from top-level code or `main`, remove it if it is not needed"),
+        code(lint::unreachable_function),
+        severity(Warning)
+    )]
+    UnreachableFunction {
         #[source_code]
         src: String,
 
-        #[label("opening")]
-        first: SourceSpan,
+        #[label("unreachable function")]
+        span: SourceSpan,
 
-        #[label("closing")]
-        second: SourceSpan,
+        name: String,
     },
 
-    #[error("Expected {expected:?}, found EOF")]
-    #[diagnostic(help("Complete the expression"), code(parser::unexpected_eof))]
-    UnexpectedEOF {
+    #[error("this loop never terminates")]
+    #[diagnostic(
+        help("the condition is always true and the body never returns"),
+        code(lint::infinite_loop),
+        severity(Warning)
+    )]
+    InfiniteLoop {
         #[source_code]
         src: String,
 
-        expected: String,
+        #[label("condition is always true")]
+        span: SourceSpan,
     },
 
-    #[error("Unmatched delimiter")]
-    #[diagnostic(help("expected {expected:?}, found {found:?}"), code(parser::unmatched_delimiter))]
-    UnmatchedDelimiter {
+    #[error("this loop never runs")]
+    #[diagnostic(code(lint::loop_never_runs), severity(Warning))]
+    LoopNeverRuns {
         #[source_code]
         src: String,
 
-        #[label("opening delimiter here")]
-        opening_span: SourceSpan,
-
-        #[label("mismatched closing delimiter here")]
-        closing_span: SourceSpan,
-
-        expected: TokenKind,
-        found: TokenKind,
+        #[label("condition is always false")]
+        span: SourceSpan,
     },
 
-    #[error("unclosed delimiter")]
-    #[diagnostic(code(parse::unclosed_delimiter), help("missing closing {delimiter:?}"))]
-    UnclosedDelimiter {
+    #[error("loop condition never changes")]
+    #[diagnostic(
+        help("{names} is never modified in the loop body"),
+        code(lint::loop_invariant_condition),
+        severity(Warning)
+    )]
+    LoopInvariantCondition {
         #[source_code]
         src: String,
 
-        #[label("unclosed delimiter here")]
+        #[label("this condition has the same value in every iteration")]
         span: SourceSpan,
 
-        delimiter: TokenKind,
+        names: String,
     },
 
-    #[error("unexpected closing delimiter: '{delimiter:?}'")]
-    #[diagnostic(help("I have no clue which error message"), code(parser::unexpected_closing_delimiter))]
-    UnexpectedClosingDelimiter {
+    #[error("function '{name}' depends on global state")]
+    #[diagnostic(
+        help("pass the values as parameters and return the results to make the coupling visible"),
+        code(lint::global_state),
+        severity(Warning)
+    )]
+    GlobalState {
         #[source_code]
         src: String,
 
-        #[label("no matching opening delimiter")]
+        #[label("{globals}")]
         span: SourceSpan,
+
+        name: String,
+        globals: String,
+    },
+
+    #[error("'{name}' is a contextual keyword")]
+    #[diagnostic(
+        help("`{name}` is still accepted as a name, but reads like the syntax it introduces, consider renaming it, e.g. to `{name}_`"),
+        code(lint::contextual_keyword_name),
+        severity(Warning)
+    )]
+    ContextualKeywordName {
+        #[source_code]
+        src: String,
+
+        #[label("named like the `{name}` keyword")]
+        span: SourceSpan,
+
+        name: String,
+    },
+
+    #[error("closure captures loop variable '{name}'")]
+    #[diagnostic(
+        help(
+            "all iterations share `{name}`, so the closure sees its latest value, copy it into a variable declared in the loop body and capture that"
+        ),
+        code(lint::loop_variable_capture),
+        severity(Warning)
+    )]
+    LoopVariableCapture {
+        #[source_code]
+        src: String,
+
+        #[label("captured here, it changes every iteration")]
+        span: SourceSpan,
+
+        name: String,
+    },
+
+    #[error("comparison with a boolean literal")]
+    #[diagnostic(help("replace it with `{}`", suggestion.replacement), code(lint::bool_comparison), severity(Warning))]
+    BoolComparison {
+        #[source_code]
+        src: String,
+
+        #[label("the comparison is not needed")]
+        span: SourceSpan,
+
+        suggestion: Suggestion,
+    },
+
+    #[error("negated comparison")]
+    #[diagnostic(help("replace it with `{}`", suggestion.replacement), code(lint::negated_comparison), severity(Warning))]
+    NegatedComparison {
+        #[source_code]
+        src: String,
+
+        #[label("compare with the opposite operator instead")]
+        span: SourceSpan,
+
+        suggestion: Suggestion,
+    },
+
+    #[error("`if` turns its condition into a boolean")]
+    #[diagnostic(help("replace it with `{}`", suggestion.replacement), code(lint::if_returns_condition), severity(Warning))]
+    IfReturnsCondition {
+        #[source_code]
+        src: String,
+
+        #[label("the condition already is this boolean")]
+        span: SourceSpan,
+
+        suggestion: Suggestion,
+    },
+
+    #[error("empty `{branch}` branch")]
+    #[diagnostic(help("replace the `if` with `{}`", suggestion.replacement), code(lint::empty_branch), severity(Warning))]
+    EmptyBranch {
+        #[source_code]
+        src: String,
+
+        #[label("this `if` has an empty `{branch}` branch")]
+        span: SourceSpan,
+
+        branch: String,
+        suggestion: Suggestion,
+    },
+
+    #[error("call of `print`")]
+    #[diagnostic(
+        help("printing is linted in this program, remove the call or allow printing in this file with `--allow-print FILE`"),
+        code(lint::print_call),
+        severity(Warning)
+    )]
+    PrintCall {
+        #[source_code]
+        src: String,
+
+        #[label("prints to stdout")]
+        span: SourceSpan,
+    },
+
+    #[error("magic number {number}")]
+    #[diagnostic(
+        help("give it a name, declare `let NAME = {number};` at the top of {function} and use `NAME` here"),
+        code(lint::magic_number),
+        severity(Warning)
+    )]
+    MagicNumber {
+        #[source_code]
+        src: String,
+
+        #[label("unnamed number")]
+        span: SourceSpan,
+
+        number: String,
+        function: String,
+        /// the declaration, `NAME` still has to be renamed and used here
+        suggestion: Suggestion,
+    },
+
+    #[error("parameter '{name}' is never used")]
+    #[diagnostic(
+        help("if it is unused on purpose, e.g. to match a callback's signature, name it `_{name}`"),
+        code(lint::unused_parameter),
+        severity(Warning)
+    )]
+    UnusedParameter {
+        #[source_code]
+        src: String,
+
+        #[label("never read in the body")]
+        span: SourceSpan,
+
+        name: String,
+    },
+
+    #[error("variable '{name}' is never read")]
+    #[diagnostic(
+        help("remove it, or name it `_{name}` if it is unused on purpose"),
+        code(lint::unused_variable),
+        severity(Warning)
+    )]
+    UnusedVariable {
+        #[source_code]
+        src: String,
+
+        #[label("never read after this")]
+        span: SourceSpan,
+
+        name: String,
+    },
+
+    #[error("function '{name}' is {lines} lines long")]
+    #[diagnostic(
+        help("the limit is {limit} lines, move parts of it into smaller functions"),
+        code(lint::function_too_long),
+        severity(Warning)
+    )]
+    FunctionTooLong {
+        #[source_code]
+        src: String,
+
+        #[label("this function")]
+        span: SourceSpan,
+
+        name: String,
+        lines: usize,
+        limit: usize,
+    },
+
+    #[error("function '{name}' has {count} parameters")]
+    #[diagnostic(
+        help("the limit is {limit}, pass related values together in a struct"),
+        code(lint::too_many_parameters),
+        severity(Warning)
+    )]
+    TooManyParameters {
+        #[source_code]
+        src: String,
+
+        #[label("this function")]
+        span: SourceSpan,
+
+        name: String,
+        count: usize,
+        limit: usize,
+    },
+
+    #[error("function '{name}' has a cyclomatic complexity of {complexity}")]
+    #[diagnostic(
+        help("the limit is {limit}, every `if`, loop, `and` and `or` adds a path through the function, move some into helper functions"),
+        code(lint::function_too_complex),
+        severity(Warning)
+    )]
+    FunctionTooComplex {
+        #[source_code]
+        src: String,
+
+        #[label("this function")]
+        span: SourceSpan,
+
+        name: String,
+        complexity: usize,
+        limit: usize,
+    },
+
+    #[error("suppression of '{name}' silences nothing")]
+    #[diagnostic(
+        help("the statement it applies to has no `{name}` warning, remove the suppression"),
+        code(lint::unused_suppression),
+        severity(Warning)
+    )]
+    UnusedSuppression {
+        #[source_code]
+        src: String,
+
+        #[label("unused suppression")]
+        span: SourceSpan,
+
+        name: String,
+    },
+}
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum ParseError {
+    #[error("Parsing was cancelled")]
+    #[diagnostic(code(parser::cancelled))]
+    Cancelled,
+
+    #[error("Expected identifier")]
+    #[diagnostic(code(parser::expected_identifier), help("Expected {context} name here"))]
+    ExpectedIdentifier {
+        #[source_code]
+        src: String,
+
+        #[label("expected identifier here")]
+        span: SourceSpan,
+
+        context: String,
+    },
+
+    #[error("Expected block")]
+    #[diagnostic(code(parser::missing_block), help("Expected a block enclosed in braces"))]
+    MissingBlock {
+        #[source_code]
+        src: String,
+
+        #[label("expected block here")]
+        span: SourceSpan,
+    },
+
+    #[error("Expected {expected}, found {found}")]
+    #[diagnostic(help("The parser expected a different token here."), code(parser::unexpected_token))]
+    UnexpectedToken {
+        #[source_code]
+        src: String,
+
+        #[label("unexpected token found here")]
+        span: SourceSpan,
+
+        expected: String,
+        found: TokenKind,
+    },
+    #[error("Missing semicolon")]
+    #[diagnostic(help("statements must end with a semicolon (`;`)."), code(parser::missing_semicolon))]
+    MissingSemicolon {
+        #[source_code]
+        src: String,
+
+        #[label("expected ';' here")]
+        span: SourceSpan,
+    },
+
+    #[error("statement ended by a line break")]
+    #[diagnostic(help("end the statement with `;`"), code(parser::implicit_semicolon), severity(Warning))]
+    ImplicitSemicolon {
+        #[source_code]
+        src: String,
+
+        #[label("`;` assumed here")]
+        span: SourceSpan,
+    },
+
+    #[error("Missing comma between {context}")]
+    #[diagnostic(help("insert `{}` here", suggestion.replacement), code(parser::missing_comma))]
+    MissingComma {
+        #[source_code]
+        src: String,
+
+        #[label("expected ',' here")]
+        span: SourceSpan,
+
+        context: String,
+        suggestion: Suggestion,
+    },
+
+    #[error("unnecessary trailing semicolon")]
+    #[diagnostic(help("help: remove this semicolon"), code(parser::redundant_semicolon), severity(Warning))]
+    RedundantSemicolon {
+        #[source_code]
+        src: String,
+
+        #[label("help: remove this semicolon")]
+        span: SourceSpan,
+    },
+
+    #[error("attributes can only be written before a declaration")]
+    #[diagnostic(help("put it before a `let`, `fn`, `struct` or `type`"), code(parser::misplaced_attribute))]
+    MisplacedAttribute {
+        #[source_code]
+        src: String,
+
+        #[label("not followed by a declaration")]
+        span: SourceSpan,
+    },
+
+    #[error("attribute arguments must be literals")]
+    #[diagnostic(help("write a string, number, bool or `nil`"), code(parser::invalid_attribute_argument))]
+    InvalidAttributeArgument {
+        #[source_code]
+        src: String,
+
+        #[label("not a literal")]
+        span: SourceSpan,
+    },
+
+    #[error("more than {limit} syntax nodes")]
+    #[diagnostic(help("split the program or raise the limit"), code(parser::too_many_nodes))]
+    TooManyNodes {
+        #[source_code]
+        src: String,
+
+        #[label("parsing stopped after this statement")]
+        span: SourceSpan,
+
+        limit: usize,
+    },
+
+    #[error("More than {limit} {context}")]
+    #[diagnostic(help("pass the values in a vec or struct instead"), code(parser::too_many_arguments))]
+    TooManyArguments {
+        #[source_code]
+        src: String,
+
+        #[label("this is one too many")]
+        span: SourceSpan,
+
+        limit: usize,
+        context: String,
+    },
+
+    #[error("trailing comma")]
+    #[diagnostic(
+        help("remove the comma before the closing delimiter"),
+        code(parser::trailing_comma),
+        severity(Warning)
+    )]
+    TrailingComma {
+        #[source_code]
+        src: String,
+
+        #[label("help: remove this comma")]
+        span: SourceSpan,
+    },
+
+    #[error("unnecessary parenthesis")]
+    #[diagnostic(help("these parentheses are not needed"), code(parser::redundant_parenthesis), severity(Warning))]
+    RedundantParenthesis {
+        #[source_code]
+        src: String,
+
+        #[label("opening")]
+        first: SourceSpan,
+
+        #[label("closing")]
+        second: SourceSpan,
+    },
+
+    #[error("Expected {expected}, found EOF")]
+    #[diagnostic(help("Complete the expression"), code(parser::unexpected_eof))]
+    UnexpectedEOF {
+        #[source_code]
+        src: String,
+
+        expected: String,
+    },
+
+    #[error("Unmatched delimiter")]
+    #[diagnostic(help("expected {expected}, found {found}"), code(parser::unmatched_delimiter))]
+    UnmatchedDelimiter {
+        #[source_code]
+        src: String,
+
+        #[label("opening delimiter here")]
+        opening_span: SourceSpan,
+
+        #[label("mismatched closing delimiter here")]
+        closing_span: SourceSpan,
+
+        expected: TokenKind,
+        found: TokenKind,
+    },
+
+    #[error("unclosed delimiter")]
+    #[diagnostic(code(parse::unclosed_delimiter), help("missing closing {delimiter}"))]
+    UnclosedDelimiter {
+        #[source_code]
+        src: String,
+
+        #[label("unclosed delimiter here")]
+        span: SourceSpan,
+
         delimiter: TokenKind,
     },
 
-    #[error("expected '{expected:?}' but found '{found:?}'")]
+    #[error("unexpected closing delimiter: {delimiter}")]
+    #[diagnostic(help("I have no clue which error message"), code(parser::unexpected_closing_delimiter))]
+    UnexpectedClosingDelimiter {
+        #[source_code]
+        src: String,
+
+        #[label("no matching opening delimiter")]
+        span: SourceSpan,
+        delimiter: TokenKind,
+    },
+
+    #[error("expected {expected} but found {found}")]
     #[diagnostic(help("I have no clue which error message"), code(parser::mismatched_delimiter))]
     MismatchedDelimiter {
         #[source_code]
@@ -521,6 +1301,45 @@ pub enum LexError {
         character: char,
     },
 
+    #[error("Integer literal is too large")]
+    #[diagnostic(
+        help("Int values must fit into 64 bits (at most 9223372036854775807)"),
+        code(lexer::integer_overflow)
+    )]
+    IntegerOverflow {
+        #[source_code]
+        src: String,
+
+        #[label("this literal overflows Int")]
+        span: SourceSpan,
+    },
+
+    #[error("float literal loses precision")]
+    #[diagnostic(help("this literal is stored as {value}"), code(lexer::float_precision_loss), severity(Warning))]
+    FloatPrecisionLoss {
+        #[source_code]
+        src: String,
+
+        #[label("cannot be represented exactly")]
+        span: SourceSpan,
+
+        value: String,
+    },
+
+    #[error("float literal exceeds 2^53")]
+    #[diagnostic(
+        help("integers above 9007199254740992 cannot all be represented exactly as Float"),
+        code(lexer::unsafe_float_magnitude),
+        severity(Warning)
+    )]
+    UnsafeFloatMagnitude {
+        #[source_code]
+        src: String,
+
+        #[label("nearby integers are rounded")]
+        span: SourceSpan,
+    },
+
     #[error("Unterminated string literal")]
     #[diagnostic(help("Make sure all string literals are closed with a `\"`."), code(lexer::unterminated_string))]
     UnterminatedString {
@@ -530,4 +1349,178 @@ pub enum LexError {
         #[label("string starts here but never ends")]
         span: SourceSpan,
     },
+
+    #[error("source is {size} bytes, more than the limit of {limit}")]
+    #[diagnostic(help("split the program or raise the limit"), code(lexer::source_too_large))]
+    SourceTooLarge { size: usize, limit: usize },
+
+    #[error("more than {limit} tokens")]
+    #[diagnostic(help("split the program or raise the limit"), code(lexer::too_many_tokens))]
+    TooManyTokens {
+        #[source_code]
+        src: String,
+
+        #[label("lexing stopped here")]
+        span: SourceSpan,
+
+        limit: usize,
+    },
+}
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum PreprocessorError {
+    #[error("Cannot include '{path}': {reason}")]
+    #[diagnostic(
+        help("Include paths are relative to the including file, then to the include paths and `lox_modules`"),
+        code(preprocessor::include_not_found)
+    )]
+    IncludeNotFound {
+        #[source_code]
+        src: String,
+
+        #[label("included here")]
+        span: SourceSpan,
+
+        path: String,
+        reason: String,
+    },
+
+    #[error("'{path}' includes itself: {cycle}")]
+    #[diagnostic(help("A file can't be included while it is being expanded"), code(preprocessor::recursive_include))]
+    RecursiveInclude {
+        #[source_code]
+        src: String,
+
+        #[label("included again here")]
+        span: SourceSpan,
+
+        path: String,
+        /// the files of the cycle joined by arrows, starting and ending with `path`
+        cycle: String,
+
+        /// the includes leading from `path` to the file including it again
+        #[related]
+        steps: Vec<IncludeStep>,
+    },
+
+    #[error("'#if' is never closed")]
+    #[diagnostic(
+        help("End the conditional block with `#end` in the same file"),
+        code(preprocessor::unterminated_condition)
+    )]
+    UnterminatedCondition {
+        #[source_code]
+        src: String,
+
+        #[label("opened here")]
+        span: SourceSpan,
+    },
+
+    #[error("'{directive}' without a matching '#if'")]
+    #[diagnostic(code(preprocessor::unmatched_directive))]
+    UnmatchedDirective {
+        #[source_code]
+        src: String,
+
+        #[label("no '#if' is open here")]
+        span: SourceSpan,
+
+        directive: String,
+    },
+
+    #[error("Malformed condition")]
+    #[diagnostic(help("Write `#if NAME` or `#if !NAME`"), code(preprocessor::malformed_condition))]
+    MalformedCondition {
+        #[source_code]
+        src: String,
+
+        #[label("expected a single name")]
+        span: SourceSpan,
+    },
+
+    #[error("Malformed include directive")]
+    #[diagnostic(help("Write `#include \"file.lox\"`"), code(preprocessor::malformed_include))]
+    MalformedInclude {
+        #[source_code]
+        src: String,
+
+        #[label("expected a quoted path")]
+        span: SourceSpan,
+    },
+}
+
+/// an `#include` in a cycle, shown in the file containing it
+#[derive(Debug, Error, Diagnostic)]
+#[error("'{file}' includes '{included}'")]
+#[diagnostic(severity(Advice))]
+pub struct IncludeStep {
+    #[source_code]
+    pub src: String,
+
+    #[label("included here")]
+    pub span: SourceSpan,
+
+    pub file: String,
+    pub included: String,
+}
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum EncodingError {
+    #[error("'{path}' is not valid UTF-8 at byte {offset}")]
+    #[diagnostic(
+        help("save the file as UTF-8, Latin-1 files can be read with `--latin1`"),
+        code(source::invalid_utf8)
+    )]
+    InvalidUtf8 {
+        #[source_code]
+        src: String,
+
+        #[label("invalid byte")]
+        span: SourceSpan,
+
+        path: String,
+        offset: usize,
+    },
+}
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum AttributeError {
+    #[error("unknown attribute `@{name}`")]
+    #[diagnostic(help("check the spelling, embedders register their own attributes"), code(attributes::unknown))]
+    UnknownAttribute {
+        #[source_code]
+        src: String,
+
+        #[label("not a known attribute")]
+        span: SourceSpan,
+
+        name: String,
+    },
+
+    #[error("`@{name}` can't be written before a {target}")]
+    #[diagnostic(help("it can be written before a {targets}"), code(attributes::invalid_target))]
+    InvalidAttributeTarget {
+        #[source_code]
+        src: String,
+
+        #[label("not allowed here")]
+        span: SourceSpan,
+
+        name: String,
+        target: String,
+        targets: String,
+    },
+
+    #[error("`@{name}` doesn't take this argument")]
+    #[diagnostic(help("its arguments are: {params}"), code(attributes::unexpected_argument))]
+    UnexpectedAttributeArgument {
+        #[source_code]
+        src: String,
+
+        #[label("unexpected argument")]
+        span: SourceSpan,
+
+        name: String,
+        params: String,
+    },
 }
@@ -0,0 +1,191 @@
+use crate::ast::{AstNode, BlockExpr, Expr, Program, Stmt};
+use crate::sarif::escape;
+use crate::visitor::{Visitor, walk_block, walk_expr, walk_stmt};
+use miette::SourceSpan;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// the global variables each function reads and writes, lambdas count towards the function they are written in
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GlobalAccess {
+    pub functions: BTreeMap<String, FunctionGlobals>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionGlobals {
+    /// span of the function's name
+    pub span: SourceSpan,
+    pub reads: BTreeSet<String>,
+    pub writes: BTreeSet<String>,
+}
+
+impl FunctionGlobals {
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty() && self.writes.is_empty()
+    }
+}
+
+impl GlobalAccess {
+    /// globals are the variables declared at the top level, top-level functions and natives aren't counted
+    pub fn build(program: &Program) -> Self {
+        let globals = program
+            .statements
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                Stmt::VarDecl(var_decl) => Some(var_decl.ident.node.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let mut builder = GlobalAccessBuilder {
+            globals,
+            access: GlobalAccess::default(),
+            functions: vec![],
+            scopes: vec![],
+        };
+        builder.visit_program(program);
+        builder.access
+    }
+
+    /// functions touching at least one global
+    pub fn coupled_functions(&self) -> impl Iterator<Item = (&str, &FunctionGlobals)> {
+        self.functions
+            .iter()
+            .filter(|(_, globals)| !globals.is_empty())
+            .map(|(name, globals)| (name.as_str(), globals))
+    }
+
+    pub fn to_json(&self) -> String {
+        let functions: Vec<String> = self
+            .coupled_functions()
+            .map(|(name, globals)| {
+                let reads: Vec<String> = globals.reads.iter().map(|global| format!("\"{}\"", escape(global))).collect();
+                let writes: Vec<String> = globals.writes.iter().map(|global| format!("\"{}\"", escape(global))).collect();
+                format!(
+                    "{{\"name\":\"{}\",\"reads\":[{}],\"writes\":[{}]}}",
+                    escape(name),
+                    reads.join(","),
+                    writes.join(",")
+                )
+            })
+            .collect();
+
+        format!("{{\"functions\":[{}]}}", functions.join(","))
+    }
+}
+
+struct GlobalAccessBuilder {
+    globals: BTreeSet<String>,
+    access: GlobalAccess,
+    /// names of the functions being walked, innermost last
+    functions: Vec<String>,
+    /// locals declared inside functions, shadowing globals of the same name
+    scopes: Vec<BTreeSet<String>>,
+}
+
+impl GlobalAccessBuilder {
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    /// the function accessing `name`, if it is a global used inside a function
+    fn accessing(&mut self, name: &str) -> Option<&mut FunctionGlobals> {
+        let function = self.functions.last()?;
+        if !self.globals.contains(name) || self.scopes.iter().any(|scope| scope.contains(name)) {
+            return None;
+        }
+        self.access.functions.get_mut(function)
+    }
+}
+
+impl Visitor for GlobalAccessBuilder {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        match &stmt.node {
+            Stmt::VarDecl(var_decl) => {
+                walk_stmt(self, stmt);
+                self.declare(&var_decl.ident.node);
+            }
+            Stmt::FunDecl(fun_decl) => {
+                self.declare(&fun_decl.name.node);
+                let name = fun_decl.name.node.clone();
+                self.access.functions.insert(
+                    name.clone(),
+                    FunctionGlobals {
+                        span: fun_decl.name.span,
+                        reads: BTreeSet::new(),
+                        writes: BTreeSet::new(),
+                    },
+                );
+                self.functions.push(name);
+                self.scopes
+                    .push(fun_decl.params.iter().map(|param| param.name.node.clone()).collect());
+                walk_stmt(self, stmt);
+                self.scopes.pop();
+                self.functions.pop();
+            }
+            // the initializer's variable is scoped to the loop
+            Stmt::For(_) if !self.functions.is_empty() => {
+                self.scopes.push(BTreeSet::new());
+                walk_stmt(self, stmt);
+                self.scopes.pop();
+            }
+            _ => walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        match &expr.node {
+            Expr::Variable(ident) => {
+                if let Some(function) = self.accessing(&ident.node) {
+                    function.reads.insert(ident.node.clone());
+                }
+            }
+            Expr::Assign(assign) => {
+                if let Some(function) = self.accessing(&assign.target.node) {
+                    function.writes.insert(assign.target.node.clone());
+                }
+            }
+            Expr::Lambda(lambda) if !self.functions.is_empty() => {
+                self.scopes
+                    .push(lambda.parameters.iter().map(|param| param.name.node.clone()).collect());
+                walk_expr(self, expr);
+                self.scopes.pop();
+                return;
+            }
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_block(&mut self, block: &BlockExpr) {
+        if self.functions.is_empty() {
+            walk_block(self, block);
+            return;
+        }
+        self.scopes.push(BTreeSet::new());
+        walk_block(self, block);
+        self.scopes.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_names_are_escaped() {
+        let globals = FunctionGlobals {
+            span: SourceSpan::from(0..3),
+            reads: BTreeSet::from([r#"say "hi""#.to_string()]),
+            writes: BTreeSet::from([r"C:\dir".to_string()]),
+        };
+        let access = GlobalAccess {
+            functions: BTreeMap::from([(r"a\b".to_string(), globals)]),
+        };
+        assert_eq!(
+            access.to_json(),
+            r#"{"functions":[{"name":"a\\b","reads":["say \"hi\""],"writes":["C:\\dir"]}]}"#
+        );
+    }
+}
@@ -1,40 +1,100 @@
 use crate::MethodRegistry;
 use crate::ast::{
-    AstNode, BinaryOp, BlockExpr, Expr, ExprStmt, FunDeclStmt, LiteralExpr, LogicalOp, Program, ReturnStmt, Stmt, StructDeclStmt,
-    TypedIdent, UnaryOp, VarDeclStmt, WhileStmt,
+    AstNode, BinaryOp, BlockExpr, Expr, ExprStmt, ForStmt, FunDeclStmt, LiteralExpr, LogicalOp, NodeId, PrimitiveType, Program, ReturnStmt,
+    Stmt, TypedIdent, UnaryOp, UnresolvedType, VarDeclStmt, WhileStmt,
 };
-use crate::builtins::{clock_native, print_native};
-use crate::error::InterpreterError;
-use crate::error::RuntimeError::DivisionByZero;
-use crate::interpreters::Function::{NativeFunction, UserFunction};
+use crate::builtins::{
+    clock_native, days_native, format_time_native, hours_native, is_bool_native, is_nil_native, is_number_native, is_string_native,
+    is_vec_native, minutes_native, now_native, parse_time_native, print_native, run_event_loop_native, schedule_native, seconds_native,
+    to_string_native, type_of_native,
+};
+#[cfg(feature = "regex")]
+use crate::builtins::{find_all_native, matches_native, replace_all_native};
+use crate::error::RuntimeError::{
+    DivisionByZero, InvalidCast, InvalidConcatenation, NotAnExpression, ReturnOutsideFunction, SuspendInExpression,
+};
+use crate::error::{InterpreterError, RuntimeError};
+use crate::interpreters::Function::{NativeFunction, RuntimeFunction, UserFunction};
+use crate::observer::{Access, BoxedObserver, ExecutionObserver, OperationCounter, ReductionPrinter, StatementTracer};
+use crate::resolver::{Binding, Bindings, Slot};
+use crate::shared::{HostData, Mutable, Shared};
 use crate::type_inferrer::{Type, TypeVarId};
-use miette::Report;
-use std::cell::RefCell;
+use miette::{Report, SourceSpan};
 use std::cmp::PartialEq;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ops::Deref;
-use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Int(i64),
     Float(f64),
-    String(Rc<str>),
+    String(Shared<str>),
     Bool(bool),
-    Function(Rc<Function>),
-    Vec(Rc<RefCell<Vec<Value>>>),
-    Struct(Rc<RefCell<HashMap<String, Value>>>),
+    Function(Shared<Function>),
+    Vec(Shared<Mutable<Vec<Value>>>),
+    Struct(Shared<Mutable<Fields>>),
+    Foreign(Shared<Foreign>),
     Nil,
 }
 
+/// the fields of a struct in declaration order, of a record in the order they are written
+pub type Fields = Vec<(String, Value)>;
+
+/// an opaque host object natives hand to scripts, dropped with the last value referring to it
+pub struct Foreign {
+    tag: &'static str,
+    data: Box<HostData>,
+}
+
+impl Foreign {
+    /// `tag` names the object for `print` and `typeOf`, declare natives with `Type::Foreign(tag)`
+    #[cfg(not(feature = "sync"))]
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<T: 'static>(tag: &'static str, data: T) -> Value {
+        Value::Foreign(Shared::new(Self { tag, data: Box::new(data) }))
+    }
+
+    #[cfg(feature = "sync")]
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<T: Send + Sync + 'static>(tag: &'static str, data: T) -> Value {
+        Value::Foreign(Shared::new(Self { tag, data: Box::new(data) }))
+    }
+
+    pub fn tag(&self) -> &'static str {
+        self.tag
+    }
+
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+}
+
+/// foreign values are equal only to themselves
+impl PartialEq for Foreign {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for Foreign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}>", self.tag)
+    }
+}
+
+// natives compare by address, the same native is always the same function
+#[allow(unpredictable_function_pointer_comparisons)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum Function {
     NativeFunction(fn(Vec<Value>) -> Result<Value, InterpreterError>),
+    /// a native that needs the interpreter, e.g. to call back into the script
+    RuntimeFunction(fn(&mut Interpreter<'_>, Vec<Value>) -> Result<Value, InterpreterError>),
     UserFunction {
         name: Option<String>,
-        params: Rc<Vec<TypedIdent>>,
-        body: Rc<AstNode<BlockExpr>>,
-        env: Env,
+        params: Shared<Vec<TypedIdent>>,
+        body: Shared<AstNode<BlockExpr>>,
+        upvalues: Vec<Upvalue>,
     },
 }
 
@@ -49,14 +109,22 @@ impl Value {
                 let elements: Vec<String> = vec.borrow().iter().map(|value| value.to_printable_value()).collect();
                 format!("[{}]", elements.join(", "))
             }
-            Value::Struct(_) => todo!(),
+            Value::Struct(fields) => {
+                let fields: Vec<String> = fields
+                    .borrow()
+                    .iter()
+                    .map(|(name, value)| format!("{name}: {}", value.to_printable_value()))
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+            Value::Foreign(foreign) => format!("<{}>", foreign.tag),
             Value::Function(function) => match function.as_ref() {
-                NativeFunction(_) => "<native_fn>".to_string(),
+                NativeFunction(_) | RuntimeFunction(_) => "<native_fn>".to_string(),
                 UserFunction {
                     name,
                     params,
                     body: _,
-                    env: _,
+                    upvalues: _,
                 } => {
                     let param_strings: Vec<String> = params.iter().map(|p| p.name.node.clone()).collect();
                     match name {
@@ -98,6 +166,25 @@ impl Value {
         }
     }
 
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "Int",
+            Value::Float(_) => "Float",
+            Value::String(_) => "String",
+            Value::Bool(_) => "Bool",
+            Value::Function(_) => "Function",
+            Value::Vec(_) => "Vec",
+            Value::Struct(_) => "Struct",
+            Value::Foreign(foreign) => foreign.tag,
+            Value::Nil => "Nil",
+        }
+    }
+
+    /// `nil` and `false` are falsey, everything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Bool(false))
+    }
+
     pub fn to_fn(&self) -> &Function {
         match self {
             Value::Function(func) => func,
@@ -106,80 +193,113 @@ impl Value {
     }
 }
 
+/// top-level functions that differ between the running program and a reloaded one
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ReloadReport {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    /// no longer declared, they stay callable so running code doesn't break
+    pub removed: Vec<String>,
+    /// top-level variables only the reloaded program declares, their initializers ran so its functions can read them
+    pub defined: Vec<String>,
+}
+
 pub struct InterpreterResult {
     pub error: Option<Report>,
+    /// ticket of the host call the program is waiting on, continue with `Interpreter::resume`
+    pub pending: Option<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ControlFlow {
     Return(Value),
+    /// returned by a native function whose result isn't ready yet, the ticket identifies the call to the host
+    Pending(u64),
 }
 
-type Env = Rc<RefCell<Environment>>;
+/// a captured local, shared between the frame that declared it and every closure capturing it
+type Upvalue = Shared<Mutable<Value>>;
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Environment {
-    values: HashMap<String, Value>,
-    parent: Option<Env>,
-}
-
-impl Environment {
-    pub fn new() -> Env {
-        Rc::new(RefCell::new(Self {
-            values: HashMap::new(),
-            parent: None,
-        }))
-    }
-
-    pub fn with_parent(parent: Env) -> Env {
-        Rc::new(RefCell::new(Self {
-            values: HashMap::new(),
-            parent: Some(parent),
-        }))
-    }
-
-    pub fn define(&mut self, name: String, value: Value) {
-        self.values.insert(name, value);
-    }
-
-    pub fn assign(&mut self, name: String, value: Value) {
-        if self.values.contains_key(&name) {
-            self.values.insert(name, value);
-        } else if let Some(parent) = &self.parent {
-            parent.borrow_mut().assign(name, value);
-        } else {
-            panic!()
-        }
-    }
-
-    pub fn get(&self, name: String) -> Value {
-        if let Some(val) = self.values.get(&name) {
-            val.clone()
-        } else if let Some(parent) = &self.parent {
-            parent.borrow().get(name)
-        } else {
-            panic!()
-        }
-    }
+enum Local {
+    Value(Value),
+    Captured(Upvalue),
 }
 
 pub struct Interpreter<'a> {
     source: String,
     program: &'a Program,
-    type_env: &'a HashMap<TypeVarId, Type>,
-    var_env: Env,
+    type_env: HashMap<TypeVarId, Type>,
+    bindings: Bindings,
+    globals: HashMap<String, Value>,
+    /// locals of all active calls, one contiguous frame per call
+    stack: Vec<Local>,
+    /// start of each block scope of the running call in `stack`, empty at the top level
+    scopes: Vec<usize>,
+    upvalues: Vec<Upvalue>,
     method_registry: MethodRegistry,
+    /// index of the next top-level statement, or of the suspended one
+    position: usize,
+    /// node id and span of the native call that returned `ControlFlow::Pending`
+    pending_call: Option<(usize, SourceSpan)>,
+    pending: Option<u64>,
+    /// callbacks registered with `schedule`, ordered by due time and then by registration
+    timers: BTreeMap<(Instant, usize), Value>,
+    scheduled: usize,
+    observers: Vec<BoxedObserver>,
 }
 
 impl<'a> Interpreter<'a> {
-    pub fn new(program: &'a Program, type_env: &'a HashMap<TypeVarId, Type>, source: String) -> Self {
-        let mut var_env = Environment::new();
-        var_env
-            .borrow_mut()
-            .define("clock".to_string(), Value::Function(Rc::new(NativeFunction(clock_native))));
-        var_env
-            .borrow_mut()
-            .define("print".to_string(), Value::Function(Rc::new(NativeFunction(print_native))));
+    pub fn new(program: &'a Program, type_env: HashMap<TypeVarId, Type>, bindings: Bindings, source: String) -> Self {
+        let mut globals = HashMap::new();
+        globals.insert("clock".to_string(), Value::Function(Shared::new(NativeFunction(clock_native))));
+        globals.insert("print".to_string(), Value::Function(Shared::new(NativeFunction(print_native))));
+        globals.insert(
+            "toString".to_string(),
+            Value::Function(Shared::new(NativeFunction(to_string_native))),
+        );
+        globals.insert("typeOf".to_string(), Value::Function(Shared::new(NativeFunction(type_of_native))));
+        globals.insert(
+            "isNumber".to_string(),
+            Value::Function(Shared::new(NativeFunction(is_number_native))),
+        );
+        globals.insert(
+            "isString".to_string(),
+            Value::Function(Shared::new(NativeFunction(is_string_native))),
+        );
+        globals.insert("isBool".to_string(), Value::Function(Shared::new(NativeFunction(is_bool_native))));
+        globals.insert("isVec".to_string(), Value::Function(Shared::new(NativeFunction(is_vec_native))));
+        globals.insert("isNil".to_string(), Value::Function(Shared::new(NativeFunction(is_nil_native))));
+        globals.insert(
+            "schedule".to_string(),
+            Value::Function(Shared::new(RuntimeFunction(schedule_native))),
+        );
+        globals.insert(
+            "runEventLoop".to_string(),
+            Value::Function(Shared::new(RuntimeFunction(run_event_loop_native))),
+        );
+        globals.insert("now".to_string(), Value::Function(Shared::new(NativeFunction(now_native))));
+        globals.insert(
+            "formatTime".to_string(),
+            Value::Function(Shared::new(NativeFunction(format_time_native))),
+        );
+        globals.insert(
+            "parseTime".to_string(),
+            Value::Function(Shared::new(NativeFunction(parse_time_native))),
+        );
+        globals.insert("seconds".to_string(), Value::Function(Shared::new(NativeFunction(seconds_native))));
+        globals.insert("minutes".to_string(), Value::Function(Shared::new(NativeFunction(minutes_native))));
+        globals.insert("hours".to_string(), Value::Function(Shared::new(NativeFunction(hours_native))));
+        globals.insert("days".to_string(), Value::Function(Shared::new(NativeFunction(days_native))));
+        #[cfg(feature = "regex")]
+        {
+            globals.insert("matches".to_string(), Value::Function(Shared::new(NativeFunction(matches_native))));
+            globals.insert("findAll".to_string(), Value::Function(Shared::new(NativeFunction(find_all_native))));
+            globals.insert(
+                "replaceAll".to_string(),
+                Value::Function(Shared::new(NativeFunction(replace_all_native))),
+            );
+        }
 
         let method_registry = MethodRegistry::new();
 
@@ -187,117 +307,428 @@ impl<'a> Interpreter<'a> {
             source,
             program,
             type_env,
-            var_env,
+            bindings,
+            globals,
+            stack: vec![],
+            scopes: vec![],
+            upvalues: vec![],
             method_registry,
+            position: 0,
+            pending_call: None,
+            pending: None,
+            timers: BTreeMap::new(),
+            scheduled: 0,
+            observers: vec![],
         }
     }
 
-    fn define_var(&mut self, name: String, value: Value) {
-        self.var_env.borrow_mut().define(name, value);
+    /// defines a host function, it has to be declared with `Resolver::with_native` and `TypeInferrer::with_native` too
+    pub fn with_native(mut self, name: &str, function: fn(Vec<Value>) -> Result<Value, InterpreterError>) -> Self {
+        self.globals
+            .insert(name.to_string(), Value::Function(Shared::new(NativeFunction(function))));
+        self
     }
 
-    fn get_var(&self, name: String) -> Value {
-        self.var_env.borrow().get(name)
+    /// writes every executed statement with the variables it reads and writes to stderr
+    pub fn with_trace(self, enabled: bool) -> Self {
+        if !enabled {
+            return self;
+        }
+        let tracer = StatementTracer::new(self.source.clone());
+        self.with_observer(Box::new(tracer))
+    }
+
+    /// writes the reduction steps of every statement's expression to stderr, for learning how evaluation works
+    pub fn with_teaching(self, enabled: bool) -> Self {
+        if !enabled {
+            return self;
+        }
+        let printer = ReductionPrinter::new(self.source.clone());
+        self.with_observer(Box::new(printer))
+    }
+
+    /// writes how often each kind of operation ran to stderr once the interpreter is dropped
+    pub fn with_operation_counts(self, enabled: bool) -> Self {
+        if !enabled {
+            return self;
+        }
+        self.with_observer(Box::new(OperationCounter::default()))
+    }
+
+    /// observers are notified in the order they were added
+    pub fn with_observer(mut self, observer: BoxedObserver) -> Self {
+        self.observers.push(observer);
+        self
     }
 
-    fn assign_var(&mut self, name: String, value: Value) {
-        self.var_env.borrow_mut().assign(name, value);
+    pub fn globals(&self) -> &HashMap<String, Value> {
+        &self.globals
+    }
+
+    /// seeds a global the resolver knows from `Resolver::with_globals`, or overwrites one the script defined, optimize
+    /// with `Optimizer::with_global_propagation(false)` for the script to see the new value
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.insert(name.to_string(), value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.get(name).cloned()
+    }
+
+    /// swaps in the top-level functions of a recompiled `program` without running it, global variables keep their values
+    /// and new ones are initialized, the error of a failed initializer is returned, `program` has to be parsed with
+    /// `Parser::with_first_node_id` continuing the running program's node ids
+    pub fn reload(
+        &mut self,
+        program: &'a Program,
+        source: String,
+        type_env: &HashMap<TypeVarId, Type>,
+        bindings: &Bindings,
+    ) -> Result<ReloadReport, Report> {
+        // the new program's node ids start after the old one's, so the old and new entries can't collide
+        self.type_env.extend(type_env.iter().map(|(id, ty)| (*id, ty.clone())));
+        self.bindings
+            .locals
+            .extend(bindings.locals.iter().map(|(id, binding)| (*id, *binding)));
+        self.bindings.captures.extend(bindings.captures.clone());
+        self.bindings.captured.extend(bindings.captured.iter().copied());
+        self.bindings
+            .declarations
+            .extend(bindings.declarations.iter().map(|(id, declaration)| (*id, *declaration)));
+
+        let old_functions = function_sources(self.program, &self.source);
+        let new_functions = function_sources(program, &source);
+        let mut report = ReloadReport::default();
+        for (name, text) in &new_functions {
+            match old_functions.get(name) {
+                None => report.added.push(name.clone()),
+                Some(old_text) if old_text != text => report.changed.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        report.removed = old_functions.into_keys().filter(|name| !new_functions.contains_key(name)).collect();
+
+        for stmt in &program.statements {
+            if let Stmt::FunDecl(fun_decl) = &stmt.node {
+                let name = fun_decl.name.node.clone();
+                let value = self.make_function(stmt.node_id, Some(name.clone()), &fun_decl.params, &fun_decl.body);
+                self.globals.insert(name, value);
+            }
+        }
+
+        self.program = program;
+        self.source = source;
+
+        for stmt in &program.statements {
+            let Stmt::VarDecl(var_decl) = &stmt.node else { continue };
+            if self.globals.contains_key(&var_decl.ident.node) {
+                continue;
+            }
+            report.defined.push(var_decl.ident.node.clone());
+            if let Err(err) = self.execute(stmt) {
+                return Err(self.top_level_error(err, stmt.span));
+            }
+        }
+        Ok(report)
+    }
+
+    /// `declaration` is the node id the resolver used for the variable's declaration
+    fn define_var(&mut self, declaration: usize, name: String, value: Value) {
+        self.notify(|observer| observer.variable_access(Access::Write, &name, &value));
+        if self.scopes.is_empty() {
+            self.globals.insert(name, value);
+        } else if self.bindings.captured.contains(&declaration) {
+            self.stack.push(Local::Captured(Shared::new(Mutable::new(value))));
+        } else {
+            self.stack.push(Local::Value(value));
+        }
+    }
+
+    fn local_index(&self, slot: Slot) -> usize {
+        self.scopes[self.scopes.len() - 1 - slot.depth] + slot.index
+    }
+
+    /// variables without a binding are globals
+    fn get_var(&self, node_id: usize, name: &str) -> Value {
+        match self.bindings.locals.get(&node_id) {
+            Some(&Binding::Local(slot)) => match &self.stack[self.local_index(slot)] {
+                Local::Value(value) => value.clone(),
+                Local::Captured(upvalue) => upvalue.borrow().clone(),
+            },
+            Some(&Binding::Upvalue(index)) => self.upvalues[index].borrow().clone(),
+            None => self.globals[name].clone(),
+        }
+    }
+
+    fn assign_var(&mut self, node_id: usize, name: &str, value: Value) {
+        match self.bindings.locals.get(&node_id) {
+            Some(&Binding::Local(slot)) => {
+                let index = self.local_index(slot);
+                match &mut self.stack[index] {
+                    Local::Value(local) => *local = value,
+                    Local::Captured(upvalue) => *upvalue.borrow_mut() = value,
+                }
+            }
+            Some(&Binding::Upvalue(index)) => *self.upvalues[index].borrow_mut() = value,
+            None => {
+                self.globals.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    /// creates the closure for the function declaration or lambda with `node_id`
+    fn make_function(&self, node_id: usize, name: Option<String>, params: &[TypedIdent], body: &AstNode<BlockExpr>) -> Value {
+        let upvalues = self
+            .bindings
+            .captures
+            .get(&node_id)
+            .into_iter()
+            .flatten()
+            .map(|capture| match *capture {
+                Binding::Local(slot) => match &self.stack[self.local_index(slot)] {
+                    Local::Captured(upvalue) => upvalue.clone(),
+                    Local::Value(_) => unreachable!("the resolver marks captured locals"),
+                },
+                Binding::Upvalue(index) => self.upvalues[index].clone(),
+            })
+            .collect();
+
+        Value::Function(Shared::new(UserFunction {
+            name,
+            params: Shared::new(params.to_vec()),
+            body: Shared::new(body.clone()),
+            upvalues,
+        }))
     }
 
     pub fn interpret(&mut self) -> InterpreterResult {
         for stmt in &self.program.statements {
             self.declare_stmt(stmt);
         }
-        for stmt in &self.program.statements {
-            let result = self.interpret_stmt(stmt);
+        self.position = 0;
+        self.run()
+    }
+
+    /// evaluates a program parsed with `Parser::parse_expression` to the value of its expression, after running the
+    /// declarations in front of it
+    pub fn evaluate(&mut self) -> Result<Value, Report> {
+        let program = self.program;
+        let Some((stmt, declarations)) = program.statements.split_last() else {
+            return Ok(Value::Nil);
+        };
+        let Stmt::ExprStmtNode(expr_stmt) = &stmt.node else {
+            let error = NotAnExpression {
+                src: self.source.clone(),
+                span: stmt.span,
+            };
+            return Err(Report::from(self.failed(error)));
+        };
+        for declaration in declarations {
+            self.declare_stmt(declaration);
+        }
+        for declaration in declarations {
+            if let Err(err) = self.execute(declaration) {
+                return Err(self.top_level_error(err, declaration.span));
+            }
+        }
+        self.interpret_expr(&expr_stmt.expr)
+            .map_err(|err| self.top_level_error(err, expr_stmt.expr.span))
+    }
+
+    /// the report for a top-level statement or expression at `span` that didn't complete, which a suspended host call
+    /// can't resume
+    fn top_level_error(&mut self, err: InterpreterError, span: SourceSpan) -> Report {
+        let error = match err {
+            InterpreterError::RuntimeError(err) => err,
+            InterpreterError::ControlFlowError(ControlFlow::Pending(_)) => {
+                let (_, span) = self.pending_call.take().expect("set by the pending native call");
+                SuspendInExpression {
+                    src: self.source.clone(),
+                    span,
+                }
+            }
+            InterpreterError::ControlFlowError(ControlFlow::Return(_)) => ReturnOutsideFunction {
+                src: self.source.clone(),
+                span,
+            },
+        };
+        Report::from(self.failed(error))
+    }
+
+    /// continues a program suspended on a host call, `value` becomes the result of that call
+    pub fn resume(&mut self, value: Value) -> InterpreterResult {
+        self.pending.take().expect("no host call is pending");
+        let stmt = &self.program.statements[self.position];
+        if let Stmt::VarDecl(var_decl) = &stmt.node {
+            self.define_var(stmt.node_id, var_decl.ident.node.clone(), value);
+        }
+        self.position += 1;
+        self.run()
+    }
+
+    fn run(&mut self) -> InterpreterResult {
+        while let Some(stmt) = self.program.statements.get(self.position) {
+            let result = self.execute(stmt);
             match result {
                 Ok(_) => {}
                 Err(InterpreterError::RuntimeError(err)) => {
                     return InterpreterResult {
-                        error: Some(Report::from(err)),
+                        error: Some(Report::from(self.failed(err))),
+                        pending: None,
+                    };
+                }
+                Err(InterpreterError::ControlFlowError(ControlFlow::Pending(ticket))) => {
+                    let (call_id, span) = self.pending_call.take().expect("set by the pending native call");
+                    // the surrounding expression can't be resumed, only a call that is the whole statement can
+                    if suspension_point(stmt) != Some(call_id) {
+                        let error = SuspendInExpression {
+                            src: self.source.clone(),
+                            span,
+                        };
+                        return InterpreterResult {
+                            error: Some(Report::from(self.failed(error))),
+                            pending: None,
+                        };
+                    }
+                    self.pending = Some(ticket);
+                    return InterpreterResult {
+                        error: None,
+                        pending: Some(ticket),
+                    };
+                }
+                Err(err) => {
+                    return InterpreterResult {
+                        error: Some(self.top_level_error(err, stmt.span)),
+                        pending: None,
                     };
                 }
-                _ => panic!(),
             }
+            self.position += 1;
+        }
+        InterpreterResult {
+            error: None,
+            pending: None,
         }
-        InterpreterResult { error: None }
     }
 
-    fn declare_stmt(&mut self, stmt: &Stmt) {
-        match stmt {
-            Stmt::FunDecl(fun_decl) => {
-                let value = Value::Function(Rc::new(UserFunction {
-                    name: Some(fun_decl.node.ident.node.clone()),
-                    params: Rc::new(fun_decl.node.params.clone()),
-                    body: Rc::new(fun_decl.node.body.clone()),
-                    env: self.var_env.clone(),
-                }));
-                self.define_var(fun_decl.node.ident.node.clone(), value)
-            }
-            _ => {}
+    fn declare_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Stmt::FunDecl(fun_decl) = &stmt.node {
+            let name = fun_decl.name.node.clone();
+            let value = self.make_function(stmt.node_id, Some(name.clone()), &fun_decl.params, &fun_decl.body);
+            self.define_var(stmt.node_id, name, value)
         }
     }
 
-    fn interpret_stmt(&mut self, stmt: &Stmt) -> Result<(), InterpreterError> {
-        match stmt {
+    fn execute(&mut self, stmt: &AstNode<Stmt>) -> Result<(), InterpreterError> {
+        if self.observers.is_empty() {
+            return self.interpret_stmt(stmt);
+        }
+        self.notify(|observer| observer.statement_enter(stmt));
+        let result = self.interpret_stmt(stmt);
+        self.notify(|observer| observer.statement_exit(stmt));
+        result
+    }
+
+    fn notify(&mut self, mut event: impl FnMut(&mut dyn ExecutionObserver)) {
+        for observer in &mut self.observers {
+            event(observer.as_mut());
+        }
+    }
+
+    /// reports a value the program created to the observers
+    fn allocated(&mut self, value: Value) -> Value {
+        self.notify(|observer| observer.allocation(&value));
+        value
+    }
+
+    fn failed(&mut self, error: RuntimeError) -> RuntimeError {
+        self.notify(|observer| observer.error(&error));
+        error
+    }
+
+    fn interpret_stmt(&mut self, stmt: &AstNode<Stmt>) -> Result<(), InterpreterError> {
+        match &stmt.node {
             Stmt::ExprStmtNode(expr) => self.expr_stmt(expr),
-            Stmt::VarDecl(var_decl) => self.var_decl(var_decl),
-            Stmt::FunDecl(fun_decl) => self.fun_decl(fun_decl),
-            Stmt::StructDecl(_) => Ok(()),
+            Stmt::VarDecl(var_decl) => self.var_decl(var_decl, stmt.node_id),
+            Stmt::FunDecl(fun_decl) => self.fun_decl(fun_decl, stmt.node_id),
+            Stmt::StructDecl(_) | Stmt::TypeAlias(_) => Ok(()),
             Stmt::While(while_stmt) => self.while_stmt(while_stmt),
+            Stmt::For(for_stmt) => self.for_stmt(for_stmt),
             Stmt::Return(return_stmt) => self.return_stmt(return_stmt),
         }
     }
 
-    fn interpret_stmts(&mut self, stmts: &Vec<Stmt>) -> Result<(), InterpreterError> {
-        for stmt in stmts {
-            self.interpret_stmt(stmt)?;
-        }
+    fn expr_stmt(&mut self, expr: &ExprStmt) -> Result<(), InterpreterError> {
+        self.interpret_expr(&expr.expr)?;
         Ok(())
     }
 
-    fn expr_stmt(&mut self, expr: &AstNode<ExprStmt>) -> Result<(), InterpreterError> {
-        self.interpret_expr(&expr.node.expr)?;
+    fn var_decl(&mut self, var_decl: &VarDeclStmt, node_id: NodeId) -> Result<(), InterpreterError> {
+        if let Some(init) = &var_decl.initializer {
+            let value = self.interpret_expr(init)?;
+            self.define_var(node_id, var_decl.ident.node.clone(), value);
+        } else {
+            self.define_var(node_id, var_decl.ident.node.clone(), Value::Nil);
+        }
+
         Ok(())
     }
 
-    fn var_decl(&mut self, var_decl: &AstNode<VarDeclStmt>) -> Result<(), InterpreterError> {
-        if let Some(init) = &var_decl.node.initializer {
-            let value = self.interpret_expr(&init)?;
-            self.define_var(var_decl.node.ident.node.clone(), value);
+    fn fun_decl(&mut self, fun_decl: &FunDeclStmt, node_id: NodeId) -> Result<(), InterpreterError> {
+        let name = fun_decl.name.node.clone();
+        // defined before the closure is created so a recursive local function can capture itself
+        self.define_var(node_id, name.clone(), Value::Nil);
+        let value = self.make_function(node_id, Some(name.clone()), &fun_decl.params, &fun_decl.body);
+        let value = self.allocated(value);
+        if self.scopes.is_empty() {
+            self.globals.insert(name, value);
         } else {
-            self.define_var(var_decl.node.ident.node.clone(), Value::Nil);
+            match self.stack.last_mut().unwrap() {
+                Local::Value(local) => *local = value,
+                Local::Captured(upvalue) => *upvalue.borrow_mut() = value,
+            }
         }
 
         Ok(())
     }
 
-    fn fun_decl(&mut self, fun_decl: &AstNode<FunDeclStmt>) -> Result<(), InterpreterError> {
-        self.define_var(
-            fun_decl.node.name.node.clone(),
-            Value::Function(Rc::new(UserFunction {
-                name: Some(fun_decl.node.name.node.clone()),
-                params: Rc::new(fun_decl.node.params.clone()),
-                body: Rc::new(fun_decl.node.body.clone()),
-                env: self.var_env.clone(),
-            })),
-        );
+    fn while_stmt(&mut self, while_stmt: &WhileStmt) -> Result<(), InterpreterError> {
+        let mut cond_value = self.interpret_expr(&while_stmt.condition)?.is_truthy();
+        while cond_value {
+            self.interpret_block_expr(&while_stmt.body.node)?;
+            cond_value = self.interpret_expr(&while_stmt.condition)?.is_truthy();
+        }
 
         Ok(())
     }
 
-    fn while_stmt(&mut self, while_stmt: &AstNode<WhileStmt>) -> Result<(), InterpreterError> {
-        let mut cond_value = self.interpret_expr(&while_stmt.node.condition)?.to_bool();
-        while cond_value {
-            self.interpret_stmts(&while_stmt.node.body.node.statements)?;
-            cond_value = self.interpret_expr(&while_stmt.node.condition)?.to_bool();
+    /// the initializer's variable is declared once, in a scope around the loop, so closures created in the body all
+    /// capture the same variable and see its latest value, a copy in a body local keeps the value of one iteration
+    fn for_stmt(&mut self, for_stmt: &ForStmt) -> Result<(), InterpreterError> {
+        let base = self.stack.len();
+        self.scopes.push(base);
+        let result = self.run_for_loop(for_stmt);
+        self.scopes.pop();
+        self.stack.truncate(base);
+        result
+    }
+
+    fn run_for_loop(&mut self, for_stmt: &ForStmt) -> Result<(), InterpreterError> {
+        if let Some(initializer) = &for_stmt.initializer {
+            self.execute(initializer)?;
+        }
+        while self.interpret_expr(&for_stmt.condition)?.is_truthy() {
+            self.interpret_block_expr(&for_stmt.body.node)?;
+            if let Some(increment) = &for_stmt.increment {
+                self.interpret_expr(increment)?;
+            }
         }
 
         Ok(())
     }
 
-    fn return_stmt(&mut self, return_stmt: &AstNode<ReturnStmt>) -> Result<(), InterpreterError> {
-        let value = if let Some(expr) = &return_stmt.node.expr {
+    fn return_stmt(&mut self, return_stmt: &ReturnStmt) -> Result<(), InterpreterError> {
+        let value = if let Some(expr) = &return_stmt.expr {
             self.interpret_expr(expr)?
         } else {
             Value::Nil
@@ -305,9 +736,19 @@ impl<'a> Interpreter<'a> {
         Err(InterpreterError::ControlFlowError(ControlFlow::Return(value)))
     }
 
+    /// blocks are scopes, mirroring the resolver so slots line up
     fn interpret_block_expr(&mut self, block: &BlockExpr) -> Result<Value, InterpreterError> {
+        let base = self.stack.len();
+        self.scopes.push(base);
+        let value = self.interpret_block_contents(block);
+        self.scopes.pop();
+        self.stack.truncate(base);
+        value
+    }
+
+    fn interpret_block_contents(&mut self, block: &BlockExpr) -> Result<Value, InterpreterError> {
         for stmt in &block.statements {
-            self.interpret_stmt(stmt)?;
+            self.execute(stmt)?;
         }
 
         if let Some(expr) = &block.expr {
@@ -318,6 +759,15 @@ impl<'a> Interpreter<'a> {
     }
 
     fn interpret_expr(&mut self, expr: &AstNode<Expr>) -> Result<Value, InterpreterError> {
+        if self.observers.is_empty() {
+            return self.eval_expr(expr);
+        }
+        let value = self.eval_expr(expr)?;
+        self.notify(|observer| observer.expression_exit(expr, &value));
+        Ok(value)
+    }
+
+    fn eval_expr(&mut self, expr: &AstNode<Expr>) -> Result<Value, InterpreterError> {
         match &expr.node {
             Expr::FieldAssign(field_assign) => {
                 let receiver = self.interpret_expr(&field_assign.receiver)?;
@@ -325,7 +775,11 @@ impl<'a> Interpreter<'a> {
 
                 match receiver {
                     Value::Struct(fields) => {
-                        fields.borrow_mut().insert(field_assign.field.node.clone(), value.clone());
+                        let mut fields = fields.borrow_mut();
+                        match fields.iter_mut().find(|(name, _)| *name == field_assign.field.node) {
+                            Some((_, field)) => *field = value.clone(),
+                            None => fields.push((field_assign.field.node.clone(), value.clone())),
+                        }
                         Ok(value)
                     }
                     _ => panic!(),
@@ -336,7 +790,7 @@ impl<'a> Interpreter<'a> {
 
                 match receiver {
                     Value::Struct(fields) => {
-                        if let Some(value) = fields.borrow().get(&field_access.field.node) {
+                        if let Some((_, value)) = fields.borrow().iter().find(|(name, _)| *name == field_access.field.node) {
                             Ok(value.clone())
                         } else {
                             panic!()
@@ -346,18 +800,43 @@ impl<'a> Interpreter<'a> {
                 }
             }
             Expr::StructInit(struct_init) => {
-                let mut field_values = HashMap::new();
+                let mut field_values = vec![];
                 for (field_name, field_expr) in &struct_init.fields {
                     let value = self.interpret_expr(field_expr)?;
-                    field_values.insert(field_name.node.clone(), value);
+                    field_values.push((field_name.node.clone(), value));
+                }
+                // evaluated in the order they are written, kept in the order they are declared
+                if let Some(fields) = self.struct_fields(&struct_init.name.node) {
+                    field_values.sort_by_key(|(name, _)| fields.iter().position(|field| field.name.node == *name));
+                }
+                Ok(self.allocated(Value::Struct(Shared::new(Mutable::new(field_values)))))
+            }
+            Expr::Cast(cast) => {
+                let value = self.interpret_expr(&cast.expr)?;
+                // the inferred target has aliases and struct names resolved
+                let target = self.type_env.get(&expr.node_id).unwrap_or(&cast.target);
+                cast_value(&value, target).ok_or_else(|| {
+                    InterpreterError::RuntimeError(InvalidCast {
+                        src: self.source.clone(),
+                        span: expr.span,
+                        value: value.to_printable_value(),
+                        target: cast.target.to_string(),
+                    })
+                })
+            }
+            Expr::Record(record) => {
+                let mut field_values = vec![];
+                for (field_name, field_expr) in &record.fields {
+                    let value = self.interpret_expr(field_expr)?;
+                    field_values.push((field_name.node.clone(), value));
                 }
-                Ok(Value::Struct(Rc::new(RefCell::new(field_values))))
+                Ok(self.allocated(Value::Struct(Shared::new(Mutable::new(field_values)))))
             }
-            Expr::Block(block) => Ok(self.interpret_block_expr(block)?),
+            Expr::Block(block) => self.interpret_block_expr(block),
             Expr::If(if_expr) => {
                 let cond_value = self.interpret_expr(&if_expr.condition)?;
 
-                let return_value = if cond_value.to_bool() {
+                let return_value = if cond_value.is_truthy() {
                     self.interpret_block_expr(&if_expr.then_branch.node)?
                 } else if let Some(else_branch) = &if_expr.else_branch {
                     self.interpret_block_expr(&else_branch.node)?
@@ -370,16 +849,21 @@ impl<'a> Interpreter<'a> {
             Expr::MethodCall(method_call) => {
                 let receiver = self.interpret_expr(&method_call.receiver)?;
                 let method_name = &method_call.method.node;
-                let receiver_ty = self.type_env.get(&method_call.receiver.node_id).expect("should work");
+                let receiver_ty = self.type_env.get(&method_call.receiver.node_id).expect("should work").clone();
 
                 let mut args = vec![receiver];
                 for arg in &method_call.arguments {
                     args.push(self.interpret_expr(arg)?)
                 }
 
-                if let Some((_, function)) = self.method_registry.lookup_method(receiver_ty, method_name) {
+                if let Some((_, function)) = self.method_registry.lookup_method(&receiver_ty, method_name) {
                     match function {
-                        NativeFunction(native_fn) => native_fn(args),
+                        NativeFunction(native_fn) => native_fn(args).map_err(|error| match error {
+                            InterpreterError::RuntimeError(error) => {
+                                InterpreterError::RuntimeError(error.at(self.source.clone(), expr.span))
+                            }
+                            error => error,
+                        }),
                         _ => panic!(),
                     }
                 } else {
@@ -388,9 +872,9 @@ impl<'a> Interpreter<'a> {
             }
 
             Expr::Literal(lit) => match &lit {
-                LiteralExpr::Int(int) => Ok(Value::Int(*int)),
-                LiteralExpr::Float(num) => Ok(Value::Float(*num)),
-                LiteralExpr::String(str) => Ok(Value::String(Rc::from(str.as_str()))),
+                LiteralExpr::Int(int) => Ok(Value::Int(int.value)),
+                LiteralExpr::Float(num) => Ok(Value::Float(num.value)),
+                LiteralExpr::String(str) => Ok(Value::String(Shared::from(str.as_str()))),
                 LiteralExpr::Bool(bool) => Ok(Value::Bool(*bool)),
                 LiteralExpr::Nil => Ok(Value::Nil),
                 LiteralExpr::VecLiteral(vec) => {
@@ -398,7 +882,7 @@ impl<'a> Interpreter<'a> {
                     for expr in vec {
                         values.push(self.interpret_expr(expr)?);
                     }
-                    Ok(Value::Vec(Rc::new(RefCell::new(values))))
+                    Ok(self.allocated(Value::Vec(Shared::new(Mutable::new(values)))))
                 }
             },
 
@@ -426,15 +910,20 @@ impl<'a> Interpreter<'a> {
                     BinaryOp::Plus => match expr_type {
                         Type::Int => Ok(Value::Int(left.to_int() + right.to_int())),
                         Type::Float => Ok(Value::Float(left.to_float() + right.to_float())),
-                        Type::String => {
-                            let left_string = left.to_string();
-                            let right_string = right.to_string();
-                            let mut buffer = String::with_capacity(left_string.len() + right_string.len());
-                            buffer.push_str(left_string);
-                            buffer.push_str(right_string);
-
-                            Ok(Value::String(Rc::from(buffer)))
-                        }
+                        Type::String => match (&left, &right) {
+                            (Value::String(left_string), Value::String(right_string)) => {
+                                let mut buffer = String::with_capacity(left_string.len() + right_string.len());
+                                buffer.push_str(left_string);
+                                buffer.push_str(right_string);
+
+                                Ok(self.allocated(Value::String(Shared::from(buffer))))
+                            }
+                            (Value::String(_), other) | (other, _) => Err(InterpreterError::RuntimeError(InvalidConcatenation {
+                                src: self.source.to_string(),
+                                span: expr.span,
+                                type_name: other.type_name().to_string(),
+                            })),
+                        },
                         _ => panic!("{:?}", expr_type),
                     },
                     BinaryOp::Minus => match expr_type {
@@ -447,19 +936,25 @@ impl<'a> Interpreter<'a> {
                         Type::Float => Ok(Value::Float(left.to_float() * right.to_float())),
                         _ => panic!(),
                     },
-                    BinaryOp::Slash => match expr_type {
-                        Type::Int => Ok(Value::Int(left.to_int() / right.to_int())),
-                        Type::Float => {
-                            if right.to_float() == 0.0 {
-                                return Err(InterpreterError::RuntimeError(DivisionByZero {
-                                    src: self.source.to_string(),
-                                    span: expr.span,
-                                }));
-                            }
-                            Ok(Value::Float(left.to_float() / right.to_float()))
+                    BinaryOp::Slash => {
+                        let divisor_is_zero = match expr_type {
+                            Type::Int => right.to_int() == 0,
+                            Type::Float => right.to_float() == 0.0,
+                            _ => panic!(),
+                        };
+                        if divisor_is_zero {
+                            return Err(InterpreterError::RuntimeError(DivisionByZero {
+                                src: self.source.to_string(),
+                                span: binary.op.span,
+                            }));
                         }
-                        _ => panic!(),
-                    },
+
+                        match expr_type {
+                            Type::Int => Ok(Value::Int(left.to_int() / right.to_int())),
+                            Type::Float => Ok(Value::Float(left.to_float() / right.to_float())),
+                            _ => unreachable!(),
+                        }
+                    }
                     BinaryOp::Greater | BinaryOp::GreaterEqual | BinaryOp::Less | BinaryOp::LessEqual => {
                         let operand_type = self.type_env.get(&binary.left.node_id).unwrap();
                         match operand_type {
@@ -486,11 +981,16 @@ impl<'a> Interpreter<'a> {
             }
 
             Expr::Grouping(grouping) => self.interpret_expr(grouping),
-            Expr::Variable(variable) => Ok(self.get_var(variable.node.clone()).clone()),
+            Expr::Variable(variable) => {
+                let value = self.get_var(expr.node_id, &variable.node);
+                self.notify(|observer| observer.variable_access(Access::Read, &variable.node, &value));
+                Ok(value)
+            }
 
             Expr::Assign(assign) => {
                 let value = self.interpret_expr(&assign.value)?;
-                self.assign_var(assign.target.node.clone(), value.clone());
+                self.notify(|observer| observer.variable_access(Access::Write, &assign.target.node, &value));
+                self.assign_var(expr.node_id, &assign.target.node, value.clone());
                 Ok(value)
             }
 
@@ -507,57 +1007,480 @@ impl<'a> Interpreter<'a> {
             Expr::Call(call) => {
                 let callee = self.interpret_expr(call.callee.deref())?;
 
-                let func = callee.to_fn();
+                let mut arguments = Vec::new();
+                for arg in call.arguments.iter() {
+                    arguments.push(self.interpret_expr(arg)?);
+                }
 
-                match func {
-                    NativeFunction(native_fun) => {
-                        let mut arguments = Vec::new();
-                        for arg in call.arguments.iter() {
-                            let value = self.interpret_expr(arg)?;
-                            arguments.push(value);
-                        }
-                        Ok(native_fun(arguments).expect("error handling for native functions not yet implemented"))
+                let native = !matches!(callee.to_fn(), UserFunction { .. });
+                match self.call_function(callee.to_fn(), arguments) {
+                    Err(InterpreterError::ControlFlowError(ControlFlow::Pending(ticket))) => {
+                        // the innermost call is the one that suspended
+                        self.pending_call.get_or_insert((expr.node_id, expr.span));
+                        Err(InterpreterError::ControlFlowError(ControlFlow::Pending(ticket)))
                     }
-                    UserFunction {
-                        name: _,
-                        params,
-                        body,
-                        env,
-                    } => {
-                        let local_env = Environment::with_parent(env.clone());
-
-                        for (arg, param) in call.arguments.iter().zip(params.as_ref()) {
-                            let value = self.interpret_expr(arg)?;
-                            local_env.borrow_mut().define(param.name.node.clone(), value);
-                        }
+                    Err(InterpreterError::RuntimeError(error)) if native => {
+                        Err(InterpreterError::RuntimeError(error.at(self.source.clone(), expr.span)))
+                    }
+                    result => result,
+                }
+            }
 
-                        let old_env = self.var_env.clone();
-                        self.var_env = local_env;
+            Expr::Lambda(lambda) => {
+                let function = self.make_function(expr.node_id, None, &lambda.parameters, &lambda.body);
+                Ok(self.allocated(function))
+            }
+        }
+    }
 
-                        let return_val = match self.interpret_stmts(&body.node.statements) {
-                            Ok(_) => {
-                                if let Some(expr) = &body.node.expr {
-                                    self.interpret_expr(expr)?
-                                } else {
-                                    Value::Nil
-                                }
-                            }
-                            Err(InterpreterError::RuntimeError(err)) => return Err(InterpreterError::RuntimeError(err)),
-                            Err(InterpreterError::ControlFlowError(ControlFlow::Return(val))) => val,
-                        };
+    /// calls a script function from the host, e.g. one fetched with `get_global`
+    ///
+    /// the type inferrer doesn't see these calls, so arity and the parameter types are checked here
+    pub fn call(&mut self, callee: &Value, arguments: &[Value]) -> Result<Value, RuntimeError> {
+        let Value::Function(function) = callee else {
+            return Err(RuntimeError::NotCallable {
+                src: self.source.clone(),
+                span: self.host_call_span(callee),
+                found: callee.to_printable_value(),
+            });
+        };
+        // the host has no call site, the errors point at the function's declaration instead
+        if let UserFunction { params, .. } = function.as_ref() {
+            if params.len() != arguments.len() {
+                return Err(RuntimeError::WrongArgumentCount {
+                    src: self.source.clone(),
+                    span: self.host_call_span(callee),
+                    expected: params.len(),
+                    found: arguments.len(),
+                });
+            }
+            for (position, (param, argument)) in params.iter().zip(arguments).enumerate() {
+                if !self.accepts(&param.type_annotation.node, argument) {
+                    let annotation = param.type_annotation.span;
+                    return Err(RuntimeError::ArgumentTypeMismatch {
+                        src: self.source.clone(),
+                        span: SourceSpan::from(param.name.span.offset()..annotation.offset() + annotation.len()),
+                        position: position + 1,
+                        expected: Type::from(&param.type_annotation.node).to_string(),
+                        found: argument.to_printable_value(),
+                    });
+                }
+            }
+        }
 
-                        self.var_env = old_env;
-                        Ok(return_val)
-                    }
+        match self.call_function(function, arguments.to_vec()) {
+            Ok(value) => Ok(value),
+            Err(InterpreterError::RuntimeError(err)) => Err(self.failed(err)),
+            Err(InterpreterError::ControlFlowError(ControlFlow::Pending(_))) => {
+                let (_, span) = self.pending_call.take().expect("set by the pending native call");
+                let error = SuspendInExpression {
+                    src: self.source.clone(),
+                    span,
+                };
+                Err(self.failed(error))
+            }
+            // only a native function can return this, the return of a script function ends its call
+            Err(InterpreterError::ControlFlowError(ControlFlow::Return(_))) => {
+                let error = ReturnOutsideFunction {
+                    src: self.source.clone(),
+                    span: self.host_call_span(callee),
+                };
+                Err(self.failed(error))
+            }
+        }
+    }
+
+    /// where an error of a host call to `callee` points, the parameters and body of a script function, or the
+    /// declaration of the global holding any other value, or the whole program when no global holds it
+    fn host_call_span(&self, callee: &Value) -> SourceSpan {
+        if let Value::Function(function) = callee
+            && let UserFunction { params, body, .. } = function.as_ref()
+        {
+            let start = params.first().map_or(body.span, |param| param.name.span);
+            return SourceSpan::from(start.offset()..body.span.offset() + body.span.len());
+        }
+        self.program
+            .statements
+            .iter()
+            .find_map(|stmt| {
+                let name = match &stmt.node {
+                    Stmt::VarDecl(var_decl) => &var_decl.ident,
+                    Stmt::FunDecl(fun_decl) => &fun_decl.name,
+                    _ => return None,
+                };
+                (self.globals.get(&name.node) == Some(callee)).then_some(name.span)
+            })
+            .unwrap_or_else(|| SourceSpan::from(0..self.source.len()))
+    }
+
+    /// whether `value` can be passed for a parameter annotated with `ty`, a struct has to have the declared fields and a
+    /// vec elements of the element type, aliases accept what they stand for, other names may be generics and accept
+    /// anything
+    fn accepts(&self, ty: &UnresolvedType, value: &Value) -> bool {
+        if let UnresolvedType::Named(name) = ty
+            && let Some(aliased) = self.type_alias(name)
+        {
+            return self.accepts(aliased, value);
+        }
+        match (ty, value) {
+            (UnresolvedType::Primitive(primitive), value) => matches!(
+                (primitive, value),
+                (PrimitiveType::Int, Value::Int(_))
+                    | (PrimitiveType::Float, Value::Float(_))
+                    | (PrimitiveType::String, Value::String(_))
+                    | (PrimitiveType::Bool, Value::Bool(_))
+                    | (PrimitiveType::Nil, Value::Nil)
+            ),
+            (UnresolvedType::Function { .. }, value) => matches!(value, Value::Function(_)),
+            (UnresolvedType::Named(name), value) => match (self.struct_fields(name), value) {
+                // structs can refer to themselves, their field values aren't checked so the check ends
+                (Some(fields), Value::Struct(values)) => {
+                    let values = values.borrow();
+                    values.len() == fields.len() && fields.iter().all(|field| values.iter().any(|(name, _)| *name == field.name.node))
+                }
+                (Some(_), _) => false,
+                (None, _) => true,
+            },
+            (UnresolvedType::GenericApplication { base, args }, value) => match (base.as_ref(), &args[..], value) {
+                (UnresolvedType::Named(name), [element], Value::Vec(elements)) if name == "Vec" => {
+                    elements.borrow().iter().all(|value| self.accepts(element, value))
                 }
+                (UnresolvedType::Named(name), [_], _) if name == "Vec" => false,
+                _ => true,
+            },
+            (UnresolvedType::Record(fields), Value::Struct(values)) => {
+                let values = values.borrow();
+                values.len() == fields.len()
+                    && fields
+                        .iter()
+                        .all(|(name, ty)| values.iter().any(|(field, value)| field == name && self.accepts(ty, value)))
             }
+            (UnresolvedType::Record(_), _) => false,
+        }
+    }
+
+    /// the type the top-level alias `name` stands for
+    fn type_alias(&self, name: &str) -> Option<&UnresolvedType> {
+        self.program.statements.iter().find_map(|stmt| match &stmt.node {
+            Stmt::TypeAlias(type_alias) if type_alias.ident.node == name => Some(&type_alias.ty.node),
+            _ => None,
+        })
+    }
 
-            Expr::Lambda(lambda) => Ok(Value::Function(Rc::new(UserFunction {
-                name: None,
-                params: Rc::new(lambda.parameters.clone()),
-                body: Rc::new(lambda.body.deref().clone()),
-                env: self.var_env.clone(),
-            }))),
+    /// the fields of the top-level struct `name`
+    fn struct_fields(&self, name: &str) -> Option<&[TypedIdent]> {
+        self.program.statements.iter().find_map(|stmt| match &stmt.node {
+            Stmt::StructDecl(struct_decl) if struct_decl.ident.node == name => Some(&struct_decl.fields[..]),
+            _ => None,
+        })
+    }
+
+    fn call_function(&mut self, function: &Function, arguments: Vec<Value>) -> Result<Value, InterpreterError> {
+        if self.observers.is_empty() {
+            return self.invoke(function, arguments);
+        }
+        let name = match function {
+            UserFunction { name, .. } => name.as_deref(),
+            _ => None,
+        };
+        self.notify(|observer| observer.function_enter(name, &arguments));
+        let result = self.invoke(function, arguments);
+        self.notify(|observer| observer.function_exit(name, result.as_ref().ok()));
+        result
+    }
+
+    fn invoke(&mut self, function: &Function, arguments: Vec<Value>) -> Result<Value, InterpreterError> {
+        match function {
+            NativeFunction(native_fn) => native_fn(arguments),
+            RuntimeFunction(runtime_fn) => runtime_fn(self, arguments),
+            UserFunction {
+                name: _,
+                params,
+                body,
+                upvalues,
+            } => {
+                let base = self.stack.len();
+                let old_scopes = std::mem::replace(&mut self.scopes, vec![base]);
+                let old_upvalues = std::mem::replace(&mut self.upvalues, upvalues.clone());
+                for (param, value) in params.iter().zip(arguments) {
+                    self.define_var(param.name.node_id, param.name.node.clone(), value);
+                }
+
+                let result = match self.interpret_block_contents(&body.node) {
+                    Err(InterpreterError::ControlFlowError(ControlFlow::Return(val))) => Ok(val),
+                    result => result,
+                };
+
+                self.stack.truncate(base);
+                self.scopes = old_scopes;
+                self.upvalues = old_upvalues;
+                result
+            }
+        }
+    }
+
+    /// queues `callback` to be called by `runEventLoop` once `delay` has passed
+    pub(crate) fn schedule(&mut self, delay: Duration, callback: Value) {
+        self.timers.insert((Instant::now() + delay, self.scheduled), callback);
+        self.scheduled += 1;
+    }
+
+    /// calls the scheduled callbacks in order of their due time until none are left, callbacks may schedule more
+    pub(crate) fn run_event_loop(&mut self) -> Result<(), InterpreterError> {
+        while let Some(((due, _), callback)) = self.timers.pop_first() {
+            std::thread::sleep(due.saturating_duration_since(Instant::now()));
+            self.call_function(callback.to_fn(), vec![])?;
+        }
+        Ok(())
+    }
+}
+
+/// converts between Int, Float, String and Bool, other casts narrow a union and only check the value's type
+fn cast_value(value: &Value, target: &Type) -> Option<Value> {
+    let converted = match (value, target) {
+        (Value::Int(int), Type::Float) => Value::Float(*int as f64),
+        (Value::Float(float), Type::Int) if (i64::MIN as f64..=i64::MAX as f64).contains(float) => Value::Int(*float as i64),
+        (Value::Bool(bool), Type::Int) => Value::Int(*bool as i64),
+        (Value::Bool(bool), Type::Float) => Value::Float(*bool as i64 as f64),
+        (Value::Int(int), Type::Bool) => Value::Bool(*int != 0),
+        (Value::Float(float), Type::Bool) => Value::Bool(*float != 0.0),
+        (Value::Int(_) | Value::Float(_) | Value::Bool(_), Type::String) => Value::String(Shared::from(value.to_printable_value())),
+        (Value::String(string), Type::Int) => Value::Int(string.trim().parse().ok()?),
+        (Value::String(string), Type::Float) => Value::Float(string.trim().parse().ok()?),
+        (Value::String(string), Type::Bool) => Value::Bool(string.trim().parse().ok()?),
+        (Value::Int(_), Type::Int)
+        | (Value::Float(_), Type::Float)
+        | (Value::String(_), Type::String)
+        | (Value::Bool(_), Type::Bool)
+        | (Value::Nil, Type::Nil)
+        | (Value::Vec(_), Type::Vec(_))
+        | (Value::Function(_), Type::Function { .. })
+        | (Value::Struct(_), Type::Struct { .. } | Type::Record(_)) => value.clone(),
+        _ => return None,
+    };
+    Some(converted)
+}
+
+/// the call a top-level statement consists of, where a program can suspend
+fn suspension_point(stmt: &AstNode<Stmt>) -> Option<usize> {
+    let expr = match &stmt.node {
+        Stmt::ExprStmtNode(expr_stmt) => &expr_stmt.expr,
+        Stmt::VarDecl(var_decl) => var_decl.initializer.as_ref()?,
+        _ => return None,
+    };
+    matches!(expr.node, Expr::Call(_)).then_some(expr.node_id)
+}
+
+/// source text of each top-level function declaration, keyed by name
+fn function_sources(program: &Program, source: &str) -> BTreeMap<String, String> {
+    program
+        .statements
+        .iter()
+        .filter_map(|stmt| match &stmt.node {
+            Stmt::FunDecl(fun_decl) => {
+                let start = stmt.span.offset();
+                Some((fun_decl.name.node.clone(), source[start..start + stmt.span.len()].to_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(feature = "sync")]
+const _: () = {
+    fn assert_send<T: Send>() {}
+    let _ = assert_send::<Interpreter<'static>>;
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+
+    /// runs `source` and returns its globals, or the code of the diagnostic that stopped it
+    fn run(source: &str) -> Result<HashMap<String, Value>, String> {
+        let compilation = Compiler::new().check(source);
+        let Some(checked) = &compilation.program else {
+            let codes: Vec<_> = compilation
+                .diagnostics
+                .iter()
+                .filter_map(|d| d.code().map(|code| code.to_string()))
+                .collect();
+            return Err(codes.join(", "));
+        };
+        let mut interpreter = Interpreter::new(
+            &checked.program,
+            checked.type_env.clone(),
+            checked.bindings.clone(),
+            compilation.source.clone(),
+        );
+        match interpreter.interpret().error {
+            Some(error) => Err(error.code().map(|code| code.to_string()).unwrap_or_default()),
+            None => Ok(interpreter.globals().clone()),
         }
     }
+
+    fn evaluate(source: &str) -> Result<Value, String> {
+        let compilation = Compiler::new().check(source);
+        let checked = compilation.program.as_ref().expect("the source checks");
+        let mut interpreter = Interpreter::new(
+            &checked.program,
+            checked.type_env.clone(),
+            checked.bindings.clone(),
+            compilation.source.clone(),
+        );
+        interpreter
+            .evaluate()
+            .map_err(|error| error.code().map(|code| code.to_string()).unwrap_or_default())
+    }
+
+    #[test]
+    fn evaluate_returns_the_value_of_the_last_expression() {
+        assert_eq!(evaluate("fn double(n: Int) -> Int { n * 2 } double(21);"), Ok(Value::Int(42)));
+    }
+
+    #[test]
+    fn evaluating_a_declaration_is_an_error() {
+        assert_eq!(evaluate("let x = 1;").unwrap_err(), "runtime::not_an_expression");
+    }
+
+    /// parses and checks `source` with node ids starting at `first_node_id`
+    fn check(source: &str, first_node_id: NodeId) -> (Program, HashMap<TypeVarId, Type>, Bindings) {
+        let mut lexer = crate::Lexer::new(source);
+        let tokens = lexer.lex().tokens;
+        let program = crate::Parser::new(tokens, source.to_string())
+            .with_first_node_id(first_node_id)
+            .parse()
+            .ast;
+        let mut resolver = crate::Resolver::new(&program, source.to_string());
+        assert!(resolver.resolve().is_empty());
+        let bindings = resolver.bindings().clone();
+        let mut type_inferrer = crate::TypeInferrer::new(&program, source.to_string());
+        let type_env = type_inferrer.infer().type_env.clone();
+        (program, type_env, bindings)
+    }
+
+    #[test]
+    fn reload_swaps_functions_and_defines_new_globals() {
+        let old_source = "fn f() -> Int { 1 } let a = f();";
+        let (old, type_env, bindings) = check(old_source, 1);
+        let new_source = "fn f() -> Int { 2 } fn g() -> Int { 3 } let a = 10; let b = f() + g();";
+        let (new, new_type_env, new_bindings) = check(new_source, old.next_node_id);
+
+        let mut interpreter = Interpreter::new(&old, type_env, bindings, old_source.to_string());
+        assert!(interpreter.interpret().error.is_none());
+        let report = interpreter
+            .reload(&new, new_source.to_string(), &new_type_env, &new_bindings)
+            .unwrap();
+
+        assert_eq!(report.added, vec!["g"]);
+        assert_eq!(report.changed, vec!["f"]);
+        assert_eq!(report.defined, vec!["b"]);
+        assert_eq!(interpreter.get_global("a"), Some(Value::Int(1)));
+        assert_eq!(interpreter.get_global("b"), Some(Value::Int(5)));
+    }
+
+    /// runs `source` and calls its global `name` from the host
+    fn call_global(source: &str, name: &str, arguments: &[Value]) -> Result<Value, RuntimeError> {
+        let compilation = Compiler::new().check(source);
+        let checked = compilation.program.as_ref().expect("the source checks");
+        let mut interpreter = Interpreter::new(
+            &checked.program,
+            checked.type_env.clone(),
+            checked.bindings.clone(),
+            compilation.source.clone(),
+        );
+        assert!(interpreter.interpret().error.is_none());
+        let callee = interpreter.get_global(name).unwrap();
+        interpreter.call(&callee, arguments)
+    }
+
+    #[test]
+    fn host_calls_check_arguments_against_aliases() {
+        let source = "type Count = Int; fn twice(n: Count) -> Int { n * 2 }";
+        assert_eq!(call_global(source, "twice", &[Value::Int(4)]).unwrap(), Value::Int(8));
+        let error = call_global(source, "twice", &[Value::String(Shared::from("4"))]).unwrap_err();
+        assert!(matches!(error, RuntimeError::ArgumentTypeMismatch { position: 1, .. }));
+    }
+
+    #[test]
+    fn calling_a_value_from_the_host_points_at_its_declaration() {
+        let source = "let answer = 42;";
+        let error = call_global(source, "answer", &[]).unwrap_err();
+        let RuntimeError::NotCallable { src, span, .. } = error else {
+            panic!("expected NotCallable, got {error:?}");
+        };
+        assert_eq!(src, source);
+        assert_eq!(&src[span.offset()..span.offset() + span.len()], "answer");
+    }
+
+    /// runs `source` and returns the text its runtime error points at
+    fn error_location(source: &str) -> String {
+        let compilation = Compiler::new().check(source);
+        let checked = compilation.program.as_ref().expect("the source checks");
+        let mut interpreter = Interpreter::new(
+            &checked.program,
+            checked.type_env.clone(),
+            checked.bindings.clone(),
+            compilation.source.clone(),
+        );
+        let error = interpreter.interpret().error.expect("the program fails");
+        let span = error.labels().and_then(|mut labels| labels.next()).expect("the error has a label");
+        source[span.offset()..span.offset() + span.len()].to_string()
+    }
+
+    #[test]
+    fn native_errors_point_at_the_call() {
+        assert_eq!(error_location("let t = parseTime(\"x\", \"%Y\");"), "parseTime(\"x\", \"%Y\")");
+    }
+
+    #[test]
+    fn errors_of_callbacks_point_into_the_callback() {
+        let source = "let zero = 0;
+schedule(0, fn() -> Int { 1 / zero });
+runEventLoop();";
+        assert_eq!(error_location(source), "/");
+    }
+
+    #[test]
+    fn local_closures_are_called_through_their_slot() {
+        let globals = run("fn counter() -> Int {
+    let count = 0;
+    let increment = fn() -> Int { count = count + 1; count };
+    increment();
+    increment()
+}
+let result = counter();")
+        .unwrap();
+        assert_eq!(globals["result"], Value::Int(2));
+    }
+
+    #[test]
+    fn casts_convert_between_primitives() {
+        let globals = run("let a = 3.7 as Int; let b = \" 12 \" as Int; let c = 1 as Float;").unwrap();
+        assert_eq!(globals["a"], Value::Int(3));
+        assert_eq!(globals["b"], Value::Int(12));
+        assert_eq!(globals["c"], Value::Float(1.0));
+    }
+
+    #[test]
+    fn casting_a_float_outside_the_int_range_fails() {
+        assert_eq!(run("let a = 10000000000000000000.0 as Int;").unwrap_err(), "runtime::invalid_cast");
+        assert_eq!(run("let a = -10000000000000000000.0 as Int;").unwrap_err(), "runtime::invalid_cast");
+        assert_eq!(
+            run("let a = 9000000000000000000.0 as Int;").unwrap()["a"],
+            Value::Int(9000000000000000000)
+        );
+    }
+
+    #[test]
+    fn casts_resolve_aliases_and_struct_names() {
+        let globals = run("struct Point { x: Int } type Number = Float; type P = Point;
+let n = 2 as Number;
+let p = Point { x: 1 } as P;
+let x = p.x;")
+        .unwrap();
+        assert_eq!(globals["n"], Value::Float(2.0));
+        assert_eq!(globals["x"], Value::Int(1));
+    }
 }
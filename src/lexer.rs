@@ -1,5 +1,7 @@
 use crate::error::LexError;
+use memchr::{memchr, memchr2};
 use miette::{Report, SourceSpan};
+use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
@@ -26,6 +28,7 @@ pub enum TokenKind {
     LessEqual,
     Colon,
     Arrow,
+    At,
 
     String(String),
     Ident(String),
@@ -33,6 +36,7 @@ pub enum TokenKind {
     Int(i64),
 
     And,
+    As,
     Else,
     True,
     False,
@@ -45,6 +49,7 @@ pub enum TokenKind {
     Let,
     While,
     Struct,
+    Type,
 
     TypeInt,
     TypeFloat,
@@ -53,27 +58,196 @@ pub enum TokenKind {
     TypeNil,
     TypeVec,
 
+    /// a slice the lexer reported an error for, it keeps the token stream in step with the source
+    Error,
     EOF,
 }
 
+/// how tokens are named in diagnostics, e.g. "`)`", "identifier `foo`" or "string literal"
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            TokenKind::LeftParen => "(",
+            TokenKind::RightParen => ")",
+            TokenKind::LeftBrace => "{",
+            TokenKind::RightBrace => "}",
+            TokenKind::LeftBracket => "[",
+            TokenKind::RightBracket => "]",
+            TokenKind::Comma => ",",
+            TokenKind::Dot => ".",
+            TokenKind::Minus => "-",
+            TokenKind::Plus => "+",
+            TokenKind::Semicolon => ";",
+            TokenKind::Slash => "/",
+            TokenKind::Star => "*",
+            TokenKind::Bang => "!",
+            TokenKind::BangEqual => "!=",
+            TokenKind::Equal => "=",
+            TokenKind::EqualEqual => "==",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::Colon => ":",
+            TokenKind::Arrow => "->",
+            TokenKind::At => "@",
+            TokenKind::String(_) => return write!(f, "string literal"),
+            TokenKind::Ident(name) => return write!(f, "identifier `{name}`"),
+            TokenKind::Float(value) => return write!(f, "number `{value}`"),
+            TokenKind::Int(value) => return write!(f, "number `{value}`"),
+            TokenKind::And => "and",
+            TokenKind::As => "as",
+            TokenKind::Else => "else",
+            TokenKind::True => "true",
+            TokenKind::False => "false",
+            TokenKind::For => "for",
+            TokenKind::Fn => "fn",
+            TokenKind::If => "if",
+            TokenKind::Nil => "nil",
+            TokenKind::Or => "or",
+            TokenKind::Return => "return",
+            TokenKind::Let => "let",
+            TokenKind::While => "while",
+            TokenKind::Struct => "struct",
+            TokenKind::Type => "type",
+            TokenKind::TypeInt => "Int",
+            TokenKind::TypeFloat => "Float",
+            TokenKind::TypeString => "String",
+            TokenKind::TypeBool => "Bool",
+            TokenKind::TypeNil => "Nil",
+            TokenKind::TypeVec => "Vec",
+            TokenKind::Error => return write!(f, "invalid token"),
+            TokenKind::EOF => return write!(f, "end of file"),
+        };
+        write!(f, "`{symbol}`")
+    }
+}
+
+impl TokenKind {
+    /// reserved words, including the built-in type names
+    pub fn is_keyword(&self) -> bool {
+        KEYWORDS.iter().any(|(_, kind)| kind == self)
+    }
+
+    /// tokens that are an expression on their own
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::String(_) | TokenKind::Float(_) | TokenKind::Int(_) | TokenKind::True | TokenKind::False | TokenKind::Nil
+        )
+    }
+
+    /// arithmetic, comparison and logical operators between two operands
+    pub fn is_binary_operator(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Plus
+                | TokenKind::Minus
+                | TokenKind::Star
+                | TokenKind::Slash
+                | TokenKind::EqualEqual
+                | TokenKind::BangEqual
+                | TokenKind::Greater
+                | TokenKind::GreaterEqual
+                | TokenKind::Less
+                | TokenKind::LessEqual
+                | TokenKind::And
+                | TokenKind::Or
+        )
+    }
+
+    /// left and right binding power of an infix operator as the parser's precedence levels have it, higher binds
+    /// tighter, a right power above the left makes an operator left associative
+    pub fn binding_power(&self) -> Option<(u8, u8)> {
+        let power = match self {
+            TokenKind::Equal => (2, 1),
+            TokenKind::Or => (3, 4),
+            TokenKind::And => (5, 6),
+            TokenKind::EqualEqual | TokenKind::BangEqual => (7, 8),
+            TokenKind::Greater | TokenKind::GreaterEqual | TokenKind::Less | TokenKind::LessEqual => (9, 10),
+            TokenKind::Plus | TokenKind::Minus => (11, 12),
+            TokenKind::Star | TokenKind::Slash => (13, 14),
+            TokenKind::As => (15, 16),
+            _ => return None,
+        };
+        Some(power)
+    }
+}
+
+/// reserved words and the token each lexes to, sorted by bytes so lookups can binary search, highlighters can use it
+/// to color exactly what the lexer treats as a keyword
+pub const KEYWORDS: [(&str, TokenKind); 21] = [
+    ("Bool", TokenKind::TypeBool),
+    ("Float", TokenKind::TypeFloat),
+    ("Int", TokenKind::TypeInt),
+    ("Nil", TokenKind::TypeNil),
+    ("String", TokenKind::TypeString),
+    ("Vec", TokenKind::TypeVec),
+    ("and", TokenKind::And),
+    ("as", TokenKind::As),
+    ("else", TokenKind::Else),
+    ("false", TokenKind::False),
+    ("fn", TokenKind::Fn),
+    ("for", TokenKind::For),
+    ("if", TokenKind::If),
+    ("let", TokenKind::Let),
+    ("nil", TokenKind::Nil),
+    ("or", TokenKind::Or),
+    ("return", TokenKind::Return),
+    ("struct", TokenKind::Struct),
+    ("true", TokenKind::True),
+    ("type", TokenKind::Type),
+    ("while", TokenKind::While),
+];
+
+/// lengths of the shortest and the longest keyword, longer identifiers skip the table
+const KEYWORD_LENGTHS: std::ops::RangeInclusive<usize> = 2..=6;
+
+/// the keyword token for `word`, `None` for identifiers
+pub fn keyword(word: &str) -> Option<TokenKind> {
+    if !KEYWORD_LENGTHS.contains(&word.len()) {
+        return None;
+    }
+    let index = KEYWORDS.binary_search_by(|(keyword, _)| keyword.cmp(&word)).ok()?;
+    Some(KEYWORDS[index].1.clone())
+}
+
+/// words upcoming syntax gives a meaning, they are lexed as identifiers and the parser only treats them as keywords
+/// where an identifier can't appear, so programs using them as names keep parsing
+pub const CONTEXTUAL_KEYWORDS: [&str; 3] = ["match", "enum", "interface"];
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token<'a> {
     pub token_kind: TokenKind,
     pub span: SourceSpan,
     pub literal: &'a str,
+    /// a line break separates the token from the one before it
+    pub newline_before: bool,
+}
+
+/// a `//` or `/* */` comment, skipped by the parser but kept for passes reading annotations in comments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment<'a> {
+    pub span: SourceSpan,
+    /// the whole comment, including `//` or `/*` and `*/`
+    pub text: &'a str,
 }
 
 pub struct LexerResult<'a> {
     pub errors: &'a Vec<Report>,
     pub tokens: Vec<Token<'a>>,
+    pub comments: Vec<Comment<'a>>,
 }
 
 pub struct Lexer<'a> {
     source: &'a str,
     tokens: Vec<Token<'a>>,
+    comments: Vec<Comment<'a>>,
     errors: Vec<Report>,
     position: usize,
     start: usize,
+    max_source_len: Option<usize>,
+    max_tokens: Option<usize>,
 }
 
 impl<'a> Lexer<'a> {
@@ -81,16 +255,48 @@ impl<'a> Lexer<'a> {
         Lexer {
             source,
             tokens: vec![],
+            comments: vec![],
             errors: vec![],
             position: 0,
             start: 0,
+            max_source_len: None,
+            max_tokens: None,
         }
     }
 
-    pub fn lex(&mut self) -> LexerResult {
+    /// a longer source in bytes isn't lexed at all, only `LexError::SourceTooLarge` is reported
+    pub fn with_max_source_len(mut self, limit: usize) -> Self {
+        self.max_source_len = Some(limit);
+        self
+    }
+
+    /// lexing stops with `LexError::TooManyTokens` when the limit is reached, the tokens so far end with EOF
+    pub fn with_max_tokens(mut self, limit: usize) -> Self {
+        self.max_tokens = Some(limit);
+        self
+    }
+
+    pub fn lex(&mut self) -> LexerResult<'_> {
+        if let Some(limit) = self.max_source_len.filter(|limit| self.source.len() > *limit) {
+            self.errors.push(
+                LexError::SourceTooLarge {
+                    size: self.source.len(),
+                    limit,
+                }
+                .into(),
+            );
+            self.position = self.source.len();
+        }
+
         while self.position < self.source.len() {
             self.start = self.position;
-            let c = self.source[self.position..].chars().next().unwrap();
+            let byte = self.source.as_bytes()[self.position];
+            // nearly all of a program is ASCII, which doesn't need decoding
+            let c = if byte.is_ascii() {
+                byte as char
+            } else {
+                self.source[self.position..].chars().next().unwrap()
+            };
 
             self.position += c.len_utf8();
 
@@ -102,6 +308,7 @@ impl<'a> Lexer<'a> {
                 '[' => self.create_token(TokenKind::LeftBracket),
                 ']' => self.create_token(TokenKind::RightBracket),
                 ',' => self.create_token(TokenKind::Comma),
+                '@' => self.create_token(TokenKind::At),
                 '.' => self.create_token(TokenKind::Dot),
                 '-' => {
                     if self.match_char('>') {
@@ -115,22 +322,27 @@ impl<'a> Lexer<'a> {
                 ':' => self.create_token(TokenKind::Colon),
                 '/' => {
                     if self.match_char('/') {
-                        while self.position < self.source.len() && !self.match_char('\n') {
-                            if let Some(c) = self.peek() {
-                                self.position += c.len_utf8();
-                            }
-                        }
+                        let end = memchr(b'\n', &self.source.as_bytes()[self.position..])
+                            .map_or(self.source.len(), |newline| self.position + newline);
+                        self.position = end;
+                        self.push_comment();
+                        // the line break is skipped with the comment
+                        self.position = (end + 1).min(self.source.len());
                         continue;
                     } else if self.match_char('*') {
                         let mut nesting = 1;
                         while nesting > 0 && self.position < self.source.len() {
-                            if let Some(c) = self.peek() {
-                                self.position += c.len_utf8();
-                                match c {
-                                    '/' if self.match_char('*') => nesting += 1,
-                                    '*' if self.match_char('/') => nesting -= 1,
-                                    _ => {}
-                                }
+                            // only a `/` or `*` can open or close a comment
+                            let Some(offset) = memchr2(b'/', b'*', &self.source.as_bytes()[self.position..]) else {
+                                self.position = self.source.len();
+                                break;
+                            };
+                            let c = self.source.as_bytes()[self.position + offset];
+                            self.position += offset + 1;
+                            match c {
+                                b'/' if self.match_char('*') => nesting += 1,
+                                b'*' if self.match_char('/') => nesting -= 1,
+                                _ => {}
                             }
                         }
                         if nesting > 0 {
@@ -142,6 +354,7 @@ impl<'a> Lexer<'a> {
                                 .into(),
                             )
                         }
+                        self.push_comment();
                         continue;
                     } else {
                         self.create_token(TokenKind::Slash)
@@ -177,14 +390,12 @@ impl<'a> Lexer<'a> {
                     }
                 }
                 '"' => {
-                    let rest = &self.source[self.start..];
-                    let token = match rest[1..].find('"') {
-                        Some(pos) => {
-                            let end_offset = pos + 1;
-                            self.position = self.start + end_offset + 1;
-                            self.create_token(TokenKind::String(rest[1..end_offset].to_string()))
-                        }
-                        None => {
+                    let bytes = self.source.as_bytes();
+                    let mut value = String::new();
+                    let mut position = self.start + 1;
+                    // `\"` and `\\` are the only escapes, any other backslash stands for itself
+                    loop {
+                        let Some(offset) = memchr2(b'"', b'\\', &bytes[position..]) else {
                             self.errors.push(
                                 LexError::UnterminatedString {
                                     span: (self.start..self.source.len()).into(),
@@ -192,71 +403,99 @@ impl<'a> Lexer<'a> {
                                 }
                                 .into(),
                             );
-                            continue;
+                            self.position = self.source.len();
+                            break self.create_token(TokenKind::Error);
+                        };
+                        let found = position + offset;
+                        value.push_str(&self.source[position..found]);
+                        if bytes[found] == b'"' {
+                            self.position = found + 1;
+                            break self.create_token(TokenKind::String(value));
                         }
-                    };
-                    token
+                        match bytes.get(found + 1) {
+                            Some(escaped @ (b'"' | b'\\')) => {
+                                value.push(*escaped as char);
+                                position = found + 2;
+                            }
+                            _ => {
+                                value.push('\\');
+                                position = found + 1;
+                            }
+                        }
+                    }
                 }
                 'a'..='z' | 'A'..='Z' | '_' => {
-                    let rest = &self.source[self.start..];
-                    let end_offset = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+                    let bytes = &self.source.as_bytes()[self.start..];
+                    let ascii_len = bytes
+                        .iter()
+                        .position(|byte| !byte.is_ascii_alphanumeric() && *byte != b'_')
+                        .unwrap_or(bytes.len());
+                    let rest = &self.source[self.start + ascii_len..];
+                    // a non-ASCII letter continues the identifier
+                    let non_ascii_len = if rest.starts_with(|c: char| !c.is_ascii()) {
+                        rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len())
+                    } else {
+                        0
+                    };
 
-                    self.position = self.start + end_offset;
+                    self.position = self.start + ascii_len + non_ascii_len;
 
                     let literal = &self.source[self.start..self.position];
 
-                    let kind = match literal {
-                        "and" => TokenKind::And,
-                        "else" => TokenKind::Else,
-                        "false" => TokenKind::False,
-                        "for" => TokenKind::For,
-                        "fn" => TokenKind::Fn,
-                        "if" => TokenKind::If,
-                        "nil" => TokenKind::Nil,
-                        "or" => TokenKind::Or,
-                        "return" => TokenKind::Return,
-                        "true" => TokenKind::True,
-                        "let" => TokenKind::Let,
-                        "while" => TokenKind::While,
-                        "struct" => TokenKind::Struct,
-                        "Float" => TokenKind::TypeFloat,
-                        "String" => TokenKind::TypeString,
-                        "Bool" => TokenKind::TypeBool,
-                        "Nil" => TokenKind::TypeNil,
-                        "Vec" => TokenKind::TypeVec,
-                        "Int" => TokenKind::TypeInt,
-                        _ => TokenKind::Ident(literal.to_string()),
-                    };
+                    let kind = keyword(literal).unwrap_or_else(|| TokenKind::Ident(literal.to_string()));
 
                     self.create_token(kind)
                 }
                 '0'..='9' => {
                     let rest = &self.source[self.start..];
-                    let first_part_offset = rest.find(|c| !matches!(c, '0'..='9')).unwrap_or(rest.len());
+                    let first_part_offset = Self::digits_len(rest);
 
                     self.position = self.start + first_part_offset;
 
                     if self.match_char('.') {
                         let rest_after_dot = &self.source[self.position..];
-                        let second_part_offset = rest_after_dot.find(|c| !matches!(c, '0'..='9')).unwrap_or(rest_after_dot.len());
+                        let second_part_offset = Self::digits_len(rest_after_dot);
 
                         self.position += second_part_offset;
+                        let literal = &self.source[self.start..self.position];
+                        let digits = literal.replace('_', "");
+                        let value: f64 = digits.parse().unwrap();
+                        self.check_float_precision(&digits, value);
                         Token {
-                            token_kind: TokenKind::Float(self.source[self.start..self.position].parse().unwrap()),
+                            token_kind: TokenKind::Float(value),
                             span: SourceSpan::new(self.start.into(), self.position - self.start),
-                            literal: &self.source[self.start..self.position],
+                            literal,
+                            newline_before: false,
                         }
                     } else {
                         let literal = &rest[..first_part_offset];
+                        let Ok(value) = literal.replace('_', "").parse() else {
+                            self.errors.push(
+                                LexError::IntegerOverflow {
+                                    span: (self.start..self.position).into(),
+                                    src: self.source.to_string(),
+                                }
+                                .into(),
+                            );
+                            self.push_token(self.create_token(TokenKind::Error));
+                            continue;
+                        };
                         Token {
-                            token_kind: TokenKind::Int(literal.parse().unwrap()),
+                            token_kind: TokenKind::Int(value),
                             span: SourceSpan::new(self.start.into(), self.position - self.start),
                             literal,
+                            newline_before: false,
                         }
                     }
                 }
 
-                ' ' | '\r' | '\t' | '\n' => continue,
+                ' ' | '\r' | '\t' | '\n' => {
+                    self.position += self.source.as_bytes()[self.position..]
+                        .iter()
+                        .take_while(|byte| matches!(byte, b' ' | b'\r' | b'\t' | b'\n'))
+                        .count();
+                    continue;
+                }
                 _ => {
                     self.errors.push(
                         LexError::UnexpectedCharacter {
@@ -266,29 +505,117 @@ impl<'a> Lexer<'a> {
                         }
                         .into(),
                     );
-                    continue;
+                    self.create_token(TokenKind::Error)
                 }
             };
-            self.tokens.push(token);
+            if let Some(limit) = self.max_tokens.filter(|limit| self.tokens.len() == *limit) {
+                self.errors.push(
+                    LexError::TooManyTokens {
+                        src: self.source.to_string(),
+                        span: token.span,
+                        limit,
+                    }
+                    .into(),
+                );
+                break;
+            }
+            self.push_token(token);
         }
         let eof_token = Token {
             token_kind: TokenKind::EOF,
             span: SourceSpan::from(self.source.len() - 1),
             literal: "",
+            newline_before: false,
         };
-        self.tokens.push(eof_token);
+        self.push_token(eof_token);
         LexerResult {
             errors: &self.errors,
             tokens: self.tokens.clone(),
+            comments: self.comments.clone(),
         }
     }
 
+    /// the diagnostics of `lex`, for keeping them after the lexer is gone
+    pub fn into_errors(self) -> Vec<Report> {
+        self.errors
+    }
+
+    /// warns about float literals that can't be stored exactly
+    fn check_float_precision(&mut self, literal: &str, value: f64) {
+        const MAX_SAFE_INTEGER: f64 = 9007199254740992.0;
+
+        let span: SourceSpan = (self.start..self.position).into();
+        if value.abs() > MAX_SAFE_INTEGER {
+            self.errors.push(
+                LexError::UnsafeFloatMagnitude {
+                    span,
+                    src: self.source.to_string(),
+                }
+                .into(),
+            );
+        } else if Self::significant_digits(literal) != Self::significant_digits(&value.to_string()) {
+            self.errors.push(
+                LexError::FloatPrecisionLoss {
+                    span,
+                    src: self.source.to_string(),
+                    value: value.to_string(),
+                }
+                .into(),
+            );
+        }
+    }
+
+    /// length of the digits `text` starts with, a `_` between two digits separates groups like in `1_000`
+    fn digits_len(text: &str) -> usize {
+        let bytes = text.as_bytes();
+        let mut len = 0;
+        while let Some(byte) = bytes.get(len) {
+            match byte {
+                b'0'..=b'9' => len += 1,
+                b'_' if len > 0 && bytes.get(len + 1).is_some_and(u8::is_ascii_digit) => len += 1,
+                _ => break,
+            }
+        }
+        len
+    }
+
+    /// `007.500` and `7.5` both become `75`
+    fn significant_digits(number: &str) -> String {
+        let number = if number.contains('.') {
+            number.trim_end_matches('0')
+        } else {
+            number
+        };
+        number.replace('.', "").trim_start_matches('0').to_string()
+    }
+
+    fn push_token(&mut self, mut token: Token<'a>) {
+        let previous_end = self
+            .tokens
+            .last()
+            .map_or(0, |previous| previous.span.offset() + previous.span.len());
+        token.newline_before = self
+            .source
+            .get(previous_end..token.span.offset())
+            .is_some_and(|gap| memchr(b'\n', gap.as_bytes()).is_some());
+        self.tokens.push(token);
+    }
+
+    /// the comment from `start` to the current position
+    fn push_comment(&mut self) {
+        self.comments.push(Comment {
+            span: (self.start..self.position).into(),
+            text: &self.source[self.start..self.position],
+        });
+    }
+
     fn create_token(&self, token_kind: TokenKind) -> Token<'a> {
         let literal = &self.source[self.start..self.position];
         Token {
             token_kind,
             span: SourceSpan::new(self.start.into(), self.position - self.start),
             literal,
+            newline_before: false,
         }
     }
 
@@ -1,14 +1,35 @@
 pub mod ast;
+pub mod attributes;
 pub mod builtins;
+pub mod call_graph;
+pub mod cancellation;
+pub mod cfg;
+pub mod compiler;
 pub mod error;
+pub mod global_access;
 pub mod interpreters;
 pub mod lexer;
+pub mod lint;
 pub mod method_registry;
+pub mod node_table;
+pub mod observer;
+pub mod optimizer;
 pub mod parser;
+pub mod preprocessor;
+pub mod printer;
+pub mod purity;
 pub mod resolver;
+pub mod sarif;
+pub mod session;
+pub mod shared;
+pub mod source;
+pub mod stats;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod type_inferrer;
+pub mod visitor;
 
-pub use lexer::{Lexer, Token, TokenKind};
+pub use lexer::{Comment, Lexer, Token, TokenKind};
 pub use method_registry::MethodRegistry;
 pub use parser::Parser;
 pub use resolver::Resolver;
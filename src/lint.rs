@@ -0,0 +1,1204 @@
+use crate::ast::{AstNode, BinaryOp, BlockExpr, Expr, Ident, IfExpr, LiteralExpr, Node, Program, Stmt, TypedIdent, UnaryOp};
+use crate::call_graph::CallGraph;
+use crate::cfg::{CfgNode, ControlFlowGraph, Loop};
+use crate::error::LintWarning::{
+    BoolComparison, ContextualKeywordName, EmptyBranch, FunctionTooComplex, FunctionTooLong, GlobalState, IfReturnsCondition, InfiniteLoop,
+    LoopInvariantCondition, LoopNeverRuns, LoopVariableCapture, MagicNumber, NegatedComparison, PrintCall, TooManyParameters,
+    UnreachableFunction, UnusedParameter, UnusedSuppression, UnusedVariable,
+};
+use crate::error::{LintWarning, Suggestion};
+use crate::global_access::GlobalAccess;
+use crate::lexer::{CONTEXTUAL_KEYWORDS, Comment};
+use crate::resolver::Bindings;
+use crate::visitor::{Visitor, walk_block, walk_expr, walk_stmt};
+use miette::{Report, SourceSpan};
+use std::cmp::Reverse;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// numbers the magic number lint accepts unless told otherwise
+pub const DEFAULT_ALLOWED_NUMBERS: [f64; 3] = [0.0, 1.0, -1.0];
+
+/// starts a comment silencing lints, e.g. `// rslox-ignore: loop-never-runs, global-state`
+pub const SUPPRESSION_PREFIX: &str = "rslox-ignore:";
+
+/// a lint silenced by a comment
+struct Suppression {
+    /// the comment
+    span: SourceSpan,
+    /// the lint's code without `lint::`, e.g. `loop_never_runs`
+    lint: String,
+}
+
+pub struct Linter<'a> {
+    source: String,
+    program: &'a Program,
+    warnings: Vec<Report>,
+    /// report functions reading or writing globals
+    global_state: bool,
+    /// report names that are contextual keywords
+    keyword_names: bool,
+    /// functions spanning more lines are reported
+    max_function_lines: Option<usize>,
+    max_parameters: Option<usize>,
+    max_complexity: Option<usize>,
+    /// numbers function bodies may use without naming them, `None` turns the magic number lint off
+    allowed_numbers: Option<Vec<f64>>,
+    /// report calls of `print`
+    print_calls: bool,
+    /// ranges of the source where `print` may be called anyway
+    print_allowed: Vec<Range<usize>>,
+    suppressions: Vec<Suppression>,
+    /// what the resolver bound each variable to, the lints about unused locals need it
+    bindings: Option<&'a Bindings>,
+    /// warnings before this offset are in a prelude or loaded session, not in the linted code
+    prefix_len: usize,
+}
+
+impl<'a> Linter<'a> {
+    pub fn new(ast: &'a Program, source: String) -> Self {
+        Self {
+            source,
+            program: ast,
+            warnings: vec![],
+            global_state: false,
+            keyword_names: false,
+            max_function_lines: None,
+            max_parameters: None,
+            max_complexity: None,
+            allowed_numbers: None,
+            print_calls: false,
+            print_allowed: vec![],
+            suppressions: vec![],
+            bindings: None,
+            prefix_len: 0,
+        }
+    }
+
+    /// off by default, small scripts use globals on purpose
+    pub fn with_global_state_lint(mut self, enabled: bool) -> Self {
+        self.global_state = enabled;
+        self
+    }
+
+    /// off by default, contextual keywords are still valid names, this helps renaming them ahead of time
+    pub fn with_keyword_lint(mut self, enabled: bool) -> Self {
+        self.keyword_names = enabled;
+        self
+    }
+
+    /// off by default, functions longer than `limit` lines from `fn` to the closing brace are reported
+    pub fn with_max_function_lines(mut self, limit: usize) -> Self {
+        self.max_function_lines = Some(limit);
+        self
+    }
+
+    /// off by default, functions with more than `limit` parameters are reported
+    pub fn with_max_parameters(mut self, limit: usize) -> Self {
+        self.max_parameters = Some(limit);
+        self
+    }
+
+    /// off by default, functions with a cyclomatic complexity above `limit` are reported, it is one more than the
+    /// number of `if`s, loops, `and`s and `or`s in the function, nested function declarations count on their own
+    pub fn with_max_complexity(mut self, limit: usize) -> Self {
+        self.max_complexity = Some(limit);
+        self
+    }
+
+    /// off by default, number literals in function bodies other than `allowed` are reported, except as the whole
+    /// initializer of a variable, which already names them
+    pub fn with_magic_number_lint(mut self, allowed: &[f64]) -> Self {
+        self.allowed_numbers = Some(allowed.to_vec());
+        self
+    }
+
+    /// off by default, for scripts that must not write to stdout, e.g. deployed ones whose output is parsed
+    pub fn with_print_lint(mut self, enabled: bool) -> Self {
+        self.print_calls = enabled;
+        self
+    }
+
+    /// `print` calls in these ranges of the source aren't reported, e.g. the ones an allowed file was included into
+    pub fn with_print_allowed(mut self, ranges: Vec<Range<usize>>) -> Self {
+        self.print_allowed = ranges;
+        self
+    }
+
+    /// the first `len` bytes of the source are declarations put in front of the linted code, their warnings are dropped
+    pub fn with_prefix(mut self, len: usize) -> Self {
+        self.prefix_len = len;
+        self
+    }
+
+    /// the bindings the resolver found for the linted program, without them unused locals and parameters aren't
+    /// reported
+    pub fn with_bindings(mut self, bindings: &'a Bindings) -> Self {
+        self.bindings = Some(bindings);
+        self
+    }
+
+    /// `// rslox-ignore: lint-name` silences a lint for the statement the comment trails on its line, or else for the
+    /// statement after it, suppressions that silence nothing are reported
+    pub fn with_suppressions(mut self, comments: &[Comment]) -> Self {
+        for comment in comments {
+            let Some(lints) = comment
+                .text
+                .strip_prefix("//")
+                .and_then(|text| text.trim().strip_prefix(SUPPRESSION_PREFIX))
+            else {
+                continue;
+            };
+            for lint in lints.split(',').map(str::trim).filter(|lint| !lint.is_empty()) {
+                self.suppressions.push(Suppression {
+                    span: comment.span,
+                    lint: lint.replace('-', "_"),
+                });
+            }
+        }
+        self
+    }
+
+    pub fn lint(&mut self) -> &Vec<Report> {
+        self.check_unreachable_functions();
+        self.check_loops();
+        self.check_loop_captures();
+        self.check_unused_parameters();
+        self.check_unused_variables();
+        self.check_conditions();
+        self.check_magic_numbers();
+        if self.print_calls {
+            self.check_print_calls();
+        }
+        self.check_function_metrics();
+        if self.global_state {
+            self.check_global_state();
+        }
+        if self.keyword_names {
+            self.check_keyword_names();
+        }
+        self.warnings.retain(|warning| {
+            let offset = warning.labels().and_then(|mut labels| labels.next()).map(|label| label.offset());
+            offset.is_none_or(|offset| offset >= self.prefix_len)
+        });
+        self.apply_suppressions();
+        &self.warnings
+    }
+
+    /// drops the warnings in statements a suppression applies to
+    fn apply_suppressions(&mut self) {
+        if self.suppressions.is_empty() {
+            return;
+        }
+        let mut statements = vec![];
+        self.program.walk(|node| {
+            if let Node::Stmt(stmt) = node {
+                statements.push(stmt.span);
+            }
+        });
+        let targets: Vec<Option<SourceSpan>> = self
+            .suppressions
+            .iter()
+            .map(|suppression| suppressed_statement(&statements, suppression.span, &self.source))
+            .collect();
+
+        let mut used = vec![false; self.suppressions.len()];
+        let warnings = std::mem::take(&mut self.warnings);
+        for warning in warnings {
+            let code = warning.code().map(|code| code.to_string());
+            let lint = code.as_deref().and_then(|code| code.strip_prefix("lint::"));
+            let offset = warning.labels().and_then(|mut labels| labels.next()).map(|label| label.offset());
+            let suppression = (0..self.suppressions.len()).find(|i| {
+                Some(self.suppressions[*i].lint.as_str()) == lint
+                    && targets[*i]
+                        .zip(offset)
+                        .is_some_and(|(target, offset)| target.offset() <= offset && offset < target.offset() + target.len())
+            });
+            match suppression {
+                Some(i) => used[i] = true,
+                None => self.warnings.push(warning),
+            }
+        }
+
+        for (i, suppression) in self.suppressions.iter().enumerate() {
+            if !used[i] {
+                self.warnings.push(
+                    UnusedSuppression {
+                        src: self.source.clone(),
+                        span: suppression.span,
+                        name: suppression.lint.replace('_', "-"),
+                    }
+                    .into(),
+                );
+            }
+        }
+    }
+
+    fn report(&mut self, warning: LintWarning) {
+        self.warnings.push(warning.into());
+    }
+
+    fn check_unreachable_functions(&mut self) {
+        let call_graph = CallGraph::build(self.program);
+        for (name, span) in call_graph.unreachable_functions() {
+            self.report(UnreachableFunction {
+                src: self.source.clone(),
+                span,
+                name: name.to_string(),
+            });
+        }
+    }
+
+    fn check_loops(&mut self) {
+        for cfg in ControlFlowGraph::build_all(self.program) {
+            for loop_info in &cfg.loops {
+                let span = loop_info.condition.span;
+                match constant_condition(loop_info.condition) {
+                    Some(true) => {
+                        let returns = loop_info.body.clone().any(|block| cfg.blocks[block].successors.contains(&cfg.exit));
+                        if !returns {
+                            self.report(InfiniteLoop {
+                                src: self.source.clone(),
+                                span,
+                            });
+                        }
+                    }
+                    Some(false) => self.report(LoopNeverRuns {
+                        src: self.source.clone(),
+                        span,
+                    }),
+                    None => self.check_loop_invariant(&cfg, loop_info),
+                }
+            }
+        }
+    }
+
+    fn check_global_state(&mut self) {
+        let access = GlobalAccess::build(self.program);
+        for (name, globals) in access.coupled_functions() {
+            let mut touched = vec![];
+            if !globals.reads.is_empty() {
+                let reads: Vec<String> = globals.reads.iter().map(|global| format!("`{global}`")).collect();
+                touched.push(format!("reads {}", reads.join(", ")));
+            }
+            if !globals.writes.is_empty() {
+                let writes: Vec<String> = globals.writes.iter().map(|global| format!("`{global}`")).collect();
+                touched.push(format!("writes {}", writes.join(", ")));
+            }
+            self.report(GlobalState {
+                src: self.source.clone(),
+                span: globals.span,
+                name: name.to_string(),
+                globals: touched.join(" and "),
+            });
+        }
+    }
+
+    fn check_keyword_names(&mut self) {
+        let mut names = KeywordNames::default();
+        names.visit_program(self.program);
+        for (span, name) in names.found {
+            self.report(ContextualKeywordName {
+                src: self.source.clone(),
+                span,
+                name,
+            });
+        }
+    }
+
+    fn check_loop_captures(&mut self) {
+        let mut captures = LoopCaptures::default();
+        captures.visit_program(self.program);
+        for (span, name) in captures.found {
+            self.report(LoopVariableCapture {
+                src: self.source.clone(),
+                span,
+                name,
+            });
+        }
+    }
+
+    fn check_conditions(&mut self) {
+        let mut conditions = Conditions {
+            source: &self.source,
+            found: vec![],
+        };
+        conditions.visit_program(self.program);
+        let src = self.source.clone();
+        for warning in conditions.found {
+            let warning = match warning {
+                Simplification::BoolComparison(span, replacement) => BoolComparison {
+                    src: src.clone(),
+                    span,
+                    suggestion: Suggestion { span, replacement },
+                },
+                Simplification::NegatedComparison(span, replacement) => NegatedComparison {
+                    src: src.clone(),
+                    span,
+                    suggestion: Suggestion { span, replacement },
+                },
+                Simplification::IfReturnsCondition(span, replacement) => IfReturnsCondition {
+                    src: src.clone(),
+                    span,
+                    suggestion: Suggestion { span, replacement },
+                },
+                Simplification::EmptyBranch(span, branch, replacement) => EmptyBranch {
+                    src: src.clone(),
+                    span,
+                    branch: branch.to_string(),
+                    suggestion: Suggestion { span, replacement },
+                },
+            };
+            self.report(warning);
+        }
+    }
+
+    fn check_magic_numbers(&mut self) {
+        let Some(allowed) = &self.allowed_numbers else { return };
+        let mut numbers = MagicNumbers {
+            allowed,
+            source: &self.source,
+            functions: vec![],
+            found: vec![],
+        };
+        numbers.visit_program(self.program);
+        for (span, number, function, suggestion) in numbers.found {
+            self.report(MagicNumber {
+                src: self.source.clone(),
+                span,
+                number,
+                function,
+                suggestion,
+            });
+        }
+    }
+
+    fn check_print_calls(&mut self) {
+        let mut calls = PrintCalls::default();
+        calls.visit_program(self.program);
+        for span in calls.found {
+            if !self.print_allowed.iter().any(|range| range.contains(&span.offset())) {
+                self.report(PrintCall {
+                    src: self.source.clone(),
+                    span,
+                });
+            }
+        }
+    }
+
+    fn check_unused_parameters(&mut self) {
+        let Some(bindings) = self.bindings else { return };
+        let mut parameters = UnusedParameters {
+            reads: local_reads(self.program, bindings),
+            found: vec![],
+        };
+        parameters.visit_program(self.program);
+        for (span, name) in parameters.found {
+            self.report(UnusedParameter {
+                src: self.source.clone(),
+                span,
+                name,
+            });
+        }
+    }
+
+    fn check_unused_variables(&mut self) {
+        let Some(bindings) = self.bindings else { return };
+        let mut variables = UnusedVariables {
+            reads: local_reads(self.program, bindings),
+            local_depth: 0,
+            found: vec![],
+        };
+        variables.visit_program(self.program);
+        for (span, name) in variables.found {
+            self.report(UnusedVariable {
+                src: self.source.clone(),
+                span,
+                name,
+            });
+        }
+    }
+
+    fn check_function_metrics(&mut self) {
+        if self.max_function_lines.is_none() && self.max_parameters.is_none() && self.max_complexity.is_none() {
+            return;
+        }
+        let mut metrics = FunctionMetrics {
+            source: &self.source,
+            found: vec![],
+        };
+        metrics.visit_program(self.program);
+        for function in metrics.found {
+            if let Some(limit) = self.max_function_lines.filter(|limit| function.lines > *limit) {
+                self.report(FunctionTooLong {
+                    src: self.source.clone(),
+                    span: function.span,
+                    name: function.name.clone(),
+                    lines: function.lines,
+                    limit,
+                });
+            }
+            if let Some(limit) = self.max_parameters.filter(|limit| function.parameters > *limit) {
+                self.report(TooManyParameters {
+                    src: self.source.clone(),
+                    span: function.span,
+                    name: function.name.clone(),
+                    count: function.parameters,
+                    limit,
+                });
+            }
+            if let Some(limit) = self.max_complexity.filter(|limit| function.complexity > *limit) {
+                self.report(FunctionTooComplex {
+                    src: self.source.clone(),
+                    span: function.span,
+                    name: function.name.clone(),
+                    complexity: function.complexity,
+                    limit,
+                });
+            }
+        }
+    }
+
+    fn check_loop_invariant(&mut self, cfg: &ControlFlowGraph, loop_info: &Loop) {
+        let mut condition = ConditionVariables::default();
+        condition.visit_expr(loop_info.condition);
+        if condition.opaque || condition.names.is_empty() {
+            return;
+        }
+
+        let mut body = Assignments::default();
+        for block in loop_info.body.clone() {
+            for node in &cfg.blocks[block].nodes {
+                match node {
+                    CfgNode::Stmt(stmt) => body.visit_stmt(stmt),
+                    CfgNode::Expr(expr) => body.visit_expr(expr),
+                }
+            }
+        }
+        // a called function might modify any of the variables
+        if body.calls || condition.names.iter().any(|name| body.assigned.contains(name)) {
+            return;
+        }
+
+        let names: Vec<String> = condition.names.iter().map(|name| format!("`{name}`")).collect();
+        self.report(LoopInvariantCondition {
+            src: self.source.clone(),
+            span: loop_info.condition.span,
+            names: names.join(", "),
+        });
+    }
+}
+
+fn constant_condition(condition: &AstNode<Expr>) -> Option<bool> {
+    match &condition.node {
+        Expr::Grouping(inner) => constant_condition(inner),
+        Expr::Literal(LiteralExpr::Bool(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+/// a condition written in a roundabout way, with the span to replace and the simpler text to replace it with
+enum Simplification {
+    BoolComparison(SourceSpan, String),
+    NegatedComparison(SourceSpan, String),
+    IfReturnsCondition(SourceSpan, String),
+    /// `then` or `else`
+    EmptyBranch(SourceSpan, &'static str, String),
+}
+
+/// `x == true`, `!(a != b)`, `if c { true } else { false }` and `if`s with an empty branch
+struct Conditions<'a> {
+    source: &'a str,
+    found: Vec<Simplification>,
+}
+
+impl Conditions<'_> {
+    fn text(&self, span: SourceSpan) -> &str {
+        &self.source[span.offset()..span.offset() + span.len()]
+    }
+
+    /// `!expr`, parenthesized unless `expr` binds tighter than `!`
+    fn negated(&self, expr: &AstNode<Expr>) -> String {
+        let text = self.text(expr.span);
+        match &expr.node {
+            Expr::Literal(_) | Expr::Variable(_) | Expr::Grouping(_) | Expr::Call(_) | Expr::MethodCall(_) | Expr::FieldAccess(_) => {
+                format!("!{text}")
+            }
+            _ => format!("!({text})"),
+        }
+    }
+
+    fn check_if(&mut self, span: SourceSpan, if_expr: &IfExpr) {
+        let condition = ungrouped(&if_expr.condition);
+        let Some(else_branch) = &if_expr.else_branch else { return };
+        let then_branch = &if_expr.then_branch;
+
+        match (bool_block(&then_branch.node), bool_block(&else_branch.node)) {
+            (Some(then_value), Some(else_value)) if then_value != else_value => {
+                let suggestion = if then_value {
+                    self.text(condition.span).to_string()
+                } else {
+                    self.negated(condition)
+                };
+                self.found.push(Simplification::IfReturnsCondition(span, suggestion));
+                return;
+            }
+            _ => {}
+        }
+
+        let is_empty = |block: &BlockExpr| block.statements.is_empty() && block.expr.is_none();
+        let is_else_if =
+            else_branch.node.statements.is_empty() && else_branch.node.expr.as_ref().is_some_and(|expr| matches!(expr.node, Expr::If(_)));
+        if is_empty(&then_branch.node) && !is_empty(&else_branch.node) && !is_else_if {
+            // the else branch's span starts at `else`
+            let else_block = self.text(else_branch.span).trim_start_matches("else").trim_start();
+            let suggestion = format!("if {} {else_block}", self.negated(condition));
+            self.found.push(Simplification::EmptyBranch(span, "then", suggestion));
+        } else if is_empty(&else_branch.node) && !is_empty(&then_branch.node) {
+            let then_end = then_branch.span.offset() + then_branch.span.len();
+            let suggestion = self.source[span.offset()..then_end].to_string();
+            self.found.push(Simplification::EmptyBranch(span, "else", suggestion));
+        }
+    }
+}
+
+impl Visitor for Conditions<'_> {
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        match &expr.node {
+            Expr::Binary(binary) if matches!(binary.op.node, BinaryOp::EqualEqual | BinaryOp::BangEqual) => {
+                let literal = |operand: &AstNode<Expr>| match operand.node {
+                    Expr::Literal(LiteralExpr::Bool(value)) => Some(value),
+                    _ => None,
+                };
+                let compared = match (literal(&binary.left), literal(&binary.right)) {
+                    (None, Some(value)) => Some((&binary.left, value)),
+                    (Some(value), None) => Some((&binary.right, value)),
+                    _ => None,
+                };
+                if let Some((operand, value)) = compared {
+                    let suggestion = if value == (binary.op.node == BinaryOp::EqualEqual) {
+                        self.text(operand.span).to_string()
+                    } else {
+                        self.negated(operand)
+                    };
+                    self.found.push(Simplification::BoolComparison(expr.span, suggestion));
+                }
+            }
+            Expr::Unary(unary) if unary.op.node == UnaryOp::Bang => {
+                if let Expr::Binary(binary) = &ungrouped(&unary.expr).node {
+                    let flipped = match binary.op.node {
+                        BinaryOp::EqualEqual => Some("!="),
+                        BinaryOp::BangEqual => Some("=="),
+                        // `<` and `>=` aren't opposites for NaN
+                        _ => None,
+                    };
+                    if let Some(flipped) = flipped {
+                        let suggestion = format!("{} {flipped} {}", self.text(binary.left.span), self.text(binary.right.span));
+                        self.found.push(Simplification::NegatedComparison(expr.span, suggestion));
+                    }
+                }
+            }
+            Expr::If(if_expr) => self.check_if(expr.span, if_expr),
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+}
+
+fn ungrouped(expr: &AstNode<Expr>) -> &AstNode<Expr> {
+    match &expr.node {
+        Expr::Grouping(inner) => ungrouped(inner),
+        _ => expr,
+    }
+}
+
+/// the value of a block that only evaluates a boolean literal
+fn bool_block(block: &BlockExpr) -> Option<bool> {
+    match block.expr.as_deref().map(|expr| &ungrouped(expr).node) {
+        Some(Expr::Literal(LiteralExpr::Bool(value))) if block.statements.is_empty() => Some(*value),
+        _ => None,
+    }
+}
+
+/// calls of `print`, including classic `print expr;` statements, which are parsed into calls
+#[derive(Default)]
+struct PrintCalls {
+    found: Vec<SourceSpan>,
+}
+
+impl Visitor for PrintCalls {
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        if let Expr::Call(call) = &expr.node
+            && let Expr::Variable(name) = &ungrouped(&call.callee).node
+            && name.node == "print"
+        {
+            self.found.push(expr.span);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// number literals in function and lambda bodies that aren't allowed, with their text and the function's description
+struct MagicNumbers<'a> {
+    allowed: &'a [f64],
+    source: &'a str,
+    /// `` `name` `` or "the closure" for each enclosing function, with where its body's first statement starts
+    functions: Vec<(String, usize)>,
+    found: Vec<(SourceSpan, String, String, Suggestion)>,
+}
+
+impl MagicNumbers<'_> {
+    fn visit_function(&mut self, function: String, body: &BlockExpr) {
+        let first = body
+            .statements
+            .first()
+            .map(|stmt| stmt.span)
+            .or(body.expr.as_ref().map(|expr| expr.span));
+        let Some(first) = first else { return };
+        self.functions.push((function, first.offset()));
+        self.visit_block(body);
+        self.functions.pop();
+    }
+
+    /// declares `number` in front of the body's first statement, indented like it when it starts its line
+    fn declaration(&self, number: &str, offset: usize) -> Suggestion {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+        let indent = &self.source[line_start..offset];
+        let indent = if indent.trim().is_empty() { indent } else { "" };
+        Suggestion {
+            span: SourceSpan::new(offset.into(), 0),
+            replacement: format!("let NAME = {number};\n{indent}"),
+        }
+    }
+}
+
+impl Visitor for MagicNumbers<'_> {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        match &stmt.node {
+            Stmt::FunDecl(fun_decl) => self.visit_function(format!("`{}`", fun_decl.name.node), &fun_decl.body.node),
+            Stmt::VarDecl(var_decl) if var_decl.initializer.as_ref().is_some_and(|init| number(init).is_some()) => {}
+            _ => walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        if let Expr::Lambda(lambda) = &expr.node {
+            self.visit_function("the closure".to_string(), &lambda.body.node);
+            return;
+        }
+        let Some(value) = number(expr) else {
+            walk_expr(self, expr);
+            return;
+        };
+        let Some((function, body_start)) = self.functions.last() else {
+            return;
+        };
+        if !self.allowed.contains(&value) {
+            let text = &self.source[expr.span.offset()..expr.span.offset() + expr.span.len()];
+            let suggestion = self.declaration(text, *body_start);
+            self.found.push((expr.span, text.to_string(), function.clone(), suggestion));
+        }
+    }
+}
+
+/// the value of a number literal, negated ones like `-1` included
+fn number(expr: &AstNode<Expr>) -> Option<f64> {
+    match &expr.node {
+        Expr::Literal(LiteralExpr::Int(number)) => Some(number.value as f64),
+        Expr::Literal(LiteralExpr::Float(number)) => Some(number.value),
+        Expr::Unary(unary) if unary.op.node == UnaryOp::Minus => number(&unary.expr).map(|value| -value),
+        _ => None,
+    }
+}
+
+/// parameters of functions and lambdas their body never reads, ones starting with `_` are unused on purpose
+struct UnusedParameters {
+    /// declarations some variable expression reads, a local shadowing a parameter has its own
+    reads: BTreeSet<usize>,
+    found: Vec<(SourceSpan, String)>,
+}
+
+impl UnusedParameters {
+    fn check(&mut self, params: &[TypedIdent]) {
+        for param in params {
+            let name = &param.name.node;
+            if !name.starts_with('_') && !self.reads.contains(&param.name.node_id) {
+                self.found.push((param.name.span, name.clone()));
+            }
+        }
+    }
+}
+
+impl Visitor for UnusedParameters {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Stmt::FunDecl(fun_decl) = &stmt.node {
+            self.check(&fun_decl.params);
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        if let Expr::Lambda(lambda) = &expr.node {
+            self.check(&lambda.parameters);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// local variables nothing reads, globals may be read by the host and ones starting with `_` are unused on purpose
+struct UnusedVariables {
+    /// declarations some variable expression reads
+    reads: BTreeSet<usize>,
+    /// blocks and function bodies the visitor is in, variables declared outside of them are globals
+    local_depth: usize,
+    found: Vec<(SourceSpan, String)>,
+}
+
+impl Visitor for UnusedVariables {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Stmt::VarDecl(var_decl) = &stmt.node
+            && self.local_depth > 0
+            && !var_decl.ident.node.starts_with('_')
+            && !self.reads.contains(&stmt.node_id)
+        {
+            self.found.push((var_decl.ident.span, var_decl.ident.node.clone()));
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_block(&mut self, block: &BlockExpr) {
+        self.local_depth += 1;
+        walk_block(self, block);
+        self.local_depth -= 1;
+    }
+}
+
+/// size and complexity of a function declaration
+struct Function {
+    name: String,
+    /// the name's span
+    span: SourceSpan,
+    lines: usize,
+    parameters: usize,
+    complexity: usize,
+}
+
+/// every function declaration, nested ones included
+struct FunctionMetrics<'a> {
+    source: &'a str,
+    found: Vec<Function>,
+}
+
+impl Visitor for FunctionMetrics<'_> {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Stmt::FunDecl(fun_decl) = &stmt.node {
+            let text = &self.source[stmt.span.offset()..stmt.span.offset() + stmt.span.len()];
+            let mut complexity = Complexity::default();
+            complexity.visit_block(&fun_decl.body.node);
+            self.found.push(Function {
+                name: fun_decl.name.node.clone(),
+                span: fun_decl.name.span,
+                lines: text.matches('\n').count() + 1,
+                parameters: fun_decl.params.len(),
+                complexity: complexity.decisions + 1,
+            });
+        }
+        walk_stmt(self, stmt);
+    }
+}
+
+/// branches and loops of a function body, not counting the bodies of nested function declarations
+#[derive(Default)]
+struct Complexity {
+    decisions: usize,
+}
+
+impl Visitor for Complexity {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        match &stmt.node {
+            Stmt::FunDecl(_) => return,
+            Stmt::While(_) | Stmt::For(_) => self.decisions += 1,
+            _ => {}
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        if let Expr::If(_) | Expr::Logical(_) = &expr.node {
+            self.decisions += 1;
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// variables read by a loop condition, `opaque` if it does more than combine variables and literals
+#[derive(Default)]
+struct ConditionVariables {
+    names: BTreeSet<String>,
+    opaque: bool,
+}
+
+impl Visitor for ConditionVariables {
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        match &expr.node {
+            Expr::Variable(ident) => {
+                self.names.insert(ident.node.clone());
+            }
+            Expr::Literal(_) | Expr::Unary(_) | Expr::Binary(_) | Expr::Logical(_) | Expr::Grouping(_) => walk_expr(self, expr),
+            _ => self.opaque = true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Assignments {
+    assigned: BTreeSet<String>,
+    calls: bool,
+}
+
+impl Visitor for Assignments {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Stmt::FunDecl(_) = &stmt.node {
+            // the body only runs when called, which is tracked separately
+            return;
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        match &expr.node {
+            Expr::Assign(assign) => {
+                self.assigned.insert(assign.target.node.clone());
+            }
+            Expr::Call(_) | Expr::MethodCall(_) => self.calls = true,
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// closures created in a loop that read a variable the loop keeps changing
+#[derive(Default)]
+struct LoopCaptures {
+    /// variables changed by each enclosing loop of the current function
+    loops: Vec<BTreeSet<String>>,
+    found: Vec<(SourceSpan, String)>,
+}
+
+impl LoopCaptures {
+    /// variables assigned in the loop, ones declared in the body are new in every iteration
+    fn changing(&self, assignments: Assignments, body: &BlockExpr) -> BTreeSet<String> {
+        let mut declarations = Declarations::default();
+        declarations.visit_block(body);
+        assignments.assigned.difference(&declarations.names).cloned().collect()
+    }
+
+    fn visit_closure(&mut self, params: &[TypedIdent], body: &BlockExpr) {
+        let mut reads = Reads::default();
+        reads.declared.extend(params.iter().map(|param| param.name.node.clone()));
+        reads.visit_block(body);
+        let changing: BTreeSet<&String> = self.loops.iter().flatten().collect();
+        let mut reported = BTreeSet::new();
+        for (span, name) in reads.reads {
+            if changing.contains(&name) && !reads.declared.contains(&name) && reported.insert(name.clone()) {
+                self.found.push((span, name));
+            }
+        }
+
+        // loops around the closure don't matter for closures nested in it, only loops in its own body
+        let loops = std::mem::take(&mut self.loops);
+        self.visit_block(body);
+        self.loops = loops;
+    }
+}
+
+impl Visitor for LoopCaptures {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        match &stmt.node {
+            Stmt::While(while_stmt) => {
+                let mut assignments = Assignments::default();
+                assignments.visit_expr(&while_stmt.condition);
+                assignments.visit_block(&while_stmt.body.node);
+                let changing = self.changing(assignments, &while_stmt.body.node);
+                self.loops.push(changing);
+                walk_stmt(self, stmt);
+                self.loops.pop();
+            }
+            Stmt::For(for_stmt) => {
+                let mut assignments = Assignments::default();
+                if let Some(increment) = &for_stmt.increment {
+                    assignments.visit_expr(increment);
+                }
+                assignments.visit_block(&for_stmt.body.node);
+                let changing = self.changing(assignments, &for_stmt.body.node);
+                self.loops.push(changing);
+                walk_stmt(self, stmt);
+                self.loops.pop();
+            }
+            Stmt::FunDecl(fun_decl) => self.visit_closure(&fun_decl.params, &fun_decl.body.node),
+            _ => walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        match &expr.node {
+            Expr::Lambda(lambda) => self.visit_closure(&lambda.parameters, &lambda.body.node),
+            _ => walk_expr(self, expr),
+        }
+    }
+}
+
+/// names declared by variable declarations, at any depth
+#[derive(Default)]
+struct Declarations {
+    names: BTreeSet<String>,
+}
+
+impl Visitor for Declarations {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if let Stmt::VarDecl(var_decl) = &stmt.node {
+            self.names.insert(var_decl.ident.node.clone());
+        }
+        walk_stmt(self, stmt);
+    }
+}
+
+/// variables a closure reads and the ones it declares itself, nested closures included
+#[derive(Default)]
+struct Reads {
+    reads: Vec<(SourceSpan, String)>,
+    declared: BTreeSet<String>,
+}
+
+impl Visitor for Reads {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        match &stmt.node {
+            Stmt::VarDecl(var_decl) => {
+                self.declared.insert(var_decl.ident.node.clone());
+            }
+            Stmt::FunDecl(fun_decl) => {
+                self.declared.extend(fun_decl.params.iter().map(|param| param.name.node.clone()));
+            }
+            _ => {}
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        match &expr.node {
+            Expr::Variable(ident) => self.reads.push((ident.span, ident.node.clone())),
+            Expr::Lambda(lambda) => {
+                self.declared.extend(lambda.parameters.iter().map(|param| param.name.node.clone()));
+            }
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// variables, functions, parameters and types named like a contextual keyword, fields are left out because they are
+/// only used after a `.`
+#[derive(Default)]
+struct KeywordNames {
+    found: Vec<(SourceSpan, String)>,
+}
+
+impl KeywordNames {
+    fn check(&mut self, name: &Ident) {
+        if CONTEXTUAL_KEYWORDS.contains(&name.node.as_str()) {
+            self.found.push((name.span, name.node.clone()));
+        }
+    }
+
+    fn check_params(&mut self, params: &[TypedIdent]) {
+        for param in params {
+            self.check(&param.name);
+        }
+    }
+}
+
+impl Visitor for KeywordNames {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        match &stmt.node {
+            Stmt::VarDecl(var_decl) => self.check(&var_decl.ident),
+            Stmt::FunDecl(fun_decl) => {
+                self.check(&fun_decl.name);
+                self.check_params(&fun_decl.params);
+            }
+            Stmt::StructDecl(struct_decl) => self.check(&struct_decl.ident),
+            Stmt::TypeAlias(type_alias) => self.check(&type_alias.ident),
+            _ => {}
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        if let Expr::Lambda(lambda) = &expr.node {
+            self.check_params(&lambda.parameters);
+        }
+        walk_expr(self, expr);
+    }
+}
+
+/// the declarations of the locals some variable expression reads, assignments don't count
+fn local_reads(program: &Program, bindings: &Bindings) -> BTreeSet<usize> {
+    let mut reads = BTreeSet::new();
+    program.walk(|node| {
+        if let Node::Expr(expr) = node
+            && let Expr::Variable(_) = &expr.node
+            && let Some(declaration) = bindings.declarations.get(&expr.node_id)
+        {
+            reads.insert(*declaration);
+        }
+    });
+    reads
+}
+
+/// the statement a suppression comment applies to, the outermost one ending before the comment on its line, or else
+/// the outermost one starting after it
+fn suppressed_statement(statements: &[SourceSpan], comment: SourceSpan, source: &str) -> Option<SourceSpan> {
+    let end = |span: &SourceSpan| span.offset() + span.len();
+    let line_start = source[..comment.offset()].rfind('\n').map_or(0, |newline| newline + 1);
+    if !source[line_start..comment.offset()].trim().is_empty() {
+        statements
+            .iter()
+            .filter(|span| end(span) <= comment.offset() && end(span) > line_start)
+            .max_by_key(|span| (end(span), span.len()))
+            .copied()
+    } else {
+        statements
+            .iter()
+            .filter(|span| span.offset() >= end(&comment))
+            .min_by_key(|span| (span.offset(), Reverse(span.len())))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Parser, Resolver};
+
+    /// the codes and labelled text of the warnings the linter reports for `source`
+    fn lint(source: &str) -> Vec<(String, String)> {
+        let mut lexer = Lexer::new(source);
+        let lex_result = lexer.lex();
+        let program = Parser::new(lex_result.tokens, source.to_string()).parse().ast;
+        let mut resolver = Resolver::new(&program, source.to_string());
+        resolver.resolve();
+        let mut linter = Linter::new(&program, source.to_string())
+            .with_bindings(resolver.bindings())
+            .with_suppressions(&lex_result.comments);
+        linter
+            .lint()
+            .iter()
+            .map(|warning| {
+                let code = warning.code().map(|code| code.to_string()).unwrap_or_default();
+                let span = warning.labels().and_then(|mut labels| labels.next()).unwrap();
+                (code, source[span.offset()..span.offset() + span.len()].to_string())
+            })
+            .collect()
+    }
+
+    /// `source` with the suggestion of each warning that has one applied on its own
+    fn fixes(source: &str) -> Vec<String> {
+        let mut lexer = Lexer::new(source);
+        let program = Parser::new(lexer.lex().tokens, source.to_string()).parse().ast;
+        let mut linter = Linter::new(&program, source.to_string()).with_magic_number_lint(&DEFAULT_ALLOWED_NUMBERS);
+        linter
+            .lint()
+            .iter()
+            .filter_map(|warning| warning.downcast_ref::<LintWarning>()?.suggestion())
+            .map(|suggestion| {
+                let start = suggestion.span.offset();
+                let end = start + suggestion.span.len();
+                format!("{}{}{}", &source[..start], suggestion.replacement, &source[end..])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn condition_simplifications_are_machine_applicable() {
+        assert_eq!(
+            fixes("let x = true; if x == true { print(1); }"),
+            vec!["let x = true; if x { print(1); }"]
+        );
+        assert_eq!(fixes("let a = 1; let b = !(a != 2);"), vec!["let a = 1; let b = a == 2;"]);
+        assert_eq!(
+            fixes("let n = 1; let d = if n > 0 { false } else { true };"),
+            vec!["let n = 1; let d = !(n > 0);"]
+        );
+        assert_eq!(
+            fixes("let n = 1; if n > 0 { } else { print(1); }"),
+            vec!["let n = 1; if !(n > 0) { print(1); }"]
+        );
+    }
+
+    #[test]
+    fn magic_numbers_are_declared_at_the_top_of_their_function() {
+        let source = "fn area(r: Float) -> Float {
+    let squared = r * r;
+    squared * 3.14
+}";
+        let fixed = "fn area(r: Float) -> Float {
+    let NAME = 3.14;
+    let squared = r * r;
+    squared * 3.14
+}";
+        assert_eq!(fixes(source), vec![fixed]);
+        assert_eq!(fixes("fn f() -> Int { 42 }"), vec!["fn f() -> Int { let NAME = 42;\n42 }"]);
+    }
+
+    #[test]
+    fn unused_locals_are_reported() {
+        let source = "fn f(n: Int) -> Int { let unused = 1; let used = n; let _ignored = 2; used }
+let global = f(1);";
+        assert_eq!(lint(source), vec![("lint::unused_variable".to_string(), "unused".to_string())]);
+    }
+
+    #[test]
+    fn assignments_dont_count_as_reads() {
+        let source = "fn f() -> Nil { let written = 1; written = 2; } f();";
+        assert_eq!(lint(source), vec![("lint::unused_variable".to_string(), "written".to_string())]);
+    }
+
+    #[test]
+    fn a_shadowing_local_doesnt_hide_an_unused_parameter() {
+        let source = "fn f(n: Int) -> Int { let n = 2; n } f(1);";
+        assert_eq!(lint(source), vec![("lint::unused_parameter".to_string(), "n".to_string())]);
+    }
+
+    #[test]
+    fn parameters_read_by_closures_are_used() {
+        let source = "fn f(n: Int, _unused: Int) -> Int { let g = fn() -> Int { n }; g() } f(1, 2);";
+        assert_eq!(lint(source), vec![]);
+    }
+
+    #[test]
+    fn suppressions_silence_unused_variables() {
+        let source = "fn f() -> Nil {
+    // rslox-ignore: unused-variable
+    let silenced = 1;
+    let trailing = 2; // rslox-ignore: unused-variable
+}
+f();";
+        assert_eq!(lint(source), vec![]);
+    }
+
+    #[test]
+    fn suppressions_that_silence_nothing_are_reported() {
+        let source = "fn f() -> Int {
+    // rslox-ignore: unused-variable
+    let used = 1;
+    used
+}
+f();";
+        assert_eq!(
+            lint(source),
+            vec![(
+                "lint::unused_suppression".to_string(),
+                "// rslox-ignore: unused-variable".to_string()
+            )]
+        );
+    }
+}
@@ -1,75 +1,774 @@
-use rub::interpreters::Interpreter;
+use miette::{MietteHandlerOpts, Report, Severity};
+use rub::ast::{Expr, FunDeclStmt, LiteralExpr, Program};
+use rub::attributes::AttributeRegistry;
+use rub::call_graph::CallGraph;
+use rub::compiler::Compiler;
+use rub::error::InterpreterError;
+use rub::interpreters::{Interpreter, Value};
+use rub::lint::{DEFAULT_ALLOWED_NUMBERS, Linter};
+use rub::node_table::ast_to_json;
+use rub::optimizer::{DEFAULT_INLINE_THRESHOLD, Optimizer};
+use rub::preprocessor::{Preprocessor, SourceMap};
+use rub::printer::print_expr;
+use rub::sarif::SarifLog;
+use rub::session::Snapshot;
+use rub::source::decode;
+use rub::stats::ProgramStats;
 use rub::{Lexer, Parser, Resolver, TypeInferrer};
+use std::collections::BTreeMap;
 use std::fs;
-use std::time::Instant;
+use std::thread;
+use std::time::{Duration, Instant};
 
-macro_rules! time_log {
-    ($start:expr, $phase:expr) => {
+/// how long each phase of a run took and what it produced, summarized when dropped with `--verbose`
+struct PhaseTimings {
+    verbose: bool,
+    phase_start: Instant,
+    phases: Vec<(&'static str, Duration, String)>,
+}
+
+impl PhaseTimings {
+    fn new(verbose: bool) -> Self {
+        Self {
+            verbose,
+            phase_start: Instant::now(),
+            phases: vec![],
+        }
+    }
+
+    /// ends the running phase, the next one starts now
+    fn end(&mut self, phase: &'static str, details: String) {
+        let elapsed = self.phase_start.elapsed();
         #[cfg(feature = "timing")]
-        println!("{} took {:?}", $phase, $start.elapsed());
-    };
+        println!("{phase} took {elapsed:?}");
+        self.phases.push((phase, elapsed, details));
+        self.phase_start = Instant::now();
+    }
 }
 
-fn interpret(code: &str) {
-    #[cfg(feature = "timing")]
-    let start = Instant::now();
+impl Drop for PhaseTimings {
+    fn drop(&mut self) {
+        if !self.verbose {
+            return;
+        }
+        let total: Duration = self.phases.iter().map(|(_, elapsed, _)| *elapsed).sum();
+        eprintln!("phases:");
+        for (phase, elapsed, details) in &self.phases {
+            let share = elapsed.as_secs_f64() / total.as_secs_f64().max(f64::EPSILON) * 100.0;
+            eprintln!("  {phase:<16} {:>12} {share:>5.1}%  {details}", format!("{elapsed:.2?}"));
+        }
+        eprintln!("  {:<16} {:>12}", "total", format!("{total:.2?}"));
+    }
+}
 
-    let mut lexer = Lexer::new(&code);
-    let lex_result = lexer.lex();
-    time_log!(start, "Lexing");
+/// errors printed by default before the rest are only counted
+const DEFAULT_MAX_ERRORS: usize = 20;
 
-    if !lex_result.errors.is_empty() {
-        for err in lex_result.errors {
-            println!("{:?}", err);
+/// prints diagnostics as they are found, or collects them into a SARIF log printed at the end
+struct Reporter {
+    source_map: SourceMap,
+    sarif: Option<SarifLog>,
+    /// errors printed before later diagnostics are only counted, 0 prints all of them
+    max_errors: usize,
+    printed_errors: usize,
+    /// errors over the limit by code
+    skipped_errors: BTreeMap<String, usize>,
+    skipped_warnings: usize,
+    /// errors reported so far, printed or not
+    errors: usize,
+    /// drops every diagnostic, for the repeated runs of `bench`
+    muted: bool,
+}
+
+impl Reporter {
+    fn new(source_map: SourceMap, options: &Options) -> Self {
+        Self {
+            source_map,
+            sarif: options.sarif.then(SarifLog::new),
+            max_errors: options.max_errors,
+            printed_errors: 0,
+            skipped_errors: BTreeMap::new(),
+            skipped_warnings: 0,
+            errors: 0,
+            muted: false,
         }
-        return;
     }
 
-    let mut parser = Parser::new(lex_result.tokens, code.to_string());
-    let parse_result = parser.parse();
-    time_log!(start, "Parsing");
+    /// a reporter for the same source that drops what it is given
+    fn muted(&self, options: &Options) -> Self {
+        Self {
+            muted: true,
+            ..Self::new(self.source_map.clone(), options)
+        }
+    }
+
+    /// with includes the file and line of each label are printed too, the rendered lines are from the expanded source
+    fn report(&mut self, diagnostic: &Report) {
+        self.emit(diagnostic, true);
+    }
+
+    /// for diagnostics with spans in the file they come from instead of the expanded source, like the preprocessor's
+    fn report_unmapped(&mut self, diagnostic: &Report) {
+        self.emit(diagnostic, false);
+    }
+
+    fn emit(&mut self, diagnostic: &Report, mapped: bool) {
+        let is_error = diagnostic.severity().unwrap_or(Severity::Error) == Severity::Error;
+        if is_error {
+            self.errors += 1;
+        }
+        if self.muted {
+            return;
+        }
+        if let Some(sarif) = &mut self.sarif {
+            match mapped {
+                true => sarif.add(diagnostic, &self.source_map),
+                false => sarif.add(diagnostic, &SourceMap::default()),
+            }
+            return;
+        }
+
+        // once the limit is reached the rest are mostly errors caused by the earlier ones
+        if self.max_errors > 0 && self.printed_errors >= self.max_errors {
+            if is_error {
+                let code = diagnostic.code().map_or("other".to_string(), |code| code.to_string());
+                *self.skipped_errors.entry(code).or_default() += 1;
+            } else {
+                self.skipped_warnings += 1;
+            }
+            return;
+        }
+        if is_error {
+            self.printed_errors += 1;
+        }
+
+        println!("{:?}", diagnostic);
+        if !mapped || !self.source_map.has_includes() {
+            return;
+        }
+        for label in diagnostic.labels().into_iter().flatten() {
+            if let Some(location) = self.source_map.locate(label.offset()) {
+                println!("  --> {location}");
+            }
+        }
+    }
+
+    fn finish(self) {
+        if let Some(sarif) = self.sarif {
+            println!("{}", sarif.to_json());
+        }
+
+        let skipped: usize = self.skipped_errors.values().sum();
+        if skipped > 0 {
+            let counts: Vec<String> = self.skipped_errors.iter().map(|(code, count)| format!("{code}: {count}")).collect();
+            println!("and {skipped} more errors ({})", counts.join(", "));
+        }
+        if self.skipped_warnings > 0 {
+            println!("and {} more warnings", self.skipped_warnings);
+        }
+    }
+}
+
+/// how often `--watch` checks the files for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// warnings are printed but don't stop the pipeline
+fn has_errors(diagnostics: &[Report]) -> bool {
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity().unwrap_or(Severity::Error) == Severity::Error)
+}
+
+#[derive(Default, Clone)]
+enum Command {
+    #[default]
+    Run,
+    /// the file is a single expression, its value is printed
+    Eval,
+    Stats,
+    CallGraph,
+    /// prints the AST as JSON with node ids and spans
+    Ast,
+    Bench,
+    /// runs the `@test` functions
+    Test,
+}
+
+#[derive(Default, Clone)]
+struct Options {
+    command: Command,
+    path: String,
+    /// output format of `callgraph`, `dot` or `json`
+    format: String,
+    /// classic Lox truthiness for `if` and `while` conditions
+    truthy_conditions: bool,
+    /// type check function bodies after the rest of the top level
+    signatures_first: bool,
+    /// show where the types in type errors come from
+    explain_types: bool,
+    /// `print expr;` statements, from `--classic` which also enables truthy conditions
+    print_statement: bool,
+    /// warn about trailing commas in lists
+    lint_trailing_commas: bool,
+    /// a line break before `}` or the end of the file may end a statement
+    lenient_semicolons: bool,
+    /// warn about functions reading or writing global variables
+    lint_globals: bool,
+    /// warn about names that are contextual keywords
+    lint_keywords: bool,
+    /// warn about unnamed numbers in functions
+    lint_magic_numbers: bool,
+    /// warn about `print` calls
+    lint_print: bool,
+    /// files that may call `print` anyway, from `--allow-print FILE`
+    print_allowed_files: Vec<String>,
+    /// numbers the magic number lint accepts, from `--allowed-numbers=0,1,2`
+    allowed_numbers: Option<Vec<f64>>,
+    /// maximum size of inlined functions, 0 disables inlining
+    inline_threshold: usize,
+    /// declarations run before the session and the program, from `--prelude=FILE`
+    prelude: Option<String>,
+    /// snapshot run before the program
+    load_session: Option<String>,
+    /// where to write a snapshot of the globals after running
+    save_session: Option<String>,
+    /// print every executed statement to stderr
+    trace: bool,
+    /// print how often each kind of operation ran to stderr
+    count_ops: bool,
+    /// print how each statement's expression is reduced to its value
+    teach: bool,
+    /// re-run whenever the file, its includes or the loaded session change
+    watch: bool,
+    /// print diagnostics as a SARIF log, from `--error-format=sarif`
+    sarif: bool,
+    /// names for `#if`, from `--define NAME=value`
+    defines: Vec<(String, String)>,
+    /// directories searched for includes, from `--include-path DIR`
+    include_paths: Vec<String>,
+    /// read files that aren't UTF-8 as Latin-1
+    latin1: bool,
+    /// columns a tab advances in reported positions and rendered diagnostics
+    tab_width: Option<usize>,
+    /// errors printed before the rest are only counted, 0 prints all
+    max_errors: usize,
+    /// print how long each phase took
+    verbose: bool,
+    /// the program's `print` writes nothing, set for the timed runs of `bench`
+    quiet: bool,
+    /// front end limits for generated input, in bytes, tokens and syntax nodes
+    max_source_len: Option<usize>,
+    max_tokens: Option<usize>,
+    max_nodes: Option<usize>,
+    /// lint functions longer than this many lines
+    max_function_lines: Option<usize>,
+    /// lint functions with more parameters
+    max_parameters: Option<usize>,
+    /// lint functions with a higher cyclomatic complexity
+    max_complexity: Option<usize>,
+}
 
-    if !parse_result.errors.is_empty() {
-        for error in parse_result.errors {
-            println!("{:?}", error);
+fn parse_args() -> Options {
+    let mut options = Options {
+        path: "source.rub".to_string(),
+        format: "dot".to_string(),
+        inline_threshold: DEFAULT_INLINE_THRESHOLD,
+        max_errors: DEFAULT_MAX_ERRORS,
+        ..Default::default()
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "run" => options.command = Command::Run,
+            "eval" => options.command = Command::Eval,
+            "stats" => options.command = Command::Stats,
+            "callgraph" => options.command = Command::CallGraph,
+            "ast" => options.command = Command::Ast,
+            "bench" => options.command = Command::Bench,
+            "test" => options.command = Command::Test,
+            "--truthy-conditions" => options.truthy_conditions = true,
+            "--signatures-first" => options.signatures_first = true,
+            "--explain-types" => options.explain_types = true,
+            "--classic" => {
+                options.truthy_conditions = true;
+                options.print_statement = true;
+            }
+            "--lint-trailing-commas" => options.lint_trailing_commas = true,
+            "--lenient-semicolons" => options.lenient_semicolons = true,
+            "--lint-globals" => options.lint_globals = true,
+            "--lint-keywords" => options.lint_keywords = true,
+            "--lint-magic-numbers" => options.lint_magic_numbers = true,
+            "--lint-print" => options.lint_print = true,
+            "--allow-print" => options.print_allowed_files.push(args.next().expect("--allow-print expects a file")),
+            "--trace" => options.trace = true,
+            "--count-ops" => options.count_ops = true,
+            "--teach" => options.teach = true,
+            "--watch" => options.watch = true,
+            "--define" => {
+                let define = args.next().expect("--define expects NAME=value");
+                let (name, value) = define.split_once('=').unwrap_or((&define, "true"));
+                options.defines.push((name.to_string(), value.to_string()));
+            }
+            "--include-path" => options.include_paths.push(args.next().expect("--include-path expects a directory")),
+            "--latin1" => options.latin1 = true,
+            "--verbose" => options.verbose = true,
+            "--max-source-bytes" => options.max_source_len = Some(limit_arg(&mut args, "--max-source-bytes")),
+            "--max-tokens" => options.max_tokens = Some(limit_arg(&mut args, "--max-tokens")),
+            "--max-nodes" => options.max_nodes = Some(limit_arg(&mut args, "--max-nodes")),
+            "--max-errors" => options.max_errors = limit_arg(&mut args, "--max-errors"),
+            "--max-function-lines" => options.max_function_lines = Some(limit_arg(&mut args, "--max-function-lines")),
+            "--max-params" => options.max_parameters = Some(limit_arg(&mut args, "--max-params")),
+            "--max-complexity" => options.max_complexity = Some(limit_arg(&mut args, "--max-complexity")),
+            "--error-format=sarif" => options.sarif = true,
+            "--error-format=human" => options.sarif = false,
+            _ if arg.starts_with("--format=") => options.format = arg["--format=".len()..].to_string(),
+            _ if arg.starts_with("--prelude=") => options.prelude = Some(arg["--prelude=".len()..].to_string()),
+            _ if arg.starts_with("--load-session=") => options.load_session = Some(arg["--load-session=".len()..].to_string()),
+            _ if arg.starts_with("--save-session=") => options.save_session = Some(arg["--save-session=".len()..].to_string()),
+            _ if arg.starts_with("--allowed-numbers=") => {
+                let numbers = arg["--allowed-numbers=".len()..].split(',').filter(|number| !number.is_empty());
+                options.allowed_numbers = Some(
+                    numbers
+                        .map(|number| number.trim().parse().expect("--allowed-numbers expects comma separated numbers"))
+                        .collect(),
+                );
+            }
+            _ if arg.starts_with("--tab-width=") => {
+                options.tab_width = Some(arg["--tab-width=".len()..].parse().expect("--tab-width expects a number"))
+            }
+            _ if arg.starts_with("--inline-threshold=") => {
+                options.inline_threshold = arg["--inline-threshold=".len()..]
+                    .parse()
+                    .expect("--inline-threshold expects a number")
+            }
+            _ => options.path = arg,
         }
+    }
+    options
+}
+
+/// the ranges of the source that came from files allowed to print, paths are compared once canonicalized
+fn print_allowed(source_map: &SourceMap, options: &Options) -> Vec<std::ops::Range<usize>> {
+    let canonical = |path: &str| fs::canonicalize(path).unwrap_or_else(|_| path.into());
+    let allowed: Vec<_> = options.print_allowed_files.iter().map(|file| canonical(file)).collect();
+    source_map
+        .files()
+        .filter(|file| allowed.contains(&canonical(file)))
+        .flat_map(|file| source_map.ranges(file))
+        .collect()
+}
+
+fn limit_arg(args: &mut impl Iterator<Item = String>, flag: &str) -> usize {
+    args.next()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or_else(|| panic!("{flag} expects a number"))
+}
+
+/// `prefix_len` is the length of the prelude and session in front of the program
+fn interpret(code: &str, prefix_len: usize, reporter: &mut Reporter, options: &Options) {
+    let mut phases = PhaseTimings::new(options.verbose);
+
+    let mut lexer = Lexer::new(code);
+    if let Some(limit) = options.max_source_len {
+        lexer = lexer.with_max_source_len(limit);
+    }
+    if let Some(limit) = options.max_tokens {
+        lexer = lexer.with_max_tokens(limit);
+    }
+    let lex_result = lexer.lex();
+    phases.end(
+        "Lexing",
+        format!("{} tokens, {} errors", lex_result.tokens.len(), lex_result.errors.len()),
+    );
+
+    for err in lex_result.errors {
+        reporter.report(err);
+    }
+    // lexer errors leave error tokens behind, parsing still finds the errors after them
+    let lex_failed = has_errors(lex_result.errors);
+
+    let mut parser = Parser::new(lex_result.tokens, code.to_string())
+        .with_declarations_before(prefix_len)
+        .with_print_statement(options.print_statement)
+        .with_trailing_comma_lint(options.lint_trailing_commas)
+        .with_semicolon_tolerance(options.lenient_semicolons);
+    if let Some(limit) = options.max_nodes {
+        parser = parser.with_max_nodes(limit);
+    }
+    let parse_result = match options.command {
+        Command::Eval => parser.parse_expression(),
+        _ => parser.parse(),
+    };
+    phases.end(
+        "Parsing",
+        format!(
+            "{} statements, {} diagnostics",
+            parse_result.ast.statements.len(),
+            parse_result.errors.len()
+        ),
+    );
+
+    for error in parse_result.errors {
+        reporter.report(error);
+    }
+    if lex_failed || has_errors(parse_result.errors) {
+        return;
+    }
+
+    let attribute_errors = AttributeRegistry::new().check(&parse_result.ast, code);
+    for error in &attribute_errors {
+        reporter.report(error);
+    }
+    if has_errors(&attribute_errors) {
         return;
     }
 
     let mut resolver = Resolver::new(&parse_result.ast, code.to_string());
     let resolving_errors = resolver.resolve();
-    time_log!(start, "Resolving");
+    phases.end("Resolving", format!("{} errors", resolving_errors.len()));
 
-    if !resolving_errors.is_empty() {
-        for error in resolving_errors {
-            println!("{:?}", error);
-        }
+    for error in resolving_errors {
+        reporter.report(error);
+    }
+    if has_errors(resolving_errors) {
         return;
     }
 
-    let mut type_inferrer = TypeInferrer::new(&parse_result.ast, code.to_string());
+    let mut type_inferrer = TypeInferrer::new(&parse_result.ast, code.to_string())
+        .with_truthy_conditions(options.truthy_conditions)
+        .with_signatures_first(options.signatures_first)
+        .with_type_explanations(options.explain_types);
     let type_inference_result = type_inferrer.infer();
-    time_log!(start, "Type Inference");
+    phases.end(
+        "Type Inference",
+        format!(
+            "{} types, {} diagnostics",
+            type_inference_result.type_env.len(),
+            type_inference_result.errors.len()
+        ),
+    );
 
-    if !type_inference_result.errors.is_empty() {
-        for error in type_inference_result.errors {
-            println!("{:?}", error);
-        }
+    for error in type_inference_result.errors {
+        reporter.report(error);
+    }
+    if has_errors(type_inference_result.errors) {
         return;
     }
 
-    // println!("{:?}", parse_result.ast);
-    let mut interpreter = Interpreter::new(&parse_result.ast, type_inference_result.type_env, code.to_string());
+    let mut linter = Linter::new(&parse_result.ast, code.to_string())
+        .with_global_state_lint(options.lint_globals)
+        .with_keyword_lint(options.lint_keywords)
+        .with_prefix(prefix_len)
+        .with_bindings(resolver.bindings())
+        .with_suppressions(&lex_result.comments);
+    if let Some(limit) = options.max_function_lines {
+        linter = linter.with_max_function_lines(limit);
+    }
+    if let Some(limit) = options.max_parameters {
+        linter = linter.with_max_parameters(limit);
+    }
+    if let Some(limit) = options.max_complexity {
+        linter = linter.with_max_complexity(limit);
+    }
+    if options.lint_print {
+        linter = linter
+            .with_print_lint(true)
+            .with_print_allowed(print_allowed(&reporter.source_map, options));
+    }
+    if options.lint_magic_numbers {
+        linter = linter.with_magic_number_lint(options.allowed_numbers.as_deref().unwrap_or(&DEFAULT_ALLOWED_NUMBERS));
+    }
+    let warnings = linter.lint();
+    for warning in warnings {
+        reporter.report(warning);
+    }
+    phases.end("Linting", format!("{} warnings", warnings.len()));
+
+    let program = Optimizer::new(resolver.mutated_globals().clone())
+        .with_inline_threshold(options.inline_threshold)
+        .optimize(&parse_result.ast);
+    phases.end("Optimizing", format!("{} statements", program.statements.len()));
+
+    // slots are assigned on the optimized program, inlining moves call arguments into a new scope
+    let mut slot_resolver = Resolver::new(&program, code.to_string());
+    slot_resolver.resolve();
+    phases.end("Slot Resolving", String::new());
+
+    // println!("{:?}", program);
+    let mut interpreter = Interpreter::new(
+        &program,
+        type_inference_result.type_env.clone(),
+        slot_resolver.bindings().clone(),
+        code.to_string(),
+    )
+    .with_trace(options.trace)
+    .with_operation_counts(options.count_ops)
+    .with_teaching(options.teach);
+    if options.quiet {
+        interpreter = interpreter.with_native("print", discard_print);
+    }
+    if let Command::Eval = options.command {
+        match interpreter.evaluate() {
+            Ok(value) => println!("{}", value.to_printable_value()),
+            Err(err) => reporter.report(&err),
+        }
+        phases.end("Evaluating", String::new());
+        return;
+    }
     let error = interpreter.interpret().error;
     if let Some(err) = error {
+        reporter.report(&err);
+    }
+    phases.end("Interpreting", String::new());
+
+    if let Some(path) = &options.save_session {
+        let snapshot = Snapshot::capture(&parse_result.ast, code, interpreter.globals());
+        for name in &snapshot.skipped {
+            println!("session: '{name}' was not saved, its value has no literal form");
+        }
+        fs::write(path, snapshot.source).unwrap_or_else(|_| panic!("Error writing session {}", path));
+    }
+}
+
+/// runs the program without and with inlining and reports how long each run took, the diagnostics and the output
+/// come from an untimed run before them, the timed runs print nothing
+fn bench(code: &str, prefix_len: usize, reporter: &mut Reporter, options: &Options) {
+    interpret(code, prefix_len, reporter, options);
+    if reporter.errors > 0 {
+        return;
+    }
+
+    let mut muted = reporter.muted(options);
+    let mut timings = vec![];
+    for inline_threshold in [0, options.inline_threshold] {
+        let options = Options {
+            inline_threshold,
+            save_session: None,
+            quiet: true,
+            ..options.clone()
+        };
+        let start = Instant::now();
+        interpret(code, prefix_len, &mut muted, &options);
+        timings.push((inline_threshold, start.elapsed()));
+    }
+
+    for (inline_threshold, elapsed) in timings {
+        println!("inline threshold {inline_threshold}: {elapsed:?}");
+    }
+}
+
+fn discard_print(_args: Vec<Value>) -> Result<Value, InterpreterError> {
+    Ok(Value::Nil)
+}
+
+struct Parsed {
+    ast: Program,
+    token_count: usize,
+}
+
+/// lexes and parses `code` for the analysis commands, `None` if there were errors
+fn parse(code: &str) -> Option<Parsed> {
+    let mut lexer = Lexer::new(code);
+    let lex_result = lexer.lex();
+    for err in lex_result.errors {
         println!("{:?}", err);
     }
-    time_log!(start, "Interpreting");
+    let lex_failed = has_errors(lex_result.errors);
+
+    let token_count = lex_result.tokens.len() - 1;
+    let mut parser = Parser::new(lex_result.tokens, code.to_string());
+    let parse_result = parser.parse();
+    for error in parse_result.errors {
+        println!("{:?}", error);
+    }
+    if lex_failed || has_errors(parse_result.errors) {
+        return None;
+    }
+
+    Some(Parsed {
+        ast: parse_result.ast,
+        token_count,
+    })
+}
+
+fn stats(code: &str) {
+    if let Some(parsed) = parse(code) {
+        print!("{}", ProgramStats::collect(&parsed.ast, parsed.token_count));
+    }
+}
+
+fn call_graph(code: &str, format: &str) {
+    let Some(parsed) = parse(code) else { return };
+
+    let call_graph = CallGraph::build(&parsed.ast);
+    match format {
+        "json" => println!("{}", call_graph.to_json()),
+        _ => print!("{}", call_graph.to_dot()),
+    }
+}
+
+fn ast(code: &str) {
+    if let Some(parsed) = parse(code) {
+        println!("{}", ast_to_json(&parsed.ast));
+    }
+}
+
+/// runs every top-level `@test` function after the top-level code, each in a fresh interpreter so tests don't see each
+/// other's globals, `@test(expected_error = "...")` passes when the function fails with an error containing the text
+fn test(code: &str, reporter: &mut Reporter) {
+    let compilation = Compiler::new().check(code);
+    for diagnostic in &compilation.diagnostics {
+        reporter.report(diagnostic);
+    }
+    let Some(checked) = &compilation.program else { return };
+
+    let tests: Vec<(&FunDeclStmt, Option<&LiteralExpr>)> = checked
+        .program
+        .functions()
+        .filter_map(|fun_decl| {
+            let attribute = fun_decl.attributes.iter().find(|attribute| attribute.name.node == "test")?;
+            Some((fun_decl, attribute.arg("expected_error", 0)))
+        })
+        .collect();
+
+    let mut failed = 0;
+    for (fun_decl, expected_error) in &tests {
+        let name = &fun_decl.name.node;
+        let mut interpreter = Interpreter::new(
+            &checked.program,
+            checked.type_env.clone(),
+            checked.bindings.clone(),
+            code.to_string(),
+        );
+        let outcome = match interpreter.interpret().error {
+            Some(err) => Err(format!("the top-level code failed: {err}")),
+            None if !fun_decl.params.is_empty() => Err("test functions take no parameters".to_string()),
+            None => {
+                let function = interpreter.get_global(name).expect("top-level functions are globals");
+                match (interpreter.call(&function, &[]), expected_error) {
+                    (Ok(_), None) => Ok(()),
+                    (Err(err), None) => Err(err.to_string()),
+                    (Err(err), Some(LiteralExpr::String(expected))) if err.to_string().contains(expected.as_str()) => Ok(()),
+                    (Err(err), Some(expected)) => Err(format!(
+                        "expected an error containing {}, got: {err}",
+                        print_expr(&Expr::Literal((*expected).clone()))
+                    )),
+                    (Ok(_), Some(expected)) => Err(format!(
+                        "expected an error containing {}",
+                        print_expr(&Expr::Literal((*expected).clone()))
+                    )),
+                }
+            }
+        };
+        match outcome {
+            Ok(()) => println!("test {name} ... ok"),
+            Err(reason) => {
+                failed += 1;
+                println!("test {name} ... FAILED");
+                println!("    {reason}");
+            }
+        }
+    }
+    println!("test result: {} passed, {failed} failed", tests.len() - failed);
+}
+
+/// runs the command once, returns the files it read
+fn run(options: &Options) -> Vec<String> {
+    let path = &options.path;
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            println!("Error reading file {path}: {err}");
+            return vec![path.clone()];
+        }
+    };
+    let source = match decode(path, bytes, options.latin1) {
+        Ok(source) => source,
+        Err(err) => {
+            println!("{:?}", Report::new(err));
+            return vec![path.clone()];
+        }
+    };
+
+    let mut preprocessor = Preprocessor::new(path, source).with_latin1(options.latin1);
+    for (name, value) in &options.defines {
+        preprocessor = preprocessor.with_define(name, value);
+    }
+    for dir in &options.include_paths {
+        preprocessor = preprocessor.with_include_path(dir);
+    }
+    let preprocess_result = preprocessor.preprocess();
+    let mut files: Vec<String> = preprocess_result.source_map.files().map(str::to_string).collect();
+    let source_map = preprocess_result.source_map.clone();
+    let source_map = match options.tab_width {
+        Some(width) => source_map.with_tab_width(width),
+        None => source_map,
+    };
+    let mut reporter = Reporter::new(source_map, options);
+    // their spans are relative to the file containing the directive, not to the expanded source
+    for error in preprocess_result.errors {
+        reporter.report_unmapped(error);
+    }
+    if has_errors(preprocess_result.errors) {
+        reporter.finish();
+        return files;
+    }
+    let source = preprocess_result.source;
+
+    // the prelude and the session are declared before the program, so its code can use their names
+    let mut prefix = String::new();
+    for path in options.prelude.iter().chain(&options.load_session) {
+        let text = fs::read_to_string(path).unwrap_or_else(|_| panic!("Error reading {}", path));
+        files.push(path.clone());
+        // a program run with the session it saved declares the session's functions again
+        let text = match &options.load_session {
+            Some(session) if session == path => Snapshot::without_redeclared(&text, source),
+            _ => text,
+        };
+        prefix.push_str(&text);
+        prefix.push('\n');
+    }
+    reporter.source_map.offset_by(prefix.len());
+    let source = format!("{prefix}{source} ");
+    match options.command {
+        Command::Run | Command::Eval => interpret(&source, prefix.len(), &mut reporter, options),
+        Command::Stats => stats(&source),
+        Command::CallGraph => call_graph(&source, &options.format),
+        Command::Ast => ast(&source),
+        Command::Bench => bench(&source, prefix.len(), &mut reporter, options),
+        Command::Test => test(&source, &mut reporter),
+    }
+    reporter.finish();
+    files
+}
+
+/// re-runs the command on a cleared screen whenever one of the files it read changes
+fn watch(options: &Options) {
+    let modified = |files: &[String]| -> Vec<_> {
+        files
+            .iter()
+            .map(|file| fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+            .collect()
+    };
+    loop {
+        print!("\x1b[2J\x1b[H");
+        let files = run(options);
+        let last_modified = modified(&files);
+        while modified(&files) == last_modified {
+            thread::sleep(WATCH_INTERVAL);
+        }
+    }
 }
 
 fn main() {
-    let mut path = "source.rub".to_string();
-    let source = fs::read_to_string(&mut path).expect(format!("Error reading file {}", path).as_str());
-    let source = format!("{} ", source);
-    interpret(&source);
+    let options = parse_args();
+    if let Some(width) = options.tab_width {
+        miette::set_hook(Box::new(move |_| Box::new(MietteHandlerOpts::new().tab_width(width).build())))
+            .expect("the handler is only set once");
+    }
+    if options.watch {
+        watch(&options);
+    } else {
+        run(&options);
+    }
 }
@@ -8,6 +8,12 @@ pub struct MethodRegistry {
     methods: HashMap<Type, HashMap<String, (Type, Function)>>,
 }
 
+impl Default for MethodRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MethodRegistry {
     pub fn new() -> Self {
         let mut registry = Self { methods: HashMap::new() };
@@ -16,17 +22,17 @@ impl MethodRegistry {
     }
 
     pub fn lookup_method(&self, base_type: &Type, method_name: &str) -> Option<&(Type, Function)> {
-        if let Some(methods) = self.methods.get(base_type) {
-            if let Some(method) = methods.get(method_name) {
-                return Some(method);
-            }
+        if let Some(methods) = self.methods.get(base_type)
+            && let Some(method) = methods.get(method_name)
+        {
+            return Some(method);
         }
 
         for (type_, methods) in &self.methods {
-            if let Some(method) = methods.get(method_name) {
-                if self.can_monomorphize(type_, base_type) {
-                    return Some(method);
-                }
+            if let Some(method) = methods.get(method_name)
+                && self.can_monomorphize(type_, base_type)
+            {
+                return Some(method);
             }
         }
 
@@ -57,7 +63,7 @@ impl MethodRegistry {
 
         self.methods
             .entry(base_type.clone())
-            .or_insert_with(HashMap::new)
+            .or_default()
             .insert(method_name.to_string(), (method_type.clone(), Function::NativeFunction(method)));
     }
 
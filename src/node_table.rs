@@ -0,0 +1,99 @@
+use crate::ast::{Node, NodeId, Program};
+use miette::SourceSpan;
+use std::collections::HashMap;
+
+/// where a statement or expression sits in its program, the index of its top-level statement followed by its index
+/// among the children of each node on the way down, unlike spans it survives reformatting and unlike node ids it
+/// survives parsing the program again
+pub type NodePath = Vec<usize>;
+
+/// side table of a program's statements and expressions, their spans and paths by node id
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NodeTable {
+    spans: HashMap<NodeId, SourceSpan>,
+    paths: HashMap<NodeId, NodePath>,
+    ids: HashMap<NodePath, NodeId>,
+}
+
+impl NodeTable {
+    pub fn build(program: &Program) -> Self {
+        let mut table = Self::default();
+        for (i, stmt) in program.statements.iter().enumerate() {
+            table.add(Node::Stmt(stmt), vec![i]);
+        }
+        table
+    }
+
+    fn add(&mut self, node: Node, path: NodePath) {
+        for (i, child) in node.children().into_iter().enumerate() {
+            let mut child_path = path.clone();
+            child_path.push(i);
+            self.add(child, child_path);
+        }
+        self.spans.insert(node.node_id(), node.span());
+        self.ids.insert(path.clone(), node.node_id());
+        self.paths.insert(node.node_id(), path);
+    }
+
+    pub fn span(&self, id: NodeId) -> Option<SourceSpan> {
+        self.spans.get(&id).copied()
+    }
+
+    pub fn path(&self, id: NodeId) -> Option<&NodePath> {
+        self.paths.get(&id)
+    }
+
+    /// the node at `path` in this program
+    pub fn id(&self, path: &[usize]) -> Option<NodeId> {
+        self.ids.get(path).copied()
+    }
+
+    /// ids of `earlier`'s nodes mapped to the ids of the nodes at the same paths here, for keeping results keyed by
+    /// the node ids of an earlier parse of the program, e.g. before it was reformatted
+    pub fn translate(&self, earlier: &NodeTable) -> HashMap<NodeId, NodeId> {
+        earlier
+            .paths
+            .iter()
+            .filter_map(|(earlier_id, path)| Some((*earlier_id, self.id(path)?)))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+}
+
+/// the program's statements and expressions as nested JSON objects with their kind, node id, path and span
+pub fn ast_to_json(program: &Program) -> String {
+    let statements: Vec<String> = program
+        .statements
+        .iter()
+        .enumerate()
+        .map(|(i, stmt)| node_to_json(Node::Stmt(stmt), &i.to_string()))
+        .collect();
+    format!("{{\"statements\":[{}]}}", statements.join(","))
+}
+
+fn node_to_json(node: Node, path: &str) -> String {
+    let (category, kind) = match node {
+        Node::Stmt(stmt) => ("stmt", stmt.node.kind()),
+        Node::Expr(expr) => ("expr", expr.node.kind()),
+    };
+    let children: Vec<String> = node
+        .children()
+        .into_iter()
+        .enumerate()
+        .map(|(i, child)| node_to_json(child, &format!("{path}.{i}")))
+        .collect();
+    format!(
+        "{{\"{category}\":\"{kind}\",\"id\":{},\"path\":\"{path}\",\"offset\":{},\"length\":{},\"children\":[{}]}}",
+        node.node_id(),
+        node.span().offset(),
+        node.span().len(),
+        children.join(",")
+    )
+}
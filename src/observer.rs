@@ -0,0 +1,272 @@
+use crate::ast::{AstNode, Expr, LiteralExpr, Stmt};
+use crate::error::RuntimeError;
+use crate::interpreters::Value;
+use crate::printer::print_expr;
+use crate::source::line_column;
+use crate::visitor::{VisitorMut, walk_expr_mut};
+use std::collections::{BTreeMap, HashMap};
+
+/// an observer handed to `Interpreter::with_observer`, `Send` with the `sync` feature so interpreters stay `Send`
+#[cfg(not(feature = "sync"))]
+pub type BoxedObserver = Box<dyn ExecutionObserver>;
+#[cfg(feature = "sync")]
+pub type BoxedObserver = Box<dyn ExecutionObserver + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// callbacks on what a running program does, the common base for tracing, profiling, coverage and debugging
+///
+/// every callback does nothing by default, so an observer only implements the events it needs
+#[allow(unused_variables)]
+pub trait ExecutionObserver {
+    /// `name` is `None` for lambdas and natives
+    fn function_enter(&mut self, name: Option<&str>, arguments: &[Value]) {}
+
+    /// `result` is `None` when the call failed or is suspended
+    fn function_exit(&mut self, name: Option<&str>, result: Option<&Value>) {}
+
+    fn statement_enter(&mut self, stmt: &AstNode<Stmt>) {}
+
+    fn statement_exit(&mut self, stmt: &AstNode<Stmt>) {}
+
+    /// an expression evaluated to `value`, expressions that fail aren't reported
+    fn expression_exit(&mut self, expr: &AstNode<Expr>, value: &Value) {}
+
+    /// reads and writes of variables, declarations and parameters count as writes
+    fn variable_access(&mut self, access: Access, name: &str, value: &Value) {}
+
+    /// a vec, struct, closure or string created by the program
+    fn allocation(&mut self, value: &Value) {}
+
+    /// a runtime error that ends the program or the host call
+    fn error(&mut self, error: &RuntimeError) {}
+}
+
+/// how often a statement is traced, later executions, e.g. in loops, are skipped
+const TRACE_REPEAT_LIMIT: usize = 10;
+
+/// writes every executed statement with the variables it reads and writes to stderr
+pub struct StatementTracer {
+    source: String,
+    /// variables read and written by each running statement, innermost last
+    frames: Vec<Vec<String>>,
+    /// how often each statement was executed
+    executions: HashMap<usize, usize>,
+}
+
+impl StatementTracer {
+    pub fn new(source: String) -> Self {
+        Self {
+            source,
+            frames: vec![],
+            executions: HashMap::new(),
+        }
+    }
+}
+
+impl ExecutionObserver for StatementTracer {
+    fn statement_enter(&mut self, _stmt: &AstNode<Stmt>) {
+        self.frames.push(vec![]);
+    }
+
+    fn statement_exit(&mut self, stmt: &AstNode<Stmt>) {
+        let (line, _) = line_column(&self.source, stmt.span);
+        let accesses = self.frames.pop().unwrap_or_default();
+        let executions = self.executions.entry(stmt.node_id).or_default();
+        *executions += 1;
+
+        if *executions <= TRACE_REPEAT_LIMIT {
+            eprintln!("[trace] line {line}: {} {}", stmt.node.kind(), accesses.join(", "));
+        } else if *executions == TRACE_REPEAT_LIMIT + 1 {
+            eprintln!("[trace] line {line}: executed {TRACE_REPEAT_LIMIT} times, further executions aren't traced");
+        }
+    }
+
+    fn variable_access(&mut self, access: Access, name: &str, value: &Value) {
+        let access = match access {
+            Access::Read => "read",
+            Access::Write => "write",
+        };
+        if let Some(frame) = self.frames.last_mut() {
+            frame.push(format!("{access} {name} = {}", trace_value(value)));
+        }
+    }
+}
+
+/// writes how the expression of each statement is reduced to its value to stderr, one rewrite per line, e.g.
+/// `(1 + 2) * 3` → `(3) * 3` → `3 * 3` → `9`
+pub struct ReductionPrinter {
+    source: String,
+    /// the partly reduced expression of each running statement, innermost last, `None` for statements without one
+    reductions: Vec<Option<Reduction>>,
+}
+
+struct Reduction {
+    expr: AstNode<Expr>,
+    printed: String,
+}
+
+impl ReductionPrinter {
+    pub fn new(source: String) -> Self {
+        Self {
+            source,
+            reductions: vec![],
+        }
+    }
+}
+
+impl ExecutionObserver for ReductionPrinter {
+    fn statement_enter(&mut self, stmt: &AstNode<Stmt>) {
+        let expr = match &stmt.node {
+            Stmt::ExprStmtNode(expr_stmt) => Some(&expr_stmt.expr),
+            Stmt::VarDecl(var_decl) => var_decl.initializer.as_ref(),
+            Stmt::Return(return_stmt) => return_stmt.expr.as_ref(),
+            _ => None,
+        };
+        let reduction = expr.filter(|expr| !matches!(expr.node, Expr::Literal(_))).map(|expr| {
+            let (line, _) = line_column(&self.source, stmt.span);
+            let printed = print_expr(&expr.node);
+            eprintln!("[teach] line {line}: {printed}");
+            Reduction {
+                expr: expr.clone(),
+                printed,
+            }
+        });
+        self.reductions.push(reduction);
+    }
+
+    fn statement_exit(&mut self, _stmt: &AstNode<Stmt>) {
+        self.reductions.pop();
+    }
+
+    fn expression_exit(&mut self, expr: &AstNode<Expr>, value: &Value) {
+        let Some(Some(reduction)) = self.reductions.last_mut() else {
+            return;
+        };
+        let Some(literal) = literal(value) else { return };
+        if matches!(expr.node, Expr::Literal(_)) {
+            return;
+        }
+
+        // expressions of called functions aren't part of the statement and aren't found
+        let mut replace = Replace {
+            node_id: expr.node_id,
+            literal: Some(literal),
+        };
+        replace.visit_expr_mut(&mut reduction.expr);
+        if replace.literal.is_some() {
+            return;
+        }
+        let printed = print_expr(&reduction.expr.node);
+        if printed != reduction.printed {
+            eprintln!("  → {printed}");
+            reduction.printed = printed;
+        }
+    }
+}
+
+/// values that can be written as a literal
+fn literal(value: &Value) -> Option<LiteralExpr> {
+    match value {
+        Value::Int(int) => Some(LiteralExpr::int(*int)),
+        Value::Float(float) => Some(LiteralExpr::float(*float)),
+        Value::String(string) => Some(LiteralExpr::String(string.to_string())),
+        Value::Bool(bool) => Some(LiteralExpr::Bool(*bool)),
+        Value::Nil => Some(LiteralExpr::Nil),
+        _ => None,
+    }
+}
+
+/// replaces the expression with `node_id` by `literal`, lambda bodies are left alone since they run on their own
+struct Replace {
+    node_id: usize,
+    literal: Option<LiteralExpr>,
+}
+
+impl VisitorMut for Replace {
+    fn visit_expr_mut(&mut self, expr: &mut AstNode<Expr>) {
+        if self.literal.is_none() || matches!(expr.node, Expr::Lambda(_)) {
+            return;
+        }
+        if expr.node_id == self.node_id {
+            expr.node = Expr::Literal(self.literal.take().unwrap());
+            return;
+        }
+        walk_expr_mut(self, expr);
+    }
+}
+
+/// width of the longest bar in the operation histogram
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// counts executed statements by kind, calls, variable accesses and allocations, and writes a histogram to stderr
+/// when the interpreter is dropped
+#[derive(Default)]
+pub struct OperationCounter {
+    counts: BTreeMap<&'static str, usize>,
+}
+
+impl OperationCounter {
+    fn count(&mut self, operation: &'static str) {
+        *self.counts.entry(operation).or_default() += 1;
+    }
+}
+
+impl ExecutionObserver for OperationCounter {
+    fn function_enter(&mut self, _name: Option<&str>, _arguments: &[Value]) {
+        self.count("call");
+    }
+
+    fn statement_enter(&mut self, stmt: &AstNode<Stmt>) {
+        self.count(stmt.node.kind());
+    }
+
+    fn variable_access(&mut self, access: Access, _name: &str, _value: &Value) {
+        self.count(match access {
+            Access::Read => "variable read",
+            Access::Write => "variable write",
+        });
+    }
+
+    fn allocation(&mut self, _value: &Value) {
+        self.count("allocation");
+    }
+}
+
+impl Drop for OperationCounter {
+    fn drop(&mut self) {
+        let mut counts: Vec<(&str, usize)> = self.counts.iter().map(|(operation, count)| (*operation, *count)).collect();
+        counts.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let max = counts.first().map_or(1, |(_, count)| *count);
+
+        eprintln!("executed operations:");
+        for (operation, count) in counts {
+            let bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(max));
+            eprintln!("  {operation:<22} {count:>10} {bar}");
+        }
+    }
+}
+
+/// like `to_printable_value`, but for every kind of value
+fn trace_value(value: &Value) -> String {
+    match value {
+        Value::Vec(elements) => {
+            let elements: Vec<String> = elements.borrow().iter().map(trace_value).collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Value::Struct(fields) => {
+            let mut fields: Vec<String> = fields
+                .borrow()
+                .iter()
+                .map(|(name, value)| format!("{name}: {}", trace_value(value)))
+                .collect();
+            fields.sort();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        _ => value.to_printable_value(),
+    }
+}
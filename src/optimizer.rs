@@ -0,0 +1,660 @@
+use crate::ast::{AstNode, BinaryOp, BlockExpr, Expr, LiteralExpr, LogicalOp, NodeId, Program, Stmt, TypedIdent, UnaryOp, VarDeclStmt};
+use crate::call_graph::CallGraph;
+use crate::purity::pure_functions;
+use crate::visitor::{Visitor, VisitorMut, walk_block_mut, walk_expr, walk_expr_mut, walk_stmt, walk_stmt_mut};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+pub const DEFAULT_INLINE_THRESHOLD: usize = 16;
+
+/// how many expressions evaluating a pure call at compile time may take before it is left to the interpreter
+const CALL_FOLD_BUDGET: usize = 10_000;
+/// how deep pure calls may nest while being evaluated at compile time
+const CALL_FOLD_DEPTH: usize = 64;
+
+/// AST to AST passes run after type inference, rewritten nodes keep their `node_id` so the type environment stays valid
+pub struct Optimizer {
+    inline_threshold: usize,
+    mutated_globals: BTreeSet<String>,
+    /// replace reads of top-level constants with their value
+    propagate_globals: bool,
+}
+
+impl Optimizer {
+    /// `mutated_globals` comes from [`crate::Resolver::mutated_globals`]
+    pub fn new(mutated_globals: BTreeSet<String>) -> Self {
+        Self {
+            inline_threshold: DEFAULT_INLINE_THRESHOLD,
+            mutated_globals,
+            propagate_globals: true,
+        }
+    }
+
+    /// functions with at most `threshold` AST nodes get inlined, 0 disables inlining
+    pub fn with_inline_threshold(mut self, threshold: usize) -> Self {
+        self.inline_threshold = threshold;
+        self
+    }
+
+    /// on by default, hosts that change globals from outside with `Interpreter::set_global` or `Interpreter::reload`
+    /// turn it off, reads of top-level variables never reassigned by the script are otherwise replaced by their value
+    pub fn with_global_propagation(mut self, enabled: bool) -> Self {
+        self.propagate_globals = enabled;
+        self
+    }
+
+    pub fn optimize(&self, program: &Program) -> Program {
+        let mut program = program.clone();
+
+        let mut propagator = ConstantPropagator::new(&program, &self.mutated_globals, self.propagate_globals);
+        propagator.visit_program_mut(&mut program);
+
+        let mut call_folder = CallFolder::new(&program);
+        call_folder.visit_program_mut(&mut program);
+
+        if self.inline_threshold > 0 {
+            let mut inliner = Inliner::new(&program, self.inline_threshold, &self.mutated_globals);
+            inliner.visit_program_mut(&mut program);
+            program.next_node_id = inliner.next_node_id;
+        }
+        program
+    }
+}
+
+#[derive(Clone)]
+struct InlineCandidate {
+    params: Vec<String>,
+    body: BlockExpr,
+    /// names the body reads or assigns that aren't parameters, they must resolve to the same globals at the call site
+    free_variables: HashSet<String>,
+}
+
+struct Inliner {
+    candidates: HashMap<String, InlineCandidate>,
+    /// names declared in the local scopes around the current node, globals aren't tracked
+    scopes: Vec<HashSet<String>>,
+    /// the parameter declarations of inlined calls are new nodes
+    next_node_id: NodeId,
+}
+
+impl Inliner {
+    /// only top-level functions without `return` that are never reassigned or redeclared are inlined
+    fn new(program: &Program, threshold: usize, mutated_globals: &BTreeSet<String>) -> Self {
+        let call_graph = CallGraph::build(program);
+
+        let mut declared = HashSet::new();
+        let mut candidates = HashMap::new();
+        for stmt in &program.statements {
+            let Stmt::FunDecl(fun_decl) = &stmt.node else { continue };
+            let name = &fun_decl.name.node;
+            if !declared.insert(name.clone()) {
+                candidates.remove(name);
+                continue;
+            }
+
+            let mut body = BodyInfo::default();
+            body.visit_block(&fun_decl.body.node);
+            if body.size > threshold || body.returns || call_graph.is_recursive(name) || mutated_globals.contains(name) {
+                continue;
+            }
+
+            let params: Vec<String> = fun_decl.params.iter().map(|param| param.name.node.clone()).collect();
+            let free_variables = body.names.into_iter().filter(|name| !params.contains(name)).collect();
+            candidates.insert(
+                name.clone(),
+                InlineCandidate {
+                    params,
+                    body: fun_decl.body.node.clone(),
+                    free_variables,
+                },
+            );
+        }
+
+        Self {
+            candidates,
+            scopes: vec![],
+            next_node_id: program.next_node_id,
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn node_id(&mut self) -> NodeId {
+        let id = self.next_node_id;
+        self.next_node_id += 1;
+        id
+    }
+
+    /// replaces `f(a, b)` with `{ let x = a; let y = b; <body of f> }`
+    fn inline_call(&mut self, expr: &mut AstNode<Expr>) {
+        let Expr::Call(call) = &expr.node else { return };
+        let Expr::Variable(callee) = &call.callee.node else { return };
+        let Some(candidate) = self.candidates.get(&callee.node).cloned() else {
+            return;
+        };
+
+        if self.is_local(&callee.node) || candidate.free_variables.iter().any(|name| self.is_local(name)) {
+            return;
+        }
+        // the parameter bindings would shadow names the arguments refer to
+        let mut argument_names = BodyInfo::default();
+        for argument in &call.arguments {
+            argument_names.visit_expr(argument);
+        }
+        if candidate.params.iter().any(|param| argument_names.names.contains(param)) {
+            return;
+        }
+
+        let mut statements: Vec<AstNode<Stmt>> = candidate
+            .params
+            .iter()
+            .zip(&call.arguments)
+            .map(|(param, argument)| {
+                let var_decl = VarDeclStmt {
+                    attributes: vec![],
+                    ident: AstNode::new(param.clone(), argument.span, self.node_id()),
+                    initializer: Some(argument.clone()),
+                    type_annotation: None,
+                };
+                AstNode::new(Stmt::VarDecl(var_decl), argument.span, self.node_id())
+            })
+            .collect();
+        statements.extend(candidate.body.statements.iter().cloned());
+
+        let inlined = BlockExpr {
+            statements,
+            expr: candidate.body.expr.clone(),
+        };
+        expr.node = Expr::Block(inlined);
+    }
+}
+
+impl VisitorMut for Inliner {
+    fn visit_stmt_mut(&mut self, stmt: &mut AstNode<Stmt>) {
+        match &mut stmt.node {
+            Stmt::FunDecl(fun_decl) => {
+                self.declare(&fun_decl.name.node);
+                self.scopes.push(param_names(&fun_decl.params));
+                walk_stmt_mut(self, stmt);
+                self.scopes.pop();
+            }
+            Stmt::VarDecl(var_decl) => {
+                let name = var_decl.ident.node.clone();
+                walk_stmt_mut(self, stmt);
+                self.declare(&name);
+            }
+            Stmt::For(_) => {
+                self.scopes.push(HashSet::new());
+                walk_stmt_mut(self, stmt);
+                self.scopes.pop();
+            }
+            _ => walk_stmt_mut(self, stmt),
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut AstNode<Expr>) {
+        match &expr.node {
+            Expr::Lambda(lambda) => {
+                self.scopes.push(param_names(&lambda.parameters));
+                walk_expr_mut(self, expr);
+                self.scopes.pop();
+            }
+            Expr::Call(_) => {
+                walk_expr_mut(self, expr);
+                self.inline_call(expr);
+            }
+            _ => walk_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockExpr) {
+        self.scopes.push(HashSet::new());
+        walk_block_mut(self, block);
+        self.scopes.pop();
+    }
+}
+
+/// node count and referenced names of a function body
+#[derive(Default)]
+struct BodyInfo {
+    size: usize,
+    returns: bool,
+    names: HashSet<String>,
+}
+
+impl Visitor for BodyInfo {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        self.size += 1;
+        if let Stmt::Return(_) = &stmt.node {
+            self.returns = true;
+        }
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        self.size += 1;
+        match &expr.node {
+            Expr::Variable(ident) => {
+                self.names.insert(ident.node.clone());
+            }
+            Expr::Assign(assign) => {
+                self.names.insert(assign.target.node.clone());
+            }
+            Expr::StructInit(struct_init) => {
+                self.names.insert(struct_init.name.node.clone());
+            }
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+}
+
+fn param_names(params: &[TypedIdent]) -> HashSet<String> {
+    params.iter().map(|param| param.name.node.clone()).collect()
+}
+
+/// replaces reads of top-level variables that are never reassigned with their literal value and folds constant operators
+struct ConstantPropagator<'a> {
+    mutated_globals: &'a BTreeSet<String>,
+    propagate_globals: bool,
+    /// names declared more than once at the top level
+    redeclared: HashSet<String>,
+    constants: HashMap<String, LiteralExpr>,
+    scopes: Vec<HashSet<String>>,
+}
+
+impl<'a> ConstantPropagator<'a> {
+    fn new(program: &Program, mutated_globals: &'a BTreeSet<String>, propagate_globals: bool) -> Self {
+        let mut declared = HashSet::new();
+        let mut redeclared = HashSet::new();
+        for stmt in &program.statements {
+            let name = match &stmt.node {
+                Stmt::VarDecl(var_decl) => &var_decl.ident.node,
+                Stmt::FunDecl(fun_decl) => &fun_decl.name.node,
+                Stmt::StructDecl(struct_decl) => &struct_decl.ident.node,
+                _ => continue,
+            };
+            if !declared.insert(name.clone()) {
+                redeclared.insert(name.clone());
+            }
+        }
+
+        Self {
+            mutated_globals,
+            propagate_globals,
+            redeclared,
+            constants: HashMap::new(),
+            scopes: vec![],
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+}
+
+impl VisitorMut for ConstantPropagator<'_> {
+    fn visit_stmt_mut(&mut self, stmt: &mut AstNode<Stmt>) {
+        match &mut stmt.node {
+            Stmt::FunDecl(fun_decl) => {
+                self.declare(&fun_decl.name.node);
+                self.scopes.push(param_names(&fun_decl.params));
+                walk_stmt_mut(self, stmt);
+                self.scopes.pop();
+            }
+            Stmt::VarDecl(_) => {
+                walk_stmt_mut(self, stmt);
+                let Stmt::VarDecl(var_decl) = &stmt.node else { unreachable!() };
+                let name = &var_decl.ident.node;
+                if !self.scopes.is_empty() {
+                    self.declare(name);
+                } else if self.propagate_globals
+                    && !self.mutated_globals.contains(name)
+                    && !self.redeclared.contains(name)
+                    && let Some(AstNode {
+                        node: Expr::Literal(literal),
+                        ..
+                    }) = &var_decl.initializer
+                    && !matches!(literal, LiteralExpr::VecLiteral(_))
+                {
+                    self.constants.insert(name.clone(), literal.clone());
+                }
+            }
+            Stmt::For(_) => {
+                self.scopes.push(HashSet::new());
+                walk_stmt_mut(self, stmt);
+                self.scopes.pop();
+            }
+            _ => walk_stmt_mut(self, stmt),
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut AstNode<Expr>) {
+        match &expr.node {
+            Expr::Variable(ident) => {
+                if !self.is_local(&ident.node)
+                    && let Some(literal) = self.constants.get(&ident.node)
+                {
+                    expr.node = Expr::Literal(literal.clone());
+                }
+            }
+            Expr::Lambda(lambda) => {
+                self.scopes.push(param_names(&lambda.parameters));
+                walk_expr_mut(self, expr);
+                self.scopes.pop();
+            }
+            _ => {
+                walk_expr_mut(self, expr);
+                if let Some(literal) = fold(&expr.node) {
+                    expr.node = Expr::Literal(literal);
+                }
+            }
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockExpr) {
+        self.scopes.push(HashSet::new());
+        walk_block_mut(self, block);
+        self.scopes.pop();
+    }
+}
+
+struct PureFunction {
+    params: Vec<String>,
+    body: BlockExpr,
+}
+
+/// replaces calls of pure functions with literal arguments by their result and drops expression statements left
+/// without effect, calls that fail, e.g. by dividing by zero, or run too long are left to the interpreter
+struct CallFolder {
+    functions: HashMap<String, PureFunction>,
+    scopes: Vec<HashSet<String>>,
+}
+
+impl CallFolder {
+    fn new(program: &Program) -> Self {
+        let pure = pure_functions(program);
+        let functions = program
+            .statements
+            .iter()
+            .filter_map(|stmt| match &stmt.node {
+                Stmt::FunDecl(fun_decl) if pure.contains(&fun_decl.name.node) => Some((
+                    fun_decl.name.node.clone(),
+                    PureFunction {
+                        params: fun_decl.params.iter().map(|param| param.name.node.clone()).collect(),
+                        body: fun_decl.body.node.clone(),
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+
+        Self { functions, scopes: vec![] }
+    }
+
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn fold_call(&self, expr: &mut AstNode<Expr>) {
+        let Expr::Call(call) = &expr.node else { return };
+        let Expr::Variable(callee) = &call.callee.node else { return };
+        if self.is_local(&callee.node) || !self.functions.contains_key(&callee.node) {
+            return;
+        }
+
+        let mut evaluator = ConstEvaluator {
+            functions: &self.functions,
+            steps: 0,
+            depth: 0,
+        };
+        if let Some(literal) = evaluator.eval(&expr.node, &HashMap::new()) {
+            expr.node = Expr::Literal(literal);
+        }
+    }
+}
+
+/// expression statements whose expression is a literal do nothing, only used in blocks, the top level of a program
+/// from `Parser::parse_expression` is a single expression statement whose value `eval` prints
+fn drop_literal_statements(statements: &mut Vec<AstNode<Stmt>>) {
+    statements.retain(|stmt| match &stmt.node {
+        Stmt::ExprStmtNode(expr_stmt) => !matches!(expr_stmt.expr.node, Expr::Literal(_)),
+        _ => true,
+    });
+}
+
+impl VisitorMut for CallFolder {
+    fn visit_stmt_mut(&mut self, stmt: &mut AstNode<Stmt>) {
+        match &mut stmt.node {
+            Stmt::FunDecl(fun_decl) => {
+                self.declare(&fun_decl.name.node);
+                self.scopes.push(param_names(&fun_decl.params));
+                walk_stmt_mut(self, stmt);
+                self.scopes.pop();
+            }
+            Stmt::VarDecl(var_decl) => {
+                let name = var_decl.ident.node.clone();
+                walk_stmt_mut(self, stmt);
+                self.declare(&name);
+            }
+            Stmt::For(_) => {
+                self.scopes.push(HashSet::new());
+                walk_stmt_mut(self, stmt);
+                self.scopes.pop();
+            }
+            _ => walk_stmt_mut(self, stmt),
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut AstNode<Expr>) {
+        match &expr.node {
+            Expr::Lambda(lambda) => {
+                self.scopes.push(param_names(&lambda.parameters));
+                walk_expr_mut(self, expr);
+                self.scopes.pop();
+            }
+            Expr::Call(_) => {
+                walk_expr_mut(self, expr);
+                self.fold_call(expr);
+            }
+            _ => walk_expr_mut(self, expr),
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockExpr) {
+        self.scopes.push(HashSet::new());
+        walk_block_mut(self, block);
+        self.scopes.pop();
+        drop_literal_statements(&mut block.statements);
+    }
+}
+
+/// evaluates pure code on literals, only `let` statements, operators, `if`, blocks and calls of pure functions are
+/// supported, everything else gives `None`
+struct ConstEvaluator<'a> {
+    functions: &'a HashMap<String, PureFunction>,
+    steps: usize,
+    depth: usize,
+}
+
+impl ConstEvaluator<'_> {
+    fn eval(&mut self, expr: &Expr, env: &HashMap<String, LiteralExpr>) -> Option<LiteralExpr> {
+        self.steps += 1;
+        if self.steps > CALL_FOLD_BUDGET {
+            return None;
+        }
+
+        match expr {
+            Expr::Literal(LiteralExpr::VecLiteral(_)) => None,
+            Expr::Literal(literal) => Some(literal.clone()),
+            Expr::Variable(ident) => env.get(&ident.node).cloned(),
+            Expr::Grouping(inner) => self.eval(&inner.node, env),
+            Expr::Unary(unary) => {
+                let operand = self.eval(&unary.expr.node, env)?;
+                fold_unary(&unary.op.node, &operand)
+            }
+            Expr::Binary(binary) => {
+                let left = self.eval(&binary.left.node, env)?;
+                let right = self.eval(&binary.right.node, env)?;
+                fold_binary(&binary.op.node, &left, &right)
+            }
+            Expr::Logical(logical) => {
+                let LiteralExpr::Bool(left) = self.eval(&logical.left.node, env)? else {
+                    return None;
+                };
+                match (&logical.op.node, left) {
+                    (LogicalOp::And, false) | (LogicalOp::Or, true) => Some(LiteralExpr::Bool(left)),
+                    _ => match self.eval(&logical.right.node, env)? {
+                        LiteralExpr::Bool(right) => Some(LiteralExpr::Bool(right)),
+                        _ => None,
+                    },
+                }
+            }
+            Expr::If(if_expr) => {
+                let LiteralExpr::Bool(condition) = self.eval(&if_expr.condition.node, env)? else {
+                    return None;
+                };
+                match (condition, &if_expr.else_branch) {
+                    (true, _) => self.eval_block(&if_expr.then_branch.node, env),
+                    (false, Some(else_branch)) => self.eval_block(&else_branch.node, env),
+                    (false, None) => Some(LiteralExpr::Nil),
+                }
+            }
+            Expr::Block(block) => self.eval_block(block, env),
+            Expr::Call(call) => {
+                let Expr::Variable(callee) = &call.callee.node else { return None };
+                // a local of the same name shadows the function
+                if env.contains_key(&callee.node) {
+                    return None;
+                }
+                let function = self.functions.get(&callee.node)?;
+                if function.params.len() != call.arguments.len() || self.depth >= CALL_FOLD_DEPTH {
+                    return None;
+                }
+
+                let mut frame = HashMap::new();
+                for (param, argument) in function.params.iter().zip(&call.arguments) {
+                    frame.insert(param.clone(), self.eval(&argument.node, env)?);
+                }
+                self.depth += 1;
+                let result = self.eval_block(&function.body, &frame);
+                self.depth -= 1;
+                result
+            }
+            _ => None,
+        }
+    }
+
+    fn eval_block(&mut self, block: &BlockExpr, env: &HashMap<String, LiteralExpr>) -> Option<LiteralExpr> {
+        let mut env = env.clone();
+        for stmt in &block.statements {
+            let Stmt::VarDecl(var_decl) = &stmt.node else { return None };
+            let value = self.eval(&var_decl.initializer.as_ref()?.node, &env)?;
+            env.insert(var_decl.ident.node.clone(), value);
+        }
+        match &block.expr {
+            Some(expr) => self.eval(&expr.node, &env),
+            None => Some(LiteralExpr::Nil),
+        }
+    }
+}
+
+/// evaluates operators whose operands are literals, `None` if the result has to be left to the interpreter
+fn fold(expr: &Expr) -> Option<LiteralExpr> {
+    match expr {
+        Expr::Grouping(inner) => match &inner.node {
+            Expr::Literal(LiteralExpr::VecLiteral(_)) => None,
+            Expr::Literal(literal) => Some(literal.clone()),
+            _ => None,
+        },
+        Expr::Unary(unary) => match &unary.expr.node {
+            Expr::Literal(operand) => fold_unary(&unary.op.node, operand),
+            _ => None,
+        },
+        Expr::Binary(binary) => match (&binary.left.node, &binary.right.node) {
+            (Expr::Literal(left), Expr::Literal(right)) => fold_binary(&binary.op.node, left, right),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn fold_unary(op: &UnaryOp, operand: &LiteralExpr) -> Option<LiteralExpr> {
+    match (op, operand) {
+        (UnaryOp::Minus, LiteralExpr::Int(int)) => int.value.checked_neg().map(LiteralExpr::int),
+        (UnaryOp::Minus, LiteralExpr::Float(float)) => Some(LiteralExpr::float(-float.value)),
+        (UnaryOp::Bang, LiteralExpr::Bool(value)) => Some(LiteralExpr::Bool(!value)),
+        _ => None,
+    }
+}
+
+fn fold_binary(op: &BinaryOp, left: &LiteralExpr, right: &LiteralExpr) -> Option<LiteralExpr> {
+    use LiteralExpr::{Bool, Float, Int, String};
+
+    let folded = match (left, right) {
+        (Int(left), Int(right)) => {
+            let (left, right) = (left.value, right.value);
+            match op {
+                BinaryOp::Plus => LiteralExpr::int(left.checked_add(right)?),
+                BinaryOp::Minus => LiteralExpr::int(left.checked_sub(right)?),
+                BinaryOp::Star => LiteralExpr::int(left.checked_mul(right)?),
+                // division by zero stays a runtime error
+                BinaryOp::Slash => LiteralExpr::int(left.checked_div(right)?),
+                BinaryOp::Greater => Bool(left > right),
+                BinaryOp::GreaterEqual => Bool(left >= right),
+                BinaryOp::Less => Bool(left < right),
+                BinaryOp::LessEqual => Bool(left <= right),
+                BinaryOp::EqualEqual => Bool(left == right),
+                BinaryOp::BangEqual => Bool(left != right),
+            }
+        }
+        (Float(left), Float(right)) => {
+            let (left, right) = (left.value, right.value);
+            match op {
+                BinaryOp::Plus => LiteralExpr::float(left + right),
+                BinaryOp::Minus => LiteralExpr::float(left - right),
+                BinaryOp::Star => LiteralExpr::float(left * right),
+                BinaryOp::Slash if right == 0.0 => return None,
+                BinaryOp::Slash => LiteralExpr::float(left / right),
+                BinaryOp::Greater => Bool(left > right),
+                BinaryOp::GreaterEqual => Bool(left >= right),
+                BinaryOp::Less => Bool(left < right),
+                BinaryOp::LessEqual => Bool(left <= right),
+                BinaryOp::EqualEqual => Bool(left == right),
+                BinaryOp::BangEqual => Bool(left != right),
+            }
+        }
+        (String(left), String(right)) => match op {
+            BinaryOp::Plus => String(format!("{left}{right}")),
+            BinaryOp::EqualEqual => Bool(left == right),
+            BinaryOp::BangEqual => Bool(left != right),
+            _ => return None,
+        },
+        (Bool(left), Bool(right)) => match op {
+            BinaryOp::EqualEqual => Bool(left == right),
+            BinaryOp::BangEqual => Bool(left != right),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(folded)
+}
@@ -2,22 +2,30 @@ use crate::ast::Expr::{Block, Call, Grouping, Lambda, Literal, Unary, Variable};
 use crate::ast::LiteralExpr::VecLiteral;
 use crate::ast::Stmt::{ExprStmtNode, Return, While};
 use crate::ast::{
-    AssignExpr, AstNode, BinaryExpr, BinaryOp, BlockExpr, CallExpr, Delimiter, Expr, ExprStmt, FieldAccessExpr, FieldAssignExpr, ForStmt,
-    FunDeclStmt, Ident, IfExpr, LambdaExpr, LiteralExpr, LogicalExpr, LogicalOp, MethodCallExpr, Program, ReturnStmt, Stmt, StructDeclStmt,
-    StructInitExpr, TypedIdent, UnaryExpr, UnaryOp, VarDeclStmt, WhileStmt,
+    AssignExpr, AstNode, Attribute, AttributeArg, BinaryExpr, BinaryOp, BlockExpr, CallExpr, CastExpr, Delimiter, Expr, ExprStmt,
+    FieldAccessExpr, FieldAssignExpr, ForStmt, FunDeclStmt, Ident, IfExpr, LambdaExpr, LiteralExpr, LogicalExpr, LogicalOp, MethodCallExpr,
+    NodeId, Number, PrimitiveType, Program, RecordExpr, ReturnStmt, Stmt, StructDeclStmt, StructInitExpr, TypeAliasStmt, TypedIdent,
+    UnaryExpr, UnaryOp, UnresolvedType, VarDeclStmt, WhileStmt,
 };
+use crate::cancellation::Cancellation;
 use crate::error::ParseError::{
-    ExpectedExpression, ExpectedIdentifier, InvalidFunctionName, InvalidStructName, InvalidVariableName, MissingBlock, MissingOperand,
-    MissingSemicolon, RedundantParenthesis, RedundantSemicolon, UnclosedDelimiter, UnexpectedClosingDelimiter, UnexpectedEOF,
+    Cancelled, ExpectedExpression, ExpectedIdentifier, ImplicitSemicolon, InvalidAttributeArgument, InvalidFunctionName, InvalidStructName,
+    InvalidVariableName, MisplacedAttribute, MissingBlock, MissingComma, MissingOperand, MissingSemicolon, RedundantParenthesis,
+    RedundantSemicolon, TooManyArguments, TooManyNodes, TrailingComma, UnclosedDelimiter, UnexpectedClosingDelimiter, UnexpectedEOF,
     UnexpectedToken, UnmatchedDelimiter,
 };
+use crate::error::Suggestion;
 use crate::type_inferrer::Type;
+use crate::visitor::{Visitor, walk_expr, walk_stmt};
 use crate::{TokenKind, lexer};
 use lexer::Token;
 use miette::{Report, SourceOffset, SourceSpan};
 
 type ParseResult<T> = Result<T, Report>;
 
+/// the most arguments a call and parameters a function can have, as in Lox
+pub const DEFAULT_MAX_ARGUMENTS: usize = 255;
+
 pub struct ParserResult<'a> {
     pub errors: &'a Vec<Report>,
     pub ast: Program,
@@ -29,6 +37,22 @@ pub struct Parser<'a> {
     errors: Vec<Report>,
     source: String,
     delimiter_stack: Vec<Delimiter>,
+    /// classic Lox `print expr;` statements
+    print_statement: bool,
+    /// warn about commas before the closing delimiter of a list
+    trailing_comma_lint: bool,
+    /// a line break before `}` or the end of the input ends a statement
+    semicolon_tolerance: bool,
+    max_arguments: usize,
+    max_nodes: Option<usize>,
+    /// statements and expressions parsed so far, counted only with a node limit
+    nodes: usize,
+    cancellation: Option<Cancellation>,
+    /// number of errors when the parse was cancelled, later ones come from stopping midway
+    cancelled_at: Option<usize>,
+    /// `parse_expression` parses the tokens before this offset as declarations
+    declarations_before: usize,
+    next_node_id: NodeId,
 }
 
 impl<'a> Parser<'a> {
@@ -118,25 +142,19 @@ impl<'a> Parser<'a> {
         self.errors.push(error);
     }
 
-    /// if `current` is not a left brace it skips the whole block
-    fn expect_block(&mut self) -> ParseResult<()> {
-        if !self.matches(&[TokenKind::LeftBrace]) {
-            let opening_span = self.current().span;
-            self.skip_next_block();
-            return Err(MissingBlock {
-                src: self.source.to_string(),
-                span: opening_span,
-            }
-            .into());
-        }
-        Ok(())
-    }
-
     /// if `current` is not a semicolon, it skips to the next statement
     fn expect_semicolon(&mut self) {
         if !self.consume(&[TokenKind::Semicolon]) {
             let previous_span = self.previous().span;
             let next_span = self.next_span(previous_span);
+            if self.semicolon_tolerance && self.line_ends_statement() {
+                let warning = ImplicitSemicolon {
+                    src: self.source.to_string(),
+                    span: next_span,
+                };
+                self.report(warning.into());
+                return;
+            }
             let error = MissingSemicolon {
                 src: self.source.to_string(),
                 span: next_span,
@@ -146,6 +164,15 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// with semicolon tolerance, the end of the input or a `}` on a later line
+    fn line_ends_statement(&self) -> bool {
+        match self.current().token_kind {
+            TokenKind::EOF => true,
+            TokenKind::RightBrace => self.current().newline_before,
+            _ => false,
+        }
+    }
+
     fn expect_expr(&self, result: ParseResult<Expr>, side: &str, span: SourceSpan) -> ParseResult<Expr> {
         result.map_err(|_| {
             MissingOperand {
@@ -165,6 +192,40 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// whether the parse was cancelled, it then jumps to the end of the input so every loop stops
+    fn checkpoint(&mut self) -> bool {
+        if self.cancelled_at.is_none() && self.cancellation.as_ref().is_some_and(Cancellation::is_cancelled) {
+            self.cancelled_at = Some(self.errors.len());
+            self.position = self.tokens.len() - 1;
+        }
+        self.cancelled_at.is_some()
+    }
+
+    fn node_id(&mut self) -> NodeId {
+        let id = self.next_node_id;
+        self.next_node_id += 1;
+        id
+    }
+
+    /// adds the statement's nodes to the count, past the limit the parse ends after it
+    fn count_nodes(&mut self, stmt: &AstNode<Stmt>) {
+        let Some(limit) = self.max_nodes else { return };
+        let mut counter = NodeCounter(0);
+        counter.visit_stmt(stmt);
+        self.nodes += counter.0;
+        if self.nodes > limit {
+            self.report(
+                TooManyNodes {
+                    src: self.source.to_string(),
+                    span: stmt.span,
+                    limit,
+                }
+                .into(),
+            );
+            self.position = self.tokens.len() - 1;
+        }
+    }
+
     /// skips past the next semicolon, stops before block ending
     fn skip_to_next_stmt(&mut self) {
         while !self.matches(&[TokenKind::Semicolon, TokenKind::RightBrace]) && !self.at_eof() {
@@ -175,11 +236,6 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// skips until next left brace
-    fn skip_to_next_block(&mut self) {
-        self.eat_to_tokens(&[TokenKind::LeftBrace]);
-    }
-
     /// skips until next left paren
     fn skip_to_next_paren(&mut self) {
         self.eat_to_tokens(&[TokenKind::LeftParen])
@@ -277,10 +333,71 @@ impl<'a> Parser<'a> {
             errors: vec![],
             source,
             delimiter_stack: vec![],
+            print_statement: false,
+            trailing_comma_lint: false,
+            semicolon_tolerance: false,
+            max_arguments: DEFAULT_MAX_ARGUMENTS,
+            max_nodes: None,
+            nodes: 0,
+            cancellation: None,
+            cancelled_at: None,
+            declarations_before: 0,
+            next_node_id: 1,
         }
     }
 
-    pub fn parse(&mut self) -> ParserResult {
+    /// parses `print expr;` as a statement like classic Lox, `print` can't be used as a value then
+    pub fn with_print_statement(mut self, enabled: bool) -> Self {
+        self.print_statement = enabled;
+        self
+    }
+
+    /// trailing commas in lists are always accepted, this warns about them for codebases that don't want them
+    pub fn with_trailing_comma_lint(mut self, enabled: bool) -> Self {
+        self.trailing_comma_lint = enabled;
+        self
+    }
+
+    /// accepts a line break before `}` or the end of the input in place of a `;` with a warning, for quick scripts
+    pub fn with_semicolon_tolerance(mut self, enabled: bool) -> Self {
+        self.semicolon_tolerance = enabled;
+        self
+    }
+
+    /// calls and functions with more arguments or parameters are errors, backends can rely on the limit
+    pub fn with_max_arguments(mut self, limit: usize) -> Self {
+        self.max_arguments = limit;
+        self
+    }
+
+    /// statements and expressions in the program, past the limit the rest of the input is skipped with
+    /// `ParseError::TooManyNodes`
+    pub fn with_max_nodes(mut self, limit: usize) -> Self {
+        self.max_nodes = Some(limit);
+        self
+    }
+
+    /// checked before every statement, a cancelled parse returns the statements so far and ends with `ParseError::Cancelled`
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// the source up to `offset` is declarations `parse_expression` parses before the expression, e.g. a prelude or a
+    /// loaded session put in front of it
+    pub fn with_declarations_before(mut self, offset: usize) -> Self {
+        self.declarations_before = offset;
+        self
+    }
+
+    /// node ids start at `id` instead of 1, pass the `next_node_id` of an earlier program whose analysis results are
+    /// kept alongside this one's, e.g. for `Interpreter::reload`
+    pub fn with_first_node_id(mut self, id: NodeId) -> Self {
+        self.next_node_id = id;
+        self
+    }
+
+    pub fn parse(&mut self) -> ParserResult<'_> {
         let left_program_span = self.current().span;
         let mut statements = vec![];
         if self.matches(&[TokenKind::EOF]) {
@@ -288,15 +405,19 @@ impl<'a> Parser<'a> {
                 ast: Program {
                     statements,
                     span: self.create_span(left_program_span, self.current().span),
+                    next_node_id: self.next_node_id,
                 },
                 errors: &self.errors,
             };
         }
 
-        while !self.at_eof() {
-            let statement = self.declaration();
+        while !self.at_eof() && !self.checkpoint() {
+            let statement = self.spanned_declaration();
             match statement {
-                Ok(stmt) => statements.push(stmt),
+                Ok(stmt) => {
+                    self.count_nodes(&stmt);
+                    statements.push(stmt);
+                }
                 Err(err) => {
                     self.report(err);
                     self.skip_to_next_stmt();
@@ -304,28 +425,219 @@ impl<'a> Parser<'a> {
             }
         }
 
+        if let Some(errors) = self.cancelled_at {
+            self.errors.truncate(errors);
+            self.report(Cancelled.into());
+        }
+
         ParserResult {
             ast: Program {
                 statements,
                 span: self.create_span(left_program_span, self.current().span),
+                next_node_id: self.next_node_id,
             },
             errors: &self.errors,
         }
     }
 
+    /// parses a source that is a single expression, as in data files, the program holds it as its last statement after
+    /// the declarations before `with_declarations_before`'s offset
+    pub fn parse_expression(&mut self) -> ParserResult<'_> {
+        let left_program_span = self.current().span;
+        let mut statements = vec![];
+        while !self.at_eof() && self.current().span.offset() < self.declarations_before {
+            match self.spanned_declaration() {
+                Ok(stmt) => {
+                    self.count_nodes(&stmt);
+                    statements.push(stmt);
+                }
+                Err(err) => {
+                    self.report(err);
+                    self.skip_to_next_stmt();
+                }
+            }
+        }
+
+        let left_expr_span = self.current().span;
+        let result = self.expression().and_then(|expr| {
+            let expr_right_span = self.previous().span;
+            if !self.at_eof() {
+                return Err(UnexpectedToken {
+                    src: self.source.to_string(),
+                    span: self.current().span,
+                    expected: "end of file after the expression".to_string(),
+                    found: self.current().token_kind.clone(),
+                }
+                .into());
+            }
+            let span = self.create_span(left_expr_span, expr_right_span);
+            Ok(AstNode::new(
+                ExprStmtNode(ExprStmt {
+                    expr: AstNode::new(expr, span, self.node_id()),
+                }),
+                span,
+                self.node_id(),
+            ))
+        });
+
+        match result {
+            Ok(stmt) => {
+                self.count_nodes(&stmt);
+                statements.push(stmt);
+            }
+            Err(err) => self.report(err),
+        }
+        ParserResult {
+            ast: Program {
+                statements,
+                span: self.create_span(left_program_span, self.current().span),
+                next_node_id: self.next_node_id,
+            },
+            errors: &self.errors,
+        }
+    }
+
+    /// the diagnostics of the parse, for keeping them after the parser is gone
+    pub fn into_errors(self) -> Vec<Report> {
+        self.errors
+    }
+
+    /// for interactive input, unclosed delimiters or a trailing operator mean the input continues on the next line and
+    /// shouldn't be parsed yet, mismatched delimiters are left to the parser to report
+    pub fn input_is_incomplete(&self) -> bool {
+        let mut delimiters = vec![];
+        for token in &self.tokens {
+            match token.token_kind {
+                TokenKind::LeftParen => delimiters.push(TokenKind::RightParen),
+                TokenKind::LeftBrace => delimiters.push(TokenKind::RightBrace),
+                TokenKind::LeftBracket => delimiters.push(TokenKind::RightBracket),
+                TokenKind::RightParen | TokenKind::RightBrace | TokenKind::RightBracket
+                    if delimiters.pop().as_ref() != Some(&token.token_kind) =>
+                {
+                    return false;
+                }
+                _ => {}
+            }
+        }
+        if !delimiters.is_empty() {
+            return true;
+        }
+
+        let last = self.tokens.iter().rev().find(|token| token.token_kind != TokenKind::EOF);
+        last.is_some_and(|token| {
+            token.token_kind.binding_power().is_some()
+                || matches!(
+                    token.token_kind,
+                    TokenKind::Bang | TokenKind::Comma | TokenKind::Dot | TokenKind::Colon | TokenKind::Arrow
+                )
+        })
+    }
+
+    /// a declaration as a node spanning its tokens, the way the program holds its statements
+    fn spanned_declaration(&mut self) -> ParseResult<AstNode<Stmt>> {
+        let left_span = self.current().span;
+        let stmt = self.declaration()?;
+        Ok(AstNode::new(
+            stmt,
+            self.create_span(left_span, self.previous().span),
+            self.node_id(),
+        ))
+    }
+
     fn declaration(&mut self) -> ParseResult<Stmt> {
+        let attributes = self.attributes()?;
         if self.matches(&[TokenKind::Let]) {
-            return self.var_declaration();
+            return self.var_declaration(attributes);
         } else if self.matches(&[TokenKind::Fn]) {
-            return self.fun_declaration();
+            return self.fun_declaration(attributes);
         } else if self.matches(&[TokenKind::Struct]) {
-            return self.struct_declaration();
+            return self.struct_declaration(attributes);
+        } else if self.matches(&[TokenKind::Type]) {
+            return self.type_alias_declaration(attributes);
+        }
+        if let Some(attribute) = attributes.last() {
+            return Err(MisplacedAttribute {
+                src: self.source.to_string(),
+                span: attribute.span,
+            }
+            .into());
         }
         self.statement()
     }
 
-    fn var_declaration(&mut self) -> ParseResult<Stmt> {
-        let var_keyword_span = self.current().span;
+    /// `@name` or `@name(args)` before a declaration, any number of them
+    fn attributes(&mut self) -> ParseResult<Vec<Attribute>> {
+        let mut attributes = vec![];
+        while self.matches(&[TokenKind::At]) {
+            let at_span = self.current().span;
+            self.advance_position();
+
+            let name_token = self.current().clone();
+            let TokenKind::Ident(name) = &name_token.token_kind else {
+                return Err(ExpectedIdentifier {
+                    src: self.source.to_string(),
+                    span: name_token.span,
+                    context: "attribute".to_string(),
+                }
+                .into());
+            };
+            let name = AstNode::new(name.clone(), name_token.span, self.node_id());
+            self.advance_position();
+
+            let mut args = vec![];
+            if self.matches(&[TokenKind::LeftParen]) {
+                self.open_delimiter(TokenKind::LeftParen)?;
+                while !self.matches(&[TokenKind::RightParen]) && !self.at_eof() {
+                    args.push(self.attribute_arg()?);
+                    if !self.consume(&[TokenKind::Comma]) {
+                        break;
+                    }
+                }
+                self.close_delimiter(TokenKind::RightParen)?;
+            }
+
+            attributes.push(Attribute {
+                name,
+                args,
+                span: self.create_span(at_span, self.previous().span),
+            });
+        }
+        Ok(attributes)
+    }
+
+    /// a literal, or `name = literal`
+    fn attribute_arg(&mut self) -> ParseResult<AttributeArg> {
+        let name = match &self.current().token_kind {
+            TokenKind::Ident(name) if self.next_is(TokenKind::Equal) => {
+                let name = AstNode::new(name.clone(), self.current().span, self.node_id());
+                self.advance_position();
+                self.advance_position();
+                Some(name)
+            }
+            _ => None,
+        };
+
+        let value_span = self.current().span;
+        let value = match self.primary()? {
+            Literal(VecLiteral(_)) => None,
+            Literal(literal) => Some(literal),
+            _ => None,
+        };
+        let span = self.create_span(value_span, self.previous().span);
+        match value {
+            Some(literal) => Ok(AttributeArg {
+                name,
+                value: AstNode::new(literal, span, self.node_id()),
+            }),
+            None => Err(InvalidAttributeArgument {
+                src: self.source.to_string(),
+                span,
+            }
+            .into()),
+        }
+    }
+
+    fn var_declaration(&mut self, attributes: Vec<Attribute>) -> ParseResult<Stmt> {
         self.advance_position();
 
         let variable_name = self.parse_variable_name()?;
@@ -339,14 +651,12 @@ impl<'a> Parser<'a> {
         let initializer = self.parse_var_initializer()?;
         self.expect_semicolon();
 
-        Ok(Stmt::VarDecl(AstNode::new(
-            VarDeclStmt {
-                ident: variable_name,
-                initializer,
-                type_annotation,
-            },
-            self.create_span(var_keyword_span, self.previous().span),
-        )))
+        Ok(Stmt::VarDecl(VarDeclStmt {
+            attributes,
+            ident: variable_name,
+            initializer,
+            type_annotation,
+        }))
     }
 
     fn parse_variable_name(&mut self) -> ParseResult<Ident> {
@@ -357,7 +667,7 @@ impl<'a> Parser<'a> {
             TokenKind::Ident(name) => {
                 let variable_span = variable_token.span;
                 self.advance_position();
-                AstNode::new(name.clone(), variable_span)
+                AstNode::new(name.clone(), variable_span, self.node_id())
             }
             TokenKind::Float(_) | TokenKind::Int(_) => {
                 if self.next_is(TokenKind::Ident(String::new())) {
@@ -410,6 +720,7 @@ impl<'a> Parser<'a> {
             Some(AstNode::new(
                 self.expression()?,
                 self.create_span(expr_left_span, self.previous().span),
+                self.node_id(),
             ))
         } else if self.matches(&[TokenKind::Semicolon]) {
             None
@@ -417,7 +728,7 @@ impl<'a> Parser<'a> {
             return Err(UnexpectedToken {
                 src: self.source.to_string(),
                 span: self.current().span,
-                expected: "'=' or ';'".to_string(),
+                expected: "`=` or `;`".to_string(),
                 found: self.current().token_kind.clone(),
             }
             .into());
@@ -425,8 +736,7 @@ impl<'a> Parser<'a> {
         Ok(initializer)
     }
 
-    fn fun_declaration(&mut self) -> ParseResult<Stmt> {
-        let fun_keyword_span = self.current().span;
+    fn fun_declaration(&mut self, attributes: Vec<Attribute>) -> ParseResult<Stmt> {
         self.advance_position();
 
         let function_name = self.parse_function_name()?;
@@ -449,16 +759,14 @@ impl<'a> Parser<'a> {
         };
         let body_right_span = self.previous().span;
 
-        Ok(Stmt::FunDecl(AstNode::new(
-            FunDeclStmt {
-                name: function_name,
-                params: parameters,
-                generics,
-                body: AstNode::new(body, self.create_span(body_left_span, body_right_span)),
-                return_type,
-            },
-            self.create_span(fun_keyword_span, self.previous().span),
-        )))
+        Ok(Stmt::FunDecl(FunDeclStmt {
+            attributes,
+            name: function_name,
+            params: parameters,
+            generics,
+            body: AstNode::new(body, self.create_span(body_left_span, body_right_span), self.node_id()),
+            return_type,
+        }))
     }
 
     /// current is struct name, ends at '{'
@@ -468,7 +776,7 @@ impl<'a> Parser<'a> {
         let struct_name = match &struct_token.token_kind {
             TokenKind::Ident(name) => {
                 self.advance_position();
-                AstNode::new(name.clone(), struct_token.span)
+                AstNode::new(name.clone(), struct_token.span, self.node_id())
             }
             TokenKind::Float(_) | TokenKind::Int(_) => {
                 if self.next_is(TokenKind::Ident(String::new())) {
@@ -481,7 +789,7 @@ impl<'a> Parser<'a> {
                         }
                         .into(),
                     );
-                    AstNode::new("err_fun".to_string(), self.current().span)
+                    AstNode::new("err_fun".to_string(), self.current().span, self.node_id())
                 } else {
                     self.skip_to_next_paren();
                     self.report(
@@ -492,7 +800,7 @@ impl<'a> Parser<'a> {
                         }
                         .into(),
                     );
-                    AstNode::new("err fun".to_string(), self.current().span)
+                    AstNode::new("err fun".to_string(), self.current().span, self.node_id())
                 }
             }
             _ => {
@@ -507,33 +815,79 @@ impl<'a> Parser<'a> {
         };
         Ok(struct_name)
     }
-    fn struct_declaration(&mut self) -> ParseResult<Stmt> {
-        let struct_keyword_span = self.current().span;
+    fn struct_declaration(&mut self, attributes: Vec<Attribute>) -> ParseResult<Stmt> {
         self.advance_position();
 
         let struct_name = self.parse_struct_name()?;
         self.open_delimiter(TokenKind::LeftBrace)?;
         let parameters = self.parse_typed_idents(TokenKind::RightBrace)?;
 
-        Ok(Stmt::StructDecl(AstNode::new(
-            StructDeclStmt {
-                ident: struct_name,
-                fields: parameters,
-            },
-            self.create_span(struct_keyword_span, self.previous().span),
-        )))
+        Ok(Stmt::StructDecl(StructDeclStmt {
+            attributes,
+            ident: struct_name,
+            fields: parameters,
+        }))
     }
 
-    fn parse_return_type(&mut self) -> ParseResult<AstNode<Type>> {
+    fn type_alias_declaration(&mut self, attributes: Vec<Attribute>) -> ParseResult<Stmt> {
+        self.advance_position();
+
+        let name_token = self.current().clone();
+        let TokenKind::Ident(name) = &name_token.token_kind else {
+            return Err(ExpectedIdentifier {
+                src: self.source.to_string(),
+                span: name_token.span,
+                context: "type alias".to_string(),
+            }
+            .into());
+        };
+        let alias_name = AstNode::new(name.clone(), name_token.span, self.node_id());
+        self.advance_position();
+
+        if !self.consume(&[TokenKind::Equal]) {
+            return Err(UnexpectedToken {
+                src: self.source.to_string(),
+                span: self.current().span,
+                expected: "`=`".to_string(),
+                found: self.current().token_kind.clone(),
+            }
+            .into());
+        }
+
+        let type_left_span = self.current().span;
+        let ty = self.parse_type()?;
+        let ty = AstNode::new(
+            UnresolvedType::from(&ty),
+            self.create_span(type_left_span, self.previous().span),
+            self.node_id(),
+        );
+        self.expect_semicolon();
+
+        Ok(Stmt::TypeAlias(TypeAliasStmt {
+            attributes,
+            ident: alias_name,
+            ty,
+        }))
+    }
+
+    fn parse_return_type(&mut self) -> ParseResult<AstNode<UnresolvedType>> {
         if !self.consume(&[TokenKind::Arrow]) {
-            return Ok(AstNode::new(Type::Nil, SourceSpan::from(0)));
+            return Ok(AstNode::new(
+                UnresolvedType::Primitive(PrimitiveType::Nil),
+                SourceSpan::from(0),
+                self.node_id(),
+            ));
         }
 
         let return_left_span = self.current().span;
         let ty = self.parse_type()?;
         let return_right_span = self.previous().span;
 
-        Ok(AstNode::new(ty, self.create_span(return_left_span, return_right_span)))
+        Ok(AstNode::new(
+            UnresolvedType::from(&ty),
+            self.create_span(return_left_span, return_right_span),
+            self.node_id(),
+        ))
     }
 
     /// current is function name, ends at '('
@@ -543,7 +897,7 @@ impl<'a> Parser<'a> {
         let function_name = match &function_token.token_kind {
             TokenKind::Ident(name) => {
                 self.advance_position();
-                AstNode::new(name.clone(), function_token.span)
+                AstNode::new(name.clone(), function_token.span, self.node_id())
             }
             TokenKind::Float(_) | TokenKind::Int(_) => {
                 if self.next_is(TokenKind::Ident(String::new())) {
@@ -556,7 +910,7 @@ impl<'a> Parser<'a> {
                         }
                         .into(),
                     );
-                    AstNode::new("err_fun".to_string(), self.current().span)
+                    AstNode::new("err_fun".to_string(), self.current().span, self.node_id())
                 } else {
                     self.skip_to_next_paren();
                     self.report(
@@ -567,7 +921,7 @@ impl<'a> Parser<'a> {
                         }
                         .into(),
                     );
-                    AstNode::new("err fun".to_string(), self.current().span)
+                    AstNode::new("err fun".to_string(), self.current().span, self.node_id())
                 }
             }
             _ => {
@@ -595,7 +949,7 @@ impl<'a> Parser<'a> {
             match &self.current().token_kind {
                 TokenKind::Ident(name) => {
                     let span = self.current().span;
-                    generics.push(AstNode::new(name.clone(), span));
+                    generics.push(AstNode::new(name.clone(), span, self.node_id()));
                     self.advance_position();
 
                     if self.consume(&[TokenKind::Greater]) {
@@ -606,7 +960,7 @@ impl<'a> Parser<'a> {
                             src: self.source.to_string(),
                             span: self.current().span,
                             found: self.current().token_kind.clone(),
-                            expected: "',' or '>'".to_string(),
+                            expected: "`,` or `>`".to_string(),
                         }
                         .into());
                     }
@@ -637,7 +991,7 @@ impl<'a> Parser<'a> {
     }
 
     /// current is `:` end is after type
-    fn parse_type_annotation(&mut self) -> ParseResult<AstNode<Type>> {
+    fn parse_type_annotation(&mut self) -> ParseResult<AstNode<UnresolvedType>> {
         if !self.consume(&[TokenKind::Colon]) {
             return Err(UnexpectedToken {
                 src: self.source.to_string(),
@@ -652,7 +1006,11 @@ impl<'a> Parser<'a> {
         let ty = self.parse_type()?;
         let annotation_right_span = self.previous().span;
 
-        Ok(AstNode::new(ty, self.create_span(annotation_left_span, annotation_right_span)))
+        Ok(AstNode::new(
+            UnresolvedType::from(&ty),
+            self.create_span(annotation_left_span, annotation_right_span),
+            self.node_id(),
+        ))
     }
 
     /// current is the type annotation
@@ -674,7 +1032,7 @@ impl<'a> Parser<'a> {
                 return Err(UnexpectedToken {
                     src: self.source.to_string(),
                     span: self.current().span,
-                    expected: "'->'".to_string(),
+                    expected: "`->`".to_string(),
                     found: self.current().token_kind.clone(),
                 }
                 .into());
@@ -693,7 +1051,7 @@ impl<'a> Parser<'a> {
                         return Err(UnexpectedToken {
                             src: self.source.to_string(),
                             span: self.current().span,
-                            expected: "'<'".to_string(),
+                            expected: "`<`".to_string(),
                             found: self.current().token_kind.clone(),
                         }
                         .into());
@@ -704,7 +1062,7 @@ impl<'a> Parser<'a> {
                         return Err(UnexpectedToken {
                             src: self.source.to_string(),
                             span: self.current().span,
-                            expected: "'>'".to_string(),
+                            expected: "`>`".to_string(),
                             found: self.current().token_kind.clone(),
                         }
                         .into());
@@ -712,6 +1070,38 @@ impl<'a> Parser<'a> {
 
                     Ok(Type::Vec(inner_type))
                 }
+                TokenKind::LeftBrace => {
+                    self.open_delimiter(TokenKind::LeftBrace)?;
+                    let mut fields = vec![];
+                    while !self.matches(&[TokenKind::RightBrace]) && !self.at_eof() {
+                        let name_token = self.current().clone();
+                        let TokenKind::Ident(name) = name_token.token_kind else {
+                            return Err(ExpectedIdentifier {
+                                src: self.source.to_string(),
+                                span: name_token.span,
+                                context: "field".to_string(),
+                            }
+                            .into());
+                        };
+                        self.advance_position();
+                        if !self.consume(&[TokenKind::Colon]) {
+                            return Err(UnexpectedToken {
+                                src: self.source.to_string(),
+                                span: self.current().span,
+                                expected: "`:`".to_string(),
+                                found: self.current().token_kind.clone(),
+                            }
+                            .into());
+                        }
+                        fields.push((name, self.parse_type()?));
+                        if !self.consume(&[TokenKind::Comma]) {
+                            break;
+                        }
+                    }
+                    self.close_delimiter(TokenKind::RightBrace)?;
+                    fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                    Ok(Type::Record(fields))
+                }
                 TokenKind::TypeInt => {
                     self.advance_position();
                     Ok(Type::Int)
@@ -759,7 +1149,7 @@ impl<'a> Parser<'a> {
                 let type_annotation = self.parse_type_annotation()?;
 
                 Ok(TypedIdent {
-                    name: AstNode::new(name.clone(), name_span),
+                    name: AstNode::new(name.clone(), name_span, self.node_id()),
                     type_annotation,
                 })
             }
@@ -778,20 +1168,29 @@ impl<'a> Parser<'a> {
     /// start at first `field` ends after the `closing_delimiter`
     fn parse_typed_idents(&mut self, closing_delimiter: TokenKind) -> ParseResult<Vec<TypedIdent>> {
         let mut fields = vec![];
+        let context = if closing_delimiter == TokenKind::RightParen {
+            "parameters"
+        } else {
+            "fields"
+        };
 
-        if self.matches(&[closing_delimiter.clone()]) {
+        if self.matches(std::slice::from_ref(&closing_delimiter)) {
             self.close_delimiter(closing_delimiter)?;
             return Ok(fields);
         }
 
         loop {
             let field = self.parse_parameter()?;
+            if context == "parameters" && fields.len() == self.max_arguments {
+                self.too_many_arguments(field.name.span, context);
+            }
             fields.push(field);
 
             match self.current().token_kind.clone() {
                 TokenKind::Comma => {
                     self.advance_position();
                     if self.current_is(closing_delimiter.clone()) {
+                        self.trailing_comma();
                         self.close_delimiter(closing_delimiter)?;
                         break;
                     }
@@ -803,16 +1202,17 @@ impl<'a> Parser<'a> {
                 TokenKind::EOF => {
                     return Err(UnexpectedEOF {
                         src: self.source.to_string(),
-                        expected: format!("{closing_delimiter:?}"),
+                        expected: closing_delimiter.to_string(),
                     }
                     .into());
                 }
+                _ if self.missing_comma(context) => {}
                 _ => {
                     return Err(UnexpectedToken {
                         src: self.source.to_string(),
                         span: self.current().span,
                         found: self.current().token_kind.clone(),
-                        expected: format!("',', or {closing_delimiter:?}"),
+                        expected: format!("`,` or {closing_delimiter}"),
                     }
                     .into());
                 }
@@ -824,7 +1224,7 @@ impl<'a> Parser<'a> {
     fn parse_function_parameters(&mut self) -> ParseResult<Vec<TypedIdent>> {
         self.open_delimiter(TokenKind::LeftParen)?;
 
-        Ok(self.parse_typed_idents(TokenKind::RightParen)?)
+        self.parse_typed_idents(TokenKind::RightParen)
     }
 
     /// current is the start of the statement
@@ -835,14 +1235,38 @@ impl<'a> Parser<'a> {
             return self.for_stmt();
         } else if self.matches(&[TokenKind::Return]) {
             return self.return_stmt();
+        } else if self.print_statement && self.current().token_kind == TokenKind::Ident("print".to_string()) {
+            return self.print_stmt();
         }
         self.expression_stmt()
     }
 
+    /// `print expr;`, a call of the `print` native
+    fn print_stmt(&mut self) -> ParseResult<Stmt> {
+        let print_span = self.current().span;
+        self.advance_position();
+
+        let expr_left_span = self.current().span;
+        let value = self.expression()?;
+        let expr_right_span = self.previous().span;
+        self.expect_semicolon();
+
+        let callee = Variable(AstNode::new("print".to_string(), print_span, self.node_id()));
+        let call = Call(CallExpr {
+            callee: Box::new(AstNode::new(callee, print_span, self.node_id())),
+            arguments: vec![AstNode::new(
+                value,
+                self.create_span(expr_left_span, expr_right_span),
+                self.node_id(),
+            )],
+        });
+        Ok(ExprStmtNode(ExprStmt {
+            expr: AstNode::new(call, self.create_span(print_span, expr_right_span), self.node_id()),
+        }))
+    }
+
     /// current is start of the statement, end is next statement
     fn expression_stmt(&mut self) -> ParseResult<Stmt> {
-        let left_span = self.current().span;
-
         let expr_left_span = self.current().span;
         let value = self.expression()?;
         let expr_right_span = self.previous().span;
@@ -853,12 +1277,9 @@ impl<'a> Parser<'a> {
             _ => self.expect_semicolon(),
         }
 
-        Ok(ExprStmtNode(AstNode::new(
-            ExprStmt {
-                expr: AstNode::new(value, self.create_span(expr_left_span, expr_right_span)),
-            },
-            self.create_span(left_span, self.previous().span),
-        )))
+        Ok(ExprStmtNode(ExprStmt {
+            expr: AstNode::new(value, self.create_span(expr_left_span, expr_right_span), self.node_id()),
+        }))
     }
     /// start is `if`, end is next statement
     fn if_expr(&mut self) -> ParseResult<Expr> {
@@ -886,22 +1307,25 @@ impl<'a> Parser<'a> {
         if self.consume(&[TokenKind::Else]) {
             else_branch = if self.matches(&[TokenKind::If]) {
                 let if_expr = self.if_expr()?;
-                Some(Box::new(AstNode::new(
+                Some(AstNode::new(
                     BlockExpr {
                         statements: vec![],
                         expr: Some(Box::new(AstNode::new(
                             if_expr,
                             self.create_span(else_branch_left_span, self.previous().span),
+                            self.node_id(),
                         ))),
                     },
                     self.create_span(else_branch_left_span, self.previous().span),
-                )))
+                    self.node_id(),
+                ))
             } else {
                 match self.block()? {
-                    Block(block) => Some(Box::new(AstNode::new(
+                    Block(block) => Some(AstNode::new(
                         block,
                         self.create_span(else_branch_left_span, self.previous().span),
-                    ))),
+                        self.node_id(),
+                    )),
                     _ => {
                         return Err(MissingBlock {
                             src: self.source.to_string(),
@@ -914,8 +1338,16 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Expr::If(IfExpr {
-            condition: Box::new(AstNode::new(condition, self.create_span(condition_left_span, condition_right_span))),
-            then_branch: AstNode::new(then_branch, self.create_span(then_branch_left_span, then_branch_right_span)),
+            condition: Box::new(AstNode::new(
+                condition,
+                self.create_span(condition_left_span, condition_right_span),
+                self.node_id(),
+            )),
+            then_branch: AstNode::new(
+                then_branch,
+                self.create_span(then_branch_left_span, then_branch_right_span),
+                self.node_id(),
+            ),
             else_branch,
         }))
     }
@@ -928,18 +1360,25 @@ impl<'a> Parser<'a> {
         let mut expression = None;
 
         while !self.matches(&[TokenKind::RightBrace]) && !self.at_eof() {
+            if self.checkpoint() {
+                break;
+            }
             let saved_pos = self.position;
+            let saved_delimiters = self.delimiter_stack.len();
+            let expr_left_span = self.current().span;
 
-            if let Ok(expr) = self.expression() {
-                if self.current_is(TokenKind::RightBrace) {
-                    let span = self.create_span(self.previous().span, self.current().span);
-                    expression = Some(Box::new(AstNode::new(expr, span)));
-                    break;
-                }
+            if let Ok(expr) = self.expression()
+                && self.current_is(TokenKind::RightBrace)
+            {
+                let span = self.create_span(expr_left_span, self.previous().span);
+                expression = Some(Box::new(AstNode::new(expr, span, self.node_id())));
+                break;
             }
 
+            // a failed attempt can leave delimiters open, the declaration reopens them
             self.position = saved_pos;
-            match self.declaration() {
+            self.delimiter_stack.truncate(saved_delimiters);
+            match self.spanned_declaration() {
                 Ok(stmt) => statements.push(stmt),
                 Err(err) => {
                     self.report(err);
@@ -978,11 +1417,10 @@ impl<'a> Parser<'a> {
 
     /// start is `while`, end is next statement
     fn while_stmt(&mut self) -> ParseResult<Stmt> {
-        let while_span = self.current().span;
         self.advance_position();
 
         let condition_span = self.current().span;
-        let condition = AstNode::new(self.parse_condition()?, condition_span);
+        let condition = AstNode::new(self.parse_condition()?, condition_span, self.node_id());
 
         let block_left_span = self.current().span;
         let block = match self.block()? {
@@ -998,27 +1436,31 @@ impl<'a> Parser<'a> {
 
         let block_right_span = self.previous().span;
 
-        Ok(While(AstNode::new(
-            WhileStmt {
-                condition,
-                body: AstNode::new(block, self.create_span(block_left_span, block_right_span)),
-            },
-            self.create_span(while_span, self.previous().span),
-        )))
+        Ok(While(WhileStmt {
+            condition,
+            body: AstNode::new(block, self.create_span(block_left_span, block_right_span), self.node_id()),
+        }))
     }
 
     /// current is for, end is after block
     fn for_stmt(&mut self) -> ParseResult<Stmt> {
-        let left_for_span = self.current().span;
         self.advance_position();
 
+        let initializer_left_span = self.current().span;
         let initializer = if self.matches(&[TokenKind::Let]) {
-            Some(self.var_declaration()?)
+            Some(self.var_declaration(vec![])?)
         } else if !self.consume(&[TokenKind::Semicolon]) {
             Some(self.expression_stmt()?)
         } else {
             None
         };
+        let initializer = initializer.map(|stmt| {
+            Box::new(AstNode::new(
+                stmt,
+                self.create_span(initializer_left_span, self.previous().span),
+                self.node_id(),
+            ))
+        });
 
         let condition_span = self.current().span;
         let condition = if !self.matches(&[TokenKind::Semicolon]) {
@@ -1026,7 +1468,7 @@ impl<'a> Parser<'a> {
         } else {
             Literal(LiteralExpr::Bool(true))
         };
-        let condition = AstNode::new(condition, condition_span);
+        let condition = AstNode::new(condition, condition_span, self.node_id());
 
         if !self.consume(&[TokenKind::Semicolon]) {
             let error = MissingSemicolon {
@@ -1041,6 +1483,7 @@ impl<'a> Parser<'a> {
             Some(AstNode::new(
                 self.expression()?,
                 self.create_span(inc_left_span, self.previous().span),
+                self.node_id(),
             ))
         } else {
             None
@@ -1057,20 +1500,16 @@ impl<'a> Parser<'a> {
                 .into());
             }
         };
-        Ok(Stmt::For(AstNode::new(
-            ForStmt {
-                condition,
-                initializer,
-                increment,
-                body: AstNode::new(body, self.create_span(body_left_span, self.previous().span)),
-            },
-            self.create_span(left_for_span, self.previous().span),
-        )))
+        Ok(Stmt::For(ForStmt {
+            condition,
+            initializer,
+            increment,
+            body: AstNode::new(body, self.create_span(body_left_span, self.previous().span), self.node_id()),
+        }))
     }
 
     /// current is `return` end is next statement
     fn return_stmt(&mut self) -> ParseResult<Stmt> {
-        let left_return_span = self.current().span;
         self.advance_position();
 
         let value = if !self.matches(&[TokenKind::Semicolon]) {
@@ -1085,16 +1524,14 @@ impl<'a> Parser<'a> {
             Some(AstNode::new(
                 self.expression()?,
                 self.create_span(left_expr_span, self.previous().span),
+                self.node_id(),
             ))
         } else {
             None
         };
 
         self.expect_semicolon();
-        Ok(Return(AstNode::new(
-            ReturnStmt { expr: value },
-            self.create_span(left_return_span, self.previous().span),
-        )))
+        Ok(Return(ReturnStmt { expr: value }))
     }
 
     /// starts at first token, ends after the last token of the expression
@@ -1103,14 +1540,84 @@ impl<'a> Parser<'a> {
             return self.lambda_expr();
         } else if self.matches(&[TokenKind::If]) {
             return self.if_expr();
+        } else if self.at_record() {
+            return self.record_expr();
         } else if self.matches(&[TokenKind::LeftBrace]) {
             return self.block();
         }
         self.assignment()
     }
 
+    /// `{` followed by `name:` starts a record, no statement of a block can begin that way
+    fn at_record(&self) -> bool {
+        self.matches(&[TokenKind::LeftBrace])
+            && self.next_is(TokenKind::Ident(String::new()))
+            && self
+                .tokens
+                .get(self.position + 2)
+                .is_some_and(|token| token.token_kind == TokenKind::Colon)
+    }
+
+    /// current is `{`, ends after `}`
+    fn record_expr(&mut self) -> ParseResult<Expr> {
+        self.open_delimiter(TokenKind::LeftBrace)?;
+        let mut fields = vec![];
+
+        while !self.matches(&[TokenKind::RightBrace]) {
+            let field_name = match self.current().token_kind.clone() {
+                TokenKind::Ident(field_name) => {
+                    let span = self.current().span;
+                    self.advance_position();
+                    AstNode::new(field_name, span, self.node_id())
+                }
+                _ => {
+                    return Err(ExpectedIdentifier {
+                        src: self.source.to_string(),
+                        span: self.current().span,
+                        context: "record field name".to_string(),
+                    }
+                    .into());
+                }
+            };
+            if !self.consume(&[TokenKind::Colon]) {
+                return Err(UnexpectedToken {
+                    src: self.source.to_string(),
+                    span: self.current().span,
+                    found: self.current().token_kind.clone(),
+                    expected: "`:` after field name".to_string(),
+                }
+                .into());
+            }
+            let expr_left_span = self.current().span;
+            let value = self.expression()?;
+            fields.push((
+                field_name,
+                AstNode::new(value, self.create_span(expr_left_span, self.previous().span), self.node_id()),
+            ));
+
+            if self.consume(&[TokenKind::Comma]) {
+                if self.matches(&[TokenKind::RightBrace]) {
+                    self.trailing_comma();
+                }
+            } else if !self.matches(&[TokenKind::RightBrace]) {
+                return Err(UnexpectedToken {
+                    src: self.source.to_string(),
+                    span: self.current().span,
+                    found: self.current().token_kind.clone(),
+                    expected: "`,` or `}`".to_string(),
+                }
+                .into());
+            }
+        }
+
+        self.close_delimiter(TokenKind::RightBrace)?;
+        Ok(Expr::Record(RecordExpr { fields }))
+    }
+
     fn parse_binary_operand(&mut self, parse_fn: fn(&mut Self) -> ParseResult<Expr>) -> ParseResult<Expr> {
-        if self.matches(&[TokenKind::LeftBrace]) {
+        if self.at_record() {
+            self.record_expr()
+        } else if self.matches(&[TokenKind::LeftBrace]) {
             self.block()
         } else {
             parse_fn(self)
@@ -1139,7 +1646,11 @@ impl<'a> Parser<'a> {
 
         Ok(Lambda(LambdaExpr {
             parameters,
-            body: Box::new(AstNode::new(body, self.create_span(body_left_span, body_right_span))),
+            body: Box::new(AstNode::new(
+                body,
+                self.create_span(body_left_span, body_right_span),
+                self.node_id(),
+            )),
             return_type,
         }))
     }
@@ -1167,12 +1678,22 @@ impl<'a> Parser<'a> {
             return match expr {
                 Variable(name) => Ok(Expr::Assign(AssignExpr {
                     target: name,
-                    value: Box::new(AstNode::new(value, self.create_span(left_assignment_span, self.previous().span))),
+                    equal_span,
+                    value: Box::new(AstNode::new(
+                        value,
+                        self.create_span(left_assignment_span, self.previous().span),
+                        self.node_id(),
+                    )),
                 })),
                 Expr::FieldAccess(field_access) => Ok(Expr::FieldAssign(FieldAssignExpr {
                     receiver: field_access.receiver,
                     field: field_access.field,
-                    value: Box::new(AstNode::new(value, self.create_span(left_result_span, self.previous().span))),
+                    equal_span,
+                    value: Box::new(AstNode::new(
+                        value,
+                        self.create_span(left_result_span, self.previous().span),
+                        self.node_id(),
+                    )),
                 })),
                 _ => Err(ExpectedIdentifier {
                     src: self.source.to_string(),
@@ -1207,9 +1728,17 @@ impl<'a> Parser<'a> {
             let right = self.expect_expr(result, "right", operator_span)?;
 
             expr = Expr::Logical(LogicalExpr {
-                left: Box::new(AstNode::new(expr, self.create_span(expr_left_span, expr_right_span))),
-                op: AstNode::new(op, operator_span),
-                right: Box::new(AstNode::new(right, self.create_span(right_left_span, right_right_span))),
+                left: Box::new(AstNode::new(
+                    expr,
+                    self.create_span(expr_left_span, expr_right_span),
+                    self.node_id(),
+                )),
+                op: AstNode::new(op, operator_span, self.node_id()),
+                right: Box::new(AstNode::new(
+                    right,
+                    self.create_span(right_left_span, right_right_span),
+                    self.node_id(),
+                )),
             })
         }
         Ok(expr)
@@ -1237,9 +1766,17 @@ impl<'a> Parser<'a> {
             let right = self.expect_expr(result, "right", operator_span)?;
 
             expr = Expr::Logical(LogicalExpr {
-                left: Box::new(AstNode::new(expr, self.create_span(expr_left_span, expr_right_span))),
-                op: AstNode::new(op, operator_span),
-                right: Box::new(AstNode::new(right, self.create_span(right_left_span, right_right_span))),
+                left: Box::new(AstNode::new(
+                    expr,
+                    self.create_span(expr_left_span, expr_right_span),
+                    self.node_id(),
+                )),
+                op: AstNode::new(op, operator_span, self.node_id()),
+                right: Box::new(AstNode::new(
+                    right,
+                    self.create_span(right_left_span, right_right_span),
+                    self.node_id(),
+                )),
             })
         }
         Ok(expr)
@@ -1267,9 +1804,17 @@ impl<'a> Parser<'a> {
             let right = self.expect_expr(result, "right", operator_span)?;
 
             expr = Expr::Binary(BinaryExpr {
-                left: Box::new(AstNode::new(expr, self.create_span(expr_left_span, expr_right_span))),
-                op: AstNode::new(op, operator_span),
-                right: Box::new(AstNode::new(right, self.create_span(right_left_span, right_right_span))),
+                left: Box::new(AstNode::new(
+                    expr,
+                    self.create_span(expr_left_span, expr_right_span),
+                    self.node_id(),
+                )),
+                op: AstNode::new(op, operator_span, self.node_id()),
+                right: Box::new(AstNode::new(
+                    right,
+                    self.create_span(right_left_span, right_right_span),
+                    self.node_id(),
+                )),
             })
         }
         Ok(expr)
@@ -1300,9 +1845,17 @@ impl<'a> Parser<'a> {
             let right = self.expect_expr(result, "right", operator_span)?;
 
             expr = Expr::Binary(BinaryExpr {
-                left: Box::new(AstNode::new(expr, self.create_span(expr_left_span, expr_right_span))),
-                op: AstNode::new(op, operator_span),
-                right: Box::new(AstNode::new(right, self.create_span(right_left_span, right_right_span))),
+                left: Box::new(AstNode::new(
+                    expr,
+                    self.create_span(expr_left_span, expr_right_span),
+                    self.node_id(),
+                )),
+                op: AstNode::new(op, operator_span, self.node_id()),
+                right: Box::new(AstNode::new(
+                    right,
+                    self.create_span(right_left_span, right_right_span),
+                    self.node_id(),
+                )),
             })
         }
         Ok(expr)
@@ -1330,9 +1883,17 @@ impl<'a> Parser<'a> {
             let right = self.expect_expr(result, "right", operator_span)?;
 
             expr = Expr::Binary(BinaryExpr {
-                left: Box::new(AstNode::new(expr, self.create_span(expr_left_span, expr_right_span))),
-                op: AstNode::new(op, operator_span),
-                right: Box::new(AstNode::new(right, self.create_span(right_left_span, right_right_span))),
+                left: Box::new(AstNode::new(
+                    expr,
+                    self.create_span(expr_left_span, expr_right_span),
+                    self.node_id(),
+                )),
+                op: AstNode::new(op, operator_span, self.node_id()),
+                right: Box::new(AstNode::new(
+                    right,
+                    self.create_span(right_left_span, right_right_span),
+                    self.node_id(),
+                )),
             })
         }
         Ok(expr)
@@ -1340,7 +1901,7 @@ impl<'a> Parser<'a> {
 
     fn factor(&mut self) -> ParseResult<Expr> {
         let expr_left_span = self.current().span;
-        let mut expr = self.parse_binary_operand(Self::unary)?;
+        let mut expr = self.parse_binary_operand(Self::cast)?;
         let expr_right_span = self.previous().span;
 
         while self.consume(&[TokenKind::Slash, TokenKind::Star]) {
@@ -1355,20 +1916,57 @@ impl<'a> Parser<'a> {
             let operator_span = operator.span;
 
             let right_left_span = self.current().span;
-            let result = self.parse_binary_operand(Self::unary);
+            let result = self.parse_binary_operand(Self::cast);
             let right_right_span = self.previous().span;
 
             let right = self.expect_expr(result, "right", operator_span)?;
 
             expr = Expr::Binary(BinaryExpr {
-                left: Box::new(AstNode::new(expr, self.create_span(expr_left_span, expr_right_span))),
-                op: AstNode::new(op, operator_span),
-                right: Box::new(AstNode::new(right, self.create_span(right_left_span, right_right_span))),
+                left: Box::new(AstNode::new(
+                    expr,
+                    self.create_span(expr_left_span, expr_right_span),
+                    self.node_id(),
+                )),
+                op: AstNode::new(op, operator_span, self.node_id()),
+                right: Box::new(AstNode::new(
+                    right,
+                    self.create_span(right_left_span, right_right_span),
+                    self.node_id(),
+                )),
             })
         }
         Ok(expr)
     }
 
+    /// `expr as Type`, binds tighter than the arithmetic operators
+    fn cast(&mut self) -> ParseResult<Expr> {
+        let expr_left_span = self.current().span;
+        let mut expr = self.unary()?;
+        let mut expr_right_span = self.previous().span;
+
+        while self.consume(&[TokenKind::As]) {
+            let type_left_span = self.current().span;
+            let ty = self.parse_type()?;
+            let type_right_span = self.previous().span;
+
+            expr = Expr::Cast(CastExpr {
+                expr: Box::new(AstNode::new(
+                    expr,
+                    self.create_span(expr_left_span, expr_right_span),
+                    self.node_id(),
+                )),
+                ty: AstNode::new(
+                    UnresolvedType::from(&ty),
+                    self.create_span(type_left_span, type_right_span),
+                    self.node_id(),
+                ),
+                target: ty,
+            });
+            expr_right_span = type_right_span;
+        }
+        Ok(expr)
+    }
+
     fn unary(&mut self) -> ParseResult<Expr> {
         if self.consume(&[TokenKind::Minus, TokenKind::Bang]) {
             let operator = self.previous();
@@ -1388,8 +1986,12 @@ impl<'a> Parser<'a> {
             let expr = self.expect_expr(result, "right", operator_span)?;
 
             Ok(Unary(UnaryExpr {
-                op: AstNode::new(op, operator_span),
-                expr: Box::new(AstNode::new(expr, self.create_span(expr_left_span, expr_right_span))),
+                op: AstNode::new(op, operator_span, self.node_id()),
+                expr: Box::new(AstNode::new(
+                    expr,
+                    self.create_span(expr_left_span, expr_right_span),
+                    self.node_id(),
+                )),
             }))
         } else {
             self.call()
@@ -1425,29 +2027,101 @@ impl<'a> Parser<'a> {
             .into());
         }
 
-        let mut arguments = vec![];
+        let arguments = self.arguments()?;
+
+        self.close_delimiter(self.current().token_kind.clone())?;
+
+        Ok(Call(CallExpr {
+            callee: Box::new(AstNode::new(callee, left_paren_span, self.node_id())),
+            arguments,
+        }))
+    }
 
-        if !self.matches(&[TokenKind::RightParen]) {
+    /// current is after '(', ends before ')'
+    fn arguments(&mut self) -> ParseResult<Vec<AstNode<Expr>>> {
+        let mut arguments = vec![];
+        if self.matches(&[TokenKind::RightParen]) {
+            return Ok(arguments);
+        }
+        loop {
             let expr_left_span = self.current().span;
-            arguments.push(AstNode::new(
+            let argument = AstNode::new(
                 self.expression()?,
                 self.create_span(expr_left_span, self.previous().span),
-            ));
-            while self.consume(&[TokenKind::Comma]) {
-                let expr_left_span = self.current().span;
-                arguments.push(AstNode::new(
-                    self.expression()?,
-                    self.create_span(expr_left_span, self.previous().span),
-                ));
+                self.node_id(),
+            );
+            if arguments.len() == self.max_arguments {
+                self.too_many_arguments(argument.span, "arguments");
+            }
+            arguments.push(argument);
+            if !self.consume(&[TokenKind::Comma]) && !self.missing_comma("arguments") {
+                return Ok(arguments);
+            }
+            if self.matches(&[TokenKind::RightParen]) {
+                self.trailing_comma();
+                return Ok(arguments);
             }
         }
+    }
 
-        self.close_delimiter(self.current().token_kind.clone())?;
+    /// reported once per list, at the first element over the limit
+    fn too_many_arguments(&mut self, span: SourceSpan, context: &str) {
+        self.report(
+            TooManyArguments {
+                src: self.source.to_string(),
+                span,
+                limit: self.max_arguments,
+                context: context.to_string(),
+            }
+            .into(),
+        );
+    }
 
-        Ok(Call(CallExpr {
-            callee: Box::new(AstNode::new(callee, left_paren_span)),
-            arguments,
-        }))
+    /// previous is a comma right before the closing delimiter
+    fn trailing_comma(&mut self) {
+        if self.trailing_comma_lint {
+            let span = self.previous().span;
+            self.report(
+                TrailingComma {
+                    src: self.source.to_string(),
+                    span,
+                }
+                .into(),
+            );
+        }
+    }
+
+    /// reports a missing comma if `current` starts another element of the list, which then continues
+    fn missing_comma(&mut self, context: &str) -> bool {
+        let starts_element = match self.current().token_kind {
+            TokenKind::Ident(_) => true,
+            TokenKind::String(_)
+            | TokenKind::Int(_)
+            | TokenKind::Float(_)
+            | TokenKind::True
+            | TokenKind::False
+            | TokenKind::Nil
+            | TokenKind::Bang
+            | TokenKind::Fn
+            | TokenKind::LeftBracket => context == "arguments",
+            _ => false,
+        };
+        if starts_element {
+            let span = self.next_span(self.previous().span);
+            self.report(
+                MissingComma {
+                    src: self.source.to_string(),
+                    span,
+                    context: context.to_string(),
+                    suggestion: Suggestion {
+                        span,
+                        replacement: ",".to_string(),
+                    },
+                }
+                .into(),
+            );
+        }
+        starts_element
     }
 
     fn finish_method_call(&mut self, receiver: Expr) -> ParseResult<Expr> {
@@ -1457,7 +2131,7 @@ impl<'a> Parser<'a> {
             TokenKind::Ident(name) => {
                 let span = self.current().span;
                 self.advance_position();
-                AstNode::new(name, span)
+                AstNode::new(name, span, self.node_id())
             }
             _ => {
                 return Err(ExpectedIdentifier {
@@ -1469,34 +2143,19 @@ impl<'a> Parser<'a> {
             }
         };
         if self.matches(&[TokenKind::LeftParen]) {
-            let mut arguments = vec![];
             self.open_delimiter(TokenKind::LeftParen)?;
-
-            if !self.matches(&[TokenKind::RightParen]) {
-                let expr_left_span = self.current().span;
-                arguments.push(AstNode::new(
-                    self.expression()?,
-                    self.create_span(expr_left_span, self.previous().span),
-                ));
-                while self.consume(&[TokenKind::Comma]) {
-                    let expr_left_span = self.current().span;
-                    arguments.push(AstNode::new(
-                        self.expression()?,
-                        self.create_span(expr_left_span, self.previous().span),
-                    ));
-                }
-            }
+            let arguments = self.arguments()?;
 
             self.close_delimiter(TokenKind::RightParen)?;
             Ok(Expr::MethodCall(MethodCallExpr {
-                receiver: Box::new(AstNode::new(receiver, self.previous().span)),
+                receiver: Box::new(AstNode::new(receiver, self.previous().span, self.node_id())),
                 method: field,
                 arguments,
             }))
         } else {
             // It's a field access
             Ok(Expr::FieldAccess(FieldAccessExpr {
-                receiver: Box::new(AstNode::new(receiver, self.previous().span)),
+                receiver: Box::new(AstNode::new(receiver, self.previous().span, self.node_id())),
                 field,
             }))
         }
@@ -1525,20 +2184,19 @@ impl<'a> Parser<'a> {
                     elements.push(AstNode::new(
                         self.expression()?,
                         self.create_span(expr_left_span, self.previous().span),
+                        self.node_id(),
                     ));
 
                     while self.consume(&[TokenKind::Comma]) {
                         if self.matches(&[TokenKind::RightBracket]) {
-                            return Err(ExpectedExpression {
-                                src: self.source.to_string(),
-                                span: self.current().span,
-                            }
-                            .into());
+                            self.trailing_comma();
+                            break;
                         }
                         let expr_left_span = self.current().span;
                         elements.push(AstNode::new(
                             self.expression()?,
                             self.create_span(expr_left_span, self.previous().span),
+                            self.node_id(),
                         ));
                     }
                 }
@@ -1557,14 +2215,19 @@ impl<'a> Parser<'a> {
                 self.advance_position();
                 Ok(Literal(LiteralExpr::Nil))
             }
+            // the lexer reported it already, it stands in for a value so the rest of the expression is still checked
+            TokenKind::Error => {
+                self.advance_position();
+                Ok(Literal(LiteralExpr::Nil))
+            }
             TokenKind::LeftParen => {
                 let opening_paren_span = self.current().span;
                 self.open_delimiter(self.current().token_kind.clone())?;
 
-                let expr = if self.next_is(TokenKind::RightParen) {
+                let expr = if self.current_is(TokenKind::RightParen) {
                     Err(ExpectedExpression {
                         src: self.source.to_string(),
-                        span: self.create_span(opening_paren_span, self.peek().span),
+                        span: self.create_span(opening_paren_span, self.current().span),
                     }
                     .into())
                 } else {
@@ -1575,11 +2238,13 @@ impl<'a> Parser<'a> {
 
                 Ok(Grouping(Box::new(AstNode::new(
                     expr,
-                    self.create_span(opening_paren_span, self.current().span),
+                    self.create_span(opening_paren_span, self.previous().span),
+                    self.node_id(),
                 ))))
             }
             TokenKind::Int(value) => {
                 let span = self.current().span;
+                let text = self.current().literal.to_string();
                 self.advance_position();
 
                 if self.current_is(TokenKind::Ident(String::new())) {
@@ -1590,10 +2255,11 @@ impl<'a> Parser<'a> {
                     }
                     .into());
                 }
-                Ok(Literal(LiteralExpr::Int(value)))
+                Ok(Literal(LiteralExpr::Int(Number { value, text: Some(text) })))
             }
             TokenKind::Float(value) => {
                 let span = self.current().span;
+                let text = self.current().literal.to_string();
                 self.advance_position();
 
                 if self.current_is(TokenKind::Ident(String::new())) {
@@ -1604,7 +2270,7 @@ impl<'a> Parser<'a> {
                     }
                     .into());
                 }
-                Ok(Literal(LiteralExpr::Float(value)))
+                Ok(Literal(LiteralExpr::Float(Number { value, text: Some(text) })))
             }
             TokenKind::String(ref value) => {
                 let string = value.clone();
@@ -1624,7 +2290,7 @@ impl<'a> Parser<'a> {
                             TokenKind::Ident(field_name) => {
                                 let span = self.current().span;
                                 self.advance_position();
-                                AstNode::new(field_name, span)
+                                AstNode::new(field_name, span, self.node_id())
                             }
                             _ => {
                                 return Err(ExpectedIdentifier {
@@ -1640,7 +2306,7 @@ impl<'a> Parser<'a> {
                                 src: self.source.to_string(),
                                 span: self.current().span,
                                 found: self.current().token_kind.clone(),
-                                expected: "':' after field name".to_string(),
+                                expected: "`:` after field name".to_string(),
                             }
                             .into());
                         }
@@ -1650,29 +2316,31 @@ impl<'a> Parser<'a> {
 
                         fields.push((
                             field_name.clone(),
-                            Box::new(AstNode::new(value, self.create_span(expr_left_span, expr_right_span))),
+                            AstNode::new(value, self.create_span(expr_left_span, expr_right_span), self.node_id()),
                         ));
-                        if !self.matches(&[TokenKind::RightBrace]) {
-                            if !self.consume(&[TokenKind::Comma]) {
-                                return Err(UnexpectedToken {
-                                    src: self.source.to_string(),
-                                    span: self.current().span,
-                                    found: self.current().token_kind.clone(),
-                                    expected: "',' or '}'".to_string(),
-                                }
-                                .into());
+                        if self.consume(&[TokenKind::Comma]) {
+                            if self.matches(&[TokenKind::RightBrace]) {
+                                self.trailing_comma();
                             }
+                        } else if !self.matches(&[TokenKind::RightBrace]) {
+                            return Err(UnexpectedToken {
+                                src: self.source.to_string(),
+                                span: self.current().span,
+                                found: self.current().token_kind.clone(),
+                                expected: "`,` or `}`".to_string(),
+                            }
+                            .into());
                         }
                     }
 
                     self.consume(&[TokenKind::RightBrace]);
 
                     Ok(Expr::StructInit(StructInitExpr {
-                        name: AstNode::new(string, name_span),
+                        name: AstNode::new(string, name_span, self.node_id()),
                         fields,
                     }))
                 } else {
-                    Ok(Variable(AstNode::new(string, name_span)))
+                    Ok(Variable(AstNode::new(string, name_span, self.node_id())))
                 }
             }
             TokenKind::EOF => Err(UnexpectedEOF {
@@ -1695,10 +2363,61 @@ impl<'a> Parser<'a> {
                     src: self.source.to_string(),
                     span: token.span,
                     found: token.token_kind,
-                    expected: "literal or '('".to_string(),
+                    expected: "literal or `(`".to_string(),
                 }
                 .into())
             }
         }
     }
 }
+
+/// statements and expressions, the nodes the parse limit counts
+struct NodeCounter(usize);
+
+impl Visitor for NodeCounter {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        self.0 += 1;
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        self.0 += 1;
+        walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lexer;
+    use crate::error::ParseError;
+
+    /// `source` with the suggestion of each parse error that has one applied on its own
+    fn fixes(source: &str) -> Vec<String> {
+        let mut lexer = Lexer::new(source);
+        let mut parser = Parser::new(lexer.lex().tokens, source.to_string());
+        parser.parse();
+        parser
+            .into_errors()
+            .iter()
+            .filter_map(|error| error.downcast_ref::<ParseError>()?.suggestion().cloned())
+            .map(|suggestion| {
+                let start = suggestion.span.offset();
+                let end = start + suggestion.span.len();
+                format!("{}{}{}", &source[..start], suggestion.replacement, &source[end..])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn missing_commas_are_inserted() {
+        assert_eq!(fixes("foo(1 2);"), vec!["foo(1, 2);"]);
+        assert_eq!(
+            fixes("fn f(a: Int b: Int) -> Int { a } f(1 2);"),
+            vec![
+                "fn f(a: Int, b: Int) -> Int { a } f(1 2);",
+                "fn f(a: Int b: Int) -> Int { a } f(1, 2);"
+            ]
+        );
+    }
+}
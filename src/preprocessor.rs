@@ -0,0 +1,340 @@
+use crate::error::IncludeStep;
+use crate::error::PreprocessorError::{
+    IncludeNotFound, MalformedCondition, MalformedInclude, RecursiveInclude, UnmatchedDirective, UnterminatedCondition,
+};
+use crate::source::{column, decode};
+use miette::{Report, SourceSpan};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// a range of the expanded source and where it was written
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    start: usize,
+    end: usize,
+    file: usize,
+    file_start: usize,
+}
+
+/// maps offsets in the expanded source back to the file they came from
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SourceMap {
+    /// name and text of every file that contributed to the source
+    files: Vec<(String, String)>,
+    segments: Vec<Segment>,
+    /// columns a tab advances to the next multiple of, `None` counts tabs as one column
+    tab_width: Option<usize>,
+}
+
+impl SourceMap {
+    /// columns are counted in characters, with this tabs advance to the next multiple of `width` like in an editor
+    pub fn with_tab_width(mut self, width: usize) -> Self {
+        self.tab_width = Some(width);
+        self
+    }
+
+    /// `file:line:column` of an offset in the expanded source, lines and columns start at 1
+    pub fn locate(&self, offset: usize) -> Option<String> {
+        let (file, line, column) = self.position(offset)?;
+        Some(format!("{file}:{line}:{column}"))
+    }
+
+    /// file, line and column of an offset in the expanded source, `\r\n` ends a line like `\n`
+    pub fn position(&self, offset: usize) -> Option<(&str, usize, usize)> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| segment.start <= offset && offset < segment.end)?;
+        let (name, text) = &self.files[segment.file];
+        let before = &text[..segment.file_start + offset - segment.start];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Some((name, line, column(&before[line_start..], self.tab_width)))
+    }
+
+    /// paths of the main file and every file it included
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// the ranges of the expanded source that came from `file`, named like in `files`
+    pub fn ranges(&self, file: &str) -> Vec<Range<usize>> {
+        self.segments
+            .iter()
+            .filter(|segment| self.files[segment.file].0 == file)
+            .map(|segment| segment.start..segment.end)
+            .collect()
+    }
+
+    /// whether offsets differ from the ones in the main file
+    pub fn has_includes(&self) -> bool {
+        self.files.len() > 1
+    }
+
+    /// shifts every offset by `len`, for text put in front of the expanded source
+    pub fn offset_by(&mut self, len: usize) {
+        for segment in &mut self.segments {
+            segment.start += len;
+            segment.end += len;
+        }
+    }
+}
+
+pub struct PreprocessorResult<'a> {
+    pub errors: &'a Vec<Report>,
+    pub source: &'a str,
+    pub source_map: &'a SourceMap,
+}
+
+/// an `#if` whose `#end` wasn't reached yet
+struct Condition {
+    span: SourceSpan,
+    active: bool,
+}
+
+/// replaces `#include "file.lox"` lines with the file's contents, paths are relative to the including file
+///
+/// a path not found there is looked up in the directories added with `with_include_path`, then in the
+/// `lox_modules` directory next to the main file, where shared libraries can be vendored
+///
+/// `#if NAME`, `#if !NAME`, `#else` and `#end` keep or drop lines depending on the names defined with `with_define`
+pub struct Preprocessor {
+    path: PathBuf,
+    source: String,
+    defines: HashMap<String, String>,
+    expanded: String,
+    source_map: SourceMap,
+    errors: Vec<Report>,
+    /// canonical paths and source map indices of the files currently being expanded, to reject includes of themselves
+    including: Vec<(PathBuf, usize)>,
+    /// the `#include` in each file being expanded that leads to the next one
+    include_spans: Vec<SourceSpan>,
+    /// read included files that aren't UTF-8 as Latin-1
+    latin1: bool,
+    include_paths: Vec<PathBuf>,
+}
+
+/// directory next to the main file searched for includes after the include paths
+pub const MODULES_DIR: &str = "lox_modules";
+
+impl Preprocessor {
+    pub fn new(path: &str, source: String) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            source,
+            defines: HashMap::new(),
+            expanded: String::new(),
+            source_map: SourceMap::default(),
+            errors: vec![],
+            including: vec![],
+            include_spans: vec![],
+            latin1: false,
+            include_paths: vec![],
+        }
+    }
+
+    /// a name is set for `#if` unless its value is `false` or `0`
+    pub fn with_define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// included files that aren't valid UTF-8 are read as Latin-1 instead of being an error
+    pub fn with_latin1(mut self, enabled: bool) -> Self {
+        self.latin1 = enabled;
+        self
+    }
+
+    /// searched in the order they were added for includes not found next to the including file
+    pub fn with_include_path(mut self, dir: &str) -> Self {
+        self.include_paths.push(PathBuf::from(dir));
+        self
+    }
+
+    pub fn preprocess(&mut self) -> PreprocessorResult<'_> {
+        let source = std::mem::take(&mut self.source);
+        self.expand(self.path.clone(), source);
+
+        PreprocessorResult {
+            errors: &self.errors,
+            source: &self.expanded,
+            source_map: &self.source_map,
+        }
+    }
+
+    fn expand(&mut self, path: PathBuf, text: String) {
+        let file = self.source_map.files.len();
+        self.source_map.files.push((path.display().to_string(), text.clone()));
+        self.including.push((canonical(&path), file));
+
+        let mut conditions: Vec<Condition> = vec![];
+        let mut copied_until = 0;
+        let mut line_start = 0;
+        for line in text.split_inclusive('\n') {
+            let directive = line.trim();
+            let (keyword, argument) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+            if matches!(keyword, "#include" | "#if" | "#else" | "#end") {
+                let span = SourceSpan::new((line_start + line.len() - line.trim_start().len()).into(), directive.len());
+                let active = conditions.iter().all(|condition| condition.active);
+                if active {
+                    self.copy(file, &text, copied_until, line_start);
+                }
+                // the directive is dropped, its line break is kept
+                copied_until = line_start + line.trim_end_matches('\n').len();
+
+                match keyword {
+                    "#include" if active => match argument.trim().strip_prefix('"').and_then(|argument| argument.strip_suffix('"')) {
+                        Some(included) => self.include(&path, included, &text, span),
+                        None => self.errors.push(MalformedInclude { src: text.clone(), span }.into()),
+                    },
+                    "#if" => {
+                        let condition = self.condition(argument.trim(), &text, span);
+                        conditions.push(Condition { span, active: condition });
+                    }
+                    "#else" | "#end" if conditions.is_empty() => self.errors.push(
+                        UnmatchedDirective {
+                            src: text.clone(),
+                            span,
+                            directive: keyword.to_string(),
+                        }
+                        .into(),
+                    ),
+                    "#else" => {
+                        let condition = conditions.last_mut().unwrap();
+                        condition.active = !condition.active;
+                    }
+                    "#end" => {
+                        conditions.pop();
+                    }
+                    _ => {}
+                }
+            } else if !conditions.iter().all(|condition| condition.active) {
+                copied_until = line_start + line.len();
+            }
+            line_start += line.len();
+        }
+        self.copy(file, &text, copied_until, text.len());
+
+        for condition in conditions {
+            self.errors.push(
+                UnterminatedCondition {
+                    src: text.clone(),
+                    span: condition.span,
+                }
+                .into(),
+            );
+        }
+        self.including.pop();
+    }
+
+    /// `NAME` or `!NAME`
+    fn condition(&mut self, argument: &str, text: &str, span: SourceSpan) -> bool {
+        let (negated, name) = match argument.strip_prefix('!') {
+            Some(name) => (true, name.trim()),
+            None => (false, argument),
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            self.errors.push(
+                MalformedCondition {
+                    src: text.to_string(),
+                    span,
+                }
+                .into(),
+            );
+            return false;
+        }
+        let defined = self.defines.get(name).is_some_and(|value| value != "false" && value != "0");
+        defined != negated
+    }
+
+    fn include(&mut self, from: &Path, included: &str, text: &str, span: SourceSpan) {
+        let path = self.locate(from, included);
+        let canonical_path = canonical(&path);
+        if let Some(start) = self.including.iter().position(|(including, _)| *including == canonical_path) {
+            self.report_cycle(start, included, text, span);
+            return;
+        }
+        match fs::read(&path) {
+            Ok(bytes) => match decode(&path.display().to_string(), bytes, self.latin1) {
+                Ok(included_text) => {
+                    self.include_spans.push(span);
+                    self.expand(path, included_text);
+                    self.include_spans.pop();
+                }
+                Err(err) => self.errors.push(err.into()),
+            },
+            Err(err) => self.errors.push(
+                IncludeNotFound {
+                    src: text.to_string(),
+                    span,
+                    path: included.to_string(),
+                    reason: err.to_string(),
+                }
+                .into(),
+            ),
+        }
+    }
+
+    /// `including[start..]` include each other in a cycle closed by the `#include` at `span` in the current file
+    fn report_cycle(&mut self, start: usize, included: &str, text: &str, span: SourceSpan) {
+        let name = |file: usize| self.source_map.files[file].0.clone();
+        let files: Vec<usize> = self.including[start..].iter().map(|(_, file)| *file).collect();
+        let steps = files
+            .windows(2)
+            .zip(&self.include_spans[start..])
+            .map(|(pair, span)| IncludeStep {
+                src: self.source_map.files[pair[0]].1.clone(),
+                span: *span,
+                file: name(pair[0]),
+                included: name(pair[1]),
+            })
+            .collect();
+        let cycle: Vec<String> = files.iter().chain(&files[..1]).map(|file| name(*file)).collect();
+
+        self.errors.push(
+            RecursiveInclude {
+                src: text.to_string(),
+                span,
+                path: included.to_string(),
+                cycle: cycle.join(" → "),
+                steps,
+            }
+            .into(),
+        );
+    }
+
+    /// the first existing file of the search order, or the path relative to `from` to report as missing
+    fn locate(&self, from: &Path, included: &str) -> PathBuf {
+        let relative = from.parent().unwrap_or(Path::new("")).join(included);
+        if relative.is_file() {
+            return relative;
+        }
+        let modules = self.path.parent().unwrap_or(Path::new("")).join(MODULES_DIR);
+        self.include_paths
+            .iter()
+            .chain(std::iter::once(&modules))
+            .map(|dir| dir.join(included))
+            .find(|path| path.is_file())
+            .unwrap_or(relative)
+    }
+
+    /// appends `text[start..end]` of `file` to the expanded source
+    fn copy(&mut self, file: usize, text: &str, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+        self.source_map.segments.push(Segment {
+            start: self.expanded.len(),
+            end: self.expanded.len() + end - start,
+            file,
+            file_start: start,
+        });
+        self.expanded.push_str(&text[start..end]);
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,424 @@
+use crate::ast::{
+    AstNode, Attribute, BinaryOp, BlockExpr, Expr, Ident, LiteralExpr, LogicalOp, Number, PrimitiveType, Program, Stmt, TypedIdent,
+    UnaryOp, UnresolvedType,
+};
+
+const INDENT: &str = "    ";
+
+/// prints a program as source the parser reads back into the same tree, up to spans and node ids
+pub fn print_program(program: &Program) -> String {
+    let mut printer = Printer::default();
+    for stmt in &program.statements {
+        printer.stmt(stmt);
+    }
+    printer.out
+}
+
+pub fn print_expr(expr: &Expr) -> String {
+    let mut printer = Printer::default();
+    printer.expr(expr);
+    printer.out
+}
+
+pub fn print_type(ty: &UnresolvedType) -> String {
+    match ty {
+        UnresolvedType::Primitive(primitive) => match primitive {
+            PrimitiveType::Nil => "Nil",
+            PrimitiveType::Int => "Int",
+            PrimitiveType::Float => "Float",
+            PrimitiveType::Bool => "Bool",
+            PrimitiveType::String => "String",
+        }
+        .to_string(),
+        UnresolvedType::Named(name) => name.clone(),
+        UnresolvedType::Function { params, return_type } => {
+            let params: Vec<String> = params.iter().map(print_type).collect();
+            format!("({}) -> {}", params.join(", "), print_type(return_type))
+        }
+        UnresolvedType::GenericApplication { base, args } => {
+            let args: Vec<String> = args.iter().map(print_type).collect();
+            format!("{}<{}>", print_type(base), args.join(", "))
+        }
+        UnresolvedType::Record(fields) => {
+            let fields: Vec<String> = fields.iter().map(|(name, ty)| format!("{name}: {}", print_type(ty))).collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn line_start(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &AstNode<Stmt>) {
+        for attribute in stmt.node.attributes() {
+            self.line_start();
+            self.attribute(attribute);
+            self.out.push('\n');
+        }
+        self.line_start();
+        self.stmt_inline(&stmt.node);
+        self.out.push('\n');
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) {
+        self.out.push_str(&format!("@{}", attribute.name.node));
+        if attribute.args.is_empty() {
+            return;
+        }
+        self.out.push('(');
+        for (i, arg) in attribute.args.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            if let Some(name) = &arg.name {
+                self.out.push_str(&format!("{} = ", name.node));
+            }
+            self.literal(&arg.value.node);
+        }
+        self.out.push(')');
+    }
+
+    /// a statement without indentation or line break, `for` initializers are printed this way
+    fn stmt_inline(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::ExprStmtNode(expr_stmt) => match &expr_stmt.expr.node {
+                // these end in a block and take no semicolon
+                Expr::If(_) | Expr::Block(_) => self.expr(&expr_stmt.expr.node),
+                // `fn` at the start of a statement begins a function declaration
+                Expr::Lambda(_) => {
+                    self.grouped(&expr_stmt.expr.node);
+                    self.out.push(';');
+                }
+                expr => {
+                    self.expr(expr);
+                    self.out.push(';');
+                }
+            },
+            Stmt::VarDecl(var_decl) => {
+                self.out.push_str(&format!("let {}", var_decl.ident.node));
+                if let Some(annotation) = &var_decl.type_annotation {
+                    self.out.push_str(&format!(": {}", print_type(&annotation.node)));
+                }
+                if let Some(initializer) = &var_decl.initializer {
+                    self.out.push_str(" = ");
+                    self.expr(&initializer.node);
+                }
+                self.out.push(';');
+            }
+            Stmt::FunDecl(fun_decl) => {
+                self.out.push_str(&format!("fn {}", fun_decl.name.node));
+                if !fun_decl.generics.is_empty() {
+                    let generics: Vec<&str> = fun_decl.generics.iter().map(|generic| generic.node.as_str()).collect();
+                    self.out.push_str(&format!("<{}>", generics.join(", ")));
+                }
+                self.signature(&fun_decl.params, &fun_decl.return_type.node);
+                self.block(&fun_decl.body.node);
+            }
+            Stmt::StructDecl(struct_decl) => {
+                self.out.push_str(&format!(
+                    "struct {} {{ {} }}",
+                    struct_decl.ident.node,
+                    typed_idents(&struct_decl.fields)
+                ));
+            }
+            Stmt::TypeAlias(type_alias) => {
+                self.out
+                    .push_str(&format!("type {} = {};", type_alias.ident.node, print_type(&type_alias.ty.node)));
+            }
+            Stmt::While(while_stmt) => {
+                self.out.push_str("while ");
+                self.condition(&while_stmt.condition.node);
+                self.out.push(' ');
+                self.block(&while_stmt.body.node);
+            }
+            Stmt::For(for_stmt) => {
+                self.out.push_str("for ");
+                match &for_stmt.initializer {
+                    Some(initializer) => self.stmt_inline(&initializer.node),
+                    None => self.out.push(';'),
+                }
+                self.out.push(' ');
+                self.expr(&for_stmt.condition.node);
+                self.out.push_str("; ");
+                if let Some(increment) = &for_stmt.increment {
+                    self.condition(&increment.node);
+                    self.out.push(' ');
+                }
+                self.block(&for_stmt.body.node);
+            }
+            Stmt::Return(return_stmt) => match &return_stmt.expr {
+                Some(expr) => {
+                    self.out.push_str("return ");
+                    self.expr(&expr.node);
+                    self.out.push(';');
+                }
+                None => self.out.push_str("return;"),
+            },
+        }
+    }
+
+    /// `(a: Int, b: Float) -> Bool `, the return type is left out when it is `Nil`
+    fn signature(&mut self, params: &[TypedIdent], return_type: &UnresolvedType) {
+        self.out.push_str(&format!("({})", typed_idents(params)));
+        if *return_type != UnresolvedType::Primitive(PrimitiveType::Nil) {
+            self.out.push_str(&format!(" -> {}", print_type(return_type)));
+        }
+        self.out.push(' ');
+    }
+
+    fn block(&mut self, block: &BlockExpr) {
+        if block.statements.is_empty() && block.expr.is_none() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{\n");
+        self.depth += 1;
+        for stmt in &block.statements {
+            self.stmt(stmt);
+        }
+        // a final `if` or block without a semicolon would be parsed as the value of the block
+        if block.expr.is_none()
+            && let Some(Stmt::ExprStmtNode(expr_stmt)) = block.statements.last().map(|stmt| &stmt.node)
+            && matches!(expr_stmt.expr.node, Expr::If(_) | Expr::Block(_))
+        {
+            self.out.insert(self.out.len() - 1, ';');
+        }
+        if let Some(expr) = &block.expr {
+            self.line_start();
+            match &expr.node {
+                Expr::Lambda(_) => self.grouped(&expr.node),
+                expr => self.expr(expr),
+            }
+            self.out.push('\n');
+        }
+        self.depth -= 1;
+        self.line_start();
+        self.out.push('}');
+    }
+
+    /// an expression followed by a block, a trailing variable would read as a struct initialization
+    fn condition(&mut self, expr: &Expr) {
+        if ends_with_variable(expr) {
+            self.grouped(expr);
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(literal) => self.literal(literal),
+            Expr::Unary(unary) => {
+                self.out.push_str(match unary.op.node {
+                    UnaryOp::Bang => "!",
+                    UnaryOp::Minus => "-",
+                });
+                self.operand(&unary.expr.node);
+            }
+            Expr::Binary(binary) => {
+                self.operand(&binary.left.node);
+                self.out.push_str(&format!(" {} ", binary_operator(&binary.op.node)));
+                self.operand(&binary.right.node);
+            }
+            Expr::Logical(logical) => {
+                self.operand(&logical.left.node);
+                self.out.push_str(match logical.op.node {
+                    LogicalOp::And => " and ",
+                    LogicalOp::Or => " or ",
+                });
+                self.operand(&logical.right.node);
+            }
+            Expr::Grouping(inner) => {
+                self.out.push('(');
+                self.expr(&inner.node);
+                self.out.push(')');
+            }
+            Expr::Variable(variable) => self.out.push_str(&variable.node),
+            Expr::Assign(assign) => {
+                self.out.push_str(&format!("{} = ", assign.target.node));
+                self.expr(&assign.value.node);
+            }
+            Expr::Call(call) => {
+                self.operand(&call.callee.node);
+                self.arguments(&call.arguments);
+            }
+            Expr::Lambda(lambda) => {
+                self.out.push_str("fn");
+                self.signature(&lambda.parameters, &lambda.return_type.node);
+                self.block(&lambda.body.node);
+            }
+            Expr::Block(block) => self.block(block),
+            Expr::If(if_expr) => {
+                self.out.push_str("if ");
+                self.condition(&if_expr.condition.node);
+                self.out.push(' ');
+                self.block(&if_expr.then_branch.node);
+                if let Some(else_branch) = &if_expr.else_branch {
+                    self.out.push_str(" else ");
+                    match &else_branch.node.expr {
+                        // `else if` is parsed into a block holding only the `if`
+                        Some(expr) if else_branch.node.statements.is_empty() && matches!(expr.node, Expr::If(_)) => self.expr(&expr.node),
+                        _ => self.block(&else_branch.node),
+                    }
+                }
+            }
+            Expr::MethodCall(method_call) => {
+                self.receiver(&method_call.receiver.node);
+                self.out.push_str(&format!(".{}", method_call.method.node));
+                self.arguments(&method_call.arguments);
+            }
+            Expr::StructInit(struct_init) => {
+                self.out.push_str(&format!("{} ", struct_init.name.node));
+                self.fields(&struct_init.fields);
+            }
+            Expr::FieldAccess(field_access) => {
+                self.receiver(&field_access.receiver.node);
+                self.out.push_str(&format!(".{}", field_access.field.node));
+            }
+            Expr::FieldAssign(field_assign) => {
+                self.receiver(&field_assign.receiver.node);
+                self.out.push_str(&format!(".{} = ", field_assign.field.node));
+                self.expr(&field_assign.value.node);
+            }
+            Expr::Record(record) => self.fields(&record.fields),
+            Expr::Cast(cast) => {
+                self.operand(&cast.expr.node);
+                self.out.push_str(&format!(" as {}", print_type(&cast.ty.node)));
+            }
+        }
+    }
+
+    /// operands of operators, callees and receivers, parenthesized unless they are a single term
+    fn operand(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(_)
+            | Expr::Grouping(_)
+            | Expr::Variable(_)
+            | Expr::Call(_)
+            | Expr::MethodCall(_)
+            | Expr::FieldAccess(_)
+            | Expr::StructInit(_) => self.expr(expr),
+            _ => self.grouped(expr),
+        }
+    }
+
+    /// `1.len()` would lex as a float followed by `len`
+    fn receiver(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(LiteralExpr::Int(_) | LiteralExpr::Float(_)) => self.grouped(expr),
+            _ => self.operand(expr),
+        }
+    }
+
+    fn grouped(&mut self, expr: &Expr) {
+        self.out.push('(');
+        self.expr(expr);
+        self.out.push(')');
+    }
+
+    fn arguments(&mut self, arguments: &[AstNode<Expr>]) {
+        self.out.push('(');
+        for (i, argument) in arguments.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(&argument.node);
+        }
+        self.out.push(')');
+    }
+
+    fn fields(&mut self, fields: &[(Ident, AstNode<Expr>)]) {
+        if fields.is_empty() {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push_str("{ ");
+        for (i, (name, value)) in fields.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&format!("{}: ", name.node));
+            self.expr(&value.node);
+        }
+        self.out.push_str(" }");
+    }
+
+    fn literal(&mut self, literal: &LiteralExpr) {
+        match literal {
+            LiteralExpr::Int(Number { text: Some(text), .. }) | LiteralExpr::Float(Number { text: Some(text), .. }) => {
+                self.out.push_str(text)
+            }
+            LiteralExpr::Int(int) => self.out.push_str(&int.value.to_string()),
+            LiteralExpr::Float(float) => {
+                let float = float.value.to_string();
+                self.out.push_str(&float);
+                if !float.contains('.') {
+                    self.out.push_str(".0");
+                }
+            }
+            LiteralExpr::String(string) => {
+                self.out.push('"');
+                self.out.push_str(&string.replace('\\', "\\\\").replace('"', "\\\""));
+                self.out.push('"');
+            }
+            LiteralExpr::Bool(bool) => self.out.push_str(&bool.to_string()),
+            LiteralExpr::VecLiteral(elements) => {
+                self.out.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(&element.node);
+                }
+                self.out.push(']');
+            }
+            LiteralExpr::Nil => self.out.push_str("nil"),
+        }
+    }
+}
+
+fn typed_idents(idents: &[TypedIdent]) -> String {
+    let idents: Vec<String> = idents
+        .iter()
+        .map(|ident| format!("{}: {}", ident.name.node, print_type(&ident.type_annotation.node)))
+        .collect();
+    idents.join(", ")
+}
+
+fn binary_operator(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Plus => "+",
+        BinaryOp::Minus => "-",
+        BinaryOp::Star => "*",
+        BinaryOp::Slash => "/",
+        BinaryOp::Greater => ">",
+        BinaryOp::GreaterEqual => ">=",
+        BinaryOp::Less => "<",
+        BinaryOp::LessEqual => "<=",
+        BinaryOp::EqualEqual => "==",
+        BinaryOp::BangEqual => "!=",
+    }
+}
+
+/// whether the last token printed for `expr` is a variable name
+fn ends_with_variable(expr: &Expr) -> bool {
+    match expr {
+        Expr::Variable(_) => true,
+        Expr::Unary(unary) => matches!(unary.expr.node, Expr::Variable(_)),
+        Expr::Binary(binary) => matches!(binary.right.node, Expr::Variable(_)),
+        Expr::Logical(logical) => matches!(logical.right.node, Expr::Variable(_)),
+        Expr::Assign(assign) => ends_with_variable(&assign.value.node),
+        Expr::FieldAssign(field_assign) => ends_with_variable(&field_assign.value.node),
+        _ => false,
+    }
+}
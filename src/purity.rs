@@ -0,0 +1,150 @@
+use crate::ast::{AstNode, BlockExpr, Expr, Program, Stmt, TypedIdent};
+use crate::visitor::{Visitor, walk_block, walk_expr, walk_stmt};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// natives without side effects, the others print, read the clock or schedule callbacks
+pub const PURE_NATIVES: [&str; 16] = [
+    "toString",
+    "typeOf",
+    "isNumber",
+    "isString",
+    "isBool",
+    "isVec",
+    "isNil",
+    "seconds",
+    "minutes",
+    "hours",
+    "days",
+    "formatTime",
+    "parseTime",
+    "matches",
+    "findAll",
+    "replaceAll",
+];
+
+/// top-level functions whose calls have no effect besides their result
+///
+/// a pure function doesn't write globals or struct fields, doesn't call methods, which may modify vecs, and only calls
+/// pure natives and other pure functions by name, it may still read globals and fail at runtime
+pub fn pure_functions(program: &Program) -> BTreeSet<String> {
+    let mut declared = BTreeSet::new();
+    let mut redeclared = BTreeSet::new();
+    for stmt in &program.statements {
+        let Stmt::FunDecl(fun_decl) = &stmt.node else { continue };
+        if !declared.insert(fun_decl.name.node.clone()) {
+            redeclared.insert(fun_decl.name.node.clone());
+        }
+    }
+
+    let mut effects = BTreeMap::new();
+    for stmt in &program.statements {
+        let Stmt::FunDecl(fun_decl) = &stmt.node else { continue };
+        if redeclared.contains(&fun_decl.name.node) {
+            continue;
+        }
+        let mut body = Effects {
+            functions: &declared,
+            scopes: vec![param_names(&fun_decl.params)],
+            impure: false,
+            callees: BTreeSet::new(),
+        };
+        body.visit_block(&fun_decl.body.node);
+        if !body.impure {
+            effects.insert(fun_decl.name.node.clone(), body.callees);
+        }
+    }
+
+    // a function calling an impure one is impure too, repeated until nothing changes
+    loop {
+        let impure: Vec<String> = effects
+            .iter()
+            .filter(|(_, callees)| callees.iter().any(|callee| !effects.contains_key(callee)))
+            .map(|(name, _)| name.clone())
+            .collect();
+        if impure.is_empty() {
+            return effects.into_keys().collect();
+        }
+        for name in impure {
+            effects.remove(&name);
+        }
+    }
+}
+
+fn param_names(params: &[TypedIdent]) -> HashSet<String> {
+    params.iter().map(|param| param.name.node.clone()).collect()
+}
+
+/// side effects of a function body, calls of top-level functions are collected to be checked afterwards
+struct Effects<'a> {
+    functions: &'a BTreeSet<String>,
+    scopes: Vec<HashSet<String>>,
+    impure: bool,
+    callees: BTreeSet<String>,
+}
+
+impl Effects<'_> {
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string());
+        }
+    }
+
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+}
+
+impl Visitor for Effects<'_> {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        match &stmt.node {
+            Stmt::VarDecl(var_decl) => {
+                walk_stmt(self, stmt);
+                self.declare(&var_decl.ident.node);
+            }
+            Stmt::FunDecl(fun_decl) => {
+                self.declare(&fun_decl.name.node);
+                self.scopes.push(param_names(&fun_decl.params));
+                walk_stmt(self, stmt);
+                self.scopes.pop();
+            }
+            Stmt::For(_) => {
+                self.scopes.push(HashSet::new());
+                walk_stmt(self, stmt);
+                self.scopes.pop();
+            }
+            _ => walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        match &expr.node {
+            Expr::Assign(assign) if !self.is_local(&assign.target.node) => self.impure = true,
+            Expr::FieldAssign(_) | Expr::MethodCall(_) => self.impure = true,
+            Expr::Call(call) => match &call.callee.node {
+                Expr::Variable(callee) if !self.is_local(&callee.node) => {
+                    if self.functions.contains(&callee.node) {
+                        self.callees.insert(callee.node.clone());
+                    } else if !PURE_NATIVES.contains(&callee.node.as_str()) {
+                        self.impure = true;
+                    }
+                }
+                // closures and parameters could be anything
+                _ => self.impure = true,
+            },
+            Expr::Lambda(lambda) => {
+                self.scopes.push(param_names(&lambda.parameters));
+                walk_expr(self, expr);
+                self.scopes.pop();
+                return;
+            }
+            _ => {}
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_block(&mut self, block: &BlockExpr) {
+        self.scopes.push(HashSet::new());
+        walk_block(self, block);
+        self.scopes.pop();
+    }
+}
@@ -1,14 +1,16 @@
 use crate::ast::{
-    AstNode, Expr, ExprStmt, FunDeclStmt, Ident, Program, ReturnStmt, Stmt, StructDeclStmt, TypedIdent, VarDeclStmt, WhileStmt,
+    AstNode, BlockExpr, Expr, ExprStmt, ForStmt, FunDeclStmt, Ident, NodeId, Program, ReturnStmt, Stmt, StructDeclStmt, TypeAliasStmt,
+    TypedIdent, UnresolvedType, VarDeclStmt, WhileStmt,
 };
-use crate::error::ResolverError;
+use crate::cancellation::Cancellation;
 use crate::error::ResolverError::{
-    DuplicateLambdaParameter, DuplicateParameter, ReturnOutsideFunction, UndefinedFunction, UndefinedGeneric, UndefinedVariable,
-    UninitializedVariable,
+    AssignmentInCondition, DuplicateLambdaParameter, DuplicateParameter, DuplicateTypeAlias, RecursiveTypeAlias, ReturnOutsideFunction,
+    UndefinedFunction, UndefinedGeneric, UndefinedType, UndefinedVariable, UninitializedVariable,
 };
+use crate::error::{ResolverError, Suggestion};
 use crate::type_inferrer::Type;
 use miette::{Report, SourceSpan};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::Deref;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -18,17 +20,71 @@ pub enum Symbol {
     Struct { fields: Vec<TypedIdent> },
 }
 
+/// a local of the current function, `depth` block scopes up from the use and `index` in declaration order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Local(Slot),
+    /// index into the captures of the running closure
+    Upvalue(usize),
+}
+
+/// everything the interpreter needs to find variables without looking them up by name, keyed by node id
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Bindings {
+    /// variable reads and assignments, globals have no entry
+    pub locals: BTreeMap<usize, Binding>,
+    /// what each function declaration or lambda captures, relative to where it is created
+    pub captures: BTreeMap<usize, Vec<Binding>>,
+    /// declarations of locals that some closure captures, they are stored in a shared cell
+    pub captured: BTreeSet<usize>,
+    /// the declaration of the local each variable read or assignment refers to
+    pub declarations: BTreeMap<usize, usize>,
+}
+
+/// slot indices of the variables of one scope, shadowing in the same scope takes a new slot
+#[derive(Debug, Default)]
+struct ScopeSlots {
+    indices: BTreeMap<String, usize>,
+    /// node id of the declaration of each slot
+    declarations: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct FunctionContext {
+    /// index into `scopes` of the function's parameter scope
+    scope_start: usize,
+    captures: Vec<Binding>,
+}
+
 pub struct Resolver<'a> {
     source: String,
     program: &'a Program,
     errors: Vec<Report>,
-    scopes: Vec<HashMap<String, Symbol>>,
+    scopes: Vec<BTreeMap<String, Symbol>>,
     inside_fn: bool,
+    mutated_globals: BTreeSet<String>,
+    /// parallel to `scopes`, globals are looked up by name and never get slots
+    slots: Vec<ScopeSlots>,
+    /// the top-level code followed by the functions currently being resolved
+    functions: Vec<FunctionContext>,
+    bindings: Bindings,
+    /// the type namespace, aliases are global wherever they are declared
+    type_aliases: BTreeMap<String, Type>,
+    /// names of the top-level functions and structs and of the type aliases, pointed at when they are declared again
+    declaration_spans: BTreeMap<String, SourceSpan>,
+    cancellation: Option<Cancellation>,
+    cancelled: bool,
 }
 
 impl<'a> Resolver<'a> {
     pub fn new(ast: &'a Program, source: String) -> Self {
-        let mut var_env = HashMap::new();
+        let mut var_env = BTreeMap::new();
         var_env.insert(
             "clock".to_string(),
             Symbol::Function {
@@ -43,6 +99,62 @@ impl<'a> Resolver<'a> {
                 generics: vec![],
             },
         );
+        var_env.insert(
+            "toString".to_string(),
+            Symbol::Function {
+                params: vec![],
+                generics: vec![],
+            },
+        );
+        var_env.insert(
+            "typeOf".to_string(),
+            Symbol::Function {
+                params: vec![],
+                generics: vec![],
+            },
+        );
+        var_env.insert(
+            "schedule".to_string(),
+            Symbol::Function {
+                params: vec![],
+                generics: vec![],
+            },
+        );
+        var_env.insert(
+            "runEventLoop".to_string(),
+            Symbol::Function {
+                params: vec![],
+                generics: vec![],
+            },
+        );
+        for name in ["isNumber", "isString", "isBool", "isVec", "isNil"] {
+            var_env.insert(
+                name.to_string(),
+                Symbol::Function {
+                    params: vec![],
+                    generics: vec![],
+                },
+            );
+        }
+        for name in ["now", "formatTime", "parseTime", "seconds", "minutes", "hours", "days"] {
+            var_env.insert(
+                name.to_string(),
+                Symbol::Function {
+                    params: vec![],
+                    generics: vec![],
+                },
+            );
+        }
+        #[cfg(feature = "regex")]
+        for name in ["matches", "findAll", "replaceAll"] {
+            var_env.insert(
+                name.to_string(),
+                Symbol::Function {
+                    params: vec![],
+                    generics: vec![],
+                },
+            );
+        }
 
         Self {
             source,
@@ -50,20 +162,72 @@ impl<'a> Resolver<'a> {
             errors: vec![],
             scopes: vec![var_env],
             inside_fn: false,
+            mutated_globals: BTreeSet::new(),
+            slots: vec![ScopeSlots::default()],
+            functions: vec![FunctionContext {
+                scope_start: 1,
+                captures: vec![],
+            }],
+            bindings: Bindings::default(),
+            type_aliases: BTreeMap::new(),
+            declaration_spans: BTreeMap::new(),
+            cancellation: None,
+            cancelled: false,
         }
     }
 
+    /// declares a global function provided by the host, like the builtin `clock`
+    pub fn with_native(mut self, name: &str) -> Self {
+        self.scopes[0].insert(
+            name.to_string(),
+            Symbol::Function {
+                params: vec![],
+                generics: vec![],
+            },
+        );
+        self
+    }
+
+    /// declares initialized global variables the host sets with `Interpreter::set_global`
+    pub fn with_globals(mut self, names: &[&str]) -> Self {
+        for name in names {
+            self.scopes[0].insert(name.to_string(), Symbol::Variable { initialized: true });
+        }
+        self
+    }
+
+    /// checked before every statement, a cancelled resolve skips the rest of the program and reports `ResolverError::Cancelled`
+    pub fn with_cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// top-level functions, structs and type aliases are declared first, so code can refer to them before their declaration
     pub fn resolve(&mut self) -> &Vec<Report> {
         for stmt in &self.program.statements {
-            self.declare_stmt(&stmt);
+            self.declare_stmt(&stmt.node);
         }
 
         for stmt in &self.program.statements {
-            self.resolve_stmt(&stmt);
+            self.resolve_stmt(stmt);
         }
         &self.errors
     }
 
+    /// globals that are assigned after their declaration, the optimizer treats all others as constants
+    pub fn mutated_globals(&self) -> &BTreeSet<String> {
+        &self.mutated_globals
+    }
+
+    pub fn bindings(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    /// the diagnostics of `resolve`, for keeping them after the resolver is gone
+    pub fn into_errors(self) -> Vec<Report> {
+        self.errors
+    }
+
     fn report(&mut self, error: ResolverError) {
         self.errors.push(error.into());
     }
@@ -77,140 +241,249 @@ impl<'a> Resolver<'a> {
         None
     }
 
-    fn curr_scope(&mut self) -> &mut HashMap<String, Symbol> {
+    /// whether `name` names a type, an alias or a struct in scope
+    fn is_type_name(&self, name: &str) -> bool {
+        self.type_aliases.contains_key(name) || matches!(self.lookup_symbol(name), Some(Symbol::Struct { .. }))
+    }
+
+    fn curr_scope(&mut self) -> &mut BTreeMap<String, Symbol> {
         self.scopes.last_mut().unwrap()
     }
 
+    fn begin_scope(&mut self) {
+        self.scopes.push(BTreeMap::new());
+        self.slots.push(ScopeSlots::default());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+        self.slots.pop();
+    }
+
+    fn begin_function(&mut self) {
+        self.functions.push(FunctionContext {
+            scope_start: self.scopes.len(),
+            captures: vec![],
+        });
+        self.begin_scope();
+    }
+
+    fn end_function(&mut self, node_id: usize) {
+        self.end_scope();
+        let function = self.functions.pop().unwrap();
+        self.bindings.captures.insert(node_id, function.captures);
+    }
+
+    /// gives `name` the next slot of the current scope, the interpreter defines locals in the same order
+    fn declare_local(&mut self, node_id: usize, name: &str) {
+        if self.scopes.len() == 1 {
+            return;
+        }
+        let slots = self.slots.last_mut().unwrap();
+        slots.indices.insert(name.to_string(), slots.declarations.len());
+        slots.declarations.push(node_id);
+    }
+
+    fn resolve_local(&mut self, node_id: usize, name: &str) {
+        let Some(scope_index) = self.scopes.iter().rposition(|scope| scope.contains_key(name)) else {
+            return;
+        };
+        let Some(&index) = self.slots[scope_index].indices.get(name) else {
+            return;
+        };
+        self.bindings
+            .declarations
+            .insert(node_id, self.slots[scope_index].declarations[index]);
+
+        let function = self.functions.len() - 1;
+        let binding = if scope_index >= self.functions[function].scope_start {
+            Binding::Local(Slot {
+                depth: self.scopes.len() - 1 - scope_index,
+                index,
+            })
+        } else {
+            Binding::Upvalue(self.capture(function, scope_index, index))
+        };
+        self.bindings.locals.insert(node_id, binding);
+    }
+
+    /// makes the local in `slots[scope_index]` available to `functions[function]`, returning its upvalue index
+    fn capture(&mut self, function: usize, scope_index: usize, index: usize) -> usize {
+        let scope_start = self.functions[function].scope_start;
+        let binding = if scope_index >= self.functions[function - 1].scope_start {
+            self.bindings.captured.insert(self.slots[scope_index].declarations[index]);
+            // the closure is created in the innermost scope of the enclosing function
+            Binding::Local(Slot {
+                depth: scope_start - 1 - scope_index,
+                index,
+            })
+        } else {
+            Binding::Upvalue(self.capture(function - 1, scope_index, index))
+        };
+
+        let captures = &mut self.functions[function].captures;
+        match captures.iter().position(|capture| *capture == binding) {
+            Some(position) => position,
+            None => {
+                captures.push(binding);
+                captures.len() - 1
+            }
+        }
+    }
+
     fn declare_stmt(&mut self, stmt: &Stmt) {
         match stmt {
             Stmt::FunDecl(fun_decl) => {
-                let name = &fun_decl.node.ident.node;
-                if let Some(_) = self.curr_scope().get(name) {
+                let name = &fun_decl.name.node;
+                if self.curr_scope().get(name).is_some() {
                     self.report(ResolverError::DuplicateFunction {
                         src: self.source.to_string(),
-                        span: fun_decl.node.ident.span,
+                        span: fun_decl.name.span,
+                        previous: self.declaration_spans.get(name).copied(),
                         name: name.clone(),
                     });
                     return;
                 }
+                self.declaration_spans.insert(name.clone(), fun_decl.name.span);
                 self.curr_scope().insert(
                     name.clone(),
                     Symbol::Function {
-                        params: fun_decl.node.params.clone(),
-                        generics: fun_decl.node.generics.clone(),
+                        params: fun_decl.params.clone(),
+                        generics: fun_decl.generics.clone(),
                     },
                 );
             }
             Stmt::StructDecl(struct_decl) => {
-                let name = &struct_decl.node.ident.node;
-                if let Some(_) = self.curr_scope().get(name) {
+                let name = &struct_decl.ident.node;
+                if self.curr_scope().get(name).is_some() {
                     self.report(ResolverError::DuplicateStruct {
                         src: self.source.clone(),
-                        span: struct_decl.node.ident.span,
+                        span: struct_decl.ident.span,
+                        previous: self.declaration_spans.get(name).copied(),
                         name: name.clone(),
                     })
                 }
+                self.declaration_spans.entry(name.clone()).or_insert(struct_decl.ident.span);
                 self.curr_scope().insert(
                     name.clone(),
                     Symbol::Struct {
-                        fields: struct_decl.node.fields.clone(),
+                        fields: struct_decl.fields.clone(),
                     },
                 );
             }
+            Stmt::TypeAlias(type_alias) => self.declare_type_alias(type_alias),
             _ => {}
         }
     }
 
-    fn resolve_stmt(&mut self, stmt: &Stmt) {
-        match stmt {
+    fn declare_type_alias(&mut self, type_alias: &TypeAliasStmt) {
+        let name = &type_alias.ident.node;
+        if self.is_type_name(name) {
+            self.report(DuplicateTypeAlias {
+                src: self.source.clone(),
+                span: type_alias.ident.span,
+                previous: self.declaration_spans.get(name).copied(),
+                name: name.clone(),
+            });
+            return;
+        }
+        self.declaration_spans.insert(name.clone(), type_alias.ident.span);
+        self.type_aliases.insert(name.clone(), Type::from(&type_alias.ty.node));
+    }
+
+    /// whether the resolve was cancelled, reported the first time it's noticed
+    fn checkpoint(&mut self) -> bool {
+        if !self.cancelled && self.cancellation.as_ref().is_some_and(Cancellation::is_cancelled) {
+            self.cancelled = true;
+            self.report(ResolverError::Cancelled);
+        }
+        self.cancelled
+    }
+
+    fn resolve_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        if self.checkpoint() {
+            return;
+        }
+        match &stmt.node {
             Stmt::ExprStmtNode(expr_stmt) => self.resolve_expr_stmt(expr_stmt),
-            Stmt::VarDecl(var_decl) => self.resolve_var_decl(var_decl),
-            Stmt::FunDecl(fun_decl) => self.resolve_fun_decl(fun_decl),
+            Stmt::VarDecl(var_decl) => self.resolve_var_decl(var_decl, stmt.node_id),
+            Stmt::FunDecl(fun_decl) => self.resolve_fun_decl(fun_decl, stmt.node_id),
             Stmt::StructDecl(struct_decl) => self.resolve_struct_decl(struct_decl),
+            Stmt::TypeAlias(type_alias) => self.resolve_type_alias(type_alias),
             Stmt::While(while_stmt) => self.resolve_while_stmt(while_stmt),
-            Stmt::Return(return_stmt) => self.resolve_return_stmt(return_stmt),
+            Stmt::For(for_stmt) => self.resolve_for_stmt(for_stmt),
+            Stmt::Return(return_stmt) => self.resolve_return_stmt(return_stmt, stmt.span),
         }
     }
 
-    fn resolve_expr_stmt(&mut self, expr_stmt: &AstNode<ExprStmt>) {
-        self.resolve_expr(&expr_stmt.node.expr);
+    fn resolve_expr_stmt(&mut self, expr_stmt: &ExprStmt) {
+        self.resolve_expr(&expr_stmt.expr);
     }
 
-    fn resolve_var_decl(&mut self, var_decl: &AstNode<VarDeclStmt>) {
-        if let Some(init) = &var_decl.node.initializer {
+    fn resolve_var_decl(&mut self, var_decl: &VarDeclStmt, node_id: NodeId) {
+        if let Some(init) = &var_decl.initializer {
             self.resolve_expr(init);
         }
         self.curr_scope().insert(
-            var_decl.node.ident.node.clone(),
+            var_decl.ident.node.clone(),
             Symbol::Variable {
-                initialized: var_decl.node.initializer.is_some(),
+                initialized: var_decl.initializer.is_some(),
             },
         );
+        self.declare_local(node_id, &var_decl.ident.node);
     }
 
-    fn resolve_fun_decl(&mut self, fun_decl: &AstNode<FunDeclStmt>) {
+    fn resolve_fun_decl(&mut self, fun_decl: &FunDeclStmt, node_id: NodeId) {
         self.curr_scope().insert(
-            fun_decl.node.name.node.clone(),
+            fun_decl.name.node.clone(),
             Symbol::Function {
-                params: fun_decl.node.params.clone(),
-                generics: fun_decl.node.generics.clone(),
+                params: fun_decl.params.clone(),
+                generics: fun_decl.generics.clone(),
             },
         );
+        self.declare_local(node_id, &fun_decl.name.node);
 
-        self.scopes.push(HashMap::new());
+        self.begin_function();
 
-        let generic_params: HashSet<String> = fun_decl.node.generics.iter().map(|g| g.node.clone()).collect();
-        let mut seen_params = HashSet::new();
+        let generic_params: BTreeSet<String> = fun_decl.generics.iter().map(|g| g.node.clone()).collect();
+        let mut seen_params = BTreeMap::new();
 
-        for param in &fun_decl.node.params {
+        for param in &fun_decl.params {
             let param_name = &param.name.node;
-            if !seen_params.insert(param_name.clone()) {
+            if let Some(previous) = seen_params.insert(param_name.clone(), param.name.span) {
                 self.report(DuplicateParameter {
                     src: self.source.to_string(),
                     span: param.name.span,
-                    function_name: fun_decl.node.name.node.clone(),
+                    previous,
+                    function_name: fun_decl.name.node.clone(),
                 });
                 continue;
             }
             self.check_generic_param(&param.type_annotation, &generic_params);
             self.curr_scope()
                 .insert(param.name.node.clone(), Symbol::Variable { initialized: true });
+            self.declare_local(param.name.node_id, &param.name.node);
         }
 
-        self.check_generic_param(&fun_decl.node.return_type, &generic_params);
+        self.check_generic_param(&fun_decl.return_type, &generic_params);
 
         let prev_inside_fn = self.inside_fn;
         self.inside_fn = true;
-        for stmt in &fun_decl.node.body.node.statements {
+        for stmt in &fun_decl.body.node.statements {
             self.resolve_stmt(stmt);
         }
+        if let Some(expr) = &fun_decl.body.node.expr {
+            self.resolve_expr(expr);
+        }
         self.inside_fn = prev_inside_fn;
-        self.scopes.pop();
+        self.end_function(node_id);
     }
 
-    fn check_generic_param(&mut self, ty: &AstNode<Type>, generic_params: &HashSet<String>) {
-        match &ty.node {
-            Type::Function { params, return_ty } => {
-                for param in params {
-                    self.check_generic_type(param, generic_params, ty.span);
-                }
-                self.check_generic_type(return_ty, generic_params, ty.span);
-            }
-            Type::Vec(vec_ty) => self.check_generic_type(vec_ty, generic_params, ty.span),
-            Type::Generic(name) => {
-                if !generic_params.contains(name) {
-                    self.report(UndefinedGeneric {
-                        src: self.source.to_string(),
-                        span: ty.span,
-                        name: name.clone(),
-                    });
-                }
-            }
-            _ => {}
-        }
+    fn check_generic_param(&mut self, ty: &AstNode<UnresolvedType>, generic_params: &BTreeSet<String>) {
+        self.check_generic_type(&Type::from(&ty.node), generic_params, ty.span);
     }
 
-    fn check_generic_type(&mut self, ty: &Type, generic_params: &HashSet<String>, span: SourceSpan) {
+    fn check_generic_type(&mut self, ty: &Type, generic_params: &BTreeSet<String>, span: SourceSpan) {
         match ty {
             Type::Function { params, return_ty } => {
                 for param in params {
@@ -219,53 +492,142 @@ impl<'a> Resolver<'a> {
                 self.check_generic_type(return_ty, generic_params, span);
             }
             Type::Vec(vec_ty) => self.check_generic_type(vec_ty, generic_params, span),
-            Type::Generic(name) => {
-                if !generic_params.contains(name) {
-                    self.report(UndefinedGeneric {
-                        src: self.source.to_string(),
-                        span,
-                        name: name.clone(),
-                    });
+            Type::Record(fields) => {
+                for (_, field_ty) in fields {
+                    self.check_generic_type(field_ty, generic_params, span);
                 }
             }
+            Type::Generic(name) if !generic_params.contains(name) && !self.is_type_name(name) => {
+                self.report(UndefinedGeneric {
+                    src: self.source.to_string(),
+                    span,
+                    name: name.clone(),
+                });
+            }
             _ => {}
         }
     }
 
-    fn resolve_struct_decl(&mut self, struct_decl: &AstNode<StructDeclStmt>) {
-        let name = struct_decl.node.ident.node.clone();
+    fn resolve_struct_decl(&mut self, struct_decl: &StructDeclStmt) {
+        let name = struct_decl.ident.node.clone();
         self.curr_scope().insert(
             name.clone(),
             Symbol::Struct {
-                fields: struct_decl.node.fields.clone(),
+                fields: struct_decl.fields.clone(),
             },
         );
     }
 
-    fn resolve_stmts(&mut self, stmts: &Vec<Stmt>) {
-        self.scopes.push(HashMap::new());
-        for stmt in stmts {
+    fn resolve_type_alias(&mut self, type_alias: &TypeAliasStmt) {
+        let name = &type_alias.ident.node;
+        // aliases in blocks aren't hoisted, they become visible from their declaration on
+        if !self.type_aliases.contains_key(name) {
+            self.declare_type_alias(type_alias);
+        }
+
+        let ty = Type::from(&type_alias.ty.node);
+        let mut names = vec![];
+        alias_references(&ty, &mut names);
+        for referenced in names {
+            if !self.is_type_name(&referenced) {
+                self.report(UndefinedType {
+                    src: self.source.clone(),
+                    span: type_alias.ty.span,
+                    name: referenced,
+                });
+            }
+        }
+
+        if self.refers_to(&ty, name, &mut BTreeSet::new()) {
+            self.report(RecursiveTypeAlias {
+                src: self.source.clone(),
+                span: type_alias.ident.span,
+                name: name.clone(),
+            });
+        }
+    }
+
+    /// whether `ty` mentions the alias `name`, directly or through other aliases
+    fn refers_to(&self, ty: &Type, name: &str, visited: &mut BTreeSet<String>) -> bool {
+        let mut names = vec![];
+        alias_references(ty, &mut names);
+        names.into_iter().any(|referenced| {
+            referenced == name
+                || (visited.insert(referenced.clone())
+                    && self
+                        .type_aliases
+                        .get(&referenced)
+                        .is_some_and(|aliased| self.refers_to(aliased, name, visited)))
+        })
+    }
+
+    /// declarations in blocks aren't hoisted, a local function can only be used after it
+    fn resolve_block(&mut self, block: &BlockExpr) {
+        self.begin_scope();
+        for stmt in &block.statements {
             self.resolve_stmt(stmt);
         }
-        self.scopes.pop();
+        if let Some(expr) = &block.expr {
+            self.resolve_expr(expr);
+        }
+        self.end_scope();
     }
 
-    fn resolve_while_stmt(&mut self, while_stmt: &AstNode<WhileStmt>) {
-        self.resolve_expr(&while_stmt.node.condition);
-        self.resolve_stmts(&while_stmt.node.body.node.statements);
+    fn resolve_while_stmt(&mut self, while_stmt: &WhileStmt) {
+        self.check_condition(&while_stmt.condition);
+        self.resolve_expr(&while_stmt.condition);
+        self.resolve_block(&while_stmt.body.node);
     }
 
-    fn resolve_return_stmt(&mut self, return_stmt: &AstNode<ReturnStmt>) {
+    /// the initializer's variable gets a scope around the loop, the interpreter declares it there once for all iterations
+    fn resolve_for_stmt(&mut self, for_stmt: &ForStmt) {
+        self.begin_scope();
+        if let Some(initializer) = &for_stmt.initializer {
+            self.resolve_stmt(initializer);
+        }
+        self.check_condition(&for_stmt.condition);
+        self.resolve_expr(&for_stmt.condition);
+        self.resolve_block(&for_stmt.body.node);
+        if let Some(increment) = &for_stmt.increment {
+            self.resolve_expr(increment);
+        }
+        self.end_scope();
+    }
+
+    fn resolve_return_stmt(&mut self, return_stmt: &ReturnStmt, span: SourceSpan) {
         if !self.inside_fn {
             self.report(ReturnOutsideFunction {
                 src: self.source.clone(),
-                span: return_stmt.span,
+                span,
             })
-        } else if let Some(return_expr) = &return_stmt.node.expr {
+        } else if let Some(return_expr) = &return_stmt.expr {
             self.resolve_expr(return_expr);
         }
     }
 
+    /// warns if the top-level expression of a condition is an assignment
+    fn check_condition(&mut self, condition: &AstNode<Expr>) {
+        let mut expr = &condition.node;
+        while let Expr::Grouping(inner) = expr {
+            expr = &inner.node;
+        }
+
+        let equal_span = match expr {
+            Expr::Assign(assign) => assign.equal_span,
+            Expr::FieldAssign(field_assign) => field_assign.equal_span,
+            _ => return,
+        };
+
+        self.report(AssignmentInCondition {
+            src: self.source.clone(),
+            span: equal_span,
+            suggestion: Suggestion {
+                span: equal_span,
+                replacement: "==".to_string(),
+            },
+        });
+    }
+
     fn resolve_expr(&mut self, expr: &AstNode<Expr>) {
         match &expr.node {
             Expr::FieldAssign(field_assign) => {
@@ -282,11 +644,10 @@ impl<'a> Resolver<'a> {
                         span: struct_init.name.span,
                         name: struct_init.name.node.clone(),
                     });
-                    return;
                 }
                 Some(Symbol::Struct { fields: _ }) => {
                     for (_, value) in &struct_init.fields {
-                        self.resolve_expr(&value);
+                        self.resolve_expr(value);
                     }
                 }
                 Some(_) => {
@@ -297,23 +658,33 @@ impl<'a> Resolver<'a> {
                     });
                 }
             },
-            Expr::Literal(_) => {}
-            Expr::Block(block) => {
-                self.scopes.push(HashMap::new());
-                for stmt in &block.statements {
-                    self.resolve_stmt(stmt);
+            Expr::Record(record) => {
+                for (_, value) in &record.fields {
+                    self.resolve_expr(value);
                 }
-                if let Some(expr) = &block.expr {
-                    self.resolve_expr(expr)
+            }
+            Expr::Cast(cast) => {
+                self.resolve_expr(&cast.expr);
+                let mut names = vec![];
+                alias_references(&cast.target, &mut names);
+                for name in names {
+                    if !self.is_type_name(&name) {
+                        self.report(UndefinedType {
+                            src: self.source.clone(),
+                            span: cast.ty.span,
+                            name,
+                        });
+                    }
                 }
-
-                self.scopes.pop();
             }
+            Expr::Literal(_) => {}
+            Expr::Block(block) => self.resolve_block(block),
             Expr::If(if_expr) => {
+                self.check_condition(&if_expr.condition);
                 self.resolve_expr(&if_expr.condition);
-                self.resolve_stmts(&if_expr.then_branch.node.statements);
+                self.resolve_block(&if_expr.then_branch.node);
                 if let Some(else_branch) = &if_expr.else_branch {
-                    self.resolve_stmts(&else_branch.node.statements);
+                    self.resolve_block(&else_branch.node);
                 }
             }
             Expr::MethodCall(method_call) => {
@@ -344,7 +715,7 @@ impl<'a> Resolver<'a> {
                     span: variable_expr.span,
                     name: variable_expr.node.clone(),
                 }),
-                _ => {}
+                _ => self.resolve_local(expr.node_id, &variable_expr.node),
             },
             Expr::Assign(assign) => {
                 match self.lookup_symbol(assign.target.node.as_str()) {
@@ -354,12 +725,16 @@ impl<'a> Resolver<'a> {
                         name: assign.target.node.clone(),
                     }),
                     Some(_) => {
-                        for scope in self.scopes.iter_mut().rev() {
+                        for (depth, scope) in self.scopes.iter_mut().enumerate().rev() {
                             if let Some(symbol) = scope.get_mut(&assign.target.node) {
                                 *symbol = Symbol::Variable { initialized: true };
+                                if depth == 0 {
+                                    self.mutated_globals.insert(assign.target.node.clone());
+                                }
                                 break;
                             }
                         }
+                        self.resolve_local(expr.node_id, &assign.target.node);
                     }
                 }
 
@@ -370,31 +745,33 @@ impl<'a> Resolver<'a> {
                 self.resolve_expr(logical_expr.right.deref());
             }
             Expr::Call(call) => {
-                if let Expr::Variable(ident) = &call.callee.deref().node {
-                    if let None = self.lookup_symbol(&ident.node) {
-                        self.report(UndefinedFunction {
-                            src: self.source.clone(),
-                            span: ident.span,
-                            name: ident.node.clone(),
-                        })
-                    }
+                match &call.callee.node {
+                    Expr::Variable(ident) if self.lookup_symbol(&ident.node).is_none() => self.report(UndefinedFunction {
+                        src: self.source.clone(),
+                        span: ident.span,
+                        name: ident.node.clone(),
+                    }),
+                    // a local closure is called through its slot like any other read
+                    _ => self.resolve_expr(&call.callee),
                 }
                 for argument in &call.arguments {
                     self.resolve_expr(argument);
                 }
             }
             Expr::Lambda(lambda) => {
-                self.scopes.push(HashMap::new());
-                for param in &lambda.parameters {
-                    if self.curr_scope().get(param.name.node.as_str()).is_some() {
+                self.begin_function();
+                for (i, param) in lambda.parameters.iter().enumerate() {
+                    if let Some(previous) = lambda.parameters[..i].iter().find(|other| other.name.node == param.name.node) {
                         self.report(DuplicateLambdaParameter {
                             src: self.source.to_string(),
                             span: param.name.span,
+                            previous: previous.name.span,
                         })
                     } else {
                         self.curr_scope()
                             .insert(param.name.node.clone(), Symbol::Variable { initialized: true });
                     }
+                    self.declare_local(param.name.node_id, &param.name.node);
                 }
 
                 let prev_inside_fn = self.inside_fn;
@@ -402,9 +779,77 @@ impl<'a> Resolver<'a> {
                 for stmt in &lambda.body.node.statements {
                     self.resolve_stmt(stmt);
                 }
+                if let Some(expr) = &lambda.body.node.expr {
+                    self.resolve_expr(expr);
+                }
                 self.inside_fn = prev_inside_fn;
-                self.scopes.pop();
+                self.end_function(expr.node_id);
+            }
+        }
+    }
+}
+
+/// names used in a type, which outside of generic functions can only be aliases
+fn alias_references(ty: &Type, names: &mut Vec<String>) {
+    match ty {
+        Type::Function { params, return_ty } => {
+            for param in params {
+                alias_references(param, names);
+            }
+            alias_references(return_ty, names);
+        }
+        Type::Vec(elem_ty) => alias_references(elem_ty, names),
+        Type::Record(fields) => {
+            for (_, field_ty) in fields {
+                alias_references(field_ty, names);
+            }
+        }
+        Type::Union(members) => {
+            for member in members {
+                alias_references(member, names);
             }
         }
+        Type::Generic(name) => names.push(name.clone()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::Compiler;
+
+    fn diagnostic_codes(source: &str) -> Vec<String> {
+        let compilation = Compiler::new().check(source);
+        compilation
+            .diagnostics
+            .iter()
+            .filter_map(|diagnostic| diagnostic.code().map(|code| code.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn struct_names_are_types() {
+        let source = "struct Point { x: Int, y: Int }
+type P = Point;
+type Ps = Vec<Point>;
+fn first<T>(points: Vec<T>, p: P) -> Int { p.x }
+let p: P = Point { x: 1, y: 2 };
+let ps: Ps = [p];
+let x = first(ps, p as Point);";
+        assert!(!diagnostic_codes(source).iter().any(|code| code.contains("undefined")));
+    }
+
+    #[test]
+    fn unknown_type_names_are_reported() {
+        assert!(
+            diagnostic_codes("type P = Pointt;")
+                .iter()
+                .any(|code| code.ends_with("undefined_type"))
+        );
+        assert!(
+            diagnostic_codes("let x = 1 as Pointt;")
+                .iter()
+                .any(|code| code.ends_with("undefined_type"))
+        );
     }
 }
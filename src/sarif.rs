@@ -0,0 +1,125 @@
+use crate::preprocessor::SourceMap;
+use miette::{LabeledSpan, Report, Severity};
+use std::collections::BTreeMap;
+
+/// diagnostics of a run as a SARIF 2.1.0 log, the format code scanning tools import
+#[derive(Default)]
+pub struct SarifLog {
+    /// description of each error code, taken from its first diagnostic
+    rules: BTreeMap<String, Rule>,
+    results: Vec<String>,
+}
+
+struct Rule {
+    description: String,
+    help: Option<String>,
+}
+
+impl SarifLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the primary label, or else the first, is the location, other labels like "declared here" notes are related locations
+    ///
+    /// labels outside the source map, e.g. in text prepended by a session, are left out
+    pub fn add(&mut self, diagnostic: &Report, source_map: &SourceMap) {
+        let rule_id = diagnostic.code().map_or("rub::error".to_string(), |code| code.to_string());
+        let message = diagnostic.to_string();
+        self.rules.entry(rule_id.clone()).or_insert_with(|| Rule {
+            description: message.clone(),
+            help: diagnostic.help().map(|help| help.to_string()),
+        });
+
+        let level = match diagnostic.severity().unwrap_or(Severity::Error) {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Advice => "note",
+        };
+        let mut labels: Vec<LabeledSpan> = diagnostic.labels().into_iter().flatten().collect();
+        let primary = labels.iter().position(LabeledSpan::primary).unwrap_or(0);
+        let locations: Vec<String> = (!labels.is_empty())
+            .then(|| labels.remove(primary))
+            .and_then(|label| location(&label, source_map))
+            .map(|location| format!("{{{location}}}"))
+            .into_iter()
+            .collect();
+        let related: Vec<String> = labels
+            .iter()
+            .filter_map(|label| location(label, source_map))
+            .enumerate()
+            .map(|(id, location)| format!("{{\"id\":{id},{location}}}"))
+            .collect();
+        let related = if related.is_empty() {
+            String::new()
+        } else {
+            format!(",\"relatedLocations\":[{}]", related.join(","))
+        };
+
+        self.results.push(format!(
+            "{{\"ruleId\":\"{}\",\"level\":\"{level}\",\"message\":{{\"text\":\"{}\"}},\"locations\":[{}]{related}}}",
+            escape(&rule_id),
+            escape(&message),
+            locations.join(",")
+        ));
+    }
+
+    pub fn to_json(&self) -> String {
+        let rules: Vec<String> = self
+            .rules
+            .iter()
+            .map(|(id, rule)| {
+                let help = rule
+                    .help
+                    .as_ref()
+                    .map_or(String::new(), |help| format!(",\"help\":{{\"text\":\"{}\"}}", escape(help)));
+                format!(
+                    "{{\"id\":\"{}\",\"shortDescription\":{{\"text\":\"{}\"}}{help}}}",
+                    escape(id),
+                    escape(&rule.description)
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\"version\":\"2.1.0\",\"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"rub\",\"rules\":[{}]}}}},\"results\":[{}]}}]}}",
+            rules.join(","),
+            self.results.join(",")
+        )
+    }
+}
+
+/// the fields of a SARIF location object, with the label's text as its message
+fn location(label: &LabeledSpan, source_map: &SourceMap) -> Option<String> {
+    let (file, start_line, start_column) = source_map.position(label.offset())?;
+    // the end is exclusive, located through the label's last character
+    let end = label.offset() + label.len().max(1) - 1;
+    let (end_line, end_column) = match source_map.position(end) {
+        Some((end_file, line, column)) if end_file == file => (line, column + 1),
+        _ => (start_line, start_column + 1),
+    };
+    let message = label
+        .label()
+        .map_or(String::new(), |text| format!(",\"message\":{{\"text\":\"{}\"}}", escape(text)));
+    Some(format!(
+        "\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":\"{}\"}},\"region\":{{\"startLine\":{start_line},\"startColumn\":{start_column},\"endLine\":{end_line},\"endColumn\":{end_column}}}}}{message}",
+        escape(file)
+    ))
+}
+
+/// `text` as the contents of a JSON string
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
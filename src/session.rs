@@ -0,0 +1,110 @@
+use crate::ast::{Program, Stmt};
+use crate::interpreters::Value;
+use crate::{Lexer, Parser};
+use std::collections::{BTreeSet, HashMap};
+
+/// interpreter state written as Rub source, running it before another program restores the globals
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Snapshot {
+    pub source: String,
+    /// globals whose value has no literal syntax, e.g. closures, structs, empty vecs or strings containing `"`
+    pub skipped: Vec<String>,
+}
+
+impl Snapshot {
+    /// top-level struct and function declarations are copied from `source`, variables get their current value
+    pub fn capture(program: &Program, source: &str, globals: &HashMap<String, Value>) -> Self {
+        let mut snapshot = Snapshot::default();
+        let mut declarations = String::new();
+        let mut saved = BTreeSet::new();
+
+        for stmt in &program.statements {
+            match &stmt.node {
+                Stmt::FunDecl(_) | Stmt::StructDecl(_) | Stmt::TypeAlias(_) => {
+                    let start = stmt.span.offset();
+                    declarations.push_str(&source[start..start + stmt.span.len()]);
+                    declarations.push('\n');
+                }
+                Stmt::VarDecl(var_decl) => {
+                    let name = &var_decl.ident.node;
+                    if !saved.insert(name.clone()) {
+                        continue;
+                    }
+                    match globals.get(name).and_then(literal) {
+                        Some(value) => snapshot.source.push_str(&format!("let {name} = {value};\n")),
+                        None => snapshot.skipped.push(name.clone()),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // variables come first, their literals can't refer to functions but function bodies can refer to them
+        snapshot.source.push_str(&declarations);
+        snapshot
+    }
+
+    /// the saved `session` without the functions, structs and type aliases `program` declares itself, so a program can
+    /// be run again with the session it saved, the session's variables are kept
+    pub fn without_redeclared(session: &str, program: &str) -> String {
+        let program = format!("{program} ");
+        let mut lexer = Lexer::new(&program);
+        let redeclared = declared_names(&Parser::new(lexer.lex().tokens, program.clone()).parse().ast);
+
+        let session_source = format!("{session} ");
+        let mut lexer = Lexer::new(&session_source);
+        let mut parser = Parser::new(lexer.lex().tokens, session_source.clone());
+        let parse_result = parser.parse();
+        // the errors are reported when the session runs
+        if !parse_result.errors.is_empty() {
+            return session.to_string();
+        }
+        let mut kept = String::new();
+        for stmt in &parse_result.ast.statements {
+            if declared_name(&stmt.node).is_some_and(|name| redeclared.contains(name)) {
+                continue;
+            }
+            let start = stmt.span.offset();
+            kept.push_str(&session_source[start..start + stmt.span.len()]);
+            kept.push('\n');
+        }
+        kept
+    }
+}
+
+/// the names of the program's top-level functions, structs and type aliases
+fn declared_names(program: &Program) -> BTreeSet<String> {
+    program
+        .statements
+        .iter()
+        .filter_map(|stmt| declared_name(&stmt.node))
+        .map(str::to_string)
+        .collect()
+}
+
+fn declared_name(stmt: &Stmt) -> Option<&str> {
+    match stmt {
+        Stmt::FunDecl(fun_decl) => Some(&fun_decl.name.node),
+        Stmt::StructDecl(struct_decl) => Some(&struct_decl.ident.node),
+        Stmt::TypeAlias(type_alias) => Some(&type_alias.ident.node),
+        _ => None,
+    }
+}
+
+fn literal(value: &Value) -> Option<String> {
+    match value {
+        Value::Int(int) => Some(int.to_string()),
+        Value::Float(float) if float.is_finite() => {
+            let float = float.to_string();
+            Some(if float.contains('.') { float } else { format!("{float}.0") })
+        }
+        Value::String(string) if !string.contains('"') => Some(format!("\"{string}\"")),
+        Value::Bool(bool) => Some(bool.to_string()),
+        Value::Nil => Some("nil".to_string()),
+        Value::Vec(elements) if !elements.borrow().is_empty() => {
+            let elements: Option<Vec<String>> = elements.borrow().iter().map(literal).collect();
+            Some(format!("[{}]", elements?.join(", ")))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,131 @@
+use std::cell::RefCell;
+use std::fmt;
+#[cfg(feature = "sync")]
+use std::ops::{Deref, DerefMut};
+
+/// reference counted pointer for runtime values, `Arc` with the `sync` feature so interpreters are `Send`
+#[cfg(not(feature = "sync"))]
+pub use std::rc::Rc as Shared;
+#[cfg(feature = "sync")]
+pub use std::sync::Arc as Shared;
+
+/// host data behind a foreign value, thread safe with the `sync` feature
+#[cfg(not(feature = "sync"))]
+pub type HostData = dyn std::any::Any;
+#[cfg(feature = "sync")]
+pub type HostData = dyn std::any::Any + Send + Sync;
+
+#[cfg(not(feature = "sync"))]
+type Cell<T> = RefCell<T>;
+/// reentrant so a thread can borrow a value again while it holds it, e.g. printing a vec that contains itself, the
+/// `RefCell` inside checks the borrows like without `sync`
+#[cfg(feature = "sync")]
+type Cell<T> = parking_lot::ReentrantMutex<RefCell<T>>;
+
+#[cfg(not(feature = "sync"))]
+pub type Ref<'a, T> = std::cell::Ref<'a, T>;
+#[cfg(not(feature = "sync"))]
+pub type RefMut<'a, T> = std::cell::RefMut<'a, T>;
+
+/// a borrow of a `Mutable` that holds its lock
+#[cfg(feature = "sync")]
+pub struct Ref<'a, T> {
+    // dropped before the lock
+    borrow: std::cell::Ref<'a, T>,
+    _lock: parking_lot::ReentrantMutexGuard<'a, RefCell<T>>,
+}
+
+#[cfg(feature = "sync")]
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.borrow
+    }
+}
+
+/// a mutable borrow of a `Mutable` that holds its lock
+#[cfg(feature = "sync")]
+pub struct RefMut<'a, T> {
+    // dropped before the lock
+    borrow: std::cell::RefMut<'a, T>,
+    _lock: parking_lot::ReentrantMutexGuard<'a, RefCell<T>>,
+}
+
+#[cfg(feature = "sync")]
+impl<T> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.borrow
+    }
+}
+
+#[cfg(feature = "sync")]
+impl<T> DerefMut for RefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.borrow
+    }
+}
+
+/// interior mutability for vecs, struct fields and captured variables, a `RefCell` or with the `sync` feature a
+/// `RefCell` behind a reentrant mutex
+#[derive(Default)]
+pub struct Mutable<T>(Cell<T>);
+
+impl<T> Mutable<T> {
+    #[cfg(not(feature = "sync"))]
+    pub fn new(value: T) -> Self {
+        Self(Cell::new(value))
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn new(value: T) -> Self {
+        Self(Cell::new(RefCell::new(value)))
+    }
+
+    #[cfg(not(feature = "sync"))]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.0.borrow()
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        let lock = self.0.lock();
+        let borrow = self.locked_cell().borrow();
+        Ref { borrow, _lock: lock }
+    }
+
+    #[cfg(not(feature = "sync"))]
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        self.0.borrow_mut()
+    }
+
+    #[cfg(feature = "sync")]
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        let lock = self.0.lock();
+        let borrow = self.locked_cell().borrow_mut();
+        RefMut { borrow, _lock: lock }
+    }
+
+    /// the cell for as long as `self` lives, only to be used while this thread holds the lock
+    #[cfg(feature = "sync")]
+    fn locked_cell(&self) -> &RefCell<T> {
+        // SAFETY: the cell lives inside `self`, and the guards returned with a borrow of it keep the lock until the
+        // borrow is gone, so no other thread touches the cell meanwhile
+        unsafe { &*self.0.data_ptr() }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Mutable<T> {
+    fn eq(&self, other: &Self) -> bool {
+        // a value is equal to itself even while it is borrowed mutably
+        std::ptr::eq(self, other) || *self.borrow() == *other.borrow()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mutable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.borrow().fmt(f)
+    }
+}
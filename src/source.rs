@@ -0,0 +1,94 @@
+use crate::error::EncodingError;
+use miette::SourceSpan;
+
+const BYTE_ORDER_MARK: &str = "\u{feff}";
+
+/// the text of the file at `path` without a leading byte order mark, files that aren't UTF-8 are read as Latin-1 if
+/// `latin1` is set and are an error naming the first invalid byte otherwise
+pub fn decode(path: &str, bytes: Vec<u8>, latin1: bool) -> Result<String, EncodingError> {
+    match String::from_utf8(bytes) {
+        Ok(text) if text.starts_with(BYTE_ORDER_MARK) => Ok(text[BYTE_ORDER_MARK.len()..].to_string()),
+        Ok(text) => Ok(text),
+        Err(err) if latin1 => Ok(err.as_bytes().iter().map(|&byte| char::from(byte)).collect()),
+        Err(err) => {
+            let offset = err.utf8_error().valid_up_to();
+            Err(EncodingError::InvalidUtf8 {
+                // the replacement characters only come after the label
+                src: String::from_utf8_lossy(err.as_bytes()).into_owned(),
+                span: offset.into(),
+                path: path.to_string(),
+                offset,
+            })
+        }
+    }
+}
+
+/// line and column of the start of `span`, both start at 1 and columns count characters
+pub fn line_column(source: &str, span: SourceSpan) -> (usize, usize) {
+    let offset = clamp(source, span.offset());
+    let line_start = line_start(source, offset);
+    let line = source[..line_start].matches('\n').count() + 1;
+    (line, column(&source[line_start..offset], None))
+}
+
+/// the column after `line_prefix`, the start of a line up to some offset, starting at 1
+///
+/// a tab moves to the next multiple of `tab_width` or counts as one column without one, the `\r` of a `\r\n` isn't
+/// counted so the line break has the same column in files with either line ending
+pub fn column(line_prefix: &str, tab_width: Option<usize>) -> usize {
+    let line_prefix = line_prefix.strip_suffix('\r').unwrap_or(line_prefix);
+    let mut column = 0;
+    for c in line_prefix.chars() {
+        column = match (c, tab_width) {
+            ('\t', Some(width)) if width > 0 => (column / width + 1) * width,
+            _ => column + 1,
+        };
+    }
+    column + 1
+}
+
+/// the line `span` starts on, without its line break
+pub fn line_text(source: &str, span: SourceSpan) -> &str {
+    let offset = clamp(source, span.offset());
+    let start = line_start(source, offset);
+    let end = source[offset..].find('\n').map_or(source.len(), |newline| offset + newline);
+    source[start..end].trim_end_matches('\r')
+}
+
+/// the line `span` starts on with the line number in front and carets under the span, spans over several lines are
+/// underlined to the end of their first line
+///
+/// ```text
+/// 3 | var total = count + ;
+///   |                   ^
+/// ```
+pub fn snippet(source: &str, span: SourceSpan) -> String {
+    let (line, column) = line_column(source, span);
+    let text = line_text(source, span);
+    let number = line.to_string();
+
+    // tabs are kept so the carets line up however the tabs are rendered
+    let padding: String = text.chars().take(column - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let start = clamp(source, span.offset());
+    let end = clamp(source, span.offset() + span.len());
+    let underlined = source[start..end].chars().take_while(|c| *c != '\n').count().max(1);
+
+    format!(
+        "{number} | {text}\n{} | {padding}{}",
+        " ".repeat(number.len()),
+        "^".repeat(underlined)
+    )
+}
+
+/// `offset` moved into `source` and onto a character boundary
+fn clamp(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn line_start(source: &str, offset: usize) -> usize {
+    source[..offset].rfind('\n').map_or(0, |newline| newline + 1)
+}
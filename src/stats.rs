@@ -0,0 +1,118 @@
+use crate::ast::{AstNode, BlockExpr, Expr, Program, Stmt};
+use crate::visitor::{Visitor, walk_block, walk_expr, walk_stmt};
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionStats {
+    pub name: String,
+    pub complexity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProgramStats {
+    pub token_count: usize,
+    pub node_counts: BTreeMap<&'static str, usize>,
+    pub max_depth: usize,
+    pub max_block_nesting: usize,
+    pub functions: Vec<FunctionStats>,
+}
+
+impl ProgramStats {
+    pub fn collect(program: &Program, token_count: usize) -> Self {
+        let mut collector = StatsCollector {
+            stats: ProgramStats {
+                token_count,
+                ..Default::default()
+            },
+            depth: 0,
+            block_nesting: 0,
+            function_stack: vec![],
+        };
+        collector.visit_program(program);
+        collector.stats
+    }
+}
+
+impl fmt::Display for ProgramStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "tokens: {}", self.token_count)?;
+        writeln!(f, "functions: {}", self.functions.len())?;
+        writeln!(f, "max tree depth: {}", self.max_depth)?;
+        writeln!(f, "max block nesting: {}", self.max_block_nesting)?;
+
+        writeln!(f, "nodes:")?;
+        for (kind, count) in &self.node_counts {
+            writeln!(f, "  {kind}: {count}")?;
+        }
+
+        writeln!(f, "cyclomatic complexity:")?;
+        for function in &self.functions {
+            writeln!(f, "  {}: {}", function.name, function.complexity)?;
+        }
+        Ok(())
+    }
+}
+
+struct StatsCollector {
+    stats: ProgramStats,
+    depth: usize,
+    block_nesting: usize,
+    /// indices into `stats.functions` of the functions currently being walked
+    function_stack: Vec<usize>,
+}
+
+impl StatsCollector {
+    fn enter_node(&mut self, kind: &'static str) {
+        *self.stats.node_counts.entry(kind).or_insert(0) += 1;
+        self.depth += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+    }
+
+    fn add_decision_point(&mut self) {
+        if let Some(&index) = self.function_stack.last() {
+            self.stats.functions[index].complexity += 1;
+        }
+    }
+}
+
+impl Visitor for StatsCollector {
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        self.enter_node(stmt.node.kind());
+
+        match &stmt.node {
+            Stmt::FunDecl(fun_decl) => {
+                self.stats.functions.push(FunctionStats {
+                    name: fun_decl.name.node.clone(),
+                    complexity: 1,
+                });
+                self.function_stack.push(self.stats.functions.len() - 1);
+                walk_stmt(self, stmt);
+                self.function_stack.pop();
+            }
+            Stmt::While(_) | Stmt::For(_) => {
+                self.add_decision_point();
+                walk_stmt(self, stmt);
+            }
+            _ => walk_stmt(self, stmt),
+        }
+
+        self.depth -= 1;
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        self.enter_node(expr.node.kind());
+        if matches!(expr.node, Expr::If(_) | Expr::Logical(_)) {
+            self.add_decision_point();
+        }
+        walk_expr(self, expr);
+        self.depth -= 1;
+    }
+
+    fn visit_block(&mut self, block: &BlockExpr) {
+        self.block_nesting += 1;
+        self.stats.max_block_nesting = self.stats.max_block_nesting.max(self.block_nesting);
+        walk_block(self, block);
+        self.block_nesting -= 1;
+    }
+}
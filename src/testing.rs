@@ -0,0 +1,479 @@
+use crate::ast::{
+    AssignExpr, AstNode, BinaryExpr, BinaryOp, BlockExpr, CallExpr, CastExpr, Expr, ExprStmt, FieldAccessExpr, ForStmt, FunDeclStmt, Ident,
+    IfExpr, LambdaExpr, LiteralExpr, LogicalExpr, LogicalOp, MethodCallExpr, PrimitiveType, Program, RecordExpr, ReturnStmt, Stmt,
+    StructDeclStmt, StructInitExpr, TypeAliasStmt, TypedIdent, UnaryExpr, UnaryOp, UnresolvedType, VarDeclStmt, WhileStmt,
+};
+use crate::printer::print_program;
+use crate::type_inferrer::Type;
+use crate::visitor::{VisitorMut, walk_expr_mut};
+use crate::{Lexer, Parser};
+use arbitrary::{Result, Unstructured};
+use miette::{Report, Severity, SourceSpan};
+
+const NAMES: [&str; 5] = ["a", "b", "count", "total", "x"];
+const TYPE_NAMES: [&str; 2] = ["T", "Point"];
+const STRINGS: [&str; 4] = ["a", "total", "say \"hi\"", "C:\\dir\\"];
+/// nesting of statements and expressions, keeps generated programs small
+const MAX_DEPTH: usize = 3;
+const MAX_LENGTH: usize = 4;
+
+/// a random program built from fuzzer input, it parses but doesn't have to type check
+pub fn arbitrary_program(u: &mut Unstructured) -> Result<Program> {
+    let mut generator = Generator { depth: 0 };
+    let statements = generator.list(u, |generator, u| generator.stmt(u))?;
+    Ok(Program {
+        statements,
+        span: span(),
+        next_node_id: 1,
+    })
+}
+
+/// prints `program` and parses the output, the parsed program has to equal `program` apart from node ids, spans and
+/// the parentheses the printer puts around operands
+pub fn check_round_trip(program: &Program) -> std::result::Result<(), String> {
+    let printed = print_program(program);
+    // the lexer expects at least one character
+    let source = format!("{printed} ");
+
+    let mut lexer = Lexer::new(&source);
+    let lex_result = lexer.lex();
+    if let Some(error) = first_error(lex_result.errors) {
+        return Err(format!("the printed program doesn't lex:\n{printed}\n{error:?}"));
+    }
+    let mut parser = Parser::new(lex_result.tokens, source.clone());
+    let parse_result = parser.parse();
+    if let Some(error) = first_error(parse_result.errors) {
+        return Err(format!("the printed program doesn't parse:\n{printed}\n{error:?}"));
+    }
+
+    let expected = shape(program);
+    let parsed = shape(&parse_result.ast);
+    match expected
+        .lines()
+        .zip(parsed.lines())
+        .position(|(expected, parsed)| expected != parsed)
+    {
+        Some(line) => Err(format!(
+            "parsing changed the printed program:\n{printed}\nfirst difference, expected:\n{}\nparsed:\n{}",
+            expected.lines().nth(line).unwrap_or_default(),
+            parsed.lines().nth(line).unwrap_or_default()
+        )),
+        None if expected != parsed => Err(format!("parsing changed the length of the printed program:\n{printed}")),
+        None => Ok(()),
+    }
+}
+
+/// the debug print of the program's normalized statements without node ids and spans
+fn shape(program: &Program) -> String {
+    let mut statements = program.statements.clone();
+    for stmt in &mut statements {
+        Normalizer.visit_stmt_mut(stmt);
+    }
+    let debug = format!("{statements:#?}");
+    let mut lines = debug.lines();
+    let mut shape = String::with_capacity(debug.len());
+    while let Some(line) = lines.next() {
+        let field = line.trim_start();
+        if field.starts_with("node_id: ") {
+            continue;
+        }
+        // `span` and `equal_span`, skipped up to the closing brace of the span
+        if field.ends_with("span: SourceSpan {") {
+            lines.find(|line| line.trim_start().starts_with('}'));
+            continue;
+        }
+        shape.push_str(line);
+        shape.push('\n');
+    }
+    shape
+}
+
+/// drops what printing doesn't keep, groupings are replaced by the expression they contain and numbers forget their
+/// source text
+struct Normalizer;
+
+impl VisitorMut for Normalizer {
+    fn visit_expr_mut(&mut self, expr: &mut AstNode<Expr>) {
+        while let Expr::Grouping(inner) = &expr.node {
+            *expr = (**inner).clone();
+        }
+        match &mut expr.node {
+            Expr::Literal(LiteralExpr::Int(int)) => int.text = None,
+            Expr::Literal(LiteralExpr::Float(float)) => float.text = None,
+            _ => {}
+        }
+        walk_expr_mut(self, expr);
+    }
+}
+
+fn first_error(diagnostics: &[Report]) -> Option<&Report> {
+    diagnostics
+        .iter()
+        .find(|diagnostic| diagnostic.severity().unwrap_or(Severity::Error) == Severity::Error)
+}
+
+fn span() -> SourceSpan {
+    0.into()
+}
+
+/// generated programs are only printed, their nodes share id 0
+fn node<T>(node: T) -> AstNode<T> {
+    AstNode::new(node, span(), 0)
+}
+
+fn boxed(expr: Expr) -> Box<AstNode<Expr>> {
+    Box::new(node(expr))
+}
+
+struct Generator {
+    depth: usize,
+}
+
+impl Generator {
+    fn list<T>(&mut self, u: &mut Unstructured, mut element: impl FnMut(&mut Self, &mut Unstructured) -> Result<T>) -> Result<Vec<T>> {
+        let len = u.int_in_range(0..=MAX_LENGTH)?;
+        (0..len).map(|_| element(self, u)).collect()
+    }
+
+    fn nested<T>(&mut self, u: &mut Unstructured, generate: impl FnOnce(&mut Self, &mut Unstructured) -> Result<T>) -> Result<T> {
+        self.depth += 1;
+        let result = generate(self, u);
+        self.depth -= 1;
+        result
+    }
+
+    fn ident(&mut self, u: &mut Unstructured) -> Result<Ident> {
+        Ok(node(u.choose(&NAMES)?.to_string()))
+    }
+
+    fn ty(&mut self, u: &mut Unstructured) -> Result<UnresolvedType> {
+        let max = if self.depth >= MAX_DEPTH { 1 } else { 3 };
+        Ok(match u.int_in_range(0..=max)? {
+            0 => UnresolvedType::Primitive(
+                u.choose(&[
+                    PrimitiveType::Nil,
+                    PrimitiveType::Int,
+                    PrimitiveType::Float,
+                    PrimitiveType::Bool,
+                    PrimitiveType::String,
+                ])?
+                .clone(),
+            ),
+            1 => UnresolvedType::Named(u.choose(&TYPE_NAMES)?.to_string()),
+            2 => self.nested(u, |generator, u| {
+                Ok(UnresolvedType::Function {
+                    params: generator.list(u, Self::ty)?,
+                    return_type: Box::new(generator.ty(u)?),
+                })
+            })?,
+            _ => self.nested(u, |generator, u| {
+                Ok(UnresolvedType::GenericApplication {
+                    base: Box::new(UnresolvedType::Named("Vec".to_string())),
+                    args: vec![generator.ty(u)?],
+                })
+            })?,
+        })
+    }
+
+    fn typed_idents(&mut self, u: &mut Unstructured) -> Result<Vec<TypedIdent>> {
+        self.list(u, |generator, u| {
+            Ok(TypedIdent {
+                name: generator.ident(u)?,
+                type_annotation: node(generator.ty(u)?),
+            })
+        })
+    }
+
+    fn block(&mut self, u: &mut Unstructured) -> Result<AstNode<BlockExpr>> {
+        self.nested(u, |generator, u| {
+            let statements = generator.list(u, Self::stmt)?;
+            let expr = match u.arbitrary::<bool>()? {
+                true => Some(boxed(generator.expr(u)?)),
+                false => None,
+            };
+            Ok(node(BlockExpr { statements, expr }))
+        })
+    }
+
+    fn stmt(&mut self, u: &mut Unstructured) -> Result<AstNode<Stmt>> {
+        let max = if self.depth >= MAX_DEPTH { 2 } else { 7 };
+        let stmt = match u.int_in_range(0..=max)? {
+            0 => Stmt::VarDecl(self.var_decl(u)?),
+            1 => Stmt::ExprStmtNode(ExprStmt { expr: node(self.expr(u)?) }),
+            2 => Stmt::Return(ReturnStmt {
+                expr: match u.arbitrary::<bool>()? {
+                    true => Some(node(self.expr(u)?)),
+                    false => None,
+                },
+            }),
+            3 => Stmt::While(WhileStmt {
+                condition: node(self.condition(u)?),
+                body: self.block(u)?,
+            }),
+            4 => Stmt::For(ForStmt {
+                initializer: match u.int_in_range(0..=2)? {
+                    0 => None,
+                    1 => Some(Box::new(node(Stmt::VarDecl(self.var_decl(u)?)))),
+                    _ => Some(Box::new(node(Stmt::ExprStmtNode(ExprStmt {
+                        expr: node(self.condition(u)?),
+                    })))),
+                },
+                condition: node(self.condition(u)?),
+                increment: match u.arbitrary::<bool>()? {
+                    true => Some(node(self.condition(u)?)),
+                    false => None,
+                },
+                body: self.block(u)?,
+            }),
+            5 => Stmt::FunDecl(FunDeclStmt {
+                attributes: vec![],
+                name: self.ident(u)?,
+                params: self.typed_idents(u)?,
+                body: self.block(u)?,
+                generics: match u.arbitrary::<bool>()? {
+                    true => vec![node(u.choose(&TYPE_NAMES)?.to_string())],
+                    false => vec![],
+                },
+                return_type: node(self.ty(u)?),
+            }),
+            6 => Stmt::StructDecl(StructDeclStmt {
+                attributes: vec![],
+                ident: node(u.choose(&TYPE_NAMES)?.to_string()),
+                fields: {
+                    let mut fields = self.typed_idents(u)?;
+                    if fields.is_empty() {
+                        fields.push(TypedIdent {
+                            name: self.ident(u)?,
+                            type_annotation: node(self.ty(u)?),
+                        });
+                    }
+                    fields
+                },
+            }),
+            _ => Stmt::TypeAlias(TypeAliasStmt {
+                attributes: vec![],
+                ident: node(u.choose(&TYPE_NAMES)?.to_string()),
+                ty: node(self.ty(u)?),
+            }),
+        };
+        Ok(node(stmt))
+    }
+
+    fn var_decl(&mut self, u: &mut Unstructured) -> Result<VarDeclStmt> {
+        Ok(VarDeclStmt {
+            attributes: vec![],
+            ident: self.ident(u)?,
+            initializer: match u.arbitrary::<bool>()? {
+                true => Some(node(self.expr(u)?)),
+                false => None,
+            },
+            type_annotation: match u.arbitrary::<bool>()? {
+                true => Some(node(self.ty(u)?)),
+                false => None,
+            },
+        })
+    }
+
+    fn atom(&mut self, u: &mut Unstructured) -> Result<Expr> {
+        Ok(match u.int_in_range(0..=6)? {
+            0 => Expr::Literal(LiteralExpr::int(u.int_in_range(0..=1000)?)),
+            1 => Expr::Literal(LiteralExpr::float(u.int_in_range(0..=1000)? as f64 / 4.0)),
+            2 => Expr::Literal(LiteralExpr::String(u.choose(&STRINGS)?.to_string())),
+            3 => Expr::Literal(LiteralExpr::Bool(u.arbitrary::<bool>()?)),
+            4 => Expr::Literal(LiteralExpr::Nil),
+            _ => Expr::Variable(self.ident(u)?),
+        })
+    }
+
+    /// an expression a block can follow, the parser strips parentheses around conditions so there are none
+    fn condition(&mut self, u: &mut Unstructured) -> Result<Expr> {
+        if self.depth >= MAX_DEPTH {
+            return self.atom(u);
+        }
+        self.nested(u, |generator, u| {
+            Ok(match u.int_in_range(0..=4)? {
+                0 | 1 => generator.atom(u)?,
+                2 => generator.binary(u)?,
+                3 => Expr::Logical(LogicalExpr {
+                    left: boxed(generator.expr(u)?),
+                    op: node(u.choose(&[LogicalOp::And, LogicalOp::Or])?.clone()),
+                    right: boxed(generator.expr(u)?),
+                }),
+                _ => generator.call(u)?,
+            })
+        })
+    }
+
+    fn binary(&mut self, u: &mut Unstructured) -> Result<Expr> {
+        let op = u
+            .choose(&[
+                BinaryOp::Plus,
+                BinaryOp::Minus,
+                BinaryOp::Star,
+                BinaryOp::Slash,
+                BinaryOp::Greater,
+                BinaryOp::GreaterEqual,
+                BinaryOp::Less,
+                BinaryOp::LessEqual,
+                BinaryOp::EqualEqual,
+                BinaryOp::BangEqual,
+            ])?
+            .clone();
+        Ok(Expr::Binary(BinaryExpr {
+            left: boxed(self.expr(u)?),
+            op: node(op),
+            right: boxed(self.expr(u)?),
+        }))
+    }
+
+    fn call(&mut self, u: &mut Unstructured) -> Result<Expr> {
+        Ok(Expr::Call(CallExpr {
+            callee: boxed(Expr::Variable(self.ident(u)?)),
+            arguments: self.list(u, |generator, u| Ok(node(generator.expr(u)?)))?,
+        }))
+    }
+
+    fn fields(&mut self, u: &mut Unstructured) -> Result<Vec<(Ident, AstNode<Expr>)>> {
+        let mut fields = self.list(u, |generator, u| Ok((generator.ident(u)?, node(generator.expr(u)?))))?;
+        if fields.is_empty() {
+            fields.push((self.ident(u)?, node(self.atom(u)?)));
+        }
+        Ok(fields)
+    }
+
+    fn expr(&mut self, u: &mut Unstructured) -> Result<Expr> {
+        if self.depth >= MAX_DEPTH {
+            return self.atom(u);
+        }
+        self.nested(u, |generator, u| {
+            Ok(match u.int_in_range(0..=16)? {
+                0..=3 => generator.atom(u)?,
+                4 => Expr::Unary(UnaryExpr {
+                    op: node(u.choose(&[UnaryOp::Bang, UnaryOp::Minus])?.clone()),
+                    expr: boxed(generator.expr(u)?),
+                }),
+                5 => generator.binary(u)?,
+                6 => generator.condition(u)?,
+                7 => Expr::Grouping(boxed(generator.expr(u)?)),
+                8 => Expr::Assign(AssignExpr {
+                    target: generator.ident(u)?,
+                    equal_span: span(),
+                    value: boxed(generator.expr(u)?),
+                }),
+                9 => generator.call(u)?,
+                10 => Expr::Lambda(LambdaExpr {
+                    parameters: generator.typed_idents(u)?,
+                    body: Box::new(generator.block(u)?),
+                    return_type: node(generator.ty(u)?),
+                }),
+                11 => Expr::If(IfExpr {
+                    condition: boxed(generator.condition(u)?),
+                    then_branch: generator.block(u)?,
+                    else_branch: match u.arbitrary::<bool>()? {
+                        true => Some(generator.block(u)?),
+                        false => None,
+                    },
+                }),
+                12 => Expr::MethodCall(MethodCallExpr {
+                    receiver: boxed(generator.expr(u)?),
+                    method: generator.ident(u)?,
+                    arguments: generator.list(u, |generator, u| Ok(node(generator.expr(u)?)))?,
+                }),
+                13 => Expr::FieldAccess(FieldAccessExpr {
+                    receiver: boxed(generator.expr(u)?),
+                    field: generator.ident(u)?,
+                }),
+                14 => Expr::StructInit(StructInitExpr {
+                    name: node(u.choose(&TYPE_NAMES)?.to_string()),
+                    fields: generator.fields(u)?,
+                }),
+                15 => Expr::Record(RecordExpr {
+                    fields: generator.fields(u)?,
+                }),
+                _ => {
+                    let expr = boxed(generator.expr(u)?);
+                    let ty = generator.ty(u)?;
+                    Expr::Cast(CastExpr {
+                        expr,
+                        target: Type::from(&ty),
+                        ty: node(ty),
+                    })
+                }
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// deterministic fuzzer input, a xorshift sequence started from `seed`
+    fn input(seed: u64) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..512)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arbitrary_programs_survive_printing_and_parsing() {
+        for seed in 0..200 {
+            let input = input(seed);
+            let program = arbitrary_program(&mut Unstructured::new(&input)).unwrap();
+            if let Err(error) = check_round_trip(&program) {
+                panic!("seed {seed}: {error}");
+            }
+        }
+    }
+
+    #[test]
+    fn quotes_and_backslashes_in_strings_are_escaped() {
+        let program = Program {
+            statements: vec![node(Stmt::ExprStmtNode(ExprStmt {
+                expr: node(Expr::Literal(LiteralExpr::String(r#"say "hi" to C:\dir\"#.to_string()))),
+            }))],
+            span: span(),
+            next_node_id: 1,
+        };
+        assert_eq!(print_program(&program).trim(), r#""say \"hi\" to C:\\dir\\";"#);
+        assert_eq!(check_round_trip(&program), Ok(()));
+    }
+
+    #[test]
+    fn parentheses_around_operands_are_kept() {
+        let product = Expr::Binary(BinaryExpr {
+            left: boxed(Expr::Grouping(boxed(Expr::Binary(BinaryExpr {
+                left: boxed(Expr::Literal(LiteralExpr::int(1))),
+                op: node(BinaryOp::Plus),
+                right: boxed(Expr::Literal(LiteralExpr::int(2))),
+            })))),
+            op: node(BinaryOp::Star),
+            right: boxed(Expr::Literal(LiteralExpr::int(3))),
+        });
+        let program = Program {
+            statements: vec![node(Stmt::ExprStmtNode(ExprStmt { expr: node(product) }))],
+            span: span(),
+            next_node_id: 1,
+        };
+        assert_eq!(check_round_trip(&program), Ok(()));
+    }
+
+    #[test]
+    fn differences_the_printed_text_hides_are_reported() {
+        // prints as `-1`, which parses into a negation of `1`
+        let program = Program {
+            statements: vec![node(Stmt::ExprStmtNode(ExprStmt {
+                expr: node(Expr::Literal(LiteralExpr::int(-1))),
+            }))],
+            span: span(),
+            next_node_id: 1,
+        };
+        assert!(check_round_trip(&program).is_err());
+    }
+}
@@ -1,13 +1,17 @@
 use crate::MethodRegistry;
 use crate::ast::{
-    AstNode, BinaryOp, BlockExpr, Expr, ExprStmt, FunDeclStmt, LiteralExpr, Program, ReturnStmt, Stmt, StructDeclStmt, UnaryOp,
-    VarDeclStmt, WhileStmt,
+    AstNode, BinaryOp, BlockExpr, Expr, ExprStmt, ForStmt, FunDeclStmt, Ident, LiteralExpr, NodeId, PrimitiveType, Program, ReturnStmt,
+    Stmt, StructDeclStmt, UnaryOp, UnresolvedType, VarDeclStmt, WhileStmt,
+};
+use crate::error::TypeInferrerError;
+use crate::error::TypeInferrerError::{
+    InvalidUnionOperand, NonBooleanCondition, NotCallable, StringConcatenation, TypeExplanation, TypeMismatch, UnionMemberMismatch,
+    UnknownMethod, WrongArgumentCount,
 };
-use crate::error::TypeInferrerError::{NonBooleanCondition, NotCallable, TypeMismatch, UnknownMethod, WrongArgumentCount};
-use crate::error::{ResolverError, TypeInferrerError};
 use crate::type_inferrer::Type::TypeVar;
-use miette::{Report, SourceOffset, SourceSpan};
-use std::collections::{HashMap, HashSet};
+use miette::{LabeledSpan, Report, SourceSpan};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::hash::Hash;
 use std::ops::Deref;
 
@@ -20,27 +24,208 @@ pub enum Type {
     Bool,
     String,
     Nil,
-    Function { params: Vec<Type>, return_ty: Box<Type> },
-    Struct { name: String, fields: Vec<(String, Type)> },
+    Function {
+        params: Vec<Type>,
+        return_ty: Box<Type>,
+    },
+    Struct {
+        name: String,
+        fields: Vec<(String, Type)>,
+    },
+    /// fields sorted by name, records are compared by their fields rather than a name
+    Record(Vec<(String, Type)>),
+    /// the type of a value that is one of several types, e.g. from `if` branches of different types
+    Union(Vec<Type>),
     Vec(Box<Type>),
+    /// an opaque host object, only natives can create and inspect it
+    Foreign(String),
     TypeVar(TypeVarId),
     Generic(String),
 }
 
+/// how deep types are printed by default, deeper parts are elided
+pub const DEFAULT_TYPE_DISPLAY_DEPTH: usize = 8;
+
+impl Type {
+    /// the type as written in source, e.g. `fn(Int, Vec<String>) -> Bool`, see [`TypeDisplay`] for the options
+    pub fn display(&self) -> TypeDisplay<'_> {
+        TypeDisplay {
+            ty: self,
+            type_env: None,
+            max_depth: DEFAULT_TYPE_DISPLAY_DEPTH,
+        }
+    }
+}
+
+/// a type as written in source, names are `Generic` until they are looked up as structs or aliases
+impl From<&UnresolvedType> for Type {
+    fn from(ty: &UnresolvedType) -> Self {
+        match ty {
+            UnresolvedType::Primitive(PrimitiveType::Int) => Type::Int,
+            UnresolvedType::Primitive(PrimitiveType::Float) => Type::Float,
+            UnresolvedType::Primitive(PrimitiveType::Bool) => Type::Bool,
+            UnresolvedType::Primitive(PrimitiveType::String) => Type::String,
+            UnresolvedType::Primitive(PrimitiveType::Nil) => Type::Nil,
+            UnresolvedType::Named(name) => Type::Generic(name.clone()),
+            UnresolvedType::Function { params, return_type } => Type::Function {
+                params: params.iter().map(Type::from).collect(),
+                return_ty: Box::new(Type::from(return_type.as_ref())),
+            },
+            UnresolvedType::GenericApplication { base, args } => match (base.as_ref(), &args[..]) {
+                (UnresolvedType::Named(name), [element]) if name == "Vec" => Type::Vec(Box::new(Type::from(element))),
+                _ => Type::from(base.as_ref()),
+            },
+            UnresolvedType::Record(fields) => Type::Record(fields.iter().map(|(name, ty)| (name.clone(), Type::from(ty))).collect()),
+        }
+    }
+}
+
+/// the parser reads types as [`Type`]s, the tree keeps them unresolved so aliases and struct names are looked up later
+impl From<&Type> for UnresolvedType {
+    fn from(ty: &Type) -> Self {
+        match ty {
+            Type::Int => UnresolvedType::Primitive(PrimitiveType::Int),
+            Type::Float => UnresolvedType::Primitive(PrimitiveType::Float),
+            Type::Bool => UnresolvedType::Primitive(PrimitiveType::Bool),
+            Type::String => UnresolvedType::Primitive(PrimitiveType::String),
+            Type::Nil => UnresolvedType::Primitive(PrimitiveType::Nil),
+            Type::Function { params, return_ty } => UnresolvedType::Function {
+                params: params.iter().map(UnresolvedType::from).collect(),
+                return_type: Box::new(UnresolvedType::from(return_ty.as_ref())),
+            },
+            Type::Vec(element) => UnresolvedType::GenericApplication {
+                base: Box::new(UnresolvedType::Named("Vec".to_string())),
+                args: vec![UnresolvedType::from(element.as_ref())],
+            },
+            Type::Record(fields) => {
+                UnresolvedType::Record(fields.iter().map(|(name, ty)| (name.clone(), UnresolvedType::from(ty))).collect())
+            }
+            Type::Struct { name, .. } | Type::Foreign(name) | Type::Generic(name) => UnresolvedType::Named(name.clone()),
+            Type::Union(_) | Type::TypeVar(_) => UnresolvedType::Named(ty.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.display().fmt(f)
+    }
+}
+
+/// prints a [`Type`], unresolved type variables print as `?0`
+pub struct TypeDisplay<'a> {
+    ty: &'a Type,
+    type_env: Option<&'a HashMap<TypeVarId, Type>>,
+    max_depth: usize,
+}
+
+impl<'a> TypeDisplay<'a> {
+    /// prints type variables as the type they are bound to in `type_env`, a variable bound to a type containing
+    /// itself prints as a variable the second time it is reached
+    pub fn with_type_env(mut self, type_env: &'a HashMap<TypeVarId, Type>) -> Self {
+        self.type_env = Some(type_env);
+        self
+    }
+
+    /// types nested deeper than `depth` print as `..`
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// `resolving` holds the type variables whose bound types are being printed
+    fn write(&self, f: &mut fmt::Formatter<'_>, ty: &Type, depth: usize, resolving: &mut Vec<TypeVarId>) -> fmt::Result {
+        if depth > self.max_depth {
+            return write!(f, "..");
+        }
+
+        match ty {
+            Type::Int => write!(f, "Int"),
+            Type::Float => write!(f, "Float"),
+            Type::Bool => write!(f, "Bool"),
+            Type::String => write!(f, "String"),
+            Type::Nil => write!(f, "Nil"),
+            Type::Function { params, return_ty } => {
+                write!(f, "fn(")?;
+                self.write_list(f, params, ", ", depth, resolving)?;
+                write!(f, ") -> ")?;
+                self.write(f, return_ty, depth + 1, resolving)
+            }
+            Type::Struct { name, .. } => write!(f, "{name}"),
+            Type::Record(fields) => {
+                write!(f, "{{ ")?;
+                for (i, (name, field_ty)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}: ")?;
+                    self.write(f, field_ty, depth + 1, resolving)?;
+                }
+                write!(f, " }}")
+            }
+            Type::Union(members) => self.write_list(f, members, " | ", depth, resolving),
+            Type::Vec(elem_ty) => {
+                write!(f, "Vec<")?;
+                self.write(f, elem_ty, depth + 1, resolving)?;
+                write!(f, ">")
+            }
+            Type::Foreign(name) | Type::Generic(name) => write!(f, "{name}"),
+            TypeVar(id) => match self.type_env.and_then(|type_env| type_env.get(id)) {
+                Some(bound) if !resolving.contains(id) => {
+                    resolving.push(*id);
+                    let written = self.write(f, bound, depth, resolving);
+                    resolving.pop();
+                    written
+                }
+                _ => write!(f, "?{id}"),
+            },
+        }
+    }
+
+    fn write_list(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        types: &[Type],
+        separator: &str,
+        depth: usize,
+        resolving: &mut Vec<TypeVarId>,
+    ) -> fmt::Result {
+        for (i, ty) in types.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{separator}")?;
+            }
+            self.write(f, ty, depth + 1, resolving)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, self.ty, 0, &mut vec![])
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct VarEnv {
-    scopes: Vec<HashMap<String, TypeVarId>>,
+    scopes: Vec<BTreeMap<String, TypeVarId>>,
+}
+
+impl Default for VarEnv {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl VarEnv {
     pub fn new() -> Self {
         Self {
-            scopes: vec![HashMap::new()],
+            scopes: vec![BTreeMap::new()],
         }
     }
 
     pub fn enter_scope(&mut self) {
-        self.scopes.push(HashMap::new());
+        self.scopes.push(BTreeMap::new());
     }
 
     pub fn exit_scope(&mut self) {
@@ -66,9 +251,24 @@ pub struct TypeInferrer<'a> {
     source: String,
     errors: Vec<Report>,
     current_function_return_ty: Option<Type>,
+    truthy_conditions: bool,
+    /// check function bodies after the rest of the top level
+    signatures_first: bool,
+    /// with explanations, the type each type variable was bound to and the span of the unification that bound it
+    bindings: Option<HashMap<TypeVarId, (Type, SourceSpan)>>,
+    /// explanations of the error about to be reported
+    pending_explanations: Vec<Report>,
+    /// functions provided by the host, declared next to the builtins
+    natives: Vec<(String, Type)>,
+    /// aliases are expanded when a type is looked up
+    type_aliases: HashMap<String, Type>,
+    /// functions whose body is being checked for a call, a recursive call doesn't check it again
+    instantiating: HashSet<NodeId>,
     pub var_env: VarEnv,
     pub type_env: HashMap<TypeVarId, Type>,
     method_registry: MethodRegistry,
+    /// type variables that aren't the type of a node count down from the top, node ids count up from 1
+    next_fresh_type_var: TypeVarId,
 }
 
 pub struct TypeInferenceResult<'a> {
@@ -76,6 +276,31 @@ pub struct TypeInferenceResult<'a> {
     pub type_env: &'a HashMap<TypeVarId, Type>,
 }
 
+/// the top-level functions and variables of a checked program with their inferred types
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SymbolTable {
+    pub functions: BTreeMap<String, FunctionSymbol>,
+    pub globals: BTreeMap<String, Type>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSymbol {
+    pub params: Vec<String>,
+    pub ty: Type,
+}
+
+impl SymbolTable {
+    pub fn function(&self, name: &str) -> Option<&FunctionSymbol> {
+        self.functions.get(name)
+    }
+
+    pub fn global(&self, name: &str) -> Option<&Type> {
+        self.globals.get(name)
+    }
+}
+
+// errors hold their source so they render on their own, they are only moved on the way out
+#[allow(clippy::result_large_err)]
 impl<'a> TypeInferrer<'a> {
     pub fn new(ast: &'a Program, source: String) -> Self {
         let method_registry = MethodRegistry::new();
@@ -85,14 +310,54 @@ impl<'a> TypeInferrer<'a> {
             source,
             errors: vec![],
             current_function_return_ty: None,
+            truthy_conditions: false,
+            signatures_first: false,
+            bindings: None,
+            pending_explanations: vec![],
+            natives: vec![],
+            type_aliases: HashMap::new(),
+            instantiating: HashSet::new(),
             var_env: VarEnv::new(),
             type_env: HashMap::new(),
             method_registry,
+            next_fresh_type_var: TypeVarId::MAX,
         }
     }
 
+    /// accepts conditions of any type, evaluated with classic Lox truthiness at runtime
+    pub fn with_truthy_conditions(mut self, enabled: bool) -> Self {
+        self.truthy_conditions = enabled;
+        self
+    }
+
+    /// checks function bodies after every other top-level statement, so a body can use globals declared below it and
+    /// checking a body only depends on the declared signatures of the functions it calls, not on where it is in the file
+    pub fn with_signatures_first(mut self, enabled: bool) -> Self {
+        self.signatures_first = enabled;
+        self
+    }
+
+    /// follows type errors from unification with an advice showing where the involved type variables got their types
+    pub fn with_type_explanations(mut self, enabled: bool) -> Self {
+        self.bindings = enabled.then(HashMap::new);
+        self
+    }
+
+    /// declares a host function with the given function type
+    pub fn with_native(mut self, name: &str, ty: Type) -> Self {
+        self.natives.push((name.to_string(), ty));
+        self
+    }
+
+    /// declares a global variable the host sets with `Interpreter::set_global`
+    pub fn with_global(mut self, name: &str, ty: Type) -> Self {
+        self.natives.push((name.to_string(), ty));
+        self
+    }
+
     fn report(&mut self, error: TypeInferrerError) {
         self.errors.push(error.into());
+        self.errors.append(&mut self.pending_explanations);
     }
     pub fn lookup_type(&mut self, ty: &Type) -> Type {
         match ty {
@@ -109,6 +374,16 @@ impl<'a> TypeInferrer<'a> {
                 let resolved_elem = self.lookup_type(elem_ty);
                 Type::Vec(Box::new(resolved_elem))
             }
+            Type::Union(members) => union(members.iter().map(|member| self.lookup_type(member)).collect()),
+            Type::Generic(name) if self.type_aliases.contains_key(name) => {
+                let aliased = self.type_aliases[name].clone();
+                self.lookup_type(&aliased)
+            }
+            // annotations name structs like any other type
+            Type::Generic(name) => match self.var_env.lookup(name).and_then(|id| self.type_env.get(&id)) {
+                Some(struct_ty @ Type::Struct { .. }) => struct_ty.clone(),
+                _ => ty.clone(),
+            },
             _ => ty.clone(),
         }
     }
@@ -117,7 +392,7 @@ impl<'a> TypeInferrer<'a> {
         let t = self.lookup_type(ty);
 
         match t {
-            Type::Float | Type::Bool | Type::String | Type::Nil | Type::Int => t,
+            Type::Float | Type::Bool | Type::String | Type::Nil | Type::Int | Type::Foreign(_) => t,
             Type::Generic(ref name) => substitutions.get(name).cloned().unwrap_or(t),
             Type::Function { params, return_ty } => {
                 let new_params = params.iter().map(|p| self.substitute(p, substitutions)).collect();
@@ -128,7 +403,14 @@ impl<'a> TypeInferrer<'a> {
                     return_ty: Box::new(new_return),
                 }
             }
-            Type::Struct { name, fields } => todo!(),
+            Type::Struct { .. } => t,
+            Type::Record(fields) => Type::Record(
+                fields
+                    .iter()
+                    .map(|(name, ty)| (name.clone(), self.substitute(ty, substitutions)))
+                    .collect(),
+            ),
+            Type::Union(members) => union(members.iter().map(|member| self.substitute(member, substitutions)).collect()),
             Type::Vec(elem_ty) => {
                 let new_elem = self.substitute(elem_ty.deref(), substitutions);
                 match new_elem {
@@ -153,6 +435,44 @@ impl<'a> TypeInferrer<'a> {
     }
 
     fn unify(&mut self, found: Type, expected: Type, span: SourceSpan) -> Result<Type, TypeInferrerError> {
+        let result = self.unify_types(found.clone(), expected.clone(), span);
+        if result.is_err() && self.bindings.is_some() {
+            self.explain(&[found, expected]);
+        }
+        result
+    }
+
+    /// reports where the type variables in `types` got their types, following variables bound to other variables
+    fn explain(&mut self, types: &[Type]) {
+        let Some(bindings) = &self.bindings else { return };
+        let mut steps = vec![];
+        let mut visited = HashSet::new();
+        for ty in types {
+            let mut ty = ty;
+            while let TypeVar(id) = ty {
+                let Some((bound, span)) = bindings.get(id).filter(|_| visited.insert(*id)) else {
+                    break;
+                };
+                steps.push(LabeledSpan::new_with_span(
+                    Some(format!("`?{id}` is {bound} because of this")),
+                    *span,
+                ));
+                ty = bound;
+            }
+        }
+        if steps.is_empty() {
+            return;
+        }
+
+        // the explanation follows the error it explains
+        let explanation = TypeExplanation {
+            src: self.source.clone(),
+            steps,
+        };
+        self.pending_explanations.push(explanation.into());
+    }
+
+    fn unify_types(&mut self, found: Type, expected: Type, span: SourceSpan) -> Result<Type, TypeInferrerError> {
         let found_ty = self.lookup_type(&found);
         let expected_ty = self.lookup_type(&expected);
 
@@ -162,9 +482,10 @@ impl<'a> TypeInferrer<'a> {
             (Type::String, Type::String) => Ok(Type::String),
             (Type::Bool, Type::Bool) => Ok(Type::Bool),
             (Type::Nil, Type::Nil) => Ok(Type::Nil),
+            (Type::Foreign(tag1), Type::Foreign(tag2)) if tag1 == tag2 => Ok(Type::Foreign(tag1)),
 
             (Type::Vec(elem_ty1), Type::Vec(elem_ty2)) => {
-                let unified_elem = self.unify(*elem_ty1.clone(), *elem_ty2, span)?;
+                let unified_elem = self.unify_types(*elem_ty1.clone(), *elem_ty2, span)?;
                 Ok(Type::Vec(Box::new(unified_elem)))
             }
 
@@ -178,7 +499,7 @@ impl<'a> TypeInferrer<'a> {
                     });
                 }
                 for (field1, field2) in f1.iter().zip(f2.iter()) {
-                    self.unify(field1.1.clone(), field2.1.clone(), span)?;
+                    self.unify_types(field1.1.clone(), field2.1.clone(), span)?;
                 }
                 Ok(Type::Struct { name: name1, fields: f1 })
             }
@@ -193,18 +514,68 @@ impl<'a> TypeInferrer<'a> {
                 }
 
                 for (param1, param2) in p1.iter().zip(p2.iter()) {
-                    self.unify(param1.clone(), param2.clone(), span)?;
+                    self.unify_types(param1.clone(), param2.clone(), span)?;
                 }
 
-                self.unify(*r1.clone(), *r2, span)?;
+                self.unify_types(*r1.clone(), *r2, span)?;
                 Ok(Type::Function { params: p1, return_ty: r1 })
             }
 
+            (Type::Record(found_fields), Type::Record(expected_fields)) => {
+                // width subtyping, a record with more fields can be used where fewer are expected
+                for (name, expected_ty) in &expected_fields {
+                    match found_fields.iter().find(|(found_name, _)| found_name == name) {
+                        Some((_, found_ty)) => {
+                            self.unify_types(found_ty.clone(), expected_ty.clone(), span)?;
+                        }
+                        None => {
+                            return Err(TypeMismatch {
+                                src: self.source.clone(),
+                                span,
+                                expected: Type::Record(expected_fields.clone()),
+                                found: Type::Record(found_fields.clone()),
+                            });
+                        }
+                    }
+                }
+                Ok(Type::Record(expected_fields))
+            }
+
             (ty, TypeVar(id)) | (TypeVar(id), ty) => {
+                if let Some(bindings) = &mut self.bindings {
+                    bindings.insert(id, (ty.clone(), span));
+                }
                 self.type_env.insert(id, ty);
                 Ok(TypeVar(id))
             }
 
+            (found, Type::Union(members)) => {
+                let found_members = match &found {
+                    Type::Union(found_members) => found_members.clone(),
+                    _ => vec![found.clone()],
+                };
+                if found_members.iter().all(|member| members.contains(member)) {
+                    Ok(Type::Union(members))
+                } else {
+                    Err(TypeMismatch {
+                        src: self.source.clone(),
+                        span,
+                        expected: Type::Union(members),
+                        found,
+                    })
+                }
+            }
+            (Type::Union(members), expected) => {
+                let member = members.iter().find(|member| **member != expected).cloned().unwrap();
+                Err(UnionMemberMismatch {
+                    src: self.source.clone(),
+                    span,
+                    union: Type::Union(members),
+                    member,
+                    expected,
+                })
+            }
+
             (t1, t2) => Err(TypeMismatch {
                 src: self.source.clone(),
                 span,
@@ -214,31 +585,69 @@ impl<'a> TypeInferrer<'a> {
         }
     }
 
-    pub fn infer(&mut self) -> TypeInferenceResult {
+    pub fn infer(&mut self) -> TypeInferenceResult<'_> {
         self.declare_native_functions();
 
         for stmt in &self.program.statements {
-            self.declare_stmt(stmt);
+            self.declare_stmt(&stmt.node);
         }
 
-        for stmt in &self.program.statements {
+        let program = self.program;
+        let mut statements: Vec<_> = program.statements.iter().collect();
+        if self.signatures_first {
+            // a stable sort keeps the order within both groups
+            statements.sort_by_key(|stmt| matches!(stmt.node, Stmt::FunDecl(_)));
+        }
+        for stmt in statements {
             if let Err(err) = self.infer_stmt(stmt) {
                 self.report(err);
             }
         }
 
+        // the interpreter matches on the types of expressions, so it must never see a bound type variable
+        let ids: Vec<_> = self.type_env.keys().copied().collect();
+        for id in ids {
+            self.lookup_type(&TypeVar(id));
+        }
+
         TypeInferenceResult {
             errors: &self.errors,
             type_env: &self.type_env,
         }
     }
 
+    /// the diagnostics of `infer`, for keeping them after the type inferrer is gone
+    pub fn into_errors(self) -> Vec<Report> {
+        self.errors
+    }
+
+    /// the program's top-level symbols, call after `infer` so the types are resolved
+    pub fn symbols(&mut self) -> SymbolTable {
+        let mut symbols = SymbolTable::default();
+        for stmt in &self.program.statements {
+            match &stmt.node {
+                Stmt::FunDecl(fun_decl) => {
+                    let ty = self.lookup_type(&TypeVar(fun_decl.name.node_id));
+                    let params = fun_decl.params.iter().map(|param| param.name.node.clone()).collect();
+                    symbols.functions.insert(fun_decl.name.node.clone(), FunctionSymbol { params, ty });
+                }
+                Stmt::VarDecl(var_decl) => {
+                    let Some(&id) = self.var_env.scopes[0].get(&var_decl.ident.node) else {
+                        continue;
+                    };
+                    let ty = self.lookup_type(&TypeVar(id));
+                    symbols.globals.insert(var_decl.ident.node.clone(), ty);
+                }
+                _ => {}
+            }
+        }
+        symbols
+    }
+
     fn fresh_type_var(&mut self) -> TypeVarId {
-        let typed = AstNode::new(
-            LiteralExpr::String("if you see this something is wrong".to_string()),
-            SourceSpan::new(SourceOffset::from(0), 0),
-        );
-        typed.node_id
+        let id = self.next_fresh_type_var;
+        self.next_fresh_type_var -= 1;
+        id
     }
 
     fn declare_native_functions(&mut self) {
@@ -258,106 +667,208 @@ impl<'a> TypeInferrer<'a> {
         let print_type_id = self.fresh_type_var();
         self.type_env.insert(print_type_id, print_type);
         self.var_env.insert("print".to_string(), print_type_id);
+
+        let to_string_type = Type::Function {
+            params: vec![Type::Generic("T".to_string())],
+            return_ty: Box::new(Type::String),
+        };
+        let to_string_type_id = self.fresh_type_var();
+        self.type_env.insert(to_string_type_id, to_string_type);
+        self.var_env.insert("toString".to_string(), to_string_type_id);
+
+        let schedule_type = Type::Function {
+            params: vec![
+                Type::Int,
+                Type::Function {
+                    params: vec![],
+                    return_ty: Box::new(Type::Generic("T".to_string())),
+                },
+            ],
+            return_ty: Box::new(Type::Nil),
+        };
+        let schedule_type_id = self.fresh_type_var();
+        self.type_env.insert(schedule_type_id, schedule_type);
+        self.var_env.insert("schedule".to_string(), schedule_type_id);
+
+        let run_event_loop_type = Type::Function {
+            params: vec![],
+            return_ty: Box::new(Type::Nil),
+        };
+        let run_event_loop_type_id = self.fresh_type_var();
+        self.type_env.insert(run_event_loop_type_id, run_event_loop_type);
+        self.var_env.insert("runEventLoop".to_string(), run_event_loop_type_id);
+
+        let type_of_type = Type::Function {
+            params: vec![Type::Generic("T".to_string())],
+            return_ty: Box::new(Type::String),
+        };
+        let type_of_type_id = self.fresh_type_var();
+        self.type_env.insert(type_of_type_id, type_of_type);
+        self.var_env.insert("typeOf".to_string(), type_of_type_id);
+
+        for (name, _) in TYPE_PREDICATES {
+            let predicate_type = Type::Function {
+                params: vec![Type::Generic("T".to_string())],
+                return_ty: Box::new(Type::Bool),
+            };
+            let predicate_type_id = self.fresh_type_var();
+            self.type_env.insert(predicate_type_id, predicate_type);
+            self.var_env.insert(name.to_string(), predicate_type_id);
+        }
+
+        // times and durations are milliseconds, so they combine with the usual Int arithmetic
+        for (name, params, return_ty) in [
+            ("now", vec![], Type::Int),
+            ("formatTime", vec![Type::Int, Type::String], Type::String),
+            ("parseTime", vec![Type::String, Type::String], Type::Int),
+            ("seconds", vec![Type::Int], Type::Int),
+            ("minutes", vec![Type::Int], Type::Int),
+            ("hours", vec![Type::Int], Type::Int),
+            ("days", vec![Type::Int], Type::Int),
+        ] {
+            let type_id = self.fresh_type_var();
+            self.type_env.insert(
+                type_id,
+                Type::Function {
+                    params,
+                    return_ty: Box::new(return_ty),
+                },
+            );
+            self.var_env.insert(name.to_string(), type_id);
+        }
+
+        #[cfg(feature = "regex")]
+        for (name, params, return_ty) in [
+            ("matches", 2, Type::Bool),
+            ("findAll", 2, Type::Vec(Box::new(Type::String))),
+            ("replaceAll", 3, Type::String),
+        ] {
+            let type_id = self.fresh_type_var();
+            let params = vec![Type::String; params];
+            self.type_env.insert(
+                type_id,
+                Type::Function {
+                    params,
+                    return_ty: Box::new(return_ty),
+                },
+            );
+            self.var_env.insert(name.to_string(), type_id);
+        }
+
+        for (name, ty) in self.natives.clone() {
+            let type_id = self.fresh_type_var();
+            self.type_env.insert(type_id, ty);
+            self.var_env.insert(name, type_id);
+        }
     }
 
     fn declare_stmt(&mut self, stmt: &Stmt) {
         match stmt {
             Stmt::FunDecl(fun_decl) => {
-                let name = &fun_decl.node.ident.node;
+                let name = &fun_decl.name.node;
 
                 let fn_type = Type::Function {
-                    params: fun_decl.node.params.iter().map(|p| p.type_annotation.node.clone()).collect(),
-                    return_ty: Box::new(fun_decl.node.return_type.node.clone()),
+                    params: fun_decl.params.iter().map(|p| Type::from(&p.type_annotation.node)).collect(),
+                    return_ty: Box::new(Type::from(&fun_decl.return_type.node)),
                 };
 
-                self.type_env.insert(fun_decl.node.ident.node_id, fn_type);
-                self.var_env.insert(name.clone(), fun_decl.node.ident.node_id);
+                self.type_env.insert(fun_decl.name.node_id, fn_type);
+                self.var_env.insert(name.clone(), fun_decl.name.node_id);
+            }
+            Stmt::TypeAlias(type_alias) => {
+                self.type_aliases
+                    .insert(type_alias.ident.node.clone(), Type::from(&type_alias.ty.node));
             }
             _ => {}
         }
     }
 
-    fn infer_stmt(&mut self, stmt: &Stmt) -> Result<(), TypeInferrerError> {
-        match stmt {
+    fn infer_stmt(&mut self, stmt: &AstNode<Stmt>) -> Result<(), TypeInferrerError> {
+        match &stmt.node {
             Stmt::ExprStmtNode(expr_stmt) => self.infer_expr_stmt(expr_stmt),
-            Stmt::VarDecl(var_decl) => self.infer_var_decl(var_decl),
+            Stmt::VarDecl(var_decl) => self.infer_var_decl(var_decl, stmt.span),
             Stmt::FunDecl(fun_decl) => self.infer_fun_decl(fun_decl),
-            Stmt::StructDecl(struct_decl) => self.infer_struct_decl(struct_decl),
+            Stmt::StructDecl(struct_decl) => self.infer_struct_decl(struct_decl, stmt.node_id),
+            Stmt::TypeAlias(type_alias) => {
+                self.type_aliases
+                    .insert(type_alias.ident.node.clone(), Type::from(&type_alias.ty.node));
+                Ok(())
+            }
             Stmt::While(while_stmt) => self.infer_while_stmt(while_stmt),
-            Stmt::Return(return_stmt) => self.infer_return_stmt(return_stmt),
+            Stmt::For(for_stmt) => self.infer_for_stmt(for_stmt),
+            Stmt::Return(return_stmt) => self.infer_return_stmt(return_stmt, stmt.span),
         }
     }
 
-    fn infer_expr_stmt(&mut self, expr_stmt: &AstNode<ExprStmt>) -> Result<(), TypeInferrerError> {
-        self.infer_expr(&expr_stmt.node.expr)?;
+    fn infer_expr_stmt(&mut self, expr_stmt: &ExprStmt) -> Result<(), TypeInferrerError> {
+        self.infer_expr(&expr_stmt.expr)?;
         Ok(())
     }
 
-    fn infer_var_decl(&mut self, var_decl: &AstNode<VarDeclStmt>) -> Result<(), TypeInferrerError> {
-        let var_decl_id = var_decl.node.ident.node_id.clone();
-        self.var_env.insert(var_decl.node.ident.node.clone(), var_decl_id);
+    fn infer_var_decl(&mut self, var_decl: &VarDeclStmt, span: SourceSpan) -> Result<(), TypeInferrerError> {
+        let var_decl_id = var_decl.ident.node_id;
+        self.var_env.insert(var_decl.ident.node.clone(), var_decl_id);
 
-        if let Some(type_annotation) = &var_decl.node.type_annotation {
-            self.type_env.insert(var_decl_id, type_annotation.node.clone());
+        if let Some(type_annotation) = &var_decl.type_annotation {
+            self.type_env.insert(var_decl_id, Type::from(&type_annotation.node));
         }
-        if let Some(init) = &var_decl.node.initializer {
+        if let Some(init) = &var_decl.initializer {
             let init_type = match &init.node {
                 Expr::Literal(LiteralExpr::VecLiteral(elements)) if elements.is_empty() => {
-                    if let Some(type_annotation) = &var_decl.node.type_annotation {
-                        type_annotation.node.clone()
+                    if let Some(type_annotation) = &var_decl.type_annotation {
+                        Type::from(&type_annotation.node)
                     } else {
                         return Err(TypeInferrerError::CannotInferType {
                             src: self.source.clone(),
-                            span: var_decl.span,
+                            span,
                             name: "Vec".to_string(),
                         });
                     }
                 }
                 _ => self.infer_expr(init)?,
             };
-            self.unify(TypeVar(var_decl_id), init_type, var_decl.node.ident.span)?;
+            self.unify(TypeVar(var_decl_id), init_type, var_decl.ident.span)?;
         }
 
         Ok(())
     }
 
-    fn infer_fun_decl(&mut self, fun_decl: &AstNode<FunDeclStmt>) -> Result<(), TypeInferrerError> {
-        let name = &fun_decl.node.name.node;
+    fn infer_fun_decl(&mut self, fun_decl: &FunDeclStmt) -> Result<(), TypeInferrerError> {
+        let name = &fun_decl.name.node;
 
         let fn_type = Type::Function {
-            params: fun_decl.node.params.iter().map(|p| p.type_annotation.node.clone()).collect(),
-            return_ty: Box::new(fun_decl.node.return_type.node.clone()),
+            params: fun_decl.params.iter().map(|p| Type::from(&p.type_annotation.node)).collect(),
+            return_ty: Box::new(Type::from(&fun_decl.return_type.node)),
         };
 
-        self.type_env.insert(fun_decl.node.name.node_id, fn_type);
-        self.var_env.insert(name.clone(), fun_decl.node.name.node_id);
+        self.type_env.insert(fun_decl.name.node_id, fn_type);
+        self.var_env.insert(name.clone(), fun_decl.name.node_id);
 
-        if fun_decl.node.generics.is_empty() {
+        if fun_decl.generics.is_empty() {
             self.var_env.enter_scope();
 
-            for param in &fun_decl.node.params {
+            for param in &fun_decl.params {
                 let param_id = param.name.node_id;
-                self.type_env.insert(param_id, param.type_annotation.node.clone());
+                self.type_env.insert(param_id, Type::from(&param.type_annotation.node));
                 self.var_env.insert(param.name.node.clone(), param_id);
             }
 
             let old_ret_ty = self.current_function_return_ty.clone();
-            self.current_function_return_ty = Some(fun_decl.node.return_type.node.clone());
+            self.current_function_return_ty = Some(Type::from(&fun_decl.return_type.node));
 
-            self.infer_stmts(&fun_decl.node.body.node.statements)?;
+            // the tail expression sees the body's locals
+            let body_ty = self.infer_block_expr(&fun_decl.body.node)?;
 
-            if let Some(expr) = &fun_decl.node.body.node.expr {
-                let body_ty = self.infer_expr(expr)?;
-                self.unify(fun_decl.node.return_type.node.clone(), body_ty, fun_decl.node.name.span)?;
+            if fun_decl.body.node.expr.is_some() {
+                self.unify(Type::from(&fun_decl.return_type.node), body_ty, fun_decl.name.span)?;
             } else if !fun_decl
-                .node
                 .body
                 .node
                 .statements
                 .iter()
-                .any(|stmt| matches!(stmt, Stmt::Return(_)))
+                .any(|stmt| matches!(stmt.node, Stmt::Return(_)))
             {
-                self.unify(fun_decl.node.return_type.node.clone(), Type::Nil, fun_decl.node.return_type.span)?;
+                self.unify(Type::from(&fun_decl.return_type.node), Type::Nil, fun_decl.return_type.span)?;
             }
 
             self.current_function_return_ty = old_ret_ty;
@@ -366,9 +877,9 @@ impl<'a> TypeInferrer<'a> {
         Ok(())
     }
 
-    fn infer_struct_decl(&mut self, struct_decl: &AstNode<StructDeclStmt>) -> Result<(), TypeInferrerError> {
+    fn infer_struct_decl(&mut self, struct_decl: &StructDeclStmt, node_id: NodeId) -> Result<(), TypeInferrerError> {
         let mut seen_fields = HashSet::new();
-        for field in &struct_decl.node.fields {
+        for field in &struct_decl.fields {
             if !seen_fields.insert(field.name.node.clone()) {
                 self.report(TypeInferrerError::DuplicateFieldDeclaration {
                     src: self.source.clone(),
@@ -379,21 +890,20 @@ impl<'a> TypeInferrer<'a> {
         }
 
         let struct_type = Type::Struct {
-            name: struct_decl.node.ident.node.clone(),
+            name: struct_decl.ident.node.clone(),
             fields: struct_decl
-                .node
                 .fields
                 .iter()
-                .map(|f| (f.name.node.clone(), f.type_annotation.node.clone()))
+                .map(|f| (f.name.node.clone(), Type::from(&f.type_annotation.node)))
                 .collect(),
         };
 
-        self.type_env.insert(struct_decl.node_id, struct_type);
-        self.var_env.insert(struct_decl.node.ident.node.clone(), struct_decl.node_id);
+        self.type_env.insert(node_id, struct_type);
+        self.var_env.insert(struct_decl.ident.node.clone(), node_id);
         Ok(())
     }
 
-    fn infer_stmts(&mut self, stmts: &Vec<Stmt>) -> Result<(), TypeInferrerError> {
+    fn infer_stmts(&mut self, stmts: &[AstNode<Stmt>]) -> Result<(), TypeInferrerError> {
         self.var_env.enter_scope();
 
         for stmt in stmts {
@@ -422,24 +932,80 @@ impl<'a> TypeInferrer<'a> {
         return_ty
     }
 
-    fn infer_while_stmt(&mut self, while_stmt: &AstNode<WhileStmt>) -> Result<(), TypeInferrerError> {
-        let condition_ty = self.infer_expr(&while_stmt.node.condition)?;
+    /// infers a branch with `narrowing`'s variable restricted to the given type
+    fn infer_narrowed_block(&mut self, block: &BlockExpr, narrowing: Option<(String, Type)>) -> Result<Type, TypeInferrerError> {
+        let Some((name, ty)) = narrowing else {
+            return self.infer_block_expr(block);
+        };
+        self.var_env.enter_scope();
+        let narrowed_id = self.fresh_type_var();
+        self.type_env.insert(narrowed_id, ty);
+        self.var_env.insert(name, narrowed_id);
+        let return_ty = self.infer_block_expr(block);
+        self.var_env.exit_scope();
+        return_ty
+    }
+
+    /// a type test on a variable of a union type, the variable's type in the then and else branch
+    fn narrowing(&mut self, condition: &AstNode<Expr>) -> Option<(String, Type, Type)> {
+        let (variable, type_names, negated) = type_test(condition)?;
+
+        let var_id = self.var_env.lookup(&variable.node)?;
+        let Type::Union(members) = self.lookup_type(&TypeVar(var_id)) else {
+            return None;
+        };
+        let (matching, rest): (Vec<Type>, Vec<Type>) = members
+            .into_iter()
+            .partition(|member| runtime_name(member).is_some_and(|name| type_names.contains(&name)));
+        if matching.is_empty() || rest.is_empty() {
+            return None;
+        }
+
+        let (then_ty, else_ty) = if negated {
+            (union(rest), union(matching))
+        } else {
+            (union(matching), union(rest))
+        };
+        Some((variable.node.clone(), then_ty, else_ty))
+    }
+
+    fn infer_while_stmt(&mut self, while_stmt: &WhileStmt) -> Result<(), TypeInferrerError> {
+        self.infer_condition(&while_stmt.condition)?;
+        self.infer_stmts(&while_stmt.body.node.statements)?;
+
+        Ok(())
+    }
+
+    fn infer_for_stmt(&mut self, for_stmt: &ForStmt) -> Result<(), TypeInferrerError> {
+        if let Some(initializer) = &for_stmt.initializer {
+            self.infer_stmt(initializer)?;
+        }
+        self.infer_condition(&for_stmt.condition)?;
+        self.infer_stmts(&for_stmt.body.node.statements)?;
+        if let Some(increment) = &for_stmt.increment {
+            self.infer_expr(increment)?;
+        }
+
+        Ok(())
+    }
+
+    /// conditions must be `Bool` unless classic truthiness is enabled
+    fn infer_condition(&mut self, condition: &AstNode<Expr>) -> Result<(), TypeInferrerError> {
+        let condition_ty = self.infer_expr(condition)?;
 
         match self.lookup_type(&condition_ty) {
             Type::Bool => Ok(()),
+            _ if self.truthy_conditions => Ok(()),
             found => Err(NonBooleanCondition {
                 src: self.source.clone(),
-                span: while_stmt.node.condition.span,
+                span: condition.span,
                 found,
             }),
-        }?;
-        self.infer_stmts(&while_stmt.node.body.node.statements)?;
-
-        Ok(())
+        }
     }
 
-    fn infer_return_stmt(&mut self, return_stmt: &AstNode<ReturnStmt>) -> Result<(), TypeInferrerError> {
-        if let Some(ret_expr) = &return_stmt.node.expr {
+    fn infer_return_stmt(&mut self, return_stmt: &ReturnStmt, span: SourceSpan) -> Result<(), TypeInferrerError> {
+        if let Some(ret_expr) = &return_stmt.expr {
             let ret_id = self.infer_expr(ret_expr)?;
             let ret_ty = self.lookup_type(&ret_id);
 
@@ -449,7 +1015,7 @@ impl<'a> TypeInferrer<'a> {
         } else {
             let ret_ty = Type::Nil;
             if let Some(expected_ty) = &self.current_function_return_ty {
-                self.unify(ret_ty, expected_ty.clone(), return_stmt.span)?;
+                self.unify(ret_ty, expected_ty.clone(), span)?;
             }
         }
 
@@ -466,23 +1032,77 @@ impl<'a> TypeInferrer<'a> {
                     substitutions.insert(name.clone(), arg_ty.clone());
                 }
             }
-            (Type::Generic(name), _) => {
+            (
+                Type::Function { params, return_ty },
+                Type::Function {
+                    params: arg_params,
+                    return_ty: arg_return_ty,
+                },
+            ) => {
+                for (param, arg_param) in params.iter().zip(arg_params) {
+                    self.collect_substitutions(param, arg_param, substitutions);
+                }
+                self.collect_substitutions(return_ty, arg_return_ty, substitutions);
+            }
+            (Type::Generic(name), _) if !self.type_aliases.contains_key(name) => {
                 substitutions.insert(name.clone(), arg_ty.clone());
             }
             _ => {}
         }
     }
 
+    /// span of the name of the top-level function `callee` refers to, the note on arity mismatches
+    fn declaration_span(&self, callee: &Expr) -> Option<SourceSpan> {
+        let Expr::Variable(var) = callee else { return None };
+        self.program.statements.iter().find_map(|stmt| match &stmt.node {
+            Stmt::FunDecl(fd) if fd.name.node == var.node => Some(fd.name.span),
+            _ => None,
+        })
+    }
+
+    /// checks the body of `fd` with its generic parameters replaced by the types of a call's arguments
+    fn instantiate_function(
+        &mut self,
+        fd: &FunDeclStmt,
+        params: &[Type],
+        substitutions: &HashMap<String, Type>,
+    ) -> Result<(), TypeInferrerError> {
+        for (param, param_ty) in fd.params.iter().zip(params.iter()) {
+            let substituted_ty = self.substitute(param_ty, substitutions);
+            self.type_env.insert(param.name.node_id, substituted_ty);
+            self.var_env.insert(param.name.node.clone(), param.name.node_id);
+        }
+
+        let substituted_return = self.substitute(&Type::from(&fd.return_type.node), substitutions);
+        let old_return_ty = self.current_function_return_ty.replace(substituted_return);
+        let checked = self.infer_function_body(fd);
+        self.current_function_return_ty = old_return_ty;
+        checked
+    }
+
+    fn infer_function_body(&mut self, fd: &FunDeclStmt) -> Result<(), TypeInferrerError> {
+        let body_ty = self.infer_block_expr(&fd.body.node)?;
+
+        if fd.body.node.expr.is_some() {
+            self.unify(Type::from(&fd.return_type.node), body_ty, fd.name.span)?;
+        } else if !fd.body.node.statements.iter().any(|stmt| matches!(stmt.node, Stmt::Return(_))) {
+            self.unify(Type::Nil, Type::from(&fd.return_type.node), fd.return_type.span)?;
+        }
+        Ok(())
+    }
+
     fn handle_parameters(
         &mut self,
-        params: &Vec<Type>,
-        args: &Vec<AstNode<Expr>>,
+        params: &[Type],
+        args: &[AstNode<Expr>],
         span: SourceSpan,
+        declaration: Option<SourceSpan>,
     ) -> Result<HashMap<String, Type>, TypeInferrerError> {
         if params.len() != args.len() {
             return Err(WrongArgumentCount {
                 src: self.source.clone(),
                 span,
+                declaration,
                 expected: params.len(),
                 found: args.len(),
             });
@@ -490,15 +1110,16 @@ impl<'a> TypeInferrer<'a> {
 
         let mut substitutions: HashMap<String, Type> = HashMap::new();
 
+        // each argument is inferred once, inferring it again would report its warnings twice
+        let mut arg_tys = vec![];
         for (arg, param_ty) in args.iter().zip(params.iter()) {
             let arg_ty = self.infer_expr(arg)?;
             let arg_ty = self.lookup_type(&arg_ty);
             self.collect_substitutions(param_ty, &arg_ty, &mut substitutions);
+            arg_tys.push(arg_ty);
         }
 
-        for (arg, param_ty) in args.iter().zip(params.iter()) {
-            let arg_ty = self.infer_expr(arg)?;
-            let arg_ty = self.lookup_type(&arg_ty);
+        for ((arg, param_ty), arg_ty) in args.iter().zip(params.iter()).zip(arg_tys) {
             let substituted = self.substitute(param_ty, &substitutions);
             self.unify(arg_ty, substituted, arg.span)?;
         }
@@ -506,7 +1127,93 @@ impl<'a> TypeInferrer<'a> {
         Ok(substitutions)
     }
 
+    fn is_literal_zero(expr: &AstNode<Expr>) -> bool {
+        match &expr.node {
+            Expr::Literal(LiteralExpr::Int(int)) => int.value == 0,
+            Expr::Literal(LiteralExpr::Float(float)) => float.value == 0.0,
+            Expr::Grouping(inner) => Self::is_literal_zero(inner),
+            Expr::Unary(unary) if unary.op.node == UnaryOp::Minus => Self::is_literal_zero(&unary.expr),
+            _ => false,
+        }
+    }
+
+    /// the value of an expression made of literals and operators, `None` when it isn't one or evaluating it fails
+    fn constant(expr: &AstNode<Expr>) -> Option<Constant> {
+        match &expr.node {
+            Expr::Literal(LiteralExpr::Int(int)) => Some(Constant::Int(int.value)),
+            Expr::Literal(LiteralExpr::Float(float)) => Some(Constant::Float(float.value)),
+            Expr::Literal(LiteralExpr::Bool(bool)) => Some(Constant::Bool(*bool)),
+            Expr::Grouping(inner) => Self::constant(inner),
+            Expr::Unary(unary) => match (&unary.op.node, Self::constant(&unary.expr)?) {
+                (UnaryOp::Minus, Constant::Int(int)) => int.checked_neg().map(Constant::Int),
+                (UnaryOp::Minus, Constant::Float(float)) => Some(Constant::Float(-float)),
+                (UnaryOp::Bang, Constant::Bool(bool)) => Some(Constant::Bool(!bool)),
+                _ => None,
+            },
+            Expr::Binary(binary) => {
+                let op = &binary.op.node;
+                match (Self::constant(&binary.left)?, Self::constant(&binary.right)?) {
+                    (Constant::Int(left), Constant::Int(right)) => match op {
+                        BinaryOp::Plus => left.checked_add(right).map(Constant::Int),
+                        BinaryOp::Minus => left.checked_sub(right).map(Constant::Int),
+                        BinaryOp::Star => left.checked_mul(right).map(Constant::Int),
+                        BinaryOp::Slash => left.checked_div(right).map(Constant::Int),
+                        _ => compare(op, left, right).map(Constant::Bool),
+                    },
+                    (Constant::Float(left), Constant::Float(right)) => match op {
+                        BinaryOp::Plus => Some(Constant::Float(left + right)),
+                        BinaryOp::Minus => Some(Constant::Float(left - right)),
+                        BinaryOp::Star => Some(Constant::Float(left * right)),
+                        BinaryOp::Slash => Some(Constant::Float(left / right)),
+                        _ => compare(op, left, right).map(Constant::Bool),
+                    },
+                    (Constant::Bool(left), Constant::Bool(right)) => compare(op, left, right).map(Constant::Bool),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// `get` on a vec literal with a constant index past its end
+    fn check_literal_index(&mut self, span: SourceSpan, receiver: &AstNode<Expr>, arguments: &[AstNode<Expr>]) {
+        let (Expr::Literal(LiteralExpr::VecLiteral(elements)), [index]) = (&receiver.node, arguments) else {
+            return;
+        };
+        let Some(Constant::Int(index)) = Self::constant(index) else {
+            return;
+        };
+        if usize::try_from(index).is_ok_and(|index| index < elements.len()) {
+            return;
+        }
+        self.report(TypeInferrerError::LiteralIndexOutOfRange {
+            src: self.source.clone(),
+            span,
+            index,
+            length: elements.len(),
+        });
+    }
+
+    fn string_concatenation_error(&self, operand: &AstNode<Expr>, found: Type) -> TypeInferrerError {
+        let start = operand.span.offset();
+        StringConcatenation {
+            src: self.source.clone(),
+            span: operand.span,
+            found,
+            operand: self.source[start..start + operand.span.len()].to_string(),
+        }
+    }
+
     fn infer_expr(&mut self, expr: &AstNode<Expr>) -> Result<Type, TypeInferrerError> {
+        let ty = self.infer_expr_kind(expr)?;
+        // expressions that are typed through another node, like variables, are recorded under their own id too
+        if ty != TypeVar(expr.node_id) {
+            self.type_env.entry(expr.node_id).or_insert_with(|| ty.clone());
+        }
+        Ok(ty)
+    }
+
+    fn infer_expr_kind(&mut self, expr: &AstNode<Expr>) -> Result<Type, TypeInferrerError> {
         match &expr.node {
             Expr::FieldAssign(field_assign) => {
                 let receiver_ty = self.infer_expr(&field_assign.receiver)?;
@@ -529,6 +1236,21 @@ impl<'a> TypeInferrer<'a> {
                             })
                         }
                     }
+                    Type::Record(fields) => {
+                        if let Some((_, field_ty)) = fields.iter().find(|(name, _)| *name == field_assign.field.node) {
+                            self.unify(value_ty, field_ty.clone(), field_assign.value.span)?;
+
+                            self.type_env.insert(expr.node_id, field_ty.clone());
+                            Ok(TypeVar(expr.node_id))
+                        } else {
+                            Err(TypeInferrerError::UnknownField {
+                                src: self.source.clone(),
+                                span: field_assign.field.span,
+                                field: field_assign.field.node.clone(),
+                                struct_name: "record".to_string(),
+                            })
+                        }
+                    }
                     found => Err(TypeMismatch {
                         src: self.source.clone(),
                         span: field_assign.receiver.span,
@@ -558,6 +1280,19 @@ impl<'a> TypeInferrer<'a> {
                             })
                         }
                     }
+                    Type::Record(fields) => {
+                        if let Some((_, field_ty)) = fields.iter().find(|(name, _)| *name == field_access.field.node) {
+                            self.type_env.insert(expr.node_id, field_ty.clone());
+                            Ok(TypeVar(expr.node_id))
+                        } else {
+                            Err(TypeInferrerError::UnknownField {
+                                src: self.source.clone(),
+                                span: field_access.field.span,
+                                field: field_access.field.node.clone(),
+                                struct_name: "record".to_string(),
+                            })
+                        }
+                    }
                     found => Err(TypeMismatch {
                         src: self.source.clone(),
                         span: field_access.receiver.span,
@@ -574,7 +1309,7 @@ impl<'a> TypeInferrer<'a> {
                 let struct_type = self.lookup_type(&TypeVar(struct_type_id));
 
                 if let Type::Struct { name: _, fields } = struct_type.clone() {
-                    let struct_fields: HashMap<String, Type> = fields.into_iter().map(|(name, ty)| (name, ty)).collect();
+                    let struct_fields: HashMap<String, Type> = fields.iter().cloned().collect();
                     let mut seen_fields = HashSet::new();
 
                     for (field_name, _) in &struct_init.fields {
@@ -602,7 +1337,8 @@ impl<'a> TypeInferrer<'a> {
                         self.unify(actual_type, expected_type.clone(), field_value.span)?;
                     }
 
-                    for (field_name, _) in struct_fields {
+                    // declaration order keeps the diagnostics reproducible
+                    for (field_name, _) in fields {
                         if !seen_fields.contains(&field_name) {
                             self.report(TypeInferrerError::MissingField {
                                 src: self.source.clone(),
@@ -617,6 +1353,50 @@ impl<'a> TypeInferrer<'a> {
                 self.type_env.insert(expr.node_id, struct_type.clone());
                 Ok(TypeVar(expr.node_id))
             }
+            Expr::Record(record) => {
+                let mut fields = BTreeMap::new();
+                for (field_name, value) in &record.fields {
+                    let value_ty = self.infer_expr(value)?;
+                    if fields.insert(field_name.node.clone(), value_ty).is_some() {
+                        self.report(TypeInferrerError::DuplicateFieldInstantiation {
+                            src: self.source.clone(),
+                            span: field_name.span,
+                            name: field_name.node.clone(),
+                        });
+                    }
+                }
+
+                self.type_env.insert(expr.node_id, Type::Record(fields.into_iter().collect()));
+                Ok(TypeVar(expr.node_id))
+            }
+            Expr::Cast(cast) => {
+                let from = self.infer_expr(&cast.expr)?;
+                let from = self.lookup_type(&from);
+                let to = self.lookup_type(&cast.target);
+
+                let convertible = |ty: &Type| matches!(ty, Type::Int | Type::Float | Type::String | Type::Bool);
+                match &from {
+                    _ if from == to => self.report(TypeInferrerError::PointlessCast {
+                        src: self.source.clone(),
+                        span: expr.span,
+                        ty: to.clone(),
+                    }),
+                    _ if convertible(&from) && convertible(&to) => {}
+                    // checked at runtime
+                    Type::Union(members) if members.contains(&to) => {}
+                    _ => {
+                        return Err(TypeInferrerError::InvalidCast {
+                            src: self.source.clone(),
+                            span: expr.span,
+                            from,
+                            to,
+                        });
+                    }
+                }
+
+                self.type_env.insert(expr.node_id, to);
+                Ok(TypeVar(expr.node_id))
+            }
             Expr::Literal(literal_expr) => {
                 let ty = match literal_expr {
                     LiteralExpr::Int(_) => Type::Int,
@@ -650,31 +1430,44 @@ impl<'a> TypeInferrer<'a> {
             Expr::Block(block) => self.infer_block_expr(block),
 
             Expr::If(if_expr) => {
-                let condition_ty = self.infer_expr(&if_expr.condition)?;
+                self.infer_condition(&if_expr.condition)?;
 
-                match self.lookup_type(&condition_ty) {
-                    Type::Bool => Ok(()),
-                    found => Err(NonBooleanCondition {
-                        src: self.source.clone(),
-                        span: if_expr.condition.span,
-                        found,
-                    }),
-                }?;
+                let narrowing = self.narrowing(&if_expr.condition);
+                let (then_narrowing, else_narrowing) = match narrowing {
+                    Some((name, then_ty, else_ty)) => (Some((name.clone(), then_ty)), Some((name, else_ty))),
+                    None => (None, None),
+                };
 
-                let then_return_ty = self.infer_block_expr(&if_expr.then_branch.node)?;
+                let then_return_ty = self.infer_narrowed_block(&if_expr.then_branch.node, then_narrowing)?;
                 let else_return_ty = if let Some(else_branch) = &if_expr.else_branch {
-                    self.infer_block_expr(&else_branch.node)?
+                    self.infer_narrowed_block(&else_branch.node, else_narrowing)?
                 } else {
                     Type::Nil
                 };
 
-                let return_ty = self.unify(then_return_ty, else_return_ty, if_expr.then_branch.span)?;
-                Ok(return_ty)
+                // a failed unify can bind some variables before the mismatch, those are undone for the union
+                let snapshot = (self.type_env.clone(), self.bindings.clone());
+                match self.unify_types(then_return_ty.clone(), else_return_ty.clone(), if_expr.then_branch.span) {
+                    Ok(return_ty) => Ok(return_ty),
+                    // branches of different types make a union, checked wherever it is used
+                    Err(TypeMismatch { .. }) => {
+                        (self.type_env, self.bindings) = snapshot;
+                        let then_return_ty = self.lookup_type(&then_return_ty);
+                        let else_return_ty = self.lookup_type(&else_return_ty);
+                        let return_ty = union(vec![then_return_ty, else_return_ty]);
+                        self.type_env.insert(expr.node_id, return_ty);
+                        Ok(TypeVar(expr.node_id))
+                    }
+                    Err(err) => Err(err),
+                }
             }
             Expr::MethodCall(method_call) => {
                 let receiver_ty = self.infer_expr(&method_call.receiver)?;
                 let receiver_ty = self.lookup_type(&receiver_ty);
                 self.type_env.insert(method_call.receiver.node_id, receiver_ty.clone());
+                if method_call.method.node == "get" {
+                    self.check_literal_index(expr.span, &method_call.receiver, &method_call.arguments);
+                }
 
                 if let Some((method_ty, _)) = self.method_registry.lookup_method(&receiver_ty, &method_call.method.node).cloned() {
                     match method_ty {
@@ -683,6 +1476,7 @@ impl<'a> TypeInferrer<'a> {
                                 return Err(WrongArgumentCount {
                                     src: self.source.clone(),
                                     span: method_call.method.span,
+                                    declaration: None,
                                     expected: params.len(),
                                     found: method_call.arguments.len(),
                                 });
@@ -729,6 +1523,25 @@ impl<'a> TypeInferrer<'a> {
                 let left = self.infer_expr(binary_expr.left.deref())?;
                 let right = self.infer_expr(binary_expr.right.deref())?;
 
+                // only equality accepts every type, a union always has a member the other operators reject
+                let equality = matches!(binary_expr.op.node, BinaryOp::EqualEqual | BinaryOp::BangEqual);
+                for (operand, ty) in [(&binary_expr.left, &left), (&binary_expr.right, &right)] {
+                    let ty = self.lookup_type(ty);
+                    if let Type::Union(_) = ty {
+                        if equality {
+                            self.type_env.insert(expr.node_id, Type::Bool);
+                            return Ok(TypeVar(expr.node_id));
+                        }
+                        let start = binary_expr.op.span.offset();
+                        return Err(InvalidUnionOperand {
+                            src: self.source.clone(),
+                            span: operand.span,
+                            operator: self.source[start..start + binary_expr.op.span.len()].to_string(),
+                            union: ty,
+                        });
+                    }
+                }
+
                 let result_ty = match binary_expr.op.node {
                     BinaryOp::Plus => {
                         let left_ty = self.lookup_type(&left);
@@ -737,6 +1550,12 @@ impl<'a> TypeInferrer<'a> {
                             (Type::Int, Type::Int) => Type::Int,
                             (Type::Float, Type::Float) => Type::Float,
                             (Type::String, Type::String) => Type::String,
+                            (Type::String, found) => {
+                                return Err(self.string_concatenation_error(&binary_expr.right, found));
+                            }
+                            (found, Type::String) => {
+                                return Err(self.string_concatenation_error(&binary_expr.left, found));
+                            }
                             _ => {
                                 return Err(TypeMismatch {
                                     src: self.source.clone(),
@@ -764,6 +1583,12 @@ impl<'a> TypeInferrer<'a> {
                         }
                     }
                     BinaryOp::Star | BinaryOp::Slash => {
+                        if binary_expr.op.node == BinaryOp::Slash && Self::is_literal_zero(&binary_expr.right) {
+                            self.report(TypeInferrerError::DivisionByZero {
+                                src: self.source.clone(),
+                                span: binary_expr.right.span,
+                            });
+                        }
                         let left_ty = self.lookup_type(&left);
                         let right_ty = self.lookup_type(&right);
                         match (left_ty.clone(), right_ty.clone()) {
@@ -801,6 +1626,14 @@ impl<'a> TypeInferrer<'a> {
                     }
                 };
 
+                if let Some(Constant::Bool(value)) = Self::constant(expr) {
+                    self.report(TypeInferrerError::ConstantComparison {
+                        src: self.source.clone(),
+                        span: expr.span,
+                        value,
+                    });
+                }
+
                 self.type_env.insert(expr.node_id, result_ty);
                 Ok(TypeVar(expr.node_id))
             }
@@ -808,13 +1641,13 @@ impl<'a> TypeInferrer<'a> {
             Expr::Variable(variable_expr) => {
                 let var_id = self.var_env.lookup(variable_expr.node.as_str()).unwrap();
 
-                Ok(TypeVar(var_id.clone()))
+                Ok(TypeVar(var_id))
             }
             Expr::Assign(assign_expr) => {
                 let right_ty = self.infer_expr(assign_expr.value.deref())?;
                 let left_var = self.var_env.lookup(assign_expr.target.node.as_str()).unwrap();
 
-                self.unify(TypeVar(left_var.clone()), right_ty.clone(), assign_expr.value.deref().span)?;
+                self.unify(TypeVar(left_var), right_ty.clone(), assign_expr.value.deref().span)?;
 
                 self.type_env.insert(expr.node_id, right_ty);
                 Ok(TypeVar(expr.node_id))
@@ -835,40 +1668,25 @@ impl<'a> TypeInferrer<'a> {
 
                 match callee_ty {
                     Type::Function { params, return_ty } => {
-                        let substitutions = self.handle_parameters(&params, &call_expr.arguments, call_expr.callee.span)?;
+                        let declaration = self.declaration_span(&call_expr.callee.node);
+                        let substitutions = self.handle_parameters(&params, &call_expr.arguments, call_expr.callee.span, declaration)?;
 
                         self.var_env.enter_scope();
 
-                        if let Expr::Variable(var) = &call_expr.callee.node {
-                            if let Some(fn_decl) = self.program.statements.iter().find(|stmt| {
-                                if let Stmt::FunDecl(fd) = stmt {
-                                    fd.node.ident.node == var.node
+                        if let Expr::Variable(var) = &call_expr.callee.node
+                            && let Some(fn_decl) = self.program.statements.iter().find(|stmt| {
+                                if let Stmt::FunDecl(fd) = &stmt.node {
+                                    fd.name.node == var.node
                                 } else {
                                     false
                                 }
-                            }) {
-                                if let Stmt::FunDecl(fd) = fn_decl {
-                                    for (param, param_ty) in fd.node.params.iter().zip(params.iter()) {
-                                        let substituted_ty = self.substitute(param_ty, &substitutions);
-                                        self.type_env.insert(param.name.node_id, substituted_ty);
-                                        self.var_env.insert(param.name.node.clone(), param.name.node_id);
-                                    }
-
-                                    let substituted_return = self.substitute(&fd.node.return_type.node, &substitutions);
-                                    let old_return_ty = self.current_function_return_ty.clone();
-                                    self.current_function_return_ty = Some(substituted_return.clone());
-
-                                    self.infer_stmts(&fd.node.body.node.statements)?;
-
-                                    if let Some(expr) = &fd.node.body.node.expr {
-                                        let body_ty = self.infer_expr(expr)?;
-                                        self.unify(fd.node.return_type.node.clone(), body_ty, fd.node.ident.span)?;
-                                    } else if !fd.node.body.node.statements.iter().any(|stmt| matches!(stmt, Stmt::Return(_))) {
-                                        self.unify(Type::Nil, fd.node.return_type.node.clone(), fd.node.return_type.span)?;
-                                    }
-                                    self.current_function_return_ty = old_return_ty;
-                                }
-                            }
+                            })
+                            && let Stmt::FunDecl(fd) = &fn_decl.node
+                            && self.instantiating.insert(fd.name.node_id)
+                        {
+                            let checked = self.instantiate_function(fd, &params, &substitutions);
+                            self.instantiating.remove(&fd.name.node_id);
+                            checked?;
                         }
 
                         self.var_env.exit_scope();
@@ -887,31 +1705,30 @@ impl<'a> TypeInferrer<'a> {
             Expr::Lambda(lambda) => {
                 self.var_env.enter_scope();
 
-                let param_types: Vec<Type> = lambda.parameters.iter().map(|p| p.type_annotation.node.clone()).collect();
+                let param_types: Vec<Type> = lambda.parameters.iter().map(|p| Type::from(&p.type_annotation.node)).collect();
 
                 let fn_type = Type::Function {
                     params: param_types.clone(),
-                    return_ty: Box::new(lambda.return_type.node.clone()),
+                    return_ty: Box::new(Type::from(&lambda.return_type.node)),
                 };
 
                 self.type_env.insert(expr.node_id, fn_type.clone());
 
                 for param in &lambda.parameters {
                     let param_id = param.name.node_id;
-                    self.type_env.insert(param_id, param.type_annotation.node.clone());
+                    self.type_env.insert(param_id, Type::from(&param.type_annotation.node));
                     self.var_env.insert(param.name.node.clone(), param_id);
                 }
 
                 let old_ret_ty = self.current_function_return_ty.clone();
-                self.current_function_return_ty = Some(lambda.return_type.node.clone());
+                self.current_function_return_ty = Some(Type::from(&lambda.return_type.node));
 
-                self.infer_stmts(&lambda.body.node.statements)?;
+                let body_ty = self.infer_block_expr(&lambda.body.node)?;
 
                 if let Some(expr) = &lambda.body.node.expr {
-                    let body_ty = self.infer_expr(expr)?;
-                    self.unify(lambda.return_type.node.clone(), body_ty, expr.span)?;
-                } else if !lambda.body.node.statements.iter().any(|stmt| matches!(stmt, Stmt::Return(_))) {
-                    self.unify(Type::Nil, lambda.return_type.node.clone(), lambda.return_type.span)?;
+                    self.unify(Type::from(&lambda.return_type.node), body_ty, expr.span)?;
+                } else if !lambda.body.node.statements.iter().any(|stmt| matches!(stmt.node, Stmt::Return(_))) {
+                    self.unify(Type::Nil, Type::from(&lambda.return_type.node), lambda.return_type.span)?;
                 }
 
                 self.current_function_return_ty = old_ret_ty;
@@ -921,3 +1738,139 @@ impl<'a> TypeInferrer<'a> {
         }
     }
 }
+
+/// flattens nested unions and drops duplicate members, a single member is returned as is
+fn union(members: Vec<Type>) -> Type {
+    let mut flattened = vec![];
+    for member in members {
+        let nested = match member {
+            Type::Union(nested) => nested,
+            member => vec![member],
+        };
+        for member in nested {
+            if !flattened.contains(&member) {
+                flattened.push(member);
+            }
+        }
+    }
+    if flattened.len() == 1 {
+        flattened.pop().unwrap()
+    } else {
+        Type::Union(flattened)
+    }
+}
+
+/// the natives testing the type of their argument and the `typeOf` names they accept
+const TYPE_PREDICATES: [(&str, &[&str]); 5] = [
+    ("isNumber", &["Int", "Float"]),
+    ("isString", &["String"]),
+    ("isBool", &["Bool"]),
+    ("isVec", &["Vec"]),
+    ("isNil", &["Nil"]),
+];
+
+/// `typeOf(x) == "Int"`, `isNumber(x)` or their negations, the tested variable, the accepted type names and if it's negated
+fn type_test(condition: &AstNode<Expr>) -> Option<(&Ident, Vec<&str>, bool)> {
+    match &condition.node {
+        Expr::Grouping(inner) => type_test(inner),
+        Expr::Unary(unary) if unary.op.node == UnaryOp::Bang => {
+            let (variable, type_names, negated) = type_test(&unary.expr)?;
+            Some((variable, type_names, !negated))
+        }
+        Expr::Call(call) => {
+            let (Expr::Variable(callee), [argument]) = (&call.callee.node, &call.arguments[..]) else {
+                return None;
+            };
+            let Expr::Variable(variable) = &argument.node else {
+                return None;
+            };
+            let (_, type_names) = TYPE_PREDICATES.iter().find(|(name, _)| *name == callee.node)?;
+            Some((variable, type_names.to_vec(), false))
+        }
+        Expr::Binary(binary_expr) => {
+            let negated = match binary_expr.op.node {
+                BinaryOp::EqualEqual => false,
+                BinaryOp::BangEqual => true,
+                _ => return None,
+            };
+            let (call, type_name) = match (&binary_expr.left.node, &binary_expr.right.node) {
+                (Expr::Call(call), Expr::Literal(LiteralExpr::String(type_name)))
+                | (Expr::Literal(LiteralExpr::String(type_name)), Expr::Call(call)) => (call, type_name),
+                _ => return None,
+            };
+            let (Expr::Variable(callee), [argument]) = (&call.callee.node, &call.arguments[..]) else {
+                return None;
+            };
+            let Expr::Variable(variable) = &argument.node else {
+                return None;
+            };
+            (callee.node == "typeOf").then(|| (variable, vec![type_name.as_str()], negated))
+        }
+        _ => None,
+    }
+}
+
+/// what `typeOf` returns for values of `ty`
+fn runtime_name(ty: &Type) -> Option<&'static str> {
+    match ty {
+        Type::Int => Some("Int"),
+        Type::Float => Some("Float"),
+        Type::String => Some("String"),
+        Type::Bool => Some("Bool"),
+        Type::Nil => Some("Nil"),
+        Type::Function { .. } => Some("Function"),
+        Type::Vec(_) => Some("Vec"),
+        Type::Struct { .. } | Type::Record(_) => Some("Struct"),
+        _ => None,
+    }
+}
+
+/// the value of a literal operand, see `TypeInferrer::constant`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Constant {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+/// `None` for the arithmetic operators
+fn compare<T: PartialOrd>(op: &BinaryOp, left: T, right: T) -> Option<bool> {
+    Some(match op {
+        BinaryOp::Greater => left > right,
+        BinaryOp::GreaterEqual => left >= right,
+        BinaryOp::Less => left < right,
+        BinaryOp::LessEqual => left <= right,
+        BinaryOp::EqualEqual => left == right,
+        BinaryOp::BangEqual => left != right,
+        BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Star | BinaryOp::Slash => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::Compiler;
+
+    fn diagnostic_codes(source: &str) -> Vec<String> {
+        let compilation = Compiler::new().check(source);
+        compilation
+            .diagnostics
+            .iter()
+            .filter_map(|diagnostic| diagnostic.code().map(|code| code.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn tail_expressions_see_the_locals_of_their_body() {
+        assert_eq!(diagnostic_codes("fn f(n: Int) -> Int { let m = n; m }"), Vec::<String>::new());
+        assert_eq!(
+            diagnostic_codes("let f = fn(n: Int) -> Int { let m = n; m };"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn callbacks_bind_the_generics_of_their_parameter() {
+        assert_eq!(diagnostic_codes("schedule(0, fn() -> Int { 1 });"), Vec::<String>::new());
+        assert_eq!(diagnostic_codes("schedule(0, fn() -> Nil { print(1); });"), Vec::<String>::new());
+    }
+}
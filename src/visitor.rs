@@ -0,0 +1,251 @@
+use crate::ast::{AstNode, BlockExpr, Expr, LiteralExpr, Program, Stmt};
+
+/// read-only AST traversal, overridden methods call the matching `walk_*` function to keep descending
+pub trait Visitor {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_stmt(&mut self, stmt: &AstNode<Stmt>) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &AstNode<Expr>) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_block(&mut self, block: &BlockExpr) {
+        walk_block(self, block);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for stmt in &program.statements {
+        visitor.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, stmt: &AstNode<Stmt>) {
+    match &stmt.node {
+        Stmt::ExprStmtNode(expr_stmt) => visitor.visit_expr(&expr_stmt.expr),
+        Stmt::VarDecl(var_decl) => {
+            if let Some(init) = &var_decl.initializer {
+                visitor.visit_expr(init);
+            }
+        }
+        Stmt::FunDecl(fun_decl) => visitor.visit_block(&fun_decl.body.node),
+        Stmt::StructDecl(_) | Stmt::TypeAlias(_) => {}
+        Stmt::While(while_stmt) => {
+            visitor.visit_expr(&while_stmt.condition);
+            visitor.visit_block(&while_stmt.body.node);
+        }
+        Stmt::For(for_stmt) => {
+            if let Some(initializer) = &for_stmt.initializer {
+                visitor.visit_stmt(initializer);
+            }
+            visitor.visit_expr(&for_stmt.condition);
+            if let Some(increment) = &for_stmt.increment {
+                visitor.visit_expr(increment);
+            }
+            visitor.visit_block(&for_stmt.body.node);
+        }
+        Stmt::Return(return_stmt) => {
+            if let Some(expr) = &return_stmt.expr {
+                visitor.visit_expr(expr);
+            }
+        }
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &AstNode<Expr>) {
+    match &expr.node {
+        Expr::Literal(LiteralExpr::VecLiteral(elements)) => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        Expr::Literal(_) | Expr::Variable(_) => {}
+        Expr::Unary(unary) => visitor.visit_expr(&unary.expr),
+        Expr::Binary(binary) => {
+            visitor.visit_expr(&binary.left);
+            visitor.visit_expr(&binary.right);
+        }
+        Expr::Logical(logical) => {
+            visitor.visit_expr(&logical.left);
+            visitor.visit_expr(&logical.right);
+        }
+        Expr::Grouping(inner) => visitor.visit_expr(inner),
+        Expr::Assign(assign) => visitor.visit_expr(&assign.value),
+        Expr::Call(call) => {
+            visitor.visit_expr(&call.callee);
+            for argument in &call.arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Expr::Lambda(lambda) => visitor.visit_block(&lambda.body.node),
+        Expr::Block(block) => visitor.visit_block(block),
+        Expr::If(if_expr) => {
+            visitor.visit_expr(&if_expr.condition);
+            visitor.visit_block(&if_expr.then_branch.node);
+            if let Some(else_branch) = &if_expr.else_branch {
+                visitor.visit_block(&else_branch.node);
+            }
+        }
+        Expr::MethodCall(method_call) => {
+            visitor.visit_expr(&method_call.receiver);
+            for argument in &method_call.arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Expr::StructInit(struct_init) => {
+            for (_, value) in &struct_init.fields {
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Record(record) => {
+            for (_, value) in &record.fields {
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Cast(cast) => visitor.visit_expr(&cast.expr),
+        Expr::FieldAccess(field_access) => visitor.visit_expr(&field_access.receiver),
+        Expr::FieldAssign(field_assign) => {
+            visitor.visit_expr(&field_assign.receiver);
+            visitor.visit_expr(&field_assign.value);
+        }
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &BlockExpr) {
+    for stmt in &block.statements {
+        visitor.visit_stmt(stmt);
+    }
+    if let Some(expr) = &block.expr {
+        visitor.visit_expr(expr);
+    }
+}
+
+/// in-place counterpart of [`Visitor`] for passes that rewrite the AST
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut AstNode<Stmt>) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut AstNode<Expr>) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut BlockExpr) {
+        walk_block_mut(self, block);
+    }
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(visitor: &mut V, program: &mut Program) {
+    for stmt in &mut program.statements {
+        visitor.visit_stmt_mut(stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut AstNode<Stmt>) {
+    match &mut stmt.node {
+        Stmt::ExprStmtNode(expr_stmt) => visitor.visit_expr_mut(&mut expr_stmt.expr),
+        Stmt::VarDecl(var_decl) => {
+            if let Some(init) = &mut var_decl.initializer {
+                visitor.visit_expr_mut(init);
+            }
+        }
+        Stmt::FunDecl(fun_decl) => visitor.visit_block_mut(&mut fun_decl.body.node),
+        Stmt::StructDecl(_) | Stmt::TypeAlias(_) => {}
+        Stmt::While(while_stmt) => {
+            visitor.visit_expr_mut(&mut while_stmt.condition);
+            visitor.visit_block_mut(&mut while_stmt.body.node);
+        }
+        Stmt::For(for_stmt) => {
+            if let Some(initializer) = &mut for_stmt.initializer {
+                visitor.visit_stmt_mut(initializer);
+            }
+            visitor.visit_expr_mut(&mut for_stmt.condition);
+            if let Some(increment) = &mut for_stmt.increment {
+                visitor.visit_expr_mut(increment);
+            }
+            visitor.visit_block_mut(&mut for_stmt.body.node);
+        }
+        Stmt::Return(return_stmt) => {
+            if let Some(expr) = &mut return_stmt.expr {
+                visitor.visit_expr_mut(expr);
+            }
+        }
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut AstNode<Expr>) {
+    match &mut expr.node {
+        Expr::Literal(LiteralExpr::VecLiteral(elements)) => {
+            for element in elements {
+                visitor.visit_expr_mut(element);
+            }
+        }
+        Expr::Literal(_) | Expr::Variable(_) => {}
+        Expr::Unary(unary) => visitor.visit_expr_mut(&mut unary.expr),
+        Expr::Binary(binary) => {
+            visitor.visit_expr_mut(&mut binary.left);
+            visitor.visit_expr_mut(&mut binary.right);
+        }
+        Expr::Logical(logical) => {
+            visitor.visit_expr_mut(&mut logical.left);
+            visitor.visit_expr_mut(&mut logical.right);
+        }
+        Expr::Grouping(inner) => visitor.visit_expr_mut(inner),
+        Expr::Assign(assign) => visitor.visit_expr_mut(&mut assign.value),
+        Expr::Call(call) => {
+            visitor.visit_expr_mut(&mut call.callee);
+            for argument in &mut call.arguments {
+                visitor.visit_expr_mut(argument);
+            }
+        }
+        Expr::Lambda(lambda) => visitor.visit_block_mut(&mut lambda.body.node),
+        Expr::Block(block) => visitor.visit_block_mut(block),
+        Expr::If(if_expr) => {
+            visitor.visit_expr_mut(&mut if_expr.condition);
+            visitor.visit_block_mut(&mut if_expr.then_branch.node);
+            if let Some(else_branch) = &mut if_expr.else_branch {
+                visitor.visit_block_mut(&mut else_branch.node);
+            }
+        }
+        Expr::MethodCall(method_call) => {
+            visitor.visit_expr_mut(&mut method_call.receiver);
+            for argument in &mut method_call.arguments {
+                visitor.visit_expr_mut(argument);
+            }
+        }
+        Expr::StructInit(struct_init) => {
+            for (_, value) in &mut struct_init.fields {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Expr::Record(record) => {
+            for (_, value) in &mut record.fields {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Expr::Cast(cast) => visitor.visit_expr_mut(&mut cast.expr),
+        Expr::FieldAccess(field_access) => visitor.visit_expr_mut(&mut field_access.receiver),
+        Expr::FieldAssign(field_assign) => {
+            visitor.visit_expr_mut(&mut field_assign.receiver);
+            visitor.visit_expr_mut(&mut field_assign.value);
+        }
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut BlockExpr) {
+    for stmt in &mut block.statements {
+        visitor.visit_stmt_mut(stmt);
+    }
+    if let Some(expr) = &mut block.expr {
+        visitor.visit_expr_mut(expr);
+    }
+}